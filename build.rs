@@ -0,0 +1,29 @@
+use std::env;
+use std::process::Command;
+
+fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+    // Plugins are checked against the compiler version, as operations cross the library
+    // boundary with the Rust ABI.
+    if env::var_os("CARGO_FEATURE_PLUGINS").is_some() {
+        let rustc = env::var("RUSTC").unwrap_or_else(|_| "rustc".to_string());
+        let version = Command::new(rustc).arg("--version").output()
+            .map(|output| String::from_utf8_lossy(&output.stdout).trim().to_string())
+            .unwrap_or_default();
+        println!("cargo:rustc-env=METSI_RUSTC_VERSION={version}");
+    }
+    #[cfg(feature = "grpc")]
+    compile_protos();
+}
+
+#[cfg(feature = "grpc")]
+fn compile_protos() {
+    println!("cargo:rerun-if-changed=proto/metsi.proto");
+    if env::var_os("PROTOC").is_none() {
+        let protoc = protoc_bin_vendored::protoc_bin_path().expect("vendored protoc is available");
+        env::set_var("PROTOC", protoc);
+    }
+    tonic_prost_build::configure()
+        .compile_protos(&["proto/metsi.proto"], &["proto"])
+        .expect("proto/metsi.proto compiles");
+}
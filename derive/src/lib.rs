@@ -0,0 +1,126 @@
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::{quote, ToTokens};
+use syn::{parse_macro_input, Data, DeriveInput, Error, Fields, GenericArgument, LitStr, PathArguments, Type};
+
+/// Derive OperationParams for a struct with named fields, each field being a parameter of the
+/// same name parsed with FromStr, or from JSON with `#[param(nested)]` for lists and maps.
+/// Fields of type Option are optional, fields with `#[param(default = "...")]` fall back to the
+/// parsed default, other fields are required.
+#[proc_macro_derive(OperationParams, attributes(param))]
+pub fn derive_operation_params(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(&input).unwrap_or_else(Error::into_compile_error).into()
+}
+
+/// Parameter declared by a struct field.
+struct Parameter {
+    field: syn::Ident,
+    ty: Type,
+    optional: bool,
+    nested: bool,
+    default: Option<LitStr>,
+}
+
+fn expand(input: &DeriveInput) -> Result<TokenStream2, Error> {
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => &fields.named,
+            _ => return Err(Error::new_spanned(&input.ident, "OperationParams requires named fields")),
+        },
+        _ => return Err(Error::new_spanned(&input.ident, "OperationParams can only be derived for structs")),
+    };
+    let parameters = fields.iter()
+        .map(|field| {
+            let mut default = None;
+            let mut nested = false;
+            for attr in field.attrs.iter().filter(|attr| attr.path().is_ident("param")) {
+                attr.parse_nested_meta(|meta| {
+                    if meta.path.is_ident("default") {
+                        default = Some(meta.value()?.parse::<LitStr>()?);
+                        Ok(())
+                    } else if meta.path.is_ident("nested") {
+                        nested = true;
+                        Ok(())
+                    } else {
+                        Err(meta.error("unknown param attribute, expected `default` or `nested`"))
+                    }
+                })?;
+            }
+            let (optional, ty) = match option_inner(&field.ty) {
+                Some(inner) => (true, inner.clone()),
+                None => (false, field.ty.clone()),
+            };
+            if optional && default.is_some() {
+                return Err(Error::new_spanned(&field.ty, "optional parameters cannot have a default"));
+            }
+            Ok(Parameter { field: field.ident.clone().unwrap(), ty, optional, nested, default })
+        })
+        .collect::<Result<Vec<_>, Error>>()?;
+
+    let specs = parameters.iter().map(|Parameter { field, ty, optional, nested, default }| {
+        let name = field.to_string();
+        let kind = ty.to_token_stream().to_string().replace(' ', "");
+        let required = !optional && default.is_none();
+        let default = match default {
+            Some(default) => quote!(::core::option::Option::Some(#default)),
+            None => quote!(::core::option::Option::None),
+        };
+        let check = match nested {
+            true => quote!(|value| ::metsi_rust::configuration_utils::parses_nested::<#ty>(value)),
+            false => quote!(|value| value.parse::<#ty>().is_ok()),
+        };
+        quote! {
+            ::metsi_rust::configuration_utils::ParameterSpec {
+                name: #name,
+                kind: #kind,
+                required: #required,
+                default: #default,
+                check: #check,
+            }
+        }
+    });
+    let values = parameters.iter().map(|Parameter { field, ty, optional, nested, default }| {
+        let name = field.to_string();
+        let (parser, optional_parser) = match nested {
+            true => (quote!(nested_parameter), quote!(optional_nested_parameter)),
+            false => (quote!(parameter), quote!(optional_parameter)),
+        };
+        let default = match default {
+            Some(default) => quote!(::core::option::Option::Some(#default)),
+            None => quote!(::core::option::Option::None),
+        };
+        match optional {
+            true => quote!(#field: ::metsi_rust::configuration_utils::#optional_parser::<#ty>(params, #name)?),
+            false => quote!(#field: ::metsi_rust::configuration_utils::#parser::<#ty>(params, #name, #default)?),
+        }
+    });
+    let ident = &input.ident;
+    let (impl_generics, type_generics, where_clause) = input.generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics ::metsi_rust::configuration_utils::OperationParams for #ident #type_generics #where_clause {
+            fn schema() -> ::metsi_rust::configuration_utils::ParameterSchema {
+                ::std::vec![#(#specs),*]
+            }
+
+            fn parse(params: &::metsi_rust::configuration_utils::ParameterMap) -> ::core::result::Result<Self, ::metsi_rust::configuration_utils::ParameterError> {
+                ::metsi_rust::configuration_utils::reject_unknown(&Self::schema(), params)?;
+                ::core::result::Result::Ok(#ident { #(#values),* })
+            }
+        }
+    })
+}
+
+/// Inner type of an Option type.
+fn option_inner(ty: &Type) -> Option<&Type> {
+    let Type::Path(path) = ty else { return None };
+    let segment = path.path.segments.last()?;
+    if segment.ident != "Option" {
+        return None;
+    }
+    let PathArguments::AngleBracketed(arguments) = &segment.arguments else { return None };
+    match arguments.args.first()? {
+        GenericArgument::Type(inner) => Some(inner),
+        _ => None,
+    }
+}
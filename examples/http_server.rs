@@ -0,0 +1,28 @@
+//! Serve the operations of the simple_simulation example over HTTP, such as
+//! cargo run --example http_server --features http -- 127.0.0.1:8080
+//! curl -d '{"declaration": [["sequence", ["increment"]]], "parameters": {"increment": {"increase": "2"}}, "entities": [["a", 1]]}' \
+//!     -H 'Content-Type: application/json' http://127.0.0.1:8080/run
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use metsi_rust::configuration_utils::{ParameterMap, ParameteredOperation};
+use metsi_rust::http::serve;
+use metsi_rust::simulation_runner::OperationRegistry;
+
+fn increment(val: i32, params: ParameterMap) -> i32 {
+    val + params.get("increase").unwrap().parse::<i32>().unwrap()
+}
+
+fn decrement(val: i32, params: ParameterMap) -> i32 {
+    val - params.get("decrease").unwrap().parse::<i32>().unwrap()
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let address: SocketAddr = std::env::args().nth(1).unwrap_or_else(|| "127.0.0.1:8080".to_string()).parse()?;
+    let operations: OperationRegistry<i32> = HashMap::from([
+        ("increment", increment as ParameteredOperation<i32>),
+        ("decrement", decrement as ParameteredOperation<i32>)
+    ]);
+    serve(operations, address).await?;
+    Ok(())
+}
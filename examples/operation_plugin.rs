@@ -0,0 +1,20 @@
+//! Operation plugin loaded by the plugin_host example. Build with
+//! cargo build --example operation_plugin --features plugins
+use metsi_rust::configuration_utils::ParameterMap;
+use metsi_rust::export_plugin;
+use metsi_rust::plugins::PluginRegistrar;
+
+fn increment(val: i32, params: ParameterMap) -> i32 {
+    val + params.get("increase").unwrap().parse::<i32>().unwrap()
+}
+
+fn decrement(val: i32, params: ParameterMap) -> i32 {
+    val - params.get("decrease").unwrap().parse::<i32>().unwrap()
+}
+
+fn register(registrar: &mut PluginRegistrar) {
+    registrar.register::<i32>("increment", increment);
+    registrar.register::<i32>("decrement", decrement);
+}
+
+export_plugin!(register);
@@ -0,0 +1,42 @@
+//! Run a simulation over operations loaded from the plugin libraries given as arguments, such as
+//! cargo run --example plugin_host --features plugins -- target/debug/examples/liboperation_plugin.so
+use std::process::ExitCode;
+use metsi_rust::configuration_utils::ParameterMap;
+use metsi_rust::plugins::OperationPlugins;
+use metsi_rust::simulation_runner::{OperationParameters, SimulationRunner};
+
+fn main() -> ExitCode {
+    let mut plugins = OperationPlugins::new();
+    for path in std::env::args().skip(1) {
+        // SAFETY: the arguments are trusted to be operation plugins built with export_plugin.
+        if let Err(err) = unsafe { plugins.load(&path) } {
+            eprintln!("{err}");
+            return ExitCode::FAILURE;
+        }
+    }
+    let operations = match plugins.registry::<i32>() {
+        Ok(operations) => operations,
+        Err(err) => {
+            eprintln!("{err}");
+            return ExitCode::FAILURE;
+        }
+    };
+    let parameters = OperationParameters::from([
+        ("increment", ParameterMap::from([("increase", "2")])),
+        ("decrement", ParameterMap::from([("decrease", "1")]))
+    ]);
+    let declaration = vec![
+        ("sequence", vec!["increment"]),
+        ("alternatives", vec!["increment", "decrement"])
+    ];
+    match SimulationRunner::new(operations, parameters, declaration) {
+        Ok(runner) => {
+            println!("{:?}", runner.run([10]));
+            ExitCode::SUCCESS
+        }
+        Err(err) => {
+            eprintln!("{err}");
+            ExitCode::FAILURE
+        }
+    }
+}
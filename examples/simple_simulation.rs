@@ -0,0 +1,95 @@
+use std::collections::HashMap;
+use std::process::ExitCode;
+use metsi_rust::checkpoint::Checkpoint;
+use metsi_rust::cli::{CliOptions, USAGE};
+use metsi_rust::configuration_utils::{ParameterMap, ParameteredOperation};
+use metsi_rust::simulation_runner::{OperationParameters, OperationRegistry, SimulationRunner};
+
+fn increment(val: i32, params: ParameterMap) -> i32 {
+    val + params.get("increase").unwrap().parse::<i32>().unwrap()
+}
+
+fn decrement(val: i32, params: ParameterMap) -> i32 {
+    val - params.get("decrease").unwrap().parse::<i32>().unwrap()
+}
+
+fn main() -> ExitCode {
+    let options = match CliOptions::from_env() {
+        Ok(options) => options,
+        Err(err) => {
+            eprintln!("{err}");
+            return ExitCode::FAILURE;
+        }
+    };
+    if options.help {
+        println!("{USAGE}");
+        return ExitCode::SUCCESS;
+    }
+
+    let operations: OperationRegistry<i32> = HashMap::from([
+        ("increment", increment as ParameteredOperation<i32>),
+        ("decrement", decrement as ParameteredOperation<i32>)
+    ]);
+    let parameters = OperationParameters::from([
+        ("increment", ParameterMap::from([("increase", "2")])),
+        ("decrement", ParameterMap::from([("decrease", "1")]))
+    ]);
+    let declaration = vec![
+        ("sequence", vec!["increment", "increment"]),
+        ("alternatives", vec!["increment", "decrement"]),
+        ("sequence", vec!["increment", "increment"])
+    ];
+
+    let mut runner = match SimulationRunner::new(operations, parameters, declaration) {
+        Ok(runner) => runner,
+        Err(err) => {
+            eprintln!("{err}");
+            return ExitCode::FAILURE;
+        }
+    };
+    runner.set_scenarios(vec![("up", vec!["increment"]), ("down", vec!["decrement"])])
+        .expect("scenarios choose alternatives of the declaration");
+    if !options.scenarios.is_empty() {
+        let scenarios = match runner.select_scenarios(&options.scenarios) {
+            Ok(scenarios) => scenarios,
+            Err(err) => {
+                eprintln!("{err}");
+                return ExitCode::FAILURE;
+            }
+        };
+        for (name, scenario) in scenarios {
+            for result in scenario.run([10]) {
+                println!("{name}: {:?}", result);
+            }
+        }
+        return ExitCode::SUCCESS;
+    }
+    if options.dry_run {
+        print!("{}", runner.validate());
+        return ExitCode::SUCCESS;
+    }
+    // With a checkpoint directory argument, run a batch of entities checkpointed into it.
+    if let Some(directory) = options.arguments.first() {
+        let checkpoint = Checkpoint::new(directory, 10);
+        let entities: Vec<(u32, i32)> = (0..100).map(|id| (id, id as i32)).collect();
+        let outcome = if options.resume {
+            runner.resume_batch_checkpointed(entities, &checkpoint)
+        } else {
+            runner.run_batch_checkpointed(entities, &checkpoint)
+        };
+        return match outcome {
+            Ok(outcome) => {
+                println!("{} entities completed, {} failed", outcome.results.len(), outcome.failures.len());
+                ExitCode::SUCCESS
+            }
+            Err(err) => {
+                eprintln!("{err}");
+                ExitCode::FAILURE
+            }
+        };
+    }
+    for result in runner.run([10]) {
+        println!("{:?}", result);
+    }
+    ExitCode::SUCCESS
+}
@@ -0,0 +1,118 @@
+#ifndef METSI_RUST_H
+#define METSI_RUST_H
+
+/* Generated with cbindgen from src/ffi.rs, do not edit by hand. */
+
+#include <stddef.h>
+#include <stdint.h>
+
+/**
+ * Operations registered for simulations of states of a fixed size in bytes.
+ */
+typedef struct MetsiRegistry MetsiRegistry;
+
+/**
+ * Chain results of evaluating a simulation for a state.
+ */
+typedef struct MetsiResults MetsiResults;
+
+/**
+ * Simulation compiled from a JSON configuration over the operations of a registry.
+ */
+typedef struct MetsiSimulation MetsiSimulation;
+
+/**
+ * Operation implemented in C. Reads the state of state_size bytes and writes the resulting state
+ * of state_size bytes, given the parameters of the operation as a JSON object of strings and the
+ * user data given on registration. Returns 0 on success, other values failing the event chain.
+ */
+typedef int32_t (*MetsiOperation)(const uint8_t *state,
+                                  uint8_t *result,
+                                  size_t state_size,
+                                  const char *parameters,
+                                  void *user_data);
+
+
+
+/**
+ * Message of the latest failed call on the calling thread, valid until the next failing call.
+ */
+const char *metsi_last_error(void);
+
+/**
+ * Create a registry of operations for states of state_size bytes.
+ */
+struct MetsiRegistry *metsi_registry_new(size_t state_size);
+
+/**
+ * Register the operation under the given name, replacing an operation of the same name.
+ * Returns 0 on success.
+ *
+ * # Safety
+ * registry must come from metsi_registry_new and name must be a nul-terminated string. The
+ * operation must be callable with the user data for as long as simulations compiled from the
+ * registry exist.
+ */
+int32_t metsi_registry_add(struct MetsiRegistry *registry,
+                           const char *name,
+                           MetsiOperation operation,
+                           void *user_data);
+
+/**
+ * # Safety
+ * registry must come from metsi_registry_new or be null.
+ */
+void metsi_registry_free(struct MetsiRegistry *registry);
+
+/**
+ * Compile a simulation from the JSON configuration over the operations of the registry.
+ * Returns null on failure, described by metsi_last_error.
+ *
+ * # Safety
+ * registry must come from metsi_registry_new and configuration must be a nul-terminated string.
+ */
+struct MetsiSimulation *metsi_simulation_new(const struct MetsiRegistry *registry,
+                                             const char *configuration);
+
+/**
+ * # Safety
+ * simulation must come from metsi_simulation_new or be null.
+ */
+void metsi_simulation_free(struct MetsiSimulation *simulation);
+
+/**
+ * Evaluate the event chains of the simulation for the state. Returns the chain results, or null
+ * on failure described by metsi_last_error.
+ *
+ * # Safety
+ * simulation must come from metsi_simulation_new and state must point to a state of the size of
+ * the registry the simulation was compiled from. A simulation must not be evaluated from
+ * several threads at once.
+ */
+struct MetsiResults *metsi_simulation_run(const struct MetsiSimulation *simulation,
+                                          const uint8_t *state);
+
+/**
+ * Number of chain results.
+ *
+ * # Safety
+ * results must come from metsi_simulation_run.
+ */
+size_t metsi_results_count(const struct MetsiResults *results);
+
+/**
+ * Result state of the chain with the given index, null if out of range. The state is valid
+ * until the results are freed.
+ *
+ * # Safety
+ * results must come from metsi_simulation_run.
+ */
+const uint8_t *metsi_results_get(const struct MetsiResults *results, size_t index);
+
+/**
+ * # Safety
+ * results must come from metsi_simulation_run or be null.
+ */
+void metsi_results_free(struct MetsiResults *results);
+
+#endif  /* METSI_RUST_H */
@@ -0,0 +1,288 @@
+use std::any::Any;
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Display;
+use std::hash::Hash;
+use std::sync::atomic::{AtomicU64, Ordering};
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use rayon::{ThreadPool, ThreadPoolBuildError, ThreadPoolBuilder};
+use crate::event_graph::{ChainResults, EvaluationFailure, EventDAG, EventNode};
+use crate::rng::{try_evaluate_seeded_chains, RngStream, Stochastic};
+
+/// Evaluation results of a batch run keyed by entity id. Results of each entity are keyed by the
+/// ids of their event chains, pruned chains leaving no results.
+pub type EntityResults<K, T> = BTreeMap<K, ChainResults<T>>;
+
+/// Failure of an operation during evaluation of a single entity.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EntityFailure<K> {
+    pub entity: K,
+    pub failure: EvaluationFailure,
+}
+
+/// Outcome of a batch run. Entities with a failing operation are excluded from the results and
+/// described in the failures instead, in the order the entities were given.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BatchOutcome<K, T> {
+    pub results: EntityResults<K, T>,
+    pub failures: Vec<EntityFailure<K>>,
+}
+
+impl<K: Ord, T> FromIterator<(K, Result<ChainResults<T>, EvaluationFailure>)> for BatchOutcome<K, T> {
+    fn from_iter<I: IntoIterator<Item = (K, Result<ChainResults<T>, EvaluationFailure>)>>(evaluated: I) -> Self {
+        let mut outcome = BatchOutcome { results: EntityResults::new(), failures: Vec::new() };
+        for (entity, evaluation) in evaluated {
+            match evaluation {
+                Ok(results) => { outcome.results.insert(entity, results); }
+                Err(failure) => outcome.failures.push(EntityFailure { entity, failure }),
+            }
+        }
+        outcome
+    }
+}
+
+impl<K: Display, T> BatchOutcome<K, T> {
+    /// Human readable report of the failed entities, one line per entity.
+    pub fn failure_report(&self) -> String {
+        self.failures.iter()
+            .map(|EntityFailure { entity, failure }| format!(
+                "entity {entity}: chain {} failed at {}: {}\n",
+                failure.chain, failure.location(), failure.error
+            ))
+            .collect()
+    }
+}
+
+/// Worker threads of parallel evaluation: a thread pool managed by the caller, such as an
+/// application embedding the engine, or a number of threads for a pool built for the evaluation.
+/// A thread count of 0 lets rayon choose the number of worker threads.
+#[derive(Debug, Clone, Copy)]
+pub enum Threads<'a> {
+    Count(usize),
+    Pool(&'a ThreadPool),
+}
+
+impl Threads<'_> {
+    /// Run the operation in the thread pool, building the pool of a thread count.
+    pub fn install<R, OP>(self, op: OP) -> Result<R, ThreadPoolBuildError>
+    where R: Send, OP: FnOnce() -> R + Send {
+        match self {
+            Threads::Count(threads) => Ok(ThreadPoolBuilder::new().num_threads(threads).build()?.install(op)),
+            Threads::Pool(pool) => Ok(pool.install(op)),
+        }
+    }
+
+    /// Call the function with the thread pool, building the pool of a thread count, so that
+    /// several evaluations can share a single pool.
+    pub(crate) fn with_pool<R>(self, f: impl FnOnce(&ThreadPool) -> R) -> Result<R, ThreadPoolBuildError> {
+        match self {
+            Threads::Count(threads) => Ok(f(&ThreadPoolBuilder::new().num_threads(threads).build()?)),
+            Threads::Pool(pool) => Ok(f(pool)),
+        }
+    }
+}
+
+impl From<usize> for Threads<'_> {
+    fn from(threads: usize) -> Self {
+        Threads::Count(threads)
+    }
+}
+
+impl<'a> From<&'a ThreadPool> for Threads<'a> {
+    fn from(pool: &'a ThreadPool) -> Self {
+        Threads::Pool(pool)
+    }
+}
+
+/// Evaluate the event graph rooted at the given EventNode for each of the given independent
+/// entities, collecting the results by entity id. A failing entity does not abort the batch. An
+/// entity id occurring multiple times retains the results of its last occurrence.
+pub fn run_batch<K, T, I>(root: &EventNode<T>, entities: I) -> BatchOutcome<K, T>
+where K: Ord, T: Clone, I: IntoIterator<Item = (K, T)> {
+    entities.into_iter()
+        .map(|(id, state)| (id, EventDAG::try_evaluate_chains_by_id(root, state)))
+        .collect()
+}
+
+/// Evaluate an event graph for each of the given independent entities in parallel, collecting
+/// the results by entity id. As event graphs are not shareable across threads, each worker builds
+/// its own graph with the given factory. Evaluates in the given thread pool or in a pool of the
+/// given number of threads.
+pub fn run_batch_parallel<'p, K, T, F>(graph_factory: F, entities: Vec<(K, T)>, threads: impl Into<Threads<'p>>) -> Result<BatchOutcome<K, T>, ThreadPoolBuildError>
+where K: Ord + Send, T: Clone + Send, F: Fn() -> EventNode<T> + Sync {
+    let evaluated = threads.into().install(|| {
+        entities.into_par_iter()
+            .map_init(&graph_factory, |root, (id, state)| (id, EventDAG::try_evaluate_chains_by_id(root, state)))
+            .collect::<Vec<_>>()
+    })?;
+    Ok(evaluated.into_iter().collect())
+}
+
+thread_local! {
+    /// Event graphs of the worker threads by pooled run, kept between the batches of the run.
+    static WORKER_GRAPHS: RefCell<HashMap<u64, Box<dyn Any>>> = RefCell::new(HashMap::new());
+}
+
+static NEXT_POOLED_RUN: AtomicU64 = AtomicU64::new(0);
+
+/// Parallel evaluation of successive batches of entities in a single thread pool, such as the
+/// intervals of a checkpointed run. Each worker thread builds its event graph with the factory
+/// once and keeps it for the later batches, until the run is dropped.
+pub(crate) struct PooledRun<'a, F> {
+    pool: &'a ThreadPool,
+    graph_factory: F,
+    run: u64,
+}
+
+impl<'a, F> PooledRun<'a, F> {
+    pub(crate) fn new(pool: &'a ThreadPool, graph_factory: F) -> PooledRun<'a, F> {
+        PooledRun { pool, graph_factory, run: NEXT_POOLED_RUN.fetch_add(1, Ordering::Relaxed) }
+    }
+
+    /// Evaluate the batch of entities in parallel as run_batch_parallel does.
+    pub(crate) fn run_batch<K, T>(&self, entities: Vec<(K, T)>) -> BatchOutcome<K, T>
+    where K: Ord + Send, T: Clone + Send + 'static, F: Fn() -> EventNode<T> + Sync {
+        let evaluated = self.pool.install(|| {
+            entities.into_par_iter()
+                .map(|(id, state)| (id, EventDAG::try_evaluate_chains_by_id(&self.worker_graph(), state)))
+                .collect::<Vec<_>>()
+        });
+        evaluated.into_iter().collect()
+    }
+
+    /// Event graph of the current worker thread, built on its first use within the run.
+    fn worker_graph<T: 'static>(&self) -> EventNode<T> where F: Fn() -> EventNode<T> {
+        WORKER_GRAPHS.with_borrow_mut(|graphs| {
+            graphs.entry(self.run)
+                .or_insert_with(|| Box::new((self.graph_factory)()))
+                .downcast_ref::<EventNode<T>>()
+                .cloned()
+                .expect("worker graphs of a run are of a single state type")
+        })
+    }
+}
+
+impl<F> Drop for PooledRun<'_, F> {
+    fn drop(&mut self) {
+        let run = self.run;
+        self.pool.broadcast(|_| WORKER_GRAPHS.with_borrow_mut(|graphs| { graphs.remove(&run); }));
+    }
+}
+
+/// Evaluate an event graph of stochastic payloads for each of the given independent entities as
+/// run_batch does. Each entity evaluates with a random number stream derived from the master seed
+/// by entity id, from which its nodes derive their streams as in try_evaluate_seeded_chains.
+pub fn run_seeded_batch<K, T, I>(root: &EventNode<Stochastic<T>>, entities: I, seed: u64) -> BatchOutcome<K, Stochastic<T>>
+where K: Ord + Hash, T: Clone, I: IntoIterator<Item = (K, T)> {
+    let master = RngStream::new(seed);
+    entities.into_iter()
+        .map(|(id, state)| {
+            let stream = master.derive_for(&id);
+            (id, try_evaluate_seeded_chains(root, state, stream))
+        })
+        .collect()
+}
+
+/// Evaluate an event graph of stochastic payloads for each of the given independent entities in
+/// parallel as run_batch_parallel does, with random number streams derived as in
+/// run_seeded_batch. Results do not depend on the number of worker threads.
+pub fn run_seeded_batch_parallel<'p, K, T, F>(graph_factory: F, entities: Vec<(K, T)>, threads: impl Into<Threads<'p>>, seed: u64) -> Result<BatchOutcome<K, Stochastic<T>>, ThreadPoolBuildError>
+where K: Ord + Hash + Send, T: Clone + Send, F: Fn() -> EventNode<Stochastic<T>> + Sync {
+    let master = RngStream::new(seed);
+    let evaluated = threads.into().install(|| {
+        entities.into_par_iter()
+            .map_init(&graph_factory, |root, (id, state)| {
+                let stream = master.derive_for(&id);
+                (id, try_evaluate_seeded_chains(root, state, stream))
+            })
+            .collect::<Vec<_>>()
+    })?;
+    Ok(evaluated.into_iter().collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::branching_generators::alternatives;
+    use crate::event_graph::BoxedOperation;
+    use super::*;
+
+    #[test]
+    fn entities_are_evaluated_independently() {
+        let root = EventDAG::new_node(Box::new(|x: i32| x));
+        let operations: Vec<BoxedOperation<i32>> = vec![Box::new(|x| x + 1), Box::new(|x| x * 2)];
+        alternatives(vec![root.clone()], operations).unwrap();
+
+        let results = run_batch(&root, [("stand-2", 5), ("stand-1", 1)]).results;
+        assert_eq!(2, results.len());
+        assert_eq!(vec!["stand-1", "stand-2"], results.keys().copied().collect::<Vec<_>>());
+        assert_eq!(ChainResults::from([(0, 2), (1, 2)]), results["stand-1"]);
+        assert_eq!(ChainResults::from([(0, 6), (1, 10)]), results["stand-2"]);
+    }
+
+    #[test]
+    fn parallel_batch_matches_sequential() {
+        let factory = || {
+            let root = EventDAG::new_node(Box::new(|x: i32| x));
+            let operations: Vec<BoxedOperation<i32>> = vec![Box::new(|x| x + 1), Box::new(|x| x * 2)];
+            alternatives(vec![root.clone()], operations).unwrap();
+            root
+        };
+        let entities: Vec<(usize, i32)> = (0..1000).map(|id| (id, id as i32)).collect();
+        let sequential = run_batch(&factory(), entities.clone());
+        let parallel = run_batch_parallel(factory, entities, 4).unwrap();
+        assert_eq!(sequential, parallel);
+    }
+
+    #[test]
+    fn batches_evaluate_in_the_given_pool() {
+        let factory = || {
+            let root = EventDAG::new_node(Box::new(|x: usize| x));
+            alternatives(vec![root.clone()], [Box::new(|_| rayon::current_num_threads()) as BoxedOperation<usize>]).unwrap();
+            root
+        };
+        let pool = ThreadPoolBuilder::new().num_threads(3).build().unwrap();
+        let outcome = run_batch_parallel(factory, vec![(1, 0), (2, 0)], &pool).unwrap();
+        assert_eq!(vec![ChainResults::from([(0, 3)]); 2], outcome.results.into_values().collect::<Vec<_>>());
+        let outcome = run_batch_parallel(factory, vec![(1, 0)], Threads::Count(2)).unwrap();
+        assert_eq!(ChainResults::from([(0, 2)]), outcome.results[&1]);
+    }
+
+    #[test]
+    fn failing_entities_are_reported() {
+        let root = EventDAG::new_node(Box::new(|x: i32| x));
+        let operations: Vec<BoxedOperation<i32>> = vec![
+            Box::new(|x| x + 1),
+            Box::new(|x| if x < 0 { panic!("negative state") } else { x })
+        ];
+        alternatives(vec![root.clone()], operations).unwrap()[1].borrow_mut().set_label("check");
+
+        let outcome = run_batch(&root, [(1, 1), (2, -1), (3, 3)]);
+        assert_eq!(vec![1, 3], outcome.results.keys().copied().collect::<Vec<_>>());
+        assert_eq!(vec![EntityFailure {
+            entity: 2,
+            failure: EvaluationFailure { chain: 1, operation: 1, label: Some("check".to_string()), error: "negative state".to_string() }
+        }], outcome.failures);
+        assert_eq!("entity 2: chain 1 failed at check (operation 1): negative state\n", outcome.failure_report());
+    }
+
+    #[test]
+    fn seeded_batches_are_reproducible() {
+        let factory = || {
+            let root = EventDAG::new_node(Box::new(|payload: Stochastic<f64>| payload));
+            let noise = || -> BoxedOperation<Stochastic<f64>> {
+                Box::new(|mut payload: Stochastic<f64>| Stochastic { state: payload.state + payload.rng.next_f64(), ..payload })
+            };
+            alternatives(vec![root.clone()], [noise(), noise()]).unwrap();
+            root
+        };
+        let entities: Vec<(u32, f64)> = (0..200).map(|id| (id, 0.0)).collect();
+        let sequential = run_seeded_batch(&factory(), entities.clone(), 42);
+        let single = run_seeded_batch_parallel(factory, entities.clone(), 1, 42).unwrap();
+        let parallel = run_seeded_batch_parallel(factory, entities.clone(), 4, 42).unwrap();
+        assert_eq!(sequential, single);
+        assert_eq!(sequential, parallel);
+        assert_ne!(sequential.results[&0][&0].state, sequential.results[&0][&1].state);
+        assert_ne!(sequential, run_seeded_batch(&factory(), entities, 43));
+    }
+}
@@ -1,58 +1,97 @@
 use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
 use std::rc::Rc;
 use super::event_graph::*;
-pub type GeneratorFn<T> = fn(EventNodes<T>, OperationChain<T>) -> EventNodes<T>;
+pub type GeneratorResult<T> = Result<EventNodes<T>, GeneratorError>;
+pub type GeneratorFn<T> = fn(EventNodes<T>, OperationChain<T>) -> GeneratorResult<T>;
 
-/// Generate a linear sequence of EventNodes from an OperationChain. Attach it as a follower
-/// into each of the given EventNodes.
-pub fn sequence<T: Copy + 'static>(previous: EventNodes<T>, operations: OperationChain<T>) -> EventNodes<T> {
+/// Reasons for a generator to refuse extending the graph.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GeneratorError {
+    /// The generator was given no EventNodes to attach its operations into.
+    EmptyFrontier { generator: &'static str },
+    /// The generator was given no operations to generate EventNodes from.
+    EmptyOperations { generator: &'static str },
+    /// The same EventNode would be attached twice as a follower of another EventNode.
+    DuplicateFollower { generator: &'static str },
+}
 
-    if operations.len() == 0 {
-        previous
-    }
-    else {
-        let mut nodes = EventNodes::new();
-        for op in operations {
-            nodes.push(EventDAG::new_node(Box::new(op)));
+impl fmt::Display for GeneratorError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GeneratorError::EmptyFrontier { generator } =>
+                write!(f, "{generator}: no preceding event nodes to attach into"),
+            GeneratorError::EmptyOperations { generator } =>
+                write!(f, "{generator}: no operations given to generate event nodes from"),
+            GeneratorError::DuplicateFollower { generator } =>
+                write!(f, "{generator}: event node would be attached twice into the same preceding node"),
         }
-        let leaf = nodes.iter().reduce(|acc, cur| {
-                acc.borrow_mut().add_follower_node(&cur);
-                cur
-            }).unwrap();
-        let new_root = &nodes[0];
-        for prev in previous {
-            prev.borrow_mut().add_follower_node(new_root);
-        }
-        vec![Rc::clone(leaf)]
     }
 }
 
-/// Generate a collection of individual EventNodes from an OperationChain. Attach each of them as a
-/// follower into each of the given EventNodes.
-pub fn alternatives<T: Copy + 'static>(previous: EventNodes<T>, operations: OperationChain<T>) -> EventNodes<T> {
-
-    if operations.len() == 0 {
-        previous
-    } else {
-        let mut nodes = EventNodes::new();
-        for op in operations {
-            nodes.push(EventDAG::new_node(Box::new(op)));
+impl Error for GeneratorError {}
+
+/// Validate generator inputs, materializing the operations into new EventNodes.
+fn prepare<T, I>(generator: &'static str, previous: &EventNodes<T>, operations: I) -> GeneratorResult<T>
+where T: Clone + 'static, I: IntoIterator<Item = BoxedOperation<T>> {
+    if previous.is_empty() {
+        return Err(GeneratorError::EmptyFrontier { generator });
+    }
+    let nodes: EventNodes<T> = operations.into_iter().map(EventDAG::new_node).collect();
+    if nodes.is_empty() {
+        return Err(GeneratorError::EmptyOperations { generator });
+    }
+    Ok(nodes)
+}
+
+/// Attach each of the nodes as a follower into each of the previous EventNodes, refusing
+/// duplicate attachment, including by a node given twice in previous, before attaching any.
+fn attach<T: Clone>(generator: &'static str, previous: &[EventNode<T>], nodes: &[EventNode<T>]) -> Result<(), GeneratorError> {
+    for (index, prev) in previous.iter().enumerate() {
+        let repeated = previous[..index].iter().any(|earlier| Rc::ptr_eq(earlier, prev));
+        if repeated || nodes.iter().any(|node| prev.borrow().has_follower(node)) {
+            return Err(GeneratorError::DuplicateFollower { generator });
         }
-        for prev in previous {
-            for node in nodes.iter() {
-                prev.borrow_mut().add_follower_node(&node)
-            }
+    }
+    for prev in previous {
+        for node in nodes {
+            prev.borrow_mut().add_follower_node(node);
         }
-        nodes
     }
+    Ok(())
+}
+
+/// Generate a linear sequence of EventNodes from an iterable of BoxedOperations. Attach it as a
+/// follower into each of the given EventNodes.
+pub fn sequence<T, I>(previous: EventNodes<T>, operations: I) -> GeneratorResult<T>
+where T: Clone + 'static, I: IntoIterator<Item = BoxedOperation<T>> {
+
+    let nodes = prepare("sequence", &previous, operations)?;
+    let leaf = nodes.iter().reduce(|acc, cur| {
+            acc.borrow_mut().add_follower_node(cur);
+            cur
+        }).unwrap();
+    attach("sequence", &previous, &nodes[..1])?;
+    Ok(vec![Rc::clone(leaf)])
+}
+
+/// Generate a collection of individual EventNodes from an iterable of BoxedOperations. Attach
+/// each of them as a follower into each of the given EventNodes.
+pub fn alternatives<T, I>(previous: EventNodes<T>, operations: I) -> GeneratorResult<T>
+where T: Clone + 'static, I: IntoIterator<Item = BoxedOperation<T>> {
+
+    let nodes = prepare("alternatives", &previous, operations)?;
+    attach("alternatives", &previous, &nodes)?;
+    Ok(nodes)
 }
 
 
 /// Get a map of generator functions resolvable from strings.
-pub fn generator_map<T: Copy + 'static>() -> HashMap<&'static str, GeneratorFn<T>> {
+pub fn generator_map<T: Clone + 'static>() -> HashMap<&'static str, GeneratorFn<T>> {
     HashMap::from([
-        ("sequence", sequence as GeneratorFn<T>),
-        ("alternatives", alternatives as GeneratorFn<T>)
+        ("sequence", sequence::<T, OperationChain<T>> as GeneratorFn<T>),
+        ("alternatives", alternatives::<T, OperationChain<T>> as GeneratorFn<T>)
     ])
 }
 
@@ -77,8 +116,8 @@ mod tests {
         let map = generator_map();
         let gen_fn = map.get("sequence").unwrap();
         let generator_root = EventDAG::new_node(Box::new(do_nothing));
-        let graph = gen_fn(vec![Rc::clone(&generator_root)], create_ops(increment, 2));
-        let mut payload = 0;
+        let graph = gen_fn(vec![Rc::clone(&generator_root)], create_ops(increment, 2)).unwrap();
+        let payload = 0;
         let result = generator_root.borrow().evaluate_depth(payload);
         assert_eq!(1, graph.len());
         assert_eq!(2, result[0])
@@ -88,11 +127,11 @@ mod tests {
     fn test_graph_extending() {
         let generator_root = EventDAG::new_node(Box::new(do_nothing));
 
-        let level_1 = sequence(vec![generator_root.clone()], create_ops(increment, 2));
-        let level_2 = alternatives(level_1, create_ops(increment, 2));
-        let level_3 = alternatives(level_2, create_ops(increment, 2));
-        let mut payload_one = 0;
-        let mut payload_two = 0;
+        let level_1 = sequence(vec![generator_root.clone()], create_ops(increment, 2)).unwrap();
+        let level_2 = alternatives(level_1, create_ops(increment, 2)).unwrap();
+        let level_3 = alternatives(level_2, create_ops(increment, 2)).unwrap();
+        let payload_one = 0;
+        let payload_two = 0;
         let chains_results = EventDAG::evaluate_chains(&generator_root, payload_one);
         let depth_results = generator_root.borrow().evaluate_depth( payload_two);
 
@@ -100,4 +139,36 @@ mod tests {
         assert_eq!(vec![4, 4, 4, 4], depth_results);
         assert_eq!(level_3.len(), 2);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_closure_iterators() {
+        let generator_root = EventDAG::new_node(Box::new(do_nothing));
+        let steps = (1..=3).map(|step| -> BoxedOperation<i32> { Box::new(move |x| x + step) });
+        let level_1 = sequence(vec![generator_root.clone()], steps).unwrap();
+        let factors = [2, 3].into_iter().map(|factor| -> BoxedOperation<i32> { Box::new(move |x| x * factor) });
+        let level_2 = alternatives(level_1, factors).unwrap();
+        let results = generator_root.borrow().evaluate_depth(0);
+
+        assert_eq!(vec![12, 18], results);
+        assert_eq!(level_2.len(), 2);
+    }
+
+    #[test]
+    fn test_invalid_inputs_are_rejected() {
+        let generator_root = EventDAG::new_node(Box::new(do_nothing));
+
+        let empty_frontier = sequence(vec![], create_ops(increment, 1));
+        assert_eq!(Some(GeneratorError::EmptyFrontier { generator: "sequence" }), empty_frontier.err());
+
+        let empty_operations = alternatives(vec![generator_root.clone()], create_ops(increment, 0));
+        assert_eq!(Some(GeneratorError::EmptyOperations { generator: "alternatives" }), empty_operations.err());
+
+        let duplicate = alternatives(vec![generator_root.clone(), generator_root.clone()], create_ops(increment, 1));
+        assert_eq!(Some(GeneratorError::DuplicateFollower { generator: "alternatives" }), duplicate.err());
+        assert!(generator_root.borrow().is_leaf());
+        let other_root = EventDAG::new_node(Box::new(do_nothing));
+        let duplicate = sequence(vec![other_root.clone(), generator_root.clone(), other_root.clone()], create_ops(increment, 1));
+        assert_eq!(Some(GeneratorError::DuplicateFollower { generator: "sequence" }), duplicate.err());
+        assert!(other_root.borrow().is_leaf() && generator_root.borrow().is_leaf());
+    }
+}
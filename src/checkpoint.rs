@@ -0,0 +1,333 @@
+use std::collections::BTreeSet;
+use std::fs::{self, File, OpenOptions};
+use std::io::{ErrorKind, Read, Result, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use rayon::ThreadPoolBuildError;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use crate::batch_runner::{run_batch, BatchOutcome, EntityFailure, PooledRun, Threads};
+use crate::event_graph::{ChainResults, EventNode};
+
+const RESULTS_FILE: &str = "results.jsonl";
+const FAILURES_FILE: &str = "failures.jsonl";
+const MARKER_FILE: &str = "progress.json";
+
+/// Checkpointed results of a single entity, one JSON line in the results file.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EntityRecord<K, T> {
+    pub entity: K,
+    pub results: ChainResults<T>,
+}
+
+/// Progress marker of a checkpointed batch run. Only the leading bytes of the results and
+/// failures files covered by the marker are complete, as a killed run may leave a partially
+/// written tail behind.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProgressMarker {
+    pub completed_entities: usize,
+    pub results_bytes: u64,
+    pub failures_bytes: u64,
+}
+
+/// Checkpoint configures writing the completed entities of a batch run into a directory after
+/// every interval of entities, so that a killed run loses at most the entities in flight.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Checkpoint {
+    directory: PathBuf,
+    interval: usize,
+}
+
+impl Checkpoint {
+    /// Construct a Checkpoint writing into the given directory after every interval of entities.
+    /// An interval of 0 is treated as 1.
+    pub fn new<P: Into<PathBuf>>(directory: P, interval: usize) -> Checkpoint {
+        Checkpoint { directory: directory.into(), interval: interval.max(1) }
+    }
+
+    pub fn directory(&self) -> &Path {
+        &self.directory
+    }
+
+    pub fn interval(&self) -> usize {
+        self.interval
+    }
+
+    /// Path of the JSON Lines file of completed entity results.
+    pub fn results_path(&self) -> PathBuf {
+        self.directory.join(RESULTS_FILE)
+    }
+
+    /// Path of the JSON Lines file of failed entities.
+    pub fn failures_path(&self) -> PathBuf {
+        self.directory.join(FAILURES_FILE)
+    }
+
+    /// Path of the progress marker file.
+    pub fn marker_path(&self) -> PathBuf {
+        self.directory.join(MARKER_FILE)
+    }
+
+    /// Atomically replace the progress marker.
+    fn write_marker(&self, marker: &ProgressMarker) -> Result<()> {
+        let temporary = self.directory.join(format!("{MARKER_FILE}.tmp"));
+        let mut file = File::create(&temporary)?;
+        serde_json::to_writer(&mut file, marker)?;
+        file.sync_all()?;
+        fs::rename(temporary, self.marker_path())
+    }
+
+    /// Start a checkpointed run from the given progress, truncating the checkpoint files to it.
+    fn start(&self, marker: ProgressMarker) -> Result<CheckpointWriter<'_>> {
+        fs::create_dir_all(&self.directory)?;
+        let open = |path: PathBuf, length: u64| -> Result<File> {
+            let file = OpenOptions::new().create(true).write(true).truncate(false).open(path)?;
+            file.set_len(length)?;
+            Ok(file)
+        };
+        let results = open(self.results_path(), marker.results_bytes)?;
+        let failures = open(self.failures_path(), marker.failures_bytes)?;
+        self.write_marker(&marker)?;
+        Ok(CheckpointWriter { checkpoint: self, results, failures, marker })
+    }
+}
+
+/// Appends completed entities into the checkpoint files and advances the progress marker.
+struct CheckpointWriter<'a> {
+    checkpoint: &'a Checkpoint,
+    results: File,
+    failures: File,
+    marker: ProgressMarker,
+}
+
+/// Borrowed form of EntityRecord for serialization.
+#[derive(Serialize)]
+struct EntityRecordRef<'a, K, T> {
+    entity: &'a K,
+    results: &'a ChainResults<T>,
+}
+
+impl CheckpointWriter<'_> {
+    fn append<K: Ord + Serialize, T: Serialize>(&mut self, outcome: &BatchOutcome<K, T>, completed: &[K]) -> Result<()> {
+        let mut results = Vec::new();
+        for entity in completed {
+            if let Some(states) = outcome.results.get(entity) {
+                serde_json::to_writer(&mut results, &EntityRecordRef { entity, results: states })?;
+                results.write_all(b"\n")?;
+            }
+        }
+        let mut failures = Vec::new();
+        for failure in outcome.failures.iter() {
+            serde_json::to_writer(&mut failures, failure)?;
+            failures.write_all(b"\n")?;
+        }
+        append_synced(&mut self.results, &results)?;
+        append_synced(&mut self.failures, &failures)?;
+        self.marker.completed_entities += completed.len();
+        self.marker.results_bytes += results.len() as u64;
+        self.marker.failures_bytes += failures.len() as u64;
+        self.checkpoint.write_marker(&self.marker)
+    }
+}
+
+fn append_synced(file: &mut File, bytes: &[u8]) -> Result<()> {
+    file.seek(SeekFrom::End(0))?;
+    file.write_all(bytes)?;
+    file.sync_data()
+}
+
+/// Merge the outcome of a completed interval into the outcome of the whole run.
+fn merge<K: Ord, T>(total: &mut BatchOutcome<K, T>, interval: BatchOutcome<K, T>) {
+    total.results.extend(interval.results);
+    total.failures.extend(interval.failures);
+}
+
+/// Evaluate the given entities interval by interval, appending each completed interval into the
+/// checkpoint continuing from the given progress and previous outcome.
+fn run_intervals<K, T, I, E>(checkpoint: &Checkpoint, progress: ProgressMarker, previous: BatchOutcome<K, T>, entities: I, mut evaluate: E) -> Result<BatchOutcome<K, T>>
+where K: Ord + Clone + Serialize, T: Serialize, I: IntoIterator<Item = (K, T)>, E: FnMut(Vec<(K, T)>) -> Result<BatchOutcome<K, T>> {
+    let mut writer = checkpoint.start(progress)?;
+    let mut total = previous;
+    let mut entities = entities.into_iter().peekable();
+    while entities.peek().is_some() {
+        let interval: Vec<(K, T)> = entities.by_ref().take(checkpoint.interval).collect();
+        let completed: Vec<K> = interval.iter().map(|(id, _)| id.clone()).collect();
+        let outcome = evaluate(interval)?;
+        writer.append(&outcome, &completed)?;
+        merge(&mut total, outcome);
+    }
+    Ok(total)
+}
+
+fn empty_outcome<K, T>() -> BatchOutcome<K, T> {
+    BatchOutcome { results: Default::default(), failures: Vec::new() }
+}
+
+/// Evaluate the event graph for each of the given entities as run_batch does, writing the
+/// completed entities into the checkpoint after every interval of entities.
+pub fn run_batch_checkpointed<K, T, I>(root: &EventNode<T>, entities: I, checkpoint: &Checkpoint) -> Result<BatchOutcome<K, T>>
+where K: Ord + Clone + Serialize, T: Clone + Serialize, I: IntoIterator<Item = (K, T)> {
+    run_intervals(checkpoint, ProgressMarker::default(), empty_outcome(), entities, |interval| Ok(run_batch(root, interval)))
+}
+
+/// Evaluate the event graph for each of the given entities in parallel as run_batch_parallel
+/// does, writing the completed entities into the checkpoint after every interval of entities.
+/// All intervals are evaluated in the same thread pool, each worker thread building its graph
+/// once for the whole run.
+pub fn run_batch_parallel_checkpointed<'p, K, T, F>(graph_factory: F, entities: Vec<(K, T)>, threads: impl Into<Threads<'p>>, checkpoint: &Checkpoint) -> Result<BatchOutcome<K, T>>
+where K: Ord + Clone + Send + Serialize, T: Clone + Send + Serialize + 'static, F: Fn() -> EventNode<T> + Sync {
+    threads.into().with_pool(|pool| {
+        let run = PooledRun::new(pool, graph_factory);
+        run_intervals(checkpoint, ProgressMarker::default(), empty_outcome(), entities, |interval| Ok(run.run_batch(interval)))
+    }).map_err(thread_pool_error)?
+}
+
+/// Resume a checkpointed run as run_batch_checkpointed, skipping the entities completed in the
+/// checkpoint. The returned outcome merges the checkpointed and newly evaluated entities. Without
+/// a progress marker in the checkpoint the run starts from the beginning.
+pub fn resume_batch_checkpointed<K, T, I>(root: &EventNode<T>, entities: I, checkpoint: &Checkpoint) -> Result<BatchOutcome<K, T>>
+where K: Ord + Clone + Serialize + DeserializeOwned, T: Clone + Serialize + DeserializeOwned, I: IntoIterator<Item = (K, T)> {
+    let (progress, previous, completed) = checkpoint.completed()?;
+    let remaining = entities.into_iter().filter(|(id, _)| !completed.contains(id));
+    run_intervals(checkpoint, progress, previous, remaining, |interval| Ok(run_batch(root, interval)))
+}
+
+/// Resume a checkpointed run as run_batch_parallel_checkpointed, skipping the entities completed
+/// in the checkpoint as resume_batch_checkpointed does.
+pub fn resume_batch_parallel_checkpointed<'p, K, T, F>(graph_factory: F, entities: Vec<(K, T)>, threads: impl Into<Threads<'p>>, checkpoint: &Checkpoint) -> Result<BatchOutcome<K, T>>
+where K: Ord + Clone + Send + Serialize + DeserializeOwned, T: Clone + Send + Serialize + DeserializeOwned + 'static, F: Fn() -> EventNode<T> + Sync {
+    let (progress, previous, completed) = checkpoint.completed()?;
+    let remaining = entities.into_iter().filter(|(id, _)| !completed.contains(id));
+    threads.into().with_pool(|pool| {
+        let run = PooledRun::new(pool, graph_factory);
+        run_intervals(checkpoint, progress, previous, remaining, |interval| Ok(run.run_batch(interval)))
+    }).map_err(thread_pool_error)?
+}
+
+fn thread_pool_error(err: ThreadPoolBuildError) -> std::io::Error {
+    std::io::Error::other(err)
+}
+
+/// Read the JSON lines of the given file within the first length bytes.
+fn read_lines<R: DeserializeOwned>(path: PathBuf, length: u64) -> Result<Vec<R>> {
+    let mut content = Vec::new();
+    match File::open(path) {
+        Ok(file) => { file.take(length).read_to_end(&mut content)?; }
+        Err(err) if err.kind() == ErrorKind::NotFound && length == 0 => {}
+        Err(err) => return Err(err),
+    }
+    content.split(|byte| *byte == b'\n')
+        .filter(|line| !line.is_empty())
+        .map(|line| Ok(serde_json::from_slice(line)?))
+        .collect()
+}
+
+impl Checkpoint {
+    /// Read the progress marker of the checkpoint, if one has been written.
+    pub fn progress(&self) -> Result<Option<ProgressMarker>> {
+        match File::open(self.marker_path()) {
+            Ok(file) => Ok(Some(serde_json::from_reader(file)?)),
+            Err(err) if err.kind() == ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Read the checkpointed outcome of the entities covered by the progress marker.
+    pub fn read_outcome<K: Ord + DeserializeOwned, T: DeserializeOwned>(&self) -> Result<BatchOutcome<K, T>> {
+        let progress = self.progress()?.unwrap_or_default();
+        let records: Vec<EntityRecord<K, T>> = read_lines(self.results_path(), progress.results_bytes)?;
+        let failures: Vec<EntityFailure<K>> = read_lines(self.failures_path(), progress.failures_bytes)?;
+        Ok(BatchOutcome {
+            results: records.into_iter().map(|record| (record.entity, record.results)).collect(),
+            failures
+        })
+    }
+
+    /// Progress, checkpointed outcome and ids of completed entities for resuming a run.
+    fn completed<K: Ord + Clone + DeserializeOwned, T: DeserializeOwned>(&self) -> Result<(ProgressMarker, BatchOutcome<K, T>, BTreeSet<K>)> {
+        let progress = self.progress()?.unwrap_or_default();
+        let previous: BatchOutcome<K, T> = self.read_outcome()?;
+        let completed = previous.results.keys().cloned()
+            .chain(previous.failures.iter().map(|failure| failure.entity.clone()))
+            .collect();
+        Ok((progress, previous, completed))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use crate::branching_generators::alternatives;
+    use crate::event_graph::{BoxedOperation, EventDAG};
+    use super::*;
+
+    fn create_graph() -> EventNode<i32> {
+        let root = EventDAG::new_node(Box::new(|x: i32| x));
+        let operations: Vec<BoxedOperation<i32>> = vec![
+            Box::new(|x| x + 1),
+            Box::new(|x| if x == 13 { panic!("unlucky") } else { x * 2 })
+        ];
+        alternatives(vec![root.clone()], operations).unwrap();
+        root
+    }
+
+    fn read_marker(checkpoint: &Checkpoint) -> ProgressMarker {
+        serde_json::from_reader(File::open(checkpoint.marker_path()).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn completed_entities_are_checkpointed() {
+        let directory = tempfile::tempdir().unwrap();
+        let checkpoint = Checkpoint::new(directory.path(), 4);
+        let outcome = run_batch_checkpointed(&create_graph(), (0..10).map(|id| (id, id + 10)), &checkpoint).unwrap();
+        assert_eq!(9, outcome.results.len());
+
+        let marker = read_marker(&checkpoint);
+        assert_eq!(10, marker.completed_entities);
+        let content = fs::read_to_string(checkpoint.results_path()).unwrap();
+        assert_eq!(9, content.lines().count());
+        assert_eq!(marker.results_bytes, content.len() as u64);
+        let record: EntityRecord<i32, i32> = serde_json::from_str(content.lines().next().unwrap()).unwrap();
+        assert_eq!(EntityRecord { entity: 0, results: ChainResults::from([(0, 11), (1, 20)]) }, record);
+        let checkpointed: BatchOutcome<i32, i32> = checkpoint.read_outcome().unwrap();
+        assert_eq!(outcome, checkpointed);
+        assert_eq!(3, checkpointed.failures[0].entity);
+    }
+
+    #[test]
+    fn parallel_runs_are_checkpointed() {
+        let directory = tempfile::tempdir().unwrap();
+        let checkpoint = Checkpoint::new(directory.path(), 16);
+        let entities: Vec<(i32, i32)> = (0..100).map(|id| (id, id)).collect();
+        let built = AtomicUsize::new(0);
+        let counting = || {
+            built.fetch_add(1, Ordering::Relaxed);
+            create_graph()
+        };
+        let outcome = run_batch_parallel_checkpointed(counting, entities.clone(), 2, &checkpoint).unwrap();
+        assert_eq!(run_batch(&create_graph(), entities), outcome);
+        assert!(built.into_inner() <= 2);
+        assert_eq!(100, read_marker(&checkpoint).completed_entities);
+        assert_eq!(99, fs::read_to_string(checkpoint.results_path()).unwrap().lines().count());
+    }
+
+    #[test]
+    fn killed_runs_are_resumed() {
+        let directory = tempfile::tempdir().unwrap();
+        let checkpoint = Checkpoint::new(directory.path(), 5);
+        let root = create_graph();
+        run_batch_checkpointed(&root, (0..12).map(|id| (id, id + 10)), &checkpoint).unwrap();
+        let mut results = OpenOptions::new().append(true).open(checkpoint.results_path()).unwrap();
+        results.write_all(b"{\"entity\":12,\"resu").unwrap();
+
+        let all: Vec<(i32, i32)> = (0..20).map(|id| (id, id + 10)).collect();
+        let resumed = resume_batch_checkpointed(&root, all.clone(), &checkpoint).unwrap();
+        let expected = run_batch(&root, all.clone());
+        assert_eq!(expected, resumed);
+        assert_eq!(20, read_marker(&checkpoint).completed_entities);
+        assert_eq!(expected, checkpoint.read_outcome().unwrap());
+
+        let again = resume_batch_parallel_checkpointed(create_graph, all, 2, &checkpoint).unwrap();
+        assert_eq!(expected, again);
+        assert_eq!(20, read_marker(&checkpoint).completed_entities);
+    }
+}
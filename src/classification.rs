@@ -0,0 +1,89 @@
+use std::collections::{BTreeMap, BTreeSet};
+use crate::event_graph::{EvaluationFailure, EventDAG, EventNode};
+
+/// Tags of the nodes of an event chain.
+pub type TagSet = BTreeSet<&'static str>;
+
+/// Result of an event chain annotated with the tags encountered along the chain.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TaggedResult<T> {
+    /// Index of the event chain in evaluation order.
+    pub chain: usize,
+    pub tags: TagSet,
+    pub result: T,
+}
+
+/// Evaluate the unique event chains as EventDAG::try_evaluate_chains, annotating the results
+/// with the tags of the nodes of their chains, such as the management regimes of the chains.
+pub fn try_evaluate_tagged<T: Clone>(root: &EventNode<T>, payload: T) -> Result<Vec<TaggedResult<T>>, EvaluationFailure> {
+    let chains = EventDAG::node_chains(root);
+    let results = EventDAG::try_evaluate_indexed(&chains, |_| payload.clone())?;
+    Ok(results.into_iter()
+        .map(|(chain, result)| {
+            let tags = chains[chain].iter().flat_map(|node| node.borrow().tags().to_vec()).collect();
+            TaggedResult { chain, tags, result }
+        })
+        .collect())
+}
+
+/// Group the tagged results by their tag sets, keeping the evaluation order within groups.
+pub fn results_by_tagset<T>(results: Vec<TaggedResult<T>>) -> BTreeMap<TagSet, Vec<TaggedResult<T>>> {
+    let mut groups: BTreeMap<TagSet, Vec<TaggedResult<T>>> = BTreeMap::new();
+    for result in results {
+        groups.entry(result.tags.clone()).or_default().push(result);
+    }
+    groups
+}
+
+/// Group the tagged results by each of their tags, a result belonging to the groups of all of
+/// its tags.
+pub fn results_by_tag<T: Clone>(results: &[TaggedResult<T>]) -> BTreeMap<&'static str, Vec<TaggedResult<T>>> {
+    let mut groups: BTreeMap<&'static str, Vec<TaggedResult<T>>> = BTreeMap::new();
+    for result in results {
+        for tag in result.tags.iter() {
+            groups.entry(*tag).or_default().push(result.clone());
+        }
+    }
+    groups
+}
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+    use crate::branching_generators::{alternatives, sequence};
+    use crate::event_graph::{prune, BoxedOperation};
+    use super::*;
+
+    fn regimes() -> EventNode<i32> {
+        let root = EventDAG::new_node(Box::new(|x: i32| x));
+        let operations: Vec<BoxedOperation<i32>> = vec![Box::new(|x| x + 1), Box::new(|x| x + 2), Box::new(|x| if x > 0 { prune() } else { x })];
+        let regimes = alternatives(vec![Rc::clone(&root)], operations).unwrap();
+        regimes[0].borrow_mut().add_tag("thinning");
+        regimes[1].borrow_mut().add_tag("thinning");
+        regimes[1].borrow_mut().add_tag("fertilization");
+        let end = sequence(regimes, [Box::new(|x| x * 10) as BoxedOperation<i32>]).unwrap();
+        end[0].borrow_mut().add_tag("clearcut");
+        root
+    }
+
+    #[test]
+    fn results_are_annotated_with_tags() {
+        let results = try_evaluate_tagged(&regimes(), 1).unwrap();
+        assert_eq!(vec![
+            TaggedResult { chain: 0, tags: TagSet::from(["clearcut", "thinning"]), result: 20 },
+            TaggedResult { chain: 1, tags: TagSet::from(["clearcut", "fertilization", "thinning"]), result: 30 },
+        ], results);
+        assert_eq!(3, try_evaluate_tagged(&regimes(), 0).unwrap().len());
+    }
+
+    #[test]
+    fn results_are_grouped_by_tags() {
+        let results = try_evaluate_tagged(&regimes(), 0).unwrap();
+        let by_tag = results_by_tag(&results);
+        assert_eq!(vec![10, 20, 0], by_tag["clearcut"].iter().map(|tagged| tagged.result).collect::<Vec<_>>());
+        assert_eq!(vec![10, 20], by_tag["thinning"].iter().map(|tagged| tagged.result).collect::<Vec<_>>());
+        let by_tagset = results_by_tagset(results);
+        assert_eq!(3, by_tagset.len());
+        assert_eq!(2, by_tagset[&TagSet::from(["clearcut"])][0].chain);
+    }
+}
@@ -0,0 +1,96 @@
+use std::error::Error;
+use std::fmt;
+
+pub const USAGE: &str = "\
+Options:
+  --dry-run    compile and validate the simulation, report graph statistics and warnings and exit
+  --resume     continue a checkpointed run, skipping the entities already completed
+  --scenarios NAME[,NAME...]
+               evaluate only the named scenarios
+  --help       print this help";
+
+/// Command line options common to simulation programs built on this library.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CliOptions {
+    pub dry_run: bool,
+    pub resume: bool,
+    /// Names of the scenarios to evaluate, all branches being evaluated if empty.
+    pub scenarios: Vec<String>,
+    pub help: bool,
+    /// Arguments not recognized as options, in the given order.
+    pub arguments: Vec<String>,
+}
+
+/// Command line parsing failure.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CliError {
+    UnknownOption(String),
+    MissingValue(String),
+}
+
+impl fmt::Display for CliError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CliError::UnknownOption(option) => write!(f, "unknown option '{option}'\n{USAGE}"),
+            CliError::MissingValue(option) => write!(f, "missing value for option '{option}'\n{USAGE}"),
+        }
+    }
+}
+
+impl Error for CliError {}
+
+impl CliOptions {
+    /// Parse options from the given arguments, excluding the program name.
+    pub fn parse<I: IntoIterator<Item = String>>(args: I) -> Result<CliOptions, CliError> {
+        let mut options = CliOptions::default();
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--dry-run" => options.dry_run = true,
+                "--resume" => options.resume = true,
+                "--scenarios" => {
+                    let names = args.next().ok_or_else(|| CliError::MissingValue(arg.clone()))?;
+                    options.scenarios.extend(names.split(',').map(str::to_string));
+                }
+                option if option.starts_with("--scenarios=") =>
+                    options.scenarios.extend(option["--scenarios=".len()..].split(',').map(str::to_string)),
+                "--help" | "-h" => options.help = true,
+                option if option.starts_with('-') => return Err(CliError::UnknownOption(arg)),
+                _ => options.arguments.push(arg),
+            }
+        }
+        Ok(options)
+    }
+
+    /// Parse options from the arguments of the current process.
+    pub fn from_env() -> Result<CliOptions, CliError> {
+        CliOptions::parse(std::env::args().skip(1))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn options_are_parsed() {
+        let options = CliOptions::parse(args(&["input.csv", "--dry-run"])).unwrap();
+        assert!(options.dry_run);
+        assert!(!options.resume);
+        assert!(CliOptions::parse(args(&["--resume"])).unwrap().resume);
+        assert_eq!(vec!["input.csv".to_string()], options.arguments);
+        assert_eq!(Err(CliError::UnknownOption("--fast".to_string())), CliOptions::parse(args(&["--fast"])));
+    }
+
+    #[test]
+    fn scenarios_are_parsed() {
+        let options = CliOptions::parse(args(&["--scenarios", "no_thin,heavy_thin", "--scenarios=clearcut"])).unwrap();
+        assert_eq!(vec!["no_thin", "heavy_thin", "clearcut"], options.scenarios);
+        assert!(options.arguments.is_empty());
+        assert_eq!(Err(CliError::MissingValue("--scenarios".to_string())), CliOptions::parse(args(&["--scenarios"])));
+    }
+}
@@ -0,0 +1,188 @@
+use std::collections::BTreeMap;
+use std::panic::{catch_unwind, resume_unwind, AssertUnwindSafe};
+use std::rc::Rc;
+use crate::event_graph::{is_pruned, EventDAG, EventNode};
+use crate::time_points::{SimTime, TimePoint};
+
+pub type Projector<T, V> = fn(&T) -> V;
+
+/// Points of evaluation at which a Collector observes the simulated state.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CollectionPoint {
+    /// After each operation of the event chain.
+    Operation,
+    /// At the end of each time point, that is before each time point boundary following the
+    /// first one and at the end of the event chain.
+    TimePoint,
+    /// At the end of the event chain.
+    ChainEnd,
+}
+
+/// Collector observes simulated states of event chains during evaluation.
+pub trait Collector<T> {
+    /// Point of evaluation at which this collector observes states.
+    fn point(&self) -> CollectionPoint;
+    /// Observe the state of the event chain with the given index.
+    fn observe(&mut self, chain: usize, state: &T);
+}
+
+/// Evaluate unique event chains starting from the given EventNode, invoking the collectors at
+/// their collection points. Time points begin at the given boundary EventNodes. Pruned chains
+/// produce no results, the collectors having observed their states preceding the pruning.
+pub fn evaluate_collecting<T: Clone>(
+    root: &EventNode<T>,
+    payload: T,
+    time_point_boundaries: &[EventNode<T>],
+    collectors: &mut [&mut dyn Collector<T>]
+) -> Vec<T> {
+    let notify = |collectors: &mut [&mut dyn Collector<T>], point: CollectionPoint, chain: usize, state: &T| {
+        for collector in collectors.iter_mut().filter(|collector| collector.point() == point) {
+            collector.observe(chain, state);
+        }
+    };
+    let mut results = Vec::new();
+    'chains: for (chain_index, chain) in EventDAG::node_chains(root).iter().enumerate() {
+        let mut current = payload.clone();
+        let mut time_point_started = false;
+        for node in chain {
+            if time_point_boundaries.iter().any(|boundary| Rc::ptr_eq(boundary, node)) {
+                if time_point_started {
+                    notify(collectors, CollectionPoint::TimePoint, chain_index, &current);
+                }
+                time_point_started = true;
+            }
+            current = match catch_unwind(AssertUnwindSafe(|| node.borrow().apply(current))) {
+                Ok(current) => current,
+                Err(payload) if is_pruned(&*payload) => continue 'chains,
+                Err(payload) => resume_unwind(payload),
+            };
+            notify(collectors, CollectionPoint::Operation, chain_index, &current);
+        }
+        notify(collectors, CollectionPoint::TimePoint, chain_index, &current);
+        notify(collectors, CollectionPoint::ChainEnd, chain_index, &current);
+        results.push(current);
+    }
+    results
+}
+
+/// Collect the last observed projected value of each event chain.
+pub struct LastValue<T, V> {
+    point: CollectionPoint,
+    projector: Projector<T, V>,
+    pub values: BTreeMap<usize, V>,
+}
+
+impl<T, V> LastValue<T, V> {
+    pub fn new(point: CollectionPoint, projector: Projector<T, V>) -> LastValue<T, V> {
+        LastValue { point, projector, values: BTreeMap::new() }
+    }
+}
+
+impl<T, V> Collector<T> for LastValue<T, V> {
+    fn point(&self) -> CollectionPoint {
+        self.point
+    }
+
+    fn observe(&mut self, chain: usize, state: &T) {
+        self.values.insert(chain, (self.projector)(state));
+    }
+}
+
+/// Collect the sum of observed projected values of each event chain.
+pub struct Sum<T> {
+    point: CollectionPoint,
+    projector: Projector<T, f64>,
+    pub sums: BTreeMap<usize, f64>,
+}
+
+impl<T> Sum<T> {
+    pub fn new(point: CollectionPoint, projector: Projector<T, f64>) -> Sum<T> {
+        Sum { point, projector, sums: BTreeMap::new() }
+    }
+}
+
+impl<T> Collector<T> for Sum<T> {
+    fn point(&self) -> CollectionPoint {
+        self.point
+    }
+
+    fn observe(&mut self, chain: usize, state: &T) {
+        *self.sums.entry(chain).or_default() += (self.projector)(state);
+    }
+}
+
+/// Collect the series of observed projected values of each event chain.
+pub struct TimeSeries<T, V> {
+    point: CollectionPoint,
+    projector: Projector<T, V>,
+    pub series: BTreeMap<usize, Vec<V>>,
+}
+
+impl<T, V> TimeSeries<T, V> {
+    pub fn new(point: CollectionPoint, projector: Projector<T, V>) -> TimeSeries<T, V> {
+        TimeSeries { point, projector, series: BTreeMap::new() }
+    }
+}
+
+impl<T, V> Collector<T> for TimeSeries<T, V> {
+    fn point(&self) -> CollectionPoint {
+        self.point
+    }
+
+    fn observe(&mut self, chain: usize, state: &T) {
+        self.series.entry(chain).or_default().push((self.projector)(state));
+    }
+}
+
+/// Collect the last observed projected value at each simulated time of each event chain.
+pub struct TimedValues<T, V> {
+    point: CollectionPoint,
+    projector: Projector<T, V>,
+    pub values: BTreeMap<usize, BTreeMap<TimePoint, V>>,
+}
+
+impl<T, V> TimedValues<T, V> {
+    pub fn new(point: CollectionPoint, projector: Projector<T, V>) -> TimedValues<T, V> {
+        TimedValues { point, projector, values: BTreeMap::new() }
+    }
+}
+
+impl<T: SimTime, V> Collector<T> for TimedValues<T, V> {
+    fn point(&self) -> CollectionPoint {
+        self.point
+    }
+
+    fn observe(&mut self, chain: usize, state: &T) {
+        self.values.entry(chain).or_default().insert(state.current_time(), (self.projector)(state));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::branching_generators::{alternatives, sequence};
+    use crate::event_graph::BoxedOperation;
+    use super::*;
+
+    fn as_float(x: &i32) -> f64 { *x as f64 }
+    fn identity(x: &i32) -> i32 { *x }
+
+    #[test]
+    fn collectors_observe_at_their_points() {
+        let root = EventDAG::new_node(Box::new(|x: i32| x));
+        let year_1: Vec<BoxedOperation<i32>> = vec![Box::new(|x| x + 1), Box::new(|x| x + 1)];
+        let first = sequence(vec![root.clone()], year_1).unwrap();
+        let year_2: Vec<BoxedOperation<i32>> = vec![Box::new(|x| x * 2), Box::new(|x| x * 3)];
+        let second = alternatives(first.clone(), year_2).unwrap();
+        let boundaries = vec![root.borrow().followers()[0].clone(), second[0].clone(), second[1].clone()];
+
+        let mut last = LastValue::new(CollectionPoint::ChainEnd, identity);
+        let mut sum = Sum::new(CollectionPoint::Operation, as_float);
+        let mut series = TimeSeries::new(CollectionPoint::TimePoint, identity);
+        let results = evaluate_collecting(&root, 0, &boundaries, &mut [&mut last, &mut sum, &mut series]);
+
+        assert_eq!(vec![4, 6], results);
+        assert_eq!(BTreeMap::from([(0, 4), (1, 6)]), last.values);
+        assert_eq!(BTreeMap::from([(0, 7.0), (1, 9.0)]), sum.sums);
+        assert_eq!(BTreeMap::from([(0, vec![2, 4]), (1, vec![2, 6])]), series.series);
+    }
+}
@@ -1,16 +1,161 @@
-use std::collections::HashMap;
-pub type ParameterMap = HashMap<&'static str, &'static str>;
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use crate::event_graph::BoxedOperation;
+pub use metsi_rust_derive::OperationParams;
+pub type ParameterMap<'a> = HashMap<&'a str, &'a str>;
 pub type ParameteredOperation<'a, T> = fn(T, ParameterMap) -> T;
+pub type ParameterSchema = Vec<ParameterSpec>;
 
+/// Interner of names and parameters read at runtime, leaking each distinct string once so that
+/// it can be used in declarations and ParameterMaps for the rest of the process.
+#[derive(Debug, Default)]
+pub struct Interner(HashSet<&'static str>);
 
-pub fn bound_operation<'a, T: 'a>(op: ParameteredOperation<'a, T>, params: ParameterMap) -> Box<dyn Fn(T) -> T + 'a> {
+impl Interner {
+    pub fn new() -> Interner {
+        Interner::default()
+    }
+
+    pub fn intern(&mut self, string: &str) -> &'static str {
+        match self.0.get(string) {
+            Some(interned) => interned,
+            None => {
+                let interned: &'static str = Box::leak(string.to_string().into_boxed_str());
+                self.0.insert(interned);
+                interned
+            }
+        }
+    }
+}
+
+/// Declared parameter of an operation, for validating ParameterMaps before running.
+#[derive(Debug, Clone, Copy)]
+pub struct ParameterSpec {
+    pub name: &'static str,
+    /// Type of the parameter value, such as f64.
+    pub kind: &'static str,
+    pub required: bool,
+    pub default: Option<&'static str>,
+    /// Check whether a value parses as the type of the parameter.
+    pub check: fn(&str) -> bool,
+}
+
+/// Reasons for a ParameterMap not to match the parameters of an operation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParameterError {
+    Missing(&'static str),
+    Unknown(String),
+    Invalid { name: &'static str, value: String },
+    /// A nested parameter value has no entry at the given path.
+    MissingEntry { name: &'static str, path: String },
+}
+
+impl fmt::Display for ParameterError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParameterError::Missing(name) => write!(f, "missing parameter '{name}'"),
+            ParameterError::Unknown(name) => write!(f, "unknown parameter '{name}'"),
+            ParameterError::Invalid { name, value } => write!(f, "invalid value '{value}' for parameter '{name}'"),
+            ParameterError::MissingEntry { name, path } => write!(f, "no entry '{path}' in parameter '{name}'"),
+        }
+    }
+}
+
+impl Error for ParameterError {}
+
+/// Typed parameters of an operation parsed from its ParameterMap, usually derived with
+/// #[derive(OperationParams)]. Operations parse their parameters on application, a parsing
+/// failure surfacing as a failing operation.
+pub trait OperationParams: Sized {
+    /// Declared parameters, for validating configurations before running.
+    fn schema() -> ParameterSchema;
+    fn parse(params: &ParameterMap) -> Result<Self, ParameterError>;
+}
+
+/// Parse a parameter, falling back to the given default when absent.
+pub fn parameter<V: FromStr>(params: &ParameterMap, name: &'static str, default: Option<&'static str>) -> Result<V, ParameterError> {
+    let value = params.get(name).copied().or(default).ok_or(ParameterError::Missing(name))?;
+    value.parse().map_err(|_| ParameterError::Invalid { name, value: value.to_string() })
+}
+
+/// Parse a parameter, if given.
+pub fn optional_parameter<V: FromStr>(params: &ParameterMap, name: &'static str) -> Result<Option<V>, ParameterError> {
+    params.get(name).map(|value| value.parse().map_err(|_| ParameterError::Invalid { name, value: value.to_string() })).transpose()
+}
+
+/// Parse a nested parameter value given as JSON, such as a list of thinning limits or a map of
+/// species-specific coefficients, into any deserializable type. Falls back to the given default when absent.
+pub fn nested_parameter<V: DeserializeOwned>(params: &ParameterMap, name: &'static str, default: Option<&'static str>) -> Result<V, ParameterError> {
+    let value = params.get(name).copied().or(default).ok_or(ParameterError::Missing(name))?;
+    serde_json::from_str(value).map_err(|_| ParameterError::Invalid { name, value: value.to_string() })
+}
+
+/// Parse a nested parameter value, if given.
+pub fn optional_nested_parameter<V: DeserializeOwned>(params: &ParameterMap, name: &'static str) -> Result<Option<V>, ParameterError> {
+    params.get(name).map(|_| nested_parameter(params, name, None)).transpose()
+}
+
+/// Parse an entry of a nested parameter value at the given path of map keys and list indices,
+/// such as ["pine", "a"] of species-specific coefficients.
+pub fn parameter_entry<V: DeserializeOwned>(params: &ParameterMap, name: &'static str, path: &[&str]) -> Result<V, ParameterError> {
+    let root: Value = nested_parameter(params, name, None)?;
+    let missing = || ParameterError::MissingEntry { name, path: path.join(".") };
+    let entry = path.iter().try_fold(&root, |value, key| match value {
+        Value::Array(items) => key.parse::<usize>().ok().and_then(|index| items.get(index)),
+        _ => value.get(key),
+    }).ok_or_else(missing)?;
+    V::deserialize(entry).map_err(|_| ParameterError::Invalid { name, value: params[name].to_string() })
+}
+
+/// Check whether a value parses as a nested parameter value of the type.
+pub fn parses_nested<V: DeserializeOwned>(value: &str) -> bool {
+    serde_json::from_str::<V>(value).is_ok()
+}
+
+/// Reject parameters not declared in the schema, the first one in name order.
+pub fn reject_unknown(schema: &[ParameterSpec], params: &ParameterMap) -> Result<(), ParameterError> {
+    let mut unknown: Vec<&&str> = params.keys().filter(|name| !schema.iter().any(|spec| spec.name == **name)).collect();
+    unknown.sort();
+    unknown.first().map_or(Ok(()), |name| Err(ParameterError::Unknown(name.to_string())))
+}
+
+/// Check a ParameterMap against the schema, reporting unknown parameters in name order followed
+/// by missing and invalid parameters in declaration order.
+pub fn check_parameters(schema: &[ParameterSpec], params: &ParameterMap) -> Vec<ParameterError> {
+    let mut unknown: Vec<&str> = params.keys().copied().filter(|name| !schema.iter().any(|spec| spec.name == *name)).collect();
+    unknown.sort();
+    let mut errors: Vec<ParameterError> = unknown.into_iter().map(|name| ParameterError::Unknown(name.to_string())).collect();
+    for spec in schema {
+        match params.get(spec.name) {
+            None if spec.required => errors.push(ParameterError::Missing(spec.name)),
+            Some(value) if !(spec.check)(value) => errors.push(ParameterError::Invalid { name: spec.name, value: value.to_string() }),
+            _ => {}
+        }
+    }
+    errors
+}
+
+/// Bind a ParameterMap into a ParameteredOperation, producing a BoxedOperation usable directly
+/// in the generators.
+pub fn bound_operation<T: 'static>(op: ParameteredOperation<T>, params: ParameterMap<'static>) -> BoxedOperation<T> {
     Box::new(move |payload| op(payload, params.clone()))
 }
 
+/// Bind parameters owned by the operation, such as parameters received over the network, into a
+/// ParameteredOperation as bound_operation does. The parameters are borrowed into a ParameterMap
+/// on each application and freed with the operation.
+pub fn bound_owned_operation<T: 'static>(op: ParameteredOperation<T>, params: HashMap<String, String>) -> BoxedOperation<T> {
+    Box::new(move |payload| op(payload, params.iter().map(|(key, value)| (key.as_str(), value.as_str())).collect()))
+}
+
 #[cfg(test)]
 mod tests {
     use std::rc::Rc;
-    use crate::branching_generators::{generator_map, sequence};
+    use crate::branching_generators::sequence;
     use crate::configuration_utils::*;
     use crate::event_graph::EventDAG;
 
@@ -29,4 +174,97 @@ mod tests {
         val = operation(val);
         assert_eq!(4, val);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn owned_parameters_are_bound() {
+        let operation = bound_owned_operation(parametered_increment, HashMap::from([("increase".to_string(), "3".to_string())]));
+        assert_eq!(6, operation(operation(0)));
+    }
+
+    #[test]
+    fn strings_are_interned_once() {
+        let mut interner = Interner::new();
+        let first = interner.intern("increase");
+        let read = String::from("increase");
+        let second = interner.intern(&read);
+        assert!(std::ptr::eq(first, second));
+        assert_eq!("decrease", interner.intern("decrease"));
+    }
+
+    #[derive(Debug, PartialEq, OperationParams)]
+    struct ThinningParams {
+        intensity: f64,
+        #[param(default = "10")]
+        min_age: u32,
+        species: Option<String>,
+    }
+
+    #[test]
+    fn parameters_are_parsed_into_structs() {
+        let params = ParameterMap::from([("intensity", "0.3"), ("species", "pine")]);
+        let parsed = ThinningParams::parse(&params).unwrap();
+        assert_eq!(ThinningParams { intensity: 0.3, min_age: 10, species: Some("pine".to_string()) }, parsed);
+        let parsed = ThinningParams::parse(&ParameterMap::from([("intensity", "1"), ("min_age", "20")])).unwrap();
+        assert_eq!(ThinningParams { intensity: 1.0, min_age: 20, species: None }, parsed);
+
+        assert_eq!(Err(ParameterError::Missing("intensity")), ThinningParams::parse(&ParameterMap::new()));
+        let invalid = ParameterMap::from([("intensity", "much")]);
+        assert_eq!(Err(ParameterError::Invalid { name: "intensity", value: "much".to_string() }), ThinningParams::parse(&invalid));
+        let unknown = ParameterMap::from([("intensity", "0.3"), ("intensty", "0.4")]);
+        assert_eq!(Err(ParameterError::Unknown("intensty".to_string())), ThinningParams::parse(&unknown));
+    }
+
+    #[derive(Debug, PartialEq, OperationParams)]
+    struct PricingParams {
+        #[param(nested)]
+        prices: HashMap<String, Vec<f64>>,
+        #[param(nested, default = "[]")]
+        limits: Vec<u32>,
+    }
+
+    #[test]
+    fn nested_parameters_are_parsed() {
+        let params = ParameterMap::from([
+            ("limits", "[10, 20]"),
+            ("coefficients", r#"{"pine": {"a": 1.5, "b": [0.1, 0.2]}, "spruce": {"a": 2.0}}"#),
+        ]);
+        assert_eq!(Ok(vec![10, 20]), nested_parameter::<Vec<u32>>(&params, "limits", None));
+        let coefficients: HashMap<String, HashMap<String, Value>> = nested_parameter(&params, "coefficients", None).unwrap();
+        assert_eq!(2, coefficients.len());
+        assert_eq!(Ok(1.5), parameter_entry::<f64>(&params, "coefficients", &["pine", "a"]));
+        assert_eq!(Ok(0.2), parameter_entry::<f64>(&params, "coefficients", &["pine", "b", "1"]));
+        let missing = ParameterError::MissingEntry { name: "coefficients", path: "spruce.b".to_string() };
+        assert_eq!(Err(missing), parameter_entry::<f64>(&params, "coefficients", &["spruce", "b"]));
+        assert_eq!(Err(ParameterError::Invalid { name: "limits", value: "[10, 20]".to_string() }), nested_parameter::<Vec<bool>>(&params, "limits", None));
+
+        let params = ParameterMap::from([("prices", r#"{"pine": [50.0, 20.0]}"#)]);
+        let parsed = PricingParams::parse(&params).unwrap();
+        assert_eq!(PricingParams { prices: HashMap::from([("pine".to_string(), vec![50.0, 20.0])]), limits: vec![] }, parsed);
+        let invalid = ParameterMap::from([("prices", "{}"), ("limits", "10")]);
+        assert_eq!(vec![ParameterError::Invalid { name: "limits", value: "10".to_string() }], check_parameters(&PricingParams::schema(), &invalid));
+    }
+
+    #[test]
+    fn schemas_validate_parameter_maps() {
+        let schema = ThinningParams::schema();
+        let declared: Vec<(&str, &str, bool, Option<&str>)> = schema.iter().map(|spec| (spec.name, spec.kind, spec.required, spec.default)).collect();
+        assert_eq!(vec![("intensity", "f64", true, None), ("min_age", "u32", false, Some("10")), ("species", "String", false, None)], declared);
+        let params = ParameterMap::from([("min_age", "-1"), ("ratio", "2")]);
+        assert_eq!(vec![
+            ParameterError::Unknown("ratio".to_string()),
+            ParameterError::Missing("intensity"),
+            ParameterError::Invalid { name: "min_age", value: "-1".to_string() },
+        ], check_parameters(&schema, &params));
+        assert!(check_parameters(&schema, &ParameterMap::from([("intensity", "0.5")])).is_empty());
+    }
+
+    #[test]
+    fn bound_operations_are_generable() {
+        let root = EventDAG::new_node(Box::new(|x| x));
+        let operations = ["1", "2", "3"].into_iter()
+            .map(|increase| bound_operation(parametered_increment, ParameterMap::from([("increase", increase)])));
+        sequence(vec![Rc::clone(&root)], operations).unwrap();
+        let result = root.borrow().evaluate_depth(0);
+        assert_eq!(vec![6], result);
+    }
+}
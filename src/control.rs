@@ -0,0 +1,680 @@
+use std::collections::{BTreeMap, HashMap};
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use serde::Deserialize;
+use serde_yaml::{Mapping, Value};
+use crate::branching_generators::generator_map;
+use crate::configuration_utils::{Interner, ParameterMap};
+use crate::control_schema::{check_schema, SchemaError};
+use crate::lint::{lint_graph, lint_references, Lint};
+use crate::simulation_runner::{OperationParameters, OperationRegistry, RunResult, RunnerError, SimulationDeclaration};
+use crate::time_points::{
+    EventSchedule, Interpolation, MinimumInterval, ScheduledEvents, TimePoint, TimePointSimulation, TimedParameter, TimedParameters,
+    TimedState
+};
+
+/// Reasons for failing to load a Python Metsi control.yaml or to compile it.
+#[derive(Debug)]
+pub enum ControlError {
+    Io(io::Error),
+    Yaml(serde_yaml::Error),
+    /// The control file uses a structure of Python Metsi not supported by the engine.
+    Unsupported(String),
+    /// The control file violates the schema of control files.
+    Schema(Vec<SchemaError>),
+    /// An include directive could not be resolved.
+    Include(String),
+    /// Several overlays produce a scenario of the same name.
+    DuplicateScenario(String),
+    Runner(RunnerError),
+}
+
+impl fmt::Display for ControlError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ControlError::Io(err) => write!(f, "{err}"),
+            ControlError::Yaml(err) => write!(f, "{err}"),
+            ControlError::Unsupported(message) => write!(f, "unsupported control file structure: {message}"),
+            ControlError::Schema(errors) => {
+                let errors: Vec<String> = errors.iter().map(|error| error.to_string()).collect();
+                write!(f, "invalid control file:\n{}", errors.join("\n"))
+            }
+            ControlError::Include(message) => write!(f, "invalid include: {message}"),
+            ControlError::DuplicateScenario(name) => write!(f, "several overlays of scenario '{name}'"),
+            ControlError::Runner(err) => write!(f, "{err}"),
+        }
+    }
+}
+
+impl Error for ControlError {}
+
+impl From<io::Error> for ControlError {
+    fn from(err: io::Error) -> Self {
+        ControlError::Io(err)
+    }
+}
+
+impl From<serde_yaml::Error> for ControlError {
+    fn from(err: serde_yaml::Error) -> Self {
+        ControlError::Yaml(err)
+    }
+}
+
+impl From<RunnerError> for ControlError {
+    fn from(err: RunnerError) -> Self {
+        ControlError::Runner(err)
+    }
+}
+
+/// Sections of control.yaml understood by the loader. Other sections, such as
+/// app_configuration and export, concern the Python application and are ignored.
+#[derive(Deserialize)]
+struct ControlFile {
+    simulation_events: Vec<SimulationEvents>,
+    #[serde(default)]
+    operation_params: HashMap<String, ParameterSets>,
+    #[serde(default)]
+    run_constraints: HashMap<String, RunConstraint>,
+    #[serde(default)]
+    time_scoped_params: HashMap<String, HashMap<String, TimeScopedValues>>,
+}
+
+#[derive(Deserialize)]
+struct SimulationEvents {
+    time_points: Vec<TimePoint>,
+    /// Single-key maps from generator name to its operations.
+    generators: Vec<HashMap<String, Vec<Value>>>,
+}
+
+/// Parameters of an operation, given as a list of parameter sets or as a single set.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ParameterSets {
+    Many(Vec<HashMap<String, Value>>),
+    One(HashMap<String, Value>),
+}
+
+/// Values of a time-scoped parameter at declared time points.
+#[derive(Deserialize)]
+struct TimeScopedValues {
+    #[serde(default)]
+    interpolation: Interpolation,
+    values: HashMap<TimePoint, Value>,
+}
+
+#[derive(Deserialize)]
+struct RunConstraint {
+    minimum_time_interval: Option<TimePoint>,
+}
+
+/// Simulation configuration loaded from a Python Metsi control.yaml: the simulation_events as an
+/// event schedule, operation_params as operation parameters, the minimum_time_interval
+/// run_constraints as minimum intervals of their operations and time_scoped_params as
+/// parameters interpolated between time points.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ControlConfiguration {
+    pub schedule: EventSchedule,
+    pub parameters: OperationParameters,
+    pub constraints: Vec<MinimumInterval>,
+    pub timed_parameters: TimedParameters,
+}
+
+/// Parameter value as a string, lists and nested maps as JSON for parsing as nested parameters.
+fn parameter_value(operation: &str, key: &str, value: &Value) -> Result<String, ControlError> {
+    match value {
+        Value::String(string) => Ok(string.clone()),
+        Value::Number(number) => Ok(number.to_string()),
+        Value::Bool(boolean) => Ok(boolean.to_string()),
+        Value::Sequence(_) | Value::Mapping(_) => serde_json::to_string(value)
+            .map_err(|_| ControlError::Unsupported(format!("nested parameter '{key}' of operation '{operation}' not representable as JSON"))),
+        _ => Err(ControlError::Unsupported(format!("non-scalar parameter '{key}' of operation '{operation}'"))),
+    }
+}
+
+/// Deep merge of the overriding value over the base value: mappings are merged by key,
+/// sequences concatenated and other values replaced.
+fn merge(base: Value, overriding: Value) -> Value {
+    match (base, overriding) {
+        (Value::Mapping(mut base), Value::Mapping(overriding)) => {
+            for (key, value) in overriding {
+                let merged = match base.remove(&key) {
+                    Some(previous) => merge(previous, value),
+                    None => value,
+                };
+                base.insert(key, merged);
+            }
+            Value::Mapping(base)
+        }
+        (Value::Sequence(mut base), Value::Sequence(overriding)) => {
+            base.extend(overriding);
+            Value::Sequence(base)
+        }
+        (_, overriding) => overriding,
+    }
+}
+
+/// Overlay of the overriding value over the base value: mappings are merged by key and a
+/// mapping with integer keys over a sequence overrides the entries at those positions, such as
+/// the generators of a single block of simulation_events. Other values are replaced.
+fn overlay(base: Value, overriding: Value) -> Result<Value, ControlError> {
+    match (base, overriding) {
+        (Value::Mapping(mut base), Value::Mapping(overriding)) => {
+            for (key, value) in overriding {
+                let overlaid = match base.remove(&key) {
+                    Some(previous) => overlay(previous, value)?,
+                    None => value,
+                };
+                base.insert(key, overlaid);
+            }
+            Ok(Value::Mapping(base))
+        }
+        (Value::Sequence(mut base), Value::Mapping(overriding)) => {
+            for (key, value) in overriding {
+                let position = match key.as_u64() {
+                    Some(position) if (position as usize) < base.len() => position as usize,
+                    _ => return Err(ControlError::Unsupported(format!("overlay of sequence position {key:?}"))),
+                };
+                base[position] = overlay(base[position].clone(), value)?;
+            }
+            Ok(Value::Sequence(base))
+        }
+        (_, overriding) => Ok(overriding),
+    }
+}
+
+/// Contents of the included control file or fragment of one, with its own includes resolved. The
+/// files being included are tracked to detect include cycles.
+fn include(path: &Path, fragment: bool, including: &mut Vec<PathBuf>) -> Result<Value, ControlError> {
+    let path = fs::canonicalize(path)
+        .map_err(|err| ControlError::Include(format!("{}: {err}", path.display())))?;
+    if including.contains(&path) {
+        return Err(ControlError::Include(format!("{} includes itself", path.display())));
+    }
+    including.push(path.clone());
+    let directory = path.parent().unwrap_or(Path::new("."));
+    let value = resolve(&fs::read_to_string(&path)?, Some(&path), directory, fragment, including);
+    including.pop();
+    value
+}
+
+/// Replace the values tagged with !include with the contents of the named files.
+fn include_tagged(value: Value, directory: &Path, including: &mut Vec<PathBuf>) -> Result<Value, ControlError> {
+    match value {
+        Value::Tagged(tagged) if tagged.tag == "include" => match tagged.value {
+            Value::String(file) => include(&directory.join(file), true, including),
+            _ => Err(ControlError::Include("!include of a non-path value".to_string())),
+        },
+        Value::Tagged(mut tagged) => {
+            tagged.value = include_tagged(tagged.value, directory, including)?;
+            Ok(Value::Tagged(tagged))
+        }
+        Value::Sequence(sequence) => sequence.into_iter()
+            .map(|value| include_tagged(value, directory, including))
+            .collect::<Result<_, _>>()
+            .map(Value::Sequence),
+        Value::Mapping(mapping) => mapping.into_iter()
+            .map(|(key, value)| Ok((key, include_tagged(value, directory, including)?)))
+            .collect::<Result<Mapping, ControlError>>()
+            .map(Value::Mapping),
+        value => Ok(value),
+    }
+}
+
+/// Contents of a control file with anchor merge keys applied and includes resolved relative to
+/// the given directory. The files listed in a top-level include entry are merged in order, the
+/// including file merged over them. The contents of each file, unlike those of fragments
+/// included with !include, are checked against the schema, violations being located in the file.
+fn resolve(yaml: &str, file: Option<&Path>, directory: &Path, fragment: bool, including: &mut Vec<PathBuf>) -> Result<Value, ControlError> {
+    let mut value: Value = serde_yaml::from_str(yaml)?;
+    value.apply_merge()?;
+    if fragment {
+        return include_tagged(value, directory, including);
+    }
+    let errors = check_schema(&value, true, Some((yaml, file)));
+    if !errors.is_empty() {
+        return Err(ControlError::Schema(errors));
+    }
+    let mut value = include_tagged(value, directory, including)?;
+    let files = match value.as_mapping_mut().and_then(|mapping| mapping.remove("include")) {
+        None => return Ok(value),
+        Some(Value::String(file)) => vec![file],
+        Some(Value::Sequence(files)) => files.into_iter()
+            .map(|file| match file {
+                Value::String(file) => Ok(file),
+                _ => Err(ControlError::Include("non-path entry in include".to_string())),
+            })
+            .collect::<Result<_, _>>()?,
+        Some(_) => return Err(ControlError::Include("include is to be a path or a list of paths".to_string())),
+    };
+    let mut base = Value::Mapping(Mapping::new());
+    for file in files {
+        base = merge(base, include(&directory.join(file), false, including)?);
+    }
+    Ok(merge(base, value))
+}
+
+fn declaration(events: &SimulationEvents, interner: &mut Interner) -> Result<SimulationDeclaration, ControlError> {
+    events.generators.iter().map(|generator| {
+        let mut entries = generator.iter();
+        let (name, operations) = match (entries.next(), entries.next()) {
+            (Some(entry), None) => entry,
+            _ => return Err(ControlError::Unsupported("generator entry without exactly one generator".to_string())),
+        };
+        let operations = operations.iter()
+            .map(|operation| match operation {
+                Value::String(operation) => Ok(interner.intern(operation)),
+                _ => Err(ControlError::Unsupported(format!("nested generator in '{name}'"))),
+            })
+            .collect::<Result<Vec<&'static str>, ControlError>>()?;
+        Ok((interner.intern(name), operations))
+    }).collect()
+}
+
+impl ControlConfiguration {
+    /// Load the configuration from the contents of a control.yaml, included files being
+    /// resolved relative to the working directory.
+    pub fn from_yaml(yaml: &str) -> Result<ControlConfiguration, ControlError> {
+        ControlConfiguration::from_yaml_in(yaml, Path::new("."))
+    }
+
+    /// Load the configuration from the contents of a control.yaml, included files being
+    /// resolved relative to the given directory.
+    ///
+    /// Event blocks and parameters can be shared with YAML anchors and merge keys, with
+    /// `!include file.yaml` values replaced by the contents of the file, and with a top-level
+    /// `include` list of files merged under the control file, sequences such as
+    /// simulation_events being concatenated.
+    pub fn from_yaml_in<P: AsRef<Path>>(yaml: &str, directory: P) -> Result<ControlConfiguration, ControlError> {
+        ControlConfiguration::from_value(resolve(yaml, None, directory.as_ref(), false, &mut Vec::new())?)
+    }
+
+    /// Configuration of the resolved contents of a control file, checked against the schema.
+    fn from_value(value: Value) -> Result<ControlConfiguration, ControlError> {
+        let errors = check_schema(&value, false, None);
+        if !errors.is_empty() {
+            return Err(ControlError::Schema(errors));
+        }
+        let control: ControlFile = serde_yaml::from_value(value)?;
+        let mut interner = Interner::new();
+        let schedule = control.simulation_events.iter()
+            .map(|events| Ok(ScheduledEvents { time_points: events.time_points.clone(), declaration: declaration(events, &mut interner)? }))
+            .collect::<Result<EventSchedule, ControlError>>()?;
+        let mut parameters = OperationParameters::new();
+        for (operation, sets) in control.operation_params.iter() {
+            let set = match sets {
+                ParameterSets::One(set) => set,
+                ParameterSets::Many(sets) if sets.len() == 1 => &sets[0],
+                ParameterSets::Many(_) => return Err(ControlError::Unsupported(format!("several parameter sets of operation '{operation}'"))),
+            };
+            let map = set.iter()
+                .map(|(key, value)| Ok((interner.intern(key), interner.intern(&parameter_value(operation, key, value)?))))
+                .collect::<Result<ParameterMap, ControlError>>()?;
+            parameters.insert(interner.intern(operation), map);
+        }
+        let mut constraints: Vec<MinimumInterval> = control.run_constraints.iter()
+            .filter_map(|(operation, constraint)| constraint.minimum_time_interval
+                .map(|interval| MinimumInterval { operations: vec![interner.intern(operation)], interval }))
+            .collect();
+        constraints.sort_by_key(|constraint| constraint.operations[0]);
+        let mut timed_parameters = TimedParameters::new();
+        for (operation, scoped) in control.time_scoped_params.iter() {
+            let mut map = HashMap::new();
+            for (key, scoped) in scoped.iter() {
+                let values = scoped.values.iter()
+                    .map(|(time_point, value)| Ok((*time_point, interner.intern(&parameter_value(operation, key, value)?))))
+                    .collect::<Result<_, ControlError>>()?;
+                map.insert(interner.intern(key), TimedParameter::new(values, scoped.interpolation));
+            }
+            timed_parameters.insert(interner.intern(operation), map);
+        }
+        Ok(ControlConfiguration { schedule, parameters, constraints, timed_parameters })
+    }
+
+    /// Load the configuration from a control.yaml file, included files being resolved relative
+    /// to its directory.
+    pub fn read<P: AsRef<Path>>(path: P) -> Result<ControlConfiguration, ControlError> {
+        let path = path.as_ref();
+        let directory = path.parent().unwrap_or(Path::new("."));
+        ControlConfiguration::from_value(resolve(&fs::read_to_string(path)?, Some(path), directory, false, &mut Vec::new())?)
+    }
+
+    /// Compile the configuration over the registered operations.
+    pub fn compile<T: Clone + 'static>(&self, operations: &OperationRegistry<TimedState<T>>) -> Result<TimePointSimulation<T>, ControlError> {
+        Ok(TimePointSimulation::with_timed_parameters(
+            &generator_map(), operations, &self.parameters, &self.timed_parameters, self.schedule.clone(), self.constraints.clone()
+        )?)
+    }
+
+    /// Compile the configuration as compile and warn about operations registered or configured
+    /// but never referenced by the simulation events, unreachable branches and more event chains
+    /// than the given limit.
+    pub fn lint<T: Clone + 'static>(&self, operations: &OperationRegistry<TimedState<T>>, chain_limit: usize) -> Result<Vec<Lint>, ControlError> {
+        let simulation = self.compile(operations)?;
+        let referenced: Vec<&'static str> = self.schedule.iter()
+            .flat_map(|events| events.declaration.iter().flat_map(|(_, names)| names.iter().copied()))
+            .collect();
+        let configured = self.parameters.keys()
+            .chain(self.timed_parameters.keys())
+            .chain(self.constraints.iter().flat_map(|constraint| constraint.operations.iter()));
+        let mut lints = lint_references(operations.keys(), configured, &referenced);
+        lints.extend(lint_graph(simulation.root(), chain_limit));
+        Ok(lints)
+    }
+}
+
+/// Scenario variants of a base configuration by scenario name.
+pub type Scenarios = BTreeMap<String, ControlConfiguration>;
+
+/// Run results of the initial states by scenario name.
+pub type ScenarioResults<T> = BTreeMap<String, Vec<RunResult<TimedState<T>>>>;
+
+fn scenarios(base: Value, overlays: Vec<(String, Value)>) -> Result<Scenarios, ControlError> {
+    let mut scenarios = Scenarios::new();
+    for (name, overriding) in overlays {
+        if scenarios.contains_key(&name) {
+            return Err(ControlError::DuplicateScenario(name));
+        }
+        scenarios.insert(name, ControlConfiguration::from_value(overlay(base.clone(), overriding)?)?);
+    }
+    Ok(scenarios)
+}
+
+/// Scenario variants of the base control.yaml contents, one per named overlay, the overlay
+/// overriding the generators and parameters it declares. Includes are resolved relative to the
+/// given directory.
+pub fn scenarios_from_yaml<P: AsRef<Path>>(base: &str, overlays: &[(&str, &str)], directory: P) -> Result<Scenarios, ControlError> {
+    let directory = directory.as_ref();
+    let overlays = overlays.iter()
+        .map(|(name, yaml)| Ok((name.to_string(), resolve(yaml, None, directory, false, &mut Vec::new())?)))
+        .collect::<Result<Vec<_>, ControlError>>()?;
+    scenarios(resolve(base, None, directory, false, &mut Vec::new())?, overlays)
+}
+
+/// Scenario variants of the base control.yaml file, one per overlay file named by the file stem
+/// of the overlay, such as thinning_heavy for thinning_heavy.yaml.
+pub fn read_scenarios<P: AsRef<Path>>(base: P, overlays: &[P]) -> Result<Scenarios, ControlError> {
+    let read = |path: &Path| -> Result<Value, ControlError> {
+        resolve(&fs::read_to_string(path)?, Some(path), path.parent().unwrap_or(Path::new(".")), false, &mut Vec::new())
+    };
+    let overlays = overlays.iter()
+        .map(|path| {
+            let path = path.as_ref();
+            let name = path.file_stem().map_or(String::new(), |stem| stem.to_string_lossy().into_owned());
+            Ok((name, read(path)?))
+        })
+        .collect::<Result<Vec<_>, ControlError>>()?;
+    scenarios(read(base.as_ref())?, overlays)
+}
+
+/// Compile each scenario over the registered operations and run it for each of the initial
+/// states.
+pub fn run_scenarios<T: Clone + 'static>(
+    scenarios: &Scenarios,
+    operations: &OperationRegistry<TimedState<T>>,
+    initial_states: &[T]
+) -> Result<ScenarioResults<T>, ControlError> {
+    scenarios.iter()
+        .map(|(name, configuration)| Ok((name.clone(), configuration.compile(operations)?.run(initial_states.iter().cloned()))))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::configuration_utils::{parameter_entry, ParameteredOperation};
+
+    const CONTROL: &str = "
+app_configuration:
+  state_format: vmi13
+  run_modes: [preprocess, simulate]
+simulation_events:
+  - time_points: [2020, 2025, 2030]
+    generators:
+      - sequence:
+          - grow
+  - time_points: [2025, 2030]
+    generators:
+      - alternatives:
+          - do_nothing
+          - thinning
+operation_params:
+  grow:
+    - increase: 2
+  thinning:
+    removal: 0.5
+run_constraints:
+  thinning:
+    minimum_time_interval: 10
+";
+
+    fn grow(timed: TimedState<f64>, params: ParameterMap) -> TimedState<f64> {
+        TimedState { state: timed.state + params["increase"].parse::<f64>().unwrap(), ..timed }
+    }
+
+    fn thinning(timed: TimedState<f64>, params: ParameterMap) -> TimedState<f64> {
+        TimedState { state: timed.state * params["removal"].parse::<f64>().unwrap(), ..timed }
+    }
+
+    fn do_nothing(timed: TimedState<f64>, _params: ParameterMap) -> TimedState<f64> {
+        timed
+    }
+
+    #[test]
+    fn control_files_are_loaded() {
+        let control = ControlConfiguration::from_yaml(CONTROL).unwrap();
+        assert_eq!(vec![
+            ScheduledEvents { time_points: vec![2020, 2025, 2030], declaration: vec![("sequence", vec!["grow"])] },
+            ScheduledEvents { time_points: vec![2025, 2030], declaration: vec![("alternatives", vec!["do_nothing", "thinning"])] },
+        ], control.schedule);
+        assert_eq!(ParameterMap::from([("increase", "2")]), control.parameters["grow"]);
+        assert_eq!(ParameterMap::from([("removal", "0.5")]), control.parameters["thinning"]);
+        assert_eq!(vec![MinimumInterval { operations: vec!["thinning"], interval: 10 }], control.constraints);
+
+        let operations: OperationRegistry<TimedState<f64>> = HashMap::from([
+            ("grow", grow as ParameteredOperation<TimedState<f64>>),
+            ("thinning", thinning as ParameteredOperation<TimedState<f64>>),
+            ("do_nothing", do_nothing as ParameteredOperation<TimedState<f64>>),
+        ]);
+        let simulation = control.compile(&operations).unwrap();
+        let results: Vec<f64> = simulation.run([10.0])[0].results.iter().map(|timed| timed.state).collect();
+        assert_eq!(vec![16.0, 8.0, 9.0], results);
+    }
+
+    #[test]
+    fn unsupported_structures_are_reported() {
+        let nested = "
+simulation_events:
+  - time_points: [2020]
+    generators:
+      - sequence:
+          - grow
+          - alternatives: [do_nothing, thinning]
+";
+        assert!(matches!(ControlConfiguration::from_yaml(nested), Err(ControlError::Unsupported(_))));
+        let variants = "
+simulation_events: []
+operation_params:
+  thinning:
+    - removal: 0.5
+    - removal: 0.7
+";
+        assert!(matches!(ControlConfiguration::from_yaml(variants), Err(ControlError::Unsupported(_))));
+        assert!(matches!(ControlConfiguration::from_yaml("simulation_events: 1"), Err(ControlError::Schema(_))));
+        assert!(matches!(ControlConfiguration::from_yaml("simulation_events: ["), Err(ControlError::Yaml(_))));
+    }
+
+    #[test]
+    fn nested_parameters_are_loaded_as_json() {
+        let nested = "
+simulation_events: []
+operation_params:
+  thinning:
+    limits: [10, 20]
+    prices:
+      pine: {log: 60, pulp: 20.5}
+";
+        let control = ControlConfiguration::from_yaml(nested).unwrap();
+        assert_eq!("[10,20]", control.parameters["thinning"]["limits"]);
+        assert_eq!(Ok(20.5), parameter_entry::<f64>(&control.parameters["thinning"], "prices", &["pine", "pulp"]));
+    }
+
+    #[test]
+    fn time_scoped_parameters_are_interpolated() {
+        let scoped = CONTROL.to_string() + "
+time_scoped_params:
+  grow:
+    increase:
+      interpolation: linear
+      values:
+        2020: 1
+        2030: 3
+";
+        let control = ControlConfiguration::from_yaml(&scoped).unwrap();
+        assert_eq!(Interpolation::Linear, control.timed_parameters["grow"]["increase"].interpolation);
+        let operations: OperationRegistry<TimedState<f64>> = HashMap::from([
+            ("grow", grow as ParameteredOperation<TimedState<f64>>),
+            ("thinning", thinning as ParameteredOperation<TimedState<f64>>),
+            ("do_nothing", do_nothing as ParameteredOperation<TimedState<f64>>),
+        ]);
+        let simulation = control.compile(&operations).unwrap();
+        let results: Vec<f64> = simulation.run([10.0])[0].results.iter().map(|timed| timed.state).collect();
+        // Growth of 1, 2 and 3 in 2020, 2025 and 2030.
+        assert_eq!(vec![16.0, 8.0, 9.5], results);
+    }
+
+    #[test]
+    fn anchors_are_merged() {
+        let anchored = "
+simulation_events:
+  - time_points: [2020]
+    generators: &growth
+      - sequence: [grow]
+  - time_points: [2025]
+    generators: *growth
+operation_params:
+  thinning: &thinning
+    removal: 0.5
+    limit: 10
+  thinning_from_above:
+    <<: *thinning
+    removal: 0.7
+";
+        let control = ControlConfiguration::from_yaml(anchored).unwrap();
+        assert_eq!(control.schedule[0].declaration, control.schedule[1].declaration);
+        assert_eq!(ParameterMap::from([("removal", "0.7"), ("limit", "10")]), control.parameters["thinning_from_above"]);
+    }
+
+    #[test]
+    fn includes_are_resolved() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let directory = tempdir.path();
+        fs::create_dir_all(directory.join("blocks")).unwrap();
+        fs::write(directory.join("blocks/growth.yaml"), "- sequence: [grow]").unwrap();
+        fs::write(directory.join("common.yaml"), "
+simulation_events:
+  - time_points: [2020, 2025, 2030]
+    generators: !include blocks/growth.yaml
+operation_params:
+  grow:
+    increase: 2
+  thinning:
+    removal: 0.5
+").unwrap();
+        fs::write(directory.join("control.yaml"), "
+include: common.yaml
+simulation_events:
+  - time_points: [2025, 2030]
+    generators:
+      - alternatives: [do_nothing, thinning]
+operation_params:
+  thinning:
+    removal: 0.7
+").unwrap();
+        let control = ControlConfiguration::read(directory.join("control.yaml")).unwrap();
+        assert_eq!(vec![
+            ScheduledEvents { time_points: vec![2020, 2025, 2030], declaration: vec![("sequence", vec!["grow"])] },
+            ScheduledEvents { time_points: vec![2025, 2030], declaration: vec![("alternatives", vec!["do_nothing", "thinning"])] },
+        ], control.schedule);
+        assert_eq!(ParameterMap::from([("increase", "2")]), control.parameters["grow"]);
+        assert_eq!(ParameterMap::from([("removal", "0.7")]), control.parameters["thinning"]);
+
+        fs::write(directory.join("cycle.yaml"), "include: [common.yaml, cycle.yaml]").unwrap();
+        assert!(matches!(ControlConfiguration::read(directory.join("cycle.yaml")), Err(ControlError::Include(_))));
+        let missing = ControlConfiguration::from_yaml_in("include: missing.yaml", directory);
+        assert!(matches!(missing, Err(ControlError::Include(_))));
+    }
+
+    #[test]
+    fn overlays_produce_scenarios() {
+        let heavy = "
+operation_params:
+  thinning:
+    removal: 0.25
+";
+        let unthinned = "
+simulation_events:
+  1:
+    generators:
+      - sequence: [do_nothing]
+run_constraints: {}
+";
+        let scenarios = scenarios_from_yaml(CONTROL, &[("heavy", heavy), ("unthinned", unthinned)], ".").unwrap();
+        assert_eq!(vec!["heavy", "unthinned"], scenarios.keys().collect::<Vec<_>>());
+        assert_eq!(ParameterMap::from([("removal", "0.25")]), scenarios["heavy"].parameters["thinning"]);
+        assert_eq!(ParameterMap::from([("increase", "2")]), scenarios["heavy"].parameters["grow"]);
+        assert_eq!(vec![("sequence", vec!["do_nothing"])], scenarios["unthinned"].schedule[1].declaration);
+        assert_eq!(vec![2025, 2030], scenarios["unthinned"].schedule[1].time_points);
+
+        let operations: OperationRegistry<TimedState<f64>> = HashMap::from([
+            ("grow", grow as ParameteredOperation<TimedState<f64>>),
+            ("thinning", thinning as ParameteredOperation<TimedState<f64>>),
+            ("do_nothing", do_nothing as ParameteredOperation<TimedState<f64>>),
+        ]);
+        let results = run_scenarios(&scenarios, &operations, &[10.0]).unwrap();
+        let states = |name: &str| results[name][0].results.iter().map(|timed| timed.state).collect::<Vec<_>>();
+        assert_eq!(vec![16.0, 4.0, 5.5], states("heavy"));
+        assert_eq!(vec![16.0], states("unthinned"));
+
+        let duplicate = scenarios_from_yaml(CONTROL, &[("heavy", heavy), ("heavy", unthinned)], ".");
+        assert!(matches!(duplicate, Err(ControlError::DuplicateScenario(name)) if name == "heavy"));
+        let outside = scenarios_from_yaml(CONTROL, &[("outside", "simulation_events: {5: {time_points: [2040]}}")], ".");
+        assert!(matches!(outside, Err(ControlError::Unsupported(_))));
+    }
+
+    #[test]
+    fn schema_violations_are_reported_with_positions() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let directory = tempdir.path();
+        fs::write(directory.join("common.yaml"), "operation_params:\n  grow:\n    increase:\n").unwrap();
+        fs::write(directory.join("control.yaml"), "include: common.yaml\nsimulation_events: []\n").unwrap();
+        let errors = match ControlConfiguration::read(directory.join("control.yaml")) {
+            Err(ControlError::Schema(errors)) => errors,
+            _ => panic!("expected schema violations"),
+        };
+        assert_eq!("operation_params.grow.increase", errors[0].path);
+        let location = errors[0].location.clone().unwrap();
+        assert_eq!((Some("common.yaml".as_ref()), 3, 14), (location.file.as_deref().and_then(Path::file_name), location.line, location.column));
+
+        let missing = ControlConfiguration::from_yaml("operation_params: {}").unwrap_err();
+        assert_eq!("invalid control file:\nsimulation_events: missing required section", missing.to_string());
+    }
+
+    #[test]
+    fn configurations_are_linted() {
+        let operations: OperationRegistry<TimedState<f64>> = HashMap::from([
+            ("grow", grow as ParameteredOperation<TimedState<f64>>),
+            ("thinning", thinning as ParameteredOperation<TimedState<f64>>),
+            ("do_nothing", do_nothing as ParameteredOperation<TimedState<f64>>),
+        ]);
+        let control = ControlConfiguration::from_yaml(CONTROL).unwrap();
+        assert_eq!(Ok(Vec::new()), control.lint(&operations, 100).map_err(|error| error.to_string()));
+        assert_eq!(vec![Lint::LargeChainCount { chains: 3, limit: 2 }], control.lint(&operations, 2).unwrap());
+
+        let unthinned = scenarios_from_yaml(CONTROL, &[("unthinned", "simulation_events: {1: {generators: [sequence: [do_nothing]]}}")], ".").unwrap();
+        assert_eq!(vec![Lint::UnusedParameters("thinning"), Lint::UnreferencedOperation("thinning")],
+            unthinned["unthinned"].lint(&operations, 100).unwrap());
+    }
+}
@@ -0,0 +1,426 @@
+use std::error::Error;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use serde::de::{self, DeserializeSeed, Deserializer, EnumAccess, IgnoredAny, MapAccess, SeqAccess, Visitor};
+use serde_yaml::{Mapping, Value};
+use crate::branching_generators::generator_map;
+
+/// Position of a value in a control file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SourceLocation {
+    /// The control file, if loaded from a file.
+    pub file: Option<PathBuf>,
+    pub line: usize,
+    pub column: usize,
+}
+
+/// Violation of the control file schema at the given key path, such as
+/// simulation_events[1].generators[0]. The location is known for violations within a single
+/// file, violations of the merged configuration such as a missing section having none.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SchemaError {
+    pub path: String,
+    pub message: String,
+    pub location: Option<SourceLocation>,
+}
+
+impl fmt::Display for SchemaError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.location {
+            Some(SourceLocation { file: Some(file), line, column }) => write!(f, "{}:{line}:{column}: ", file.display())?,
+            Some(SourceLocation { file: None, line, column }) => write!(f, "{line}:{column}: ")?,
+            None => {}
+        }
+        match self.path.is_empty() {
+            true => write!(f, "{}", self.message),
+            false => write!(f, "{}: {}", self.path, self.message),
+        }
+    }
+}
+
+impl Error for SchemaError {}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Segment {
+    Key(String),
+    Index(usize),
+}
+
+type KeyPath = Vec<Segment>;
+
+fn format_path(path: &[Segment]) -> String {
+    let mut formatted = String::new();
+    for segment in path {
+        match segment {
+            Segment::Key(key) if formatted.is_empty() => formatted.push_str(key),
+            Segment::Key(key) => formatted.push_str(&format!(".{key}")),
+            Segment::Index(index) => formatted.push_str(&format!("[{index}]")),
+        }
+    }
+    formatted
+}
+
+/// Schema check collecting the violations by key path. A partial check accepts a file providing
+/// only part of a configuration, such as an included file or a scenario overlay: required keys
+/// may be missing and sequences may be overlaid by position with mappings of integer keys.
+struct Check {
+    partial: bool,
+    violations: Vec<(KeyPath, String)>,
+}
+
+fn with(path: &[Segment], segment: Segment) -> KeyPath {
+    let mut extended = path.to_vec();
+    extended.push(segment);
+    extended
+}
+
+fn key_name(key: &Value) -> String {
+    match key {
+        Value::String(key) => key.clone(),
+        key => serde_yaml::to_string(key).map_or_else(|_| "?".to_string(), |key| key.trim_end().to_string()),
+    }
+}
+
+impl Check {
+    fn violation(&mut self, path: &[Segment], message: impl Into<String>) {
+        self.violations.push((path.to_vec(), message.into()));
+    }
+
+    /// Whether the value is an included fragment, to be checked once included.
+    fn included(&mut self, path: &[Segment], value: &Value) -> bool {
+        match value {
+            Value::Tagged(tagged) if tagged.tag == "include" => true,
+            Value::Tagged(tagged) => {
+                self.violation(path, format!("unknown tag {}", tagged.tag));
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn mapping<'a>(&mut self, path: &[Segment], value: &'a Value) -> Option<&'a Mapping> {
+        match value {
+            _ if self.included(path, value) => None,
+            Value::Mapping(mapping) => Some(mapping),
+            _ => {
+                self.violation(path, "expected a mapping");
+                None
+            }
+        }
+    }
+
+    /// Check the entries of a sequence, or of a mapping of positions in a partial check.
+    fn entries(&mut self, path: &[Segment], value: &Value, check: fn(&mut Check, &[Segment], &Value)) {
+        match value {
+            _ if self.included(path, value) => {}
+            Value::Sequence(entries) => {
+                for (index, entry) in entries.iter().enumerate() {
+                    check(self, &with(path, Segment::Index(index)), entry);
+                }
+            }
+            Value::Mapping(entries) if self.partial && entries.keys().all(|key| key.as_u64().is_some()) => {
+                for (key, entry) in entries {
+                    check(self, &with(path, Segment::Key(key_name(key))), entry);
+                }
+            }
+            _ => self.violation(path, "expected a sequence"),
+        }
+    }
+
+    /// Check the keys of the mapping against the allowed keys, the required ones being present
+    /// unless partial.
+    fn keys(&mut self, path: &[Segment], mapping: &Mapping, allowed: &[&str], required: &[&str]) {
+        for key in mapping.keys() {
+            if !key.as_str().is_some_and(|key| allowed.contains(&key)) {
+                self.violation(&with(path, Segment::Key(key_name(key))), format!("unknown key, expected one of {}", allowed.join(", ")));
+            }
+        }
+        if !self.partial {
+            for key in required.iter().filter(|key| !mapping.contains_key(**key)) {
+                self.violation(&with(path, Segment::Key(key.to_string())), "missing required key");
+            }
+        }
+    }
+
+    fn integer(&mut self, path: &[Segment], value: &Value) {
+        if !value.is_i64() && !self.included(path, value) {
+            self.violation(path, "expected an integer");
+        }
+    }
+
+    fn string(&mut self, path: &[Segment], value: &Value) {
+        if !value.is_string() && !self.included(path, value) {
+            self.violation(path, "expected a path");
+        }
+    }
+
+    /// Operation of a generator. Nested generators of Python Metsi are left to be reported as
+    /// unsupported on loading.
+    fn operation(&mut self, path: &[Segment], value: &Value) {
+        if !value.is_string() && !value.is_mapping() && !self.included(path, value) {
+            self.violation(path, "expected an operation name");
+        }
+    }
+
+    fn parameter(&mut self, path: &[Segment], value: &Value) {
+        if value.is_null() {
+            self.violation(path, "missing parameter value");
+        } else {
+            self.included(path, value);
+        }
+    }
+
+    fn root(&mut self, value: &Value) {
+        let Some(root) = self.mapping(&[], value) else { return };
+        if !self.partial && !root.contains_key("simulation_events") {
+            self.violation(&[Segment::Key("simulation_events".to_string())], "missing required section");
+        }
+        for (key, value) in root {
+            let path = [Segment::Key(key_name(key))];
+            match key.as_str() {
+                Some("simulation_events") => self.entries(&path, value, Check::events),
+                Some("operation_params") => self.named(&path, value, Check::parameter_sets),
+                Some("run_constraints") => self.named(&path, value, Check::run_constraint),
+                Some("time_scoped_params") => self.named(&path, value, Check::time_scoped),
+                Some("include") => match value {
+                    Value::String(_) => {}
+                    _ => self.entries(&path, value, Check::string),
+                },
+                // Sections of the Python application, such as app_configuration and export.
+                _ => {}
+            }
+        }
+    }
+
+    /// Check the values of a mapping by operation or parameter name.
+    fn named(&mut self, path: &[Segment], value: &Value, check: fn(&mut Check, &[Segment], &Value)) {
+        let Some(mapping) = self.mapping(path, value) else { return };
+        for (key, value) in mapping {
+            check(self, &with(path, Segment::Key(key_name(key))), value);
+        }
+    }
+
+    fn events(&mut self, path: &[Segment], value: &Value) {
+        let Some(events) = self.mapping(path, value) else { return };
+        self.keys(path, events, &["time_points", "generators"], &["time_points", "generators"]);
+        if let Some(time_points) = events.get("time_points") {
+            self.entries(&with(path, Segment::Key("time_points".to_string())), time_points, Check::integer);
+        }
+        if let Some(generators) = events.get("generators") {
+            self.entries(&with(path, Segment::Key("generators".to_string())), generators, Check::generator);
+        }
+    }
+
+    fn generator(&mut self, path: &[Segment], value: &Value) {
+        let Some(generator) = self.mapping(path, value) else { return };
+        if generator.len() != 1 {
+            self.violation(path, "expected a single generator");
+            return;
+        }
+        let (name, operations) = generator.iter().next().unwrap();
+        let path = with(path, Segment::Key(key_name(name)));
+        match name.as_str() {
+            Some(name) if generator_map::<()>().contains_key(name) => self.entries(&path, operations, Check::operation),
+            _ => self.violation(&path, "unknown generator"),
+        }
+    }
+
+    fn parameter_sets(&mut self, path: &[Segment], value: &Value) {
+        match value {
+            Value::Sequence(_) => self.entries(path, value, Check::parameter_set),
+            _ => self.parameter_set(path, value),
+        }
+    }
+
+    fn parameter_set(&mut self, path: &[Segment], value: &Value) {
+        self.named(path, value, Check::parameter);
+    }
+
+    fn run_constraint(&mut self, path: &[Segment], value: &Value) {
+        let Some(constraint) = self.mapping(path, value) else { return };
+        self.keys(path, constraint, &["minimum_time_interval"], &[]);
+        if let Some(interval) = constraint.get("minimum_time_interval") {
+            self.integer(&with(path, Segment::Key("minimum_time_interval".to_string())), interval);
+        }
+    }
+
+    fn time_scoped(&mut self, path: &[Segment], value: &Value) {
+        self.named(path, value, Check::time_scoped_parameter);
+    }
+
+    fn time_scoped_parameter(&mut self, path: &[Segment], value: &Value) {
+        let Some(parameter) = self.mapping(path, value) else { return };
+        self.keys(path, parameter, &["interpolation", "values"], &["values"]);
+        if let Some(interpolation) = parameter.get("interpolation") {
+            if !matches!(interpolation.as_str(), Some("step" | "linear")) {
+                self.violation(&with(path, Segment::Key("interpolation".to_string())), "expected step or linear");
+            }
+        }
+        if let Some(values) = parameter.get("values") {
+            let path = with(path, Segment::Key("values".to_string()));
+            let Some(values) = self.mapping(&path, values) else { return };
+            for (time_point, value) in values {
+                let path = with(&path, Segment::Key(key_name(time_point)));
+                if !time_point.is_i64() {
+                    self.violation(&path, "expected an integer time point");
+                }
+                self.parameter(&path, value);
+            }
+        }
+    }
+}
+
+/// Check a control file value against the schema, the violations being located in the given
+/// source text if any. A partial check accepts files providing only part of a configuration.
+pub(crate) fn check_schema(value: &Value, partial: bool, source: Option<(&str, Option<&Path>)>) -> Vec<SchemaError> {
+    let mut check = Check { partial, violations: Vec::new() };
+    check.root(value);
+    check.violations.into_iter()
+        .map(|(path, message)| {
+            let location = source.and_then(|(text, file)| locate(text, &path).map(|(line, column)| {
+                SourceLocation { file: file.map(Path::to_path_buf), line, column }
+            }));
+            SchemaError { path: format_path(&path), message, location }
+        })
+        .collect()
+}
+
+const FOUND: &str = "metsi: key path found";
+
+/// Line and column of the value at the key path in the YAML text. The value is located by
+/// deserializing the text up to it and failing there, the parser then reporting its position.
+fn locate(text: &str, path: &[Segment]) -> Option<(usize, usize)> {
+    let error = Seek(path).deserialize(serde_yaml::Deserializer::from_str(text)).err()?;
+    match error.to_string().contains(FOUND) {
+        true => error.location().map(|location| (location.line(), location.column())),
+        false => None,
+    }
+}
+
+/// Deserialization following the key path, ignoring other values and failing at its end.
+struct Seek<'a>(&'a [Segment]);
+
+/// Visitor failing at any value, for failing at the end of a key path.
+struct Found;
+
+impl<'de> DeserializeSeed<'de> for Seek<'_> {
+    type Value = ();
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<(), D::Error> {
+        match self.0.is_empty() {
+            true => deserializer.deserialize_any(Found),
+            false => deserializer.deserialize_any(self),
+        }
+    }
+}
+
+impl<'de> Visitor<'de> for Seek<'_> {
+    type Value = ();
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a mapping or a sequence")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<(), A::Error> {
+        while let Some(key) = map.next_key::<Value>()? {
+            match &self.0[0] {
+                Segment::Key(name) if key_name(&key) == *name => map.next_value_seed(Seek(&self.0[1..]))?,
+                _ => map.next_value::<IgnoredAny>().map(|_| ())?,
+            }
+        }
+        Ok(())
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<(), A::Error> {
+        let mut index = 0;
+        loop {
+            let visited = match self.0[0] {
+                Segment::Index(position) if position == index => seq.next_element_seed(Seek(&self.0[1..]))?,
+                _ => seq.next_element::<IgnoredAny>()?.map(|_| ()),
+            };
+            if visited.is_none() {
+                return Ok(());
+            }
+            index += 1;
+        }
+    }
+
+    fn visit_enum<A: EnumAccess<'de>>(self, data: A) -> Result<(), A::Error> {
+        IgnoredAny.visit_enum(data).map(|_| ())
+    }
+
+    fn visit_bool<E: de::Error>(self, _: bool) -> Result<(), E> { Ok(()) }
+    fn visit_i64<E: de::Error>(self, _: i64) -> Result<(), E> { Ok(()) }
+    fn visit_u64<E: de::Error>(self, _: u64) -> Result<(), E> { Ok(()) }
+    fn visit_f64<E: de::Error>(self, _: f64) -> Result<(), E> { Ok(()) }
+    fn visit_str<E: de::Error>(self, _: &str) -> Result<(), E> { Ok(()) }
+    fn visit_unit<E: de::Error>(self) -> Result<(), E> { Ok(()) }
+}
+
+impl<'de> Visitor<'de> for Found {
+    type Value = ();
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("any value")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, _: A) -> Result<(), A::Error> { Err(de::Error::custom(FOUND)) }
+    fn visit_seq<A: SeqAccess<'de>>(self, _: A) -> Result<(), A::Error> { Err(de::Error::custom(FOUND)) }
+    fn visit_enum<A: EnumAccess<'de>>(self, _: A) -> Result<(), A::Error> { Err(de::Error::custom(FOUND)) }
+    fn visit_bool<E: de::Error>(self, _: bool) -> Result<(), E> { Err(E::custom(FOUND)) }
+    fn visit_i64<E: de::Error>(self, _: i64) -> Result<(), E> { Err(E::custom(FOUND)) }
+    fn visit_u64<E: de::Error>(self, _: u64) -> Result<(), E> { Err(E::custom(FOUND)) }
+    fn visit_f64<E: de::Error>(self, _: f64) -> Result<(), E> { Err(E::custom(FOUND)) }
+    fn visit_str<E: de::Error>(self, _: &str) -> Result<(), E> { Err(E::custom(FOUND)) }
+    fn visit_unit<E: de::Error>(self) -> Result<(), E> { Err(E::custom(FOUND)) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const INVALID: &str = "
+simulation_events:
+  - time_points: [2020, soon]
+    generators:
+      - sequence: [grow]
+      - repeat: [grow]
+  - time_point: [2025]
+    generators: []
+run_constraints:
+  thinning:
+    minimum_time_interval: 10
+    maximum: 5
+";
+
+    fn check(yaml: &str, partial: bool) -> Vec<String> {
+        let value: Value = serde_yaml::from_str(yaml).unwrap();
+        check_schema(&value, partial, Some((yaml, Some(Path::new("control.yaml"))))).iter().map(|error| error.to_string()).collect()
+    }
+
+    #[test]
+    fn violations_are_located() {
+        assert_eq!(vec![
+            "control.yaml:3:25: simulation_events[0].time_points[1]: expected an integer",
+            "control.yaml:6:17: simulation_events[0].generators[1].repeat: unknown generator",
+            "control.yaml:7:17: simulation_events[1].time_point: unknown key, expected one of time_points, generators",
+            "simulation_events[1].time_points: missing required key",
+            "control.yaml:12:14: run_constraints.thinning.maximum: unknown key, expected one of minimum_time_interval",
+        ], check(INVALID, false));
+    }
+
+    #[test]
+    fn partial_files_may_omit_required_keys() {
+        let overlay = "
+simulation_events:
+  1:
+    generators: !include blocks.yaml
+operation_params:
+  thinning:
+    removal:
+";
+        assert_eq!(vec!["control.yaml:7:13: operation_params.thinning.removal: missing parameter value"], check(overlay, true));
+        assert_eq!(vec!["simulation_events: expected a sequence", "operation_params.thinning.removal: missing parameter value"],
+            check(overlay, false).iter().map(|error| error.split_once(": ").unwrap().1).collect::<Vec<_>>());
+    }
+}
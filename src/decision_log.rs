@@ -0,0 +1,160 @@
+use std::fmt::Display;
+use std::io::{self, Write};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use serde::Serialize;
+use crate::batch_runner::BatchOutcome;
+use crate::decisions::{decisions, Decision};
+use crate::event_graph::{is_pruned, panic_message, ChainResults, EvaluationFailure, EventDAG, EventNode};
+
+/// Event of an event chain recorded during logged evaluation.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum DecisionEvent {
+    /// The chain took a branch after its branching node was applied.
+    Branch(Decision),
+    /// An operation pruned the chain, leaving it without a result.
+    Pruned { position: usize, operation: Option<&'static str> },
+    /// An operation failed, failing the evaluation.
+    Failed { position: usize, operation: Option<&'static str>, error: String },
+}
+
+/// Log record of an event of an event chain, written as a JSON object such as
+/// `{"entity":"a","chain":1,"event":"pruned","position":2,"operation":"thin"}`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct DecisionRecord {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub entity: Option<String>,
+    pub chain: usize,
+    #[serde(flatten)]
+    pub event: DecisionEvent,
+}
+
+/// DecisionLog writes the branching decisions, prunings and failures of logged evaluations as
+/// JSON Lines, so that runs can be audited afterwards. Failing to write the log does not fail the
+/// evaluation; the first write error is reported when finishing the log.
+pub struct DecisionLog<W> {
+    writer: W,
+    entity: Option<String>,
+    error: Option<io::Error>,
+}
+
+impl<W: Write> DecisionLog<W> {
+    pub fn new(writer: W) -> DecisionLog<W> {
+        DecisionLog { writer, entity: None, error: None }
+    }
+
+    /// Attribute the following records to the entity.
+    pub fn set_entity<K: Display>(&mut self, entity: &K) {
+        self.entity = Some(entity.to_string());
+    }
+
+    /// Write a record of the event of the event chain.
+    pub fn record(&mut self, chain: usize, event: DecisionEvent) {
+        if self.error.is_some() {
+            return;
+        }
+        let record = DecisionRecord { entity: self.entity.clone(), chain, event };
+        let written = serde_json::to_writer(&mut self.writer, &record)
+            .map_err(io::Error::from)
+            .and_then(|_| self.writer.write_all(b"\n"));
+        self.error = written.err();
+    }
+
+    /// Flush the log, returning the underlying writer or the first error in writing the log.
+    pub fn finish(mut self) -> io::Result<W> {
+        match self.error {
+            Some(error) => Err(error),
+            None => self.writer.flush().map(|_| self.writer),
+        }
+    }
+}
+
+/// Evaluate the unique event chains as EventDAG::try_evaluate_chains_by_id, recording into the
+/// log the branches taken by each chain, the operations pruning chains and a failing operation.
+pub fn try_evaluate_logged<T: Clone, W: Write>(root: &EventNode<T>, payload: T, log: &mut DecisionLog<W>) -> Result<ChainResults<T>, EvaluationFailure> {
+    let mut results = ChainResults::new();
+    'chains: for (chain, nodes) in EventDAG::node_chains(root).iter().enumerate() {
+        let mut branches = decisions(nodes).into_iter().peekable();
+        let mut current = payload.clone();
+        for (position, node) in nodes.iter().enumerate() {
+            current = match catch_unwind(AssertUnwindSafe(|| node.borrow().apply(current))) {
+                Ok(current) => current,
+                Err(panic) => {
+                    let operation = node.borrow().label();
+                    if is_pruned(&*panic) {
+                        log.record(chain, DecisionEvent::Pruned { position, operation });
+                        continue 'chains;
+                    }
+                    let error = panic_message(panic);
+                    log.record(chain, DecisionEvent::Failed { position, operation, error: error.clone() });
+                    return Err(EvaluationFailure { chain, operation: position, label: operation.map(str::to_string), error });
+                }
+            };
+            if let Some(branch) = branches.next_if(|branch| branch.position == position) {
+                log.record(chain, DecisionEvent::Branch(branch));
+            }
+        }
+        results.insert(chain, current);
+    }
+    Ok(results)
+}
+
+/// Evaluate the event graph for each of the given independent entities as run_batch, recording
+/// the evaluation of each entity into the log.
+pub fn run_batch_logged<K, T, I, W>(root: &EventNode<T>, entities: I, log: &mut DecisionLog<W>) -> BatchOutcome<K, T>
+where K: Ord + Display, T: Clone, I: IntoIterator<Item = (K, T)>, W: Write {
+    entities.into_iter()
+        .map(|(id, state)| {
+            log.set_entity(&id);
+            let evaluated = try_evaluate_logged(root, state, log);
+            (id, evaluated)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+    use crate::branching_generators::alternatives;
+    use crate::event_graph::{prune, BoxedOperation};
+    use super::*;
+
+    fn graph() -> EventNode<i32> {
+        let root = EventDAG::new_node(Box::new(|x: i32| x));
+        root.borrow_mut().set_label("start");
+        let operations: Vec<BoxedOperation<i32>> = vec![
+            Box::new(|x| x + 1),
+            Box::new(|x| if x > 0 { prune() } else { x }),
+        ];
+        let branches = alternatives(vec![Rc::clone(&root)], operations).unwrap();
+        branches[0].borrow_mut().set_label("grow");
+        branches[1].borrow_mut().set_label("thin");
+        alternatives(branches, [Box::new(|x: i32| if x > 5 { panic!("too large") } else { x }) as BoxedOperation<i32>]).unwrap();
+        root
+    }
+
+    fn lines(log: DecisionLog<Vec<u8>>) -> Vec<String> {
+        String::from_utf8(log.finish().unwrap()).unwrap().lines().map(str::to_string).collect()
+    }
+
+    #[test]
+    fn branches_and_prunings_are_logged() {
+        let mut log = DecisionLog::new(Vec::new());
+        assert_eq!(vec![2], try_evaluate_logged(&graph(), 1, &mut log).unwrap().into_values().collect::<Vec<_>>());
+        assert_eq!(vec![
+            r#"{"chain":0,"event":"branch","position":0,"node":"start","branch":0,"label":"grow"}"#,
+            r#"{"chain":1,"event":"branch","position":0,"node":"start","branch":1,"label":"thin"}"#,
+            r#"{"chain":1,"event":"pruned","position":1,"operation":"thin"}"#,
+        ], lines(log));
+    }
+
+    #[test]
+    fn failures_are_logged_by_entity() {
+        let mut log = DecisionLog::new(Vec::new());
+        let outcome = run_batch_logged(&graph(), [("a", 0), ("b", 5)], &mut log);
+        assert_eq!(vec!["b"], outcome.failures.iter().map(|failure| failure.entity).collect::<Vec<_>>());
+        let lines = lines(log);
+        assert_eq!(4, lines.len());
+        assert_eq!(r#"{"entity":"b","chain":0,"event":"failed","position":2,"operation":null,"error":"too large"}"#, lines[3]);
+    }
+}
@@ -0,0 +1,118 @@
+use std::fmt;
+use std::rc::Rc;
+use serde::Serialize;
+use crate::event_graph::{EvaluationFailure, EventDAG, EventNode, EventNodes};
+
+/// Branch taken by an event chain at a node with several followers.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Decision {
+    /// Position of the branching node within the event chain, the starting node being 0.
+    pub position: usize,
+    /// Label of the branching node.
+    pub node: Option<&'static str>,
+    /// Attachment index of the taken follower among the followers of the branching node.
+    pub branch: usize,
+    /// Label of the branch of the taken follower.
+    pub label: Option<&'static str>,
+}
+
+impl fmt::Display for Decision {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.label {
+            Some(label) => write!(f, "{label}"),
+            None => write!(f, "#{}", self.branch),
+        }
+    }
+}
+
+/// Result of an event chain with the decisions taken along the chain, translating the result
+/// back into the management program producing it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DecidedResult<T> {
+    /// Index of the event chain in evaluation order.
+    pub chain: usize,
+    pub decisions: Vec<Decision>,
+    pub result: T,
+}
+
+impl<T> DecidedResult<T> {
+    /// Labels of the taken branches in chain order, unlabeled branches by their attachment index.
+    pub fn program(&self) -> Vec<String> {
+        self.decisions.iter().map(|decision| decision.to_string()).collect()
+    }
+}
+
+/// Decisions taken along the event chain at each of its nodes with several followers.
+pub fn decisions<T: Clone>(chain: &[EventNode<T>]) -> Vec<Decision> {
+    chain.windows(2).enumerate()
+        .filter(|(_, pair)| pair[0].borrow().followers().len() > 1)
+        .map(|(position, pair)| {
+            let node = pair[0].borrow();
+            Decision {
+                position,
+                node: node.label(),
+                branch: node.followers().iter().position(|follower| Rc::ptr_eq(follower, &pair[1])).unwrap_or_default(),
+                label: node.branch_label(&pair[1]),
+            }
+        })
+        .collect()
+}
+
+/// Evaluate the unique event chains as EventDAG::try_evaluate_chains, recording the decisions
+/// taken along the chain of each result.
+pub fn try_evaluate_decided<T: Clone>(root: &EventNode<T>, payload: T) -> Result<Vec<DecidedResult<T>>, EvaluationFailure> {
+    let chains: Vec<EventNodes<T>> = EventDAG::node_chains(root);
+    let results = EventDAG::try_evaluate_indexed(&chains, |_| payload.clone())?;
+    Ok(results.into_iter()
+        .map(|(chain, result)| DecidedResult { chain, decisions: decisions(&chains[chain]), result })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::branching_generators::{alternatives, sequence};
+    use crate::event_graph::{prune, BoxedOperation, LabeledFollowers};
+    use super::*;
+
+    /// Growth followed by thinning or a pruned alternative, each followed by two harvests, or
+    /// by fertilization as a labeled branch.
+    fn programs() -> EventNode<i32> {
+        let root = EventDAG::new_node(Box::new(|x: i32| x));
+        let grown = sequence(vec![Rc::clone(&root)], [Box::new(|x| x + 1) as BoxedOperation<i32>]).unwrap();
+        grown[0].borrow_mut().set_label("grow");
+        let operations: Vec<BoxedOperation<i32>> = vec![Box::new(|x| x * 10), Box::new(|x| if x > 5 { prune() } else { x })];
+        let managed = alternatives(grown.clone(), operations).unwrap();
+        managed[0].borrow_mut().set_label("thinning");
+        let harvests: Vec<BoxedOperation<i32>> = vec![Box::new(|x| x + 2), Box::new(|x| x + 3)];
+        let harvested = alternatives(managed, harvests).unwrap();
+        harvested[1].borrow_mut().set_label("clearcut");
+        grown[0].borrow_mut().set_follower_storage(LabeledFollowers::new());
+        grown[0].borrow_mut().add_labeled_follower("fertilization", &EventDAG::new_node(Box::new(|x| x * 2)));
+        root
+    }
+
+    #[test]
+    fn decisions_are_recorded_at_branches() {
+        let results = try_evaluate_decided(&programs(), 0).unwrap();
+        assert_eq!(vec![12, 13, 3, 4, 2], results.iter().map(|decided| decided.result).collect::<Vec<_>>());
+        assert_eq!(vec![
+            Decision { position: 1, node: Some("grow"), branch: 0, label: Some("thinning") },
+            Decision { position: 2, node: Some("thinning"), branch: 1, label: Some("clearcut") },
+        ], results[1].decisions);
+        let programs: Vec<Vec<String>> = results.iter().map(|decided| decided.program()).collect();
+        assert_eq!(vec![
+            vec!["thinning", "#0"],
+            vec!["thinning", "clearcut"],
+            vec!["#1", "#0"],
+            vec!["#1", "clearcut"],
+            vec!["fertilization"],
+        ], programs);
+    }
+
+    #[test]
+    fn pruned_chains_have_no_decisions() {
+        let results = try_evaluate_decided(&programs(), 10).unwrap();
+        assert_eq!(vec![0, 1, 4], results.iter().map(|decided| decided.chain).collect::<Vec<_>>());
+        assert_eq!(vec!["fertilization"], results[2].program());
+    }
+}
@@ -0,0 +1,215 @@
+use std::collections::{BTreeSet, HashMap};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::sync::Arc;
+use rayon::prelude::*;
+use rayon::{ThreadPool, ThreadPoolBuildError, ThreadPoolBuilder};
+use crate::event_graph::{chain_count, is_pruned, panic_message, ChainResults, EvaluationFailure, EventDAG, EventNode, EventNodes};
+use crate::rng::RngStream;
+use crate::stepwise::try_evaluate_breadth_first;
+
+/// Strategy for evaluating the unique event chains of an event graph, selectable at call time.
+/// All strategies produce results by chain id as EventDAG::try_evaluate_chains_by_id, pruned
+/// chains producing no results and a panicking operation being described as an
+/// EvaluationFailure.
+pub trait EvaluationStrategy<T> {
+    fn evaluate(&self, root: &EventNode<T>, payload: T) -> Result<ChainResults<T>, EvaluationFailure>;
+}
+
+/// Evaluate each event chain separately from the initial payload.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ChainWise;
+
+/// Evaluate depth-first, chains sharing a prefix sharing its evaluation.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DepthFirst;
+
+/// Evaluate level by level, holding one state per live branch at the current depth.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct BreadthFirst;
+
+/// Evaluate the event chains in parallel. As event graphs are not shareable across threads, each
+/// worker evaluates the chains of its own graph built with the factory, which is to build graphs
+/// equal to the evaluated one.
+pub struct Parallel<F> {
+    factory: F,
+    pool: Arc<ThreadPool>,
+}
+
+/// Evaluate a random sample of the event chains, drawn without replacement from the given
+/// stream. The same sample is drawn on each evaluation.
+#[derive(Debug, Clone, Copy)]
+pub struct Sampled {
+    pub samples: usize,
+    pub stream: RngStream,
+}
+
+impl<T: Clone> EvaluationStrategy<T> for ChainWise {
+    fn evaluate(&self, root: &EventNode<T>, payload: T) -> Result<ChainResults<T>, EvaluationFailure> {
+        EventDAG::try_evaluate_chains_by_id(root, payload)
+    }
+}
+
+impl<T: Clone> EvaluationStrategy<T> for DepthFirst {
+    fn evaluate(&self, root: &EventNode<T>, payload: T) -> Result<ChainResults<T>, EvaluationFailure> {
+        let mut results = ChainResults::new();
+        visit(root, payload, 0, 0, &mut HashMap::new(), &mut results)?;
+        Ok(results)
+    }
+}
+
+impl<T: Clone> EvaluationStrategy<T> for BreadthFirst {
+    fn evaluate(&self, root: &EventNode<T>, payload: T) -> Result<ChainResults<T>, EvaluationFailure> {
+        try_evaluate_breadth_first(root, payload, &mut [])
+    }
+}
+
+impl<F> Parallel<F> {
+    /// Parallel evaluation of graphs built with the factory. A thread count of 0 lets rayon
+    /// choose the number of worker threads.
+    pub fn new(factory: F, threads: usize) -> Result<Parallel<F>, ThreadPoolBuildError> {
+        Ok(Parallel::in_pool(factory, Arc::new(ThreadPoolBuilder::new().num_threads(threads).build()?)))
+    }
+
+    /// Parallel evaluation of graphs built with the factory in the given thread pool, such as a
+    /// pool shared with the application embedding the engine.
+    pub fn in_pool(factory: F, pool: Arc<ThreadPool>) -> Parallel<F> {
+        Parallel { factory, pool }
+    }
+}
+
+impl<T, F> EvaluationStrategy<T> for Parallel<F>
+where T: Clone + Send + Sync, F: Fn() -> EventNode<T> + Sync {
+    fn evaluate(&self, root: &EventNode<T>, payload: T) -> Result<ChainResults<T>, EvaluationFailure> {
+        let chains = chain_count(root, &mut HashMap::new());
+        let evaluated = self.pool.install(|| {
+            (0..chains).into_par_iter()
+                .map_init(|| EventDAG::node_chains(&(self.factory)()), |graph_chains, chain| {
+                    evaluate_selected(graph_chains, &[chain], payload.clone())
+                })
+                .collect::<Vec<_>>()
+        });
+        let mut results = ChainResults::new();
+        for evaluated in evaluated {
+            results.extend(evaluated?);
+        }
+        Ok(results)
+    }
+}
+
+impl<T: Clone> EvaluationStrategy<T> for Sampled {
+    fn evaluate(&self, root: &EventNode<T>, payload: T) -> Result<ChainResults<T>, EvaluationFailure> {
+        let chains = EventDAG::node_chains(root);
+        let mut stream = self.stream;
+        let mut selected = BTreeSet::new();
+        // Floyd's algorithm for sampling without replacement.
+        for bound in chains.len().saturating_sub(self.samples)..chains.len() {
+            let drawn = (stream.next_u64() % (bound as u64 + 1)) as usize;
+            if !selected.insert(drawn) {
+                selected.insert(bound);
+            }
+        }
+        evaluate_selected(&chains, &selected.into_iter().collect::<Vec<_>>(), payload)
+    }
+}
+
+/// Evaluate the event chains of the given chain ids, in the given order.
+fn evaluate_selected<T: Clone>(chains: &[EventNodes<T>], selected: &[usize], payload: T) -> Result<ChainResults<T>, EvaluationFailure> {
+    let chosen: Vec<EventNodes<T>> = selected.iter().map(|chain| chains[*chain].clone()).collect();
+    EventDAG::try_evaluate_indexed(&chosen, |_| payload.clone())
+        .map(|results| results.into_iter().map(|(index, result)| (selected[index], result)).collect())
+        .map_err(|failure| EvaluationFailure { chain: selected[failure.chain], ..failure })
+}
+
+/// Evaluate the node and the nodes following it depth-first, the node being at the given
+/// position of the event chains from the given chain id onwards.
+fn visit<T: Clone>(
+    node: &EventNode<T>,
+    payload: T,
+    chain: usize,
+    position: usize,
+    counts: &mut HashMap<*const (), usize>,
+    results: &mut ChainResults<T>
+) -> Result<(), EvaluationFailure> {
+    let current = match catch_unwind(AssertUnwindSafe(|| node.borrow().apply(payload))) {
+        Ok(current) => current,
+        Err(payload) if is_pruned(&*payload) => return Ok(()),
+        Err(payload) => return Err(EvaluationFailure { chain, operation: position, label: node.borrow().label().map(str::to_string), error: panic_message(payload) }),
+    };
+    let followers = node.borrow().evaluation_order();
+    if followers.is_empty() {
+        results.insert(chain, current);
+        return Ok(());
+    }
+    let mut first = chain;
+    for follower in followers {
+        visit(&follower, current.clone(), first, position + 1, counts, results)?;
+        first += chain_count(&follower, counts);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+    use crate::branching_generators::{alternatives, sequence};
+    use crate::event_graph::{prune, BoxedOperation};
+    use super::*;
+
+    fn ops(operations: Vec<fn(i32) -> i32>) -> Vec<BoxedOperation<i32>> {
+        operations.into_iter().map(|operation| -> BoxedOperation<i32> { Box::new(operation) }).collect()
+    }
+
+    /// Three alternatives, the second one pruning large payloads, each followed by two
+    /// alternatives, one of them followed by a growth step.
+    fn graph() -> EventNode<i32> {
+        let root = EventDAG::new_node(Box::new(|x: i32| x));
+        let first = alternatives(vec![Rc::clone(&root)], ops(vec![|x| x + 1, |x| if x > 2 { prune() } else { x + 2 }, |x| x + 3])).unwrap();
+        let second = alternatives(first, ops(vec![|x| x * 10, |x| x * 100])).unwrap();
+        sequence(vec![Rc::clone(&second[1])], ops(vec![|x| x + 5])).unwrap();
+        root
+    }
+
+    fn strategies() -> Vec<Box<dyn EvaluationStrategy<i32>>> {
+        vec![
+            Box::new(ChainWise),
+            Box::new(DepthFirst),
+            Box::new(BreadthFirst),
+            Box::new(Parallel::new(graph, 2).unwrap()),
+            Box::new(Parallel::in_pool(graph, Arc::new(ThreadPoolBuilder::new().num_threads(3).build().unwrap()))),
+        ]
+    }
+
+    #[test]
+    fn strategies_produce_equal_results() {
+        let root = graph();
+        for payload in [0, 5] {
+            let expected = EventDAG::evaluate_chains_by_id(&root, payload);
+            for strategy in strategies() {
+                assert_eq!(Ok(expected.clone()), strategy.evaluate(&root, payload));
+                assert_eq!(Ok(expected.clone()), EventDAG::try_evaluate_with(&root, payload, strategy.as_ref()));
+            }
+        }
+        assert_eq!(vec![0, 1, 4, 5], EventDAG::evaluate_chains_by_id(&root, 5).into_keys().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn strategies_describe_failures_alike() {
+        let root = graph();
+        root.borrow().followers()[1].borrow_mut().set_operation(Box::new(|x| if x > 2 { panic!("large") } else { x }));
+        let expected = EvaluationFailure { chain: 2, operation: 1, label: None, error: "large".to_string() };
+        for strategy in strategies().into_iter().take(3) {
+            assert_eq!(Err(expected.clone()), strategy.evaluate(&root, 5));
+        }
+    }
+
+    #[test]
+    fn samples_are_drawn_without_replacement() {
+        let root = graph();
+        let all = EventDAG::evaluate_chains_by_id(&root, 0);
+        let sampled = Sampled { samples: 4, stream: RngStream::new(7) }.evaluate(&root, 0).unwrap();
+        assert_eq!(4, sampled.len());
+        assert!(sampled.iter().all(|(chain, result)| all[chain] == *result));
+        assert_eq!(Ok(sampled), Sampled { samples: 4, stream: RngStream::new(7) }.evaluate(&root, 0));
+        assert_eq!(Ok(all), Sampled { samples: 10, stream: RngStream::new(7) }.evaluate(&root, 0));
+    }
+}
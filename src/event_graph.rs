@@ -1,31 +1,335 @@
+use std::any::Any;
 use std::cell::RefCell;
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::panic::{catch_unwind, resume_unwind, AssertUnwindSafe};
 use std::rc::Rc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use serde::{Deserialize, Serialize};
+use crate::evaluation_strategy::EvaluationStrategy;
 
 pub type UnboundOperation<T> = dyn Fn(T) -> T;
 pub type BoxedOperation<T> = Box<UnboundOperation<T>>;
 pub type OperationChain<T> = Vec<BoxedOperation<T>>;
 type OperationResults<T> = Vec<T>;
-type UniqueChains<T> = Vec<EventNodes<T>>;
+/// Simulated time, such as the year, of the time layers of event graphs.
+pub type TimePoint = i32;
+/// Results of event chains by chain id, iterated in chain id order.
+pub type ChainResults<T> = BTreeMap<usize, T>;
+pub(crate) type UniqueChains<T> = Vec<EventNodes<T>>;
 pub type EventNode<T> = Rc<RefCell<EventDAG<T>>>;
 pub type EventNodes<T> = Vec<EventNode<T>>;
 
+/// Description of an operation failing during evaluation of an event chain.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EvaluationFailure {
+    /// Index of the failing event chain in evaluation order.
+    pub chain: usize,
+    /// Position of the failing operation within the event chain, the starting node being 0.
+    pub operation: usize,
+    /// Label of the failing node, such as the declared operation name.
+    #[serde(default)]
+    pub label: Option<String>,
+    pub error: String,
+}
+
+impl EvaluationFailure {
+    /// Failing operation by its label and position, or by its position if unlabeled.
+    pub fn location(&self) -> String {
+        match &self.label {
+            Some(label) => format!("{label} (operation {})", self.operation),
+            None => format!("operation {}", self.operation),
+        }
+    }
+}
+
+/// Extract a readable message from a panic payload.
+pub(crate) fn panic_message(payload: Box<dyn Any + Send>) -> String {
+    match payload.downcast::<String>() {
+        Ok(message) => *message,
+        Err(payload) => payload.downcast_ref::<&str>().map_or("unknown error".to_string(), |message| message.to_string())
+    }
+}
+
+/// Unwinding payload of a pruned event chain.
+struct Pruned;
+
+/// Prune the event chain under evaluation, leaving it without a result. Called from within an
+/// operation, pruning unwinds out of the operation without invoking the panic hook. Pruning, like
+/// describing failing operations as EvaluationFailures, requires unwinding panics: in a build with
+/// `panic = "abort"` pruning aborts the process.
+pub fn prune() -> ! {
+    resume_unwind(Box::new(Pruned))
+}
+
+/// Check whether an unwinding payload is due to pruning.
+pub fn is_pruned(payload: &(dyn Any + Send)) -> bool {
+    payload.is::<Pruned>()
+}
+
+/// Compose the operations of the chain into a single operation applying them in chain order,
+/// such as for an alternative of several steps within a single EventNode. An empty chain
+/// composes into the identity operation.
+pub fn compose<T: 'static>(chain: OperationChain<T>) -> BoxedOperation<T> {
+    Box::new(move |payload| chain.iter().fold(payload, |payload, operation| operation(payload)))
+}
+
+/// Number of unique event chains starting from the node, memoized by node identity.
+pub(crate) fn chain_count<T: Clone>(node: &EventNode<T>, counts: &mut HashMap<*const (), usize>) -> usize {
+    let key = node.as_ptr() as *const ();
+    if let Some(count) = counts.get(&key) {
+        return *count;
+    }
+    let followers = node.borrow().evaluation_order();
+    let count = match followers.is_empty() {
+        true => 1,
+        false => followers.iter().map(|follower| chain_count(follower, counts)).fold(0, usize::saturating_add),
+    };
+    counts.insert(key, count);
+    count
+}
+
+/// Evaluation behavior of an EventDAG node. Disabling nodes toggles their events on and off
+/// without restructuring the graph.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum NodeMode {
+    #[default]
+    Enabled,
+    /// Disabled, passing the payload through unchanged.
+    Skipped,
+    /// Disabled, pruning the event chains through the node.
+    Pruned,
+}
+
+/// Source of the revisions of EventDAG nodes, unique across all nodes.
+static REVISIONS: AtomicU64 = AtomicU64::new(0);
+
+fn next_revision() -> u64 {
+    REVISIONS.fetch_add(1, Ordering::Relaxed)
+}
+
+/// Selection of EventNodes, either a node itself or the nodes with a label.
+pub enum NodeSelector<'a, T> {
+    Node(&'a EventNode<T>),
+    Label(&'a str),
+}
+
+impl<T> NodeSelector<'_, T> {
+    pub fn matches(&self, node: &EventNode<T>) -> bool {
+        match self {
+            NodeSelector::Node(selected) => Rc::ptr_eq(selected, node),
+            NodeSelector::Label(label) => node.borrow().label == Some(*label),
+        }
+    }
+}
+
+impl<'a, T> From<&'a EventNode<T>> for NodeSelector<'a, T> {
+    fn from(node: &'a EventNode<T>) -> NodeSelector<'a, T> {
+        NodeSelector::Node(node)
+    }
+}
+
+impl<'a, T> From<&'a str> for NodeSelector<'a, T> {
+    fn from(label: &'a str) -> NodeSelector<'a, T> {
+        NodeSelector::Label(label)
+    }
+}
+
+/// Filter of event chains by the tags of their nodes, applied before evaluation so that the
+/// filtered out chains are never evaluated.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ChainFilter {
+    /// Tags each of which some node of an accepted chain has.
+    pub include: Vec<&'static str>,
+    /// Tags none of the nodes of an accepted chain has.
+    pub exclude: Vec<&'static str>,
+}
+
+impl ChainFilter {
+    pub fn accepts<T>(&self, chain: &[EventNode<T>]) -> bool {
+        let tagged = |tag: &&str| chain.iter().any(|node| node.borrow().tags.contains(tag));
+        self.include.iter().all(tagged) && !self.exclude.iter().any(tagged)
+    }
+}
+
+/// Storage of the followers of an EventDAG node in attachment order, optionally addressable by
+/// branch label.
+pub trait FollowerStorage<T: Clone> {
+    /// Followers in attachment order.
+    fn nodes(&self) -> &[EventNode<T>];
+
+    /// Attach the node as the follower of the branch with the given label, if any.
+    fn attach(&mut self, label: Option<&'static str>, node: EventNode<T>);
+
+    /// Replace the followers, keeping the branch labels of the followers still attached.
+    fn replace(&mut self, nodes: EventNodes<T>);
+
+    /// Follower of the branch with the given label. By default, the first follower with the label
+    /// as its node label.
+    fn branch(&self, label: &str) -> Option<EventNode<T>> {
+        self.nodes().iter().find(|node| node.borrow().label() == Some(label)).cloned()
+    }
+
+    /// Label of the branch of the given follower. By default, the node label of the follower.
+    fn branch_label(&self, node: &EventNode<T>) -> Option<&'static str> {
+        node.borrow().label()
+    }
+}
+
+/// Followers stored in attachment order only, branch labels falling back to node labels.
+impl<T: Clone> FollowerStorage<T> for EventNodes<T> {
+    fn nodes(&self) -> &[EventNode<T>] {
+        self
+    }
+
+    fn attach(&mut self, _label: Option<&'static str>, node: EventNode<T>) {
+        self.push(node)
+    }
+
+    fn replace(&mut self, nodes: EventNodes<T>) {
+        *self = nodes
+    }
+}
+
+/// Followers stored in attachment order and indexed by branch label for constant time lookup.
+/// Followers attached without a branch label are indexed by their node labels at attachment,
+/// the first follower of a label taking it.
+pub struct LabeledFollowers<T> {
+    nodes: EventNodes<T>,
+    branches: HashMap<&'static str, usize>,
+}
+
+impl<T> LabeledFollowers<T> {
+    pub fn new() -> LabeledFollowers<T> {
+        LabeledFollowers { nodes: EventNodes::new(), branches: HashMap::new() }
+    }
+
+    /// Branch labels and the positions of their followers.
+    pub fn labels(&self) -> &HashMap<&'static str, usize> {
+        &self.branches
+    }
+}
+
+impl<T> Default for LabeledFollowers<T> {
+    fn default() -> LabeledFollowers<T> {
+        LabeledFollowers::new()
+    }
+}
+
+impl<T: Clone> FollowerStorage<T> for LabeledFollowers<T> {
+    fn nodes(&self) -> &[EventNode<T>] {
+        &self.nodes
+    }
+
+    fn attach(&mut self, label: Option<&'static str>, node: EventNode<T>) {
+        if let Some(label) = label.or_else(|| node.borrow().label()) {
+            self.branches.entry(label).or_insert(self.nodes.len());
+        }
+        self.nodes.push(node)
+    }
+
+    fn replace(&mut self, nodes: EventNodes<T>) {
+        let mut previous = LabeledFollowers::new();
+        std::mem::swap(self, &mut previous);
+        for node in nodes {
+            let label = previous.branch_label(&node);
+            self.attach(label, node);
+        }
+    }
+
+    fn branch(&self, label: &str) -> Option<EventNode<T>> {
+        self.branches.get(label).map(|position| Rc::clone(&self.nodes[*position]))
+    }
+
+    fn branch_label(&self, node: &EventNode<T>) -> Option<&'static str> {
+        self.branches.iter()
+            .find(|(_, position)| Rc::ptr_eq(&self.nodes[**position], node))
+            .map(|(label, _)| *label)
+            .or_else(|| node.borrow().label())
+    }
+}
+
 pub struct EventDAG<T> {
     operation: BoxedOperation<T>,
-    followers: EventNodes<T>
+    followers: Box<dyn FollowerStorage<T>>,
+    mode: NodeMode,
+    priority: i32,
+    probability: f64,
+    revision: u64,
+    label: Option<&'static str>,
+    tags: Vec<&'static str>,
+    layer: Option<TimePoint>,
+    snapshot: bool,
+}
+
+/// Iterator over the distinct leaf EventNodes reachable from a node, created by
+/// EventNodeExt::leaves.
+pub struct Leaves<T> {
+    stack: EventNodes<T>,
+    visited: HashSet<*const ()>,
+}
+
+impl<T: Clone> Iterator for Leaves<T> {
+    type Item = EventNode<T>;
+
+    fn next(&mut self) -> Option<EventNode<T>> {
+        while let Some(node) = self.stack.pop() {
+            if !self.visited.insert(node.as_ptr() as *const ()) {
+                continue;
+            }
+            if node.borrow().is_leaf() {
+                return Some(node);
+            }
+            self.stack.extend(node.borrow().evaluation_order().into_iter().rev());
+        }
+        None
+    }
+}
+
+/// Graph traversals called on an EventNode, such as root.leaves().
+pub trait EventNodeExt<T> {
+    /// Iterate the distinct leaf EventNodes reachable from this node, such as for attaching
+    /// further events into them. The node is its only leaf if it has no followers. Leaves are
+    /// iterated in chain id order of the first event chains ending in them.
+    fn leaves(&self) -> Leaves<T>;
+}
+
+impl<T: Clone> EventNodeExt<T> for EventNode<T> {
+    fn leaves(&self) -> Leaves<T> {
+        Leaves { stack: vec![Rc::clone(self)], visited: HashSet::new() }
+    }
 }
 
 /// EventDAG describes a simulation, optionally branching into alternative events. All nodes
 /// of the graph hold a function with signature T -> T, where T represents the simulated state.
-/// The functions are thus simulation events. T must implement Copy (or be implicitly copyable)
-/// for moving ownership into alternative event branches.
-impl<T: Copy> EventDAG<T> {
+/// The functions are thus simulation events. T must implement Clone for passing the payload
+/// into alternative event branches.
+///
+/// The unique event chains through the graph are ordered depth-first by the evaluation order of
+/// the followers, so that a chain branching into an earlier follower precedes the chains branching
+/// into later followers. Followers are evaluated in descending order of priority, followers of
+/// equal priority in attachment order. The index of a chain in this order is its chain id.
+/// Pruned chains produce no results, so the positions of results equal chain ids only when no
+/// chain is pruned; evaluate_chains_by_id keeps the results by chain id.
+impl<T: Clone> EventDAG<T> {
     /// Construct a new EventDAG<T> node with given Operation<T> function reference
-    fn new(operation: BoxedOperation<T>) -> EventDAG<T> {
-        EventDAG { operation, followers: Vec::new()}
+    fn new(operation: BoxedOperation<T>) -> EventDAG<T> where T: 'static {
+        EventDAG {
+            operation,
+            followers: Box::new(EventNodes::new()),
+            mode: NodeMode::Enabled,
+            priority: 0,
+            probability: 1.0,
+            revision: next_revision(),
+            label: None,
+            tags: Vec::new(),
+            layer: None,
+            snapshot: false,
+        }
     }
 
-    pub fn new_node(operation: BoxedOperation<T>) -> EventNode<T> {
-        Rc::new(RefCell::new(EventDAG { operation, followers: Vec::new()}))
+    pub fn new_node(operation: BoxedOperation<T>) -> EventNode<T> where T: 'static {
+        EventDAG::new(operation).wrap()
     }
 
     pub fn wrap(self) -> EventNode<T> {
@@ -33,48 +337,230 @@ impl<T: Copy> EventDAG<T> {
     }
 
     /// Attach another EventDAG<T> into self.
-    fn add_branch(&mut self, branch: EventDAG<T>) {
-        self.followers.push(Rc::new(RefCell::new(branch)))
+    #[allow(dead_code)]
+    fn add_branch(&mut self, branch: EventDAG<T>) where T: 'static {
+        self.followers.attach(None, branch.wrap())
     }
 
     pub fn add_follower_node(&mut self, node: &EventNode<T>) {
-        self.followers.push(Rc::clone(node))
+        self.followers.attach(None, Rc::clone(node))
+    }
+
+    /// Attach the node as the follower of the branch with the given label.
+    pub fn add_labeled_follower(&mut self, label: &'static str, node: &EventNode<T>) {
+        self.followers.attach(Some(label), Rc::clone(node))
     }
 
-    fn is_leaf(&self) -> bool {
-        return self.followers.len() == 0
+    /// Followers of this EventDAG<T> in attachment order.
+    pub fn followers(&self) -> &[EventNode<T>] {
+        self.followers.nodes()
     }
 
-    /// Obtain mutable borrows for leaf nodes of this EventDAG<T>
-    fn collect_leaf_nodes(&self) -> EventNodes<T> {
-        let mut result = Vec::new();
+    /// Followers of this EventDAG<T> in evaluation order, by descending priority and then by
+    /// attachment order.
+    pub fn evaluation_order(&self) -> EventNodes<T> {
+        let mut followers = self.followers().to_vec();
+        followers.sort_by_key(|follower| Reverse(follower.borrow().priority));
+        followers
+    }
 
-        for branch in &self.followers {
-            if !branch.borrow().is_leaf() {
-                result.extend(branch.borrow().collect_leaf_nodes())
-            }
-            else {
-                result.push(Rc::clone(branch))
+    /// Follower of the branch with the given label, as looked up by the follower storage.
+    pub fn branch(&self, label: &str) -> Option<EventNode<T>> {
+        self.followers.branch(label)
+    }
+
+    /// Label of the branch of the given follower of this EventDAG<T>, as labeled by the follower
+    /// storage.
+    pub fn branch_label(&self, node: &EventNode<T>) -> Option<&'static str> {
+        self.followers.branch_label(node)
+    }
+
+    /// Replace the storage of the followers of this EventDAG<T>, moving the current followers
+    /// into it.
+    pub fn set_follower_storage<S: FollowerStorage<T> + 'static>(&mut self, mut storage: S) {
+        storage.replace(self.followers.nodes().to_vec());
+        self.followers = Box::new(storage);
+    }
+
+    /// Replace the followers of this EventDAG<T>.
+    pub(crate) fn set_followers(&mut self, followers: EventNodes<T>) {
+        self.followers.replace(followers)
+    }
+
+    /// Replace the operation of this EventDAG<T>.
+    pub fn set_operation(&mut self, operation: BoxedOperation<T>) {
+        self.operation = operation;
+        self.revision = next_revision();
+    }
+
+    /// Revision of the operation and the mode of this node, unique across all nodes and renewed
+    /// by each modification of either.
+    pub fn revision(&self) -> u64 {
+        self.revision
+    }
+
+    /// Name of the operation of this node, such as the declared operation name.
+    pub fn label(&self) -> Option<&'static str> {
+        self.label
+    }
+
+    pub fn set_label(&mut self, label: &'static str) {
+        self.label = Some(label)
+    }
+
+    /// Tags attached to this node for selecting nodes, such as by graph rewriting rules.
+    pub fn tags(&self) -> &[&'static str] {
+        &self.tags
+    }
+
+    pub fn add_tag(&mut self, tag: &'static str) {
+        if !self.has_tag(tag) {
+            self.tags.push(tag)
+        }
+    }
+
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.tags.contains(&tag)
+    }
+
+    /// Time point of the layer of the event graph this node belongs to, if constructed in
+    /// time layers.
+    pub fn layer(&self) -> Option<TimePoint> {
+        self.layer
+    }
+
+    pub fn set_layer(&mut self, time_point: TimePoint) {
+        self.layer = Some(time_point)
+    }
+
+    pub fn is_snapshot(&self) -> bool {
+        self.snapshot
+    }
+
+    /// Mark this node as a snapshot point, for storing the state entering the node during
+    /// evaluation with snapshots.
+    pub fn set_snapshot(&mut self, snapshot: bool) {
+        self.snapshot = snapshot
+    }
+
+    pub fn mode(&self) -> NodeMode {
+        self.mode
+    }
+
+    /// Set the evaluation behavior of this node, also for the graphs sharing it.
+    pub fn set_mode(&mut self, mode: NodeMode) {
+        self.mode = mode;
+        self.revision = next_revision();
+    }
+
+    pub fn priority(&self) -> i32 {
+        self.priority
+    }
+
+    /// Set the priority of this node among the followers of its preceding nodes, a higher
+    /// priority evaluating earlier. The default priority is 0.
+    pub fn set_priority(&mut self, priority: i32) {
+        self.priority = priority
+    }
+
+    pub fn probability(&self) -> f64 {
+        self.probability
+    }
+
+    /// Set the probability of the event of this node given the events of the preceding nodes,
+    /// such as the probability of a branch among alternative outcomes. The default is 1.
+    pub fn set_probability(&mut self, probability: f64) {
+        self.probability = probability
+    }
+
+    /// Apply the operation of this single EventDAG<T> node into the payload, according to the
+    /// mode of the node.
+    pub fn apply(&self, payload: T) -> T {
+        match self.mode {
+            NodeMode::Enabled => (self.operation)(payload),
+            NodeMode::Skipped => payload,
+            NodeMode::Pruned => prune(),
+        }
+    }
+
+    /// Check whether the given EventNode<T> is already attached as a follower of self.
+    pub fn has_follower(&self, node: &EventNode<T>) -> bool {
+        self.followers().iter().any(|follower| Rc::ptr_eq(follower, node))
+    }
+
+    /// Check whether this EventDAG<T> has no followers, ending the event chains through it.
+    pub fn is_leaf(&self) -> bool {
+        self.followers().is_empty()
+    }
+
+    /// Distinct EventNodes reachable from the given EventNodes, including themselves, in
+    /// breadth-first order.
+    pub fn reachable_nodes(from: &[EventNode<T>]) -> EventNodes<T> {
+        let mut visited: HashSet<*const ()> = HashSet::new();
+        let mut queue: VecDeque<EventNode<T>> = from.iter().cloned().collect();
+        let mut result = EventNodes::new();
+        while let Some(node) = queue.pop_front() {
+            if visited.insert(node.as_ptr() as *const ()) {
+                queue.extend(node.borrow().followers().iter().cloned());
+                result.push(node);
             }
         }
-        return result;
+        result
+    }
+
+    /// First EventNode with the given label reachable from the given root, in breadth-first order.
+    pub fn find(root: &EventNode<T>, label: &str) -> Option<EventNode<T>> {
+        EventDAG::reachable_nodes(&[Rc::clone(root)]).into_iter().find(|node| node.borrow().label() == Some(label))
+    }
+
+    /// EventNodes with the given tag reachable from the given root, in breadth-first order.
+    pub fn find_all(root: &EventNode<T>, tag: &str) -> EventNodes<T> {
+        EventDAG::reachable_nodes(&[Rc::clone(root)]).into_iter().filter(|node| node.borrow().has_tag(tag)).collect()
+    }
+
+    /// Set the priority of the EventNodes with the given label reachable from the given root, such
+    /// as the nodes generated for a declared operation, returning the number of nodes set.
+    pub fn prioritize(root: &EventNode<T>, label: &str, priority: i32) -> usize {
+        let nodes: EventNodes<T> = EventDAG::reachable_nodes(&[Rc::clone(root)]).into_iter()
+            .filter(|node| node.borrow().label() == Some(label))
+            .collect();
+        for node in nodes.iter() {
+            node.borrow_mut().set_priority(priority);
+        }
+        nodes.len()
+    }
+
+    /// Unique event chains starting from the given root and passing through the selected nodes,
+    /// in evaluation order.
+    pub fn chains_through<'a, S>(root: &EventNode<T>, selector: S) -> Vec<EventNodes<T>>
+    where T: 'a, S: Into<NodeSelector<'a, T>> {
+        let selector = selector.into();
+        let mut chains = EventDAG::node_chains(root);
+        chains.retain(|chain| chain.iter().any(|node| selector.matches(node)));
+        chains
+    }
+
+    /// Unique event chains starting from the given root accepted by the filter, in evaluation
+    /// order, for evaluating with try_evaluate_node_chains.
+    pub fn filtered_chains(root: &EventNode<T>, filter: &ChainFilter) -> Vec<EventNodes<T>> {
+        let mut chains = EventDAG::node_chains(root);
+        chains.retain(|chain| filter.accepts(chain));
+        chains
     }
 
     /// Generate vectors of EventNode<T> representing unique event chains through the graph starting
-    /// from the node. Recursive post-order walkthrough of the graph is performed.
-    fn node_chains(wrapped_self: &EventNode<T>) -> UniqueChains<T> {
+    /// from the node, in chain id order. Recursive post-order walkthrough of the graph is performed.
+    pub(crate) fn node_chains(wrapped_self: &EventNode<T>) -> UniqueChains<T> {
         let mut result = UniqueChains::new();
-        if wrapped_self.borrow().followers.len() == 0 {
-            let mut current = EventNodes::new();
-            current.push(Rc::clone(&wrapped_self));
-            result.push(current);
+        if wrapped_self.borrow().is_leaf() {
+            result.push(vec![Rc::clone(wrapped_self)]);
         }
         else {
-            for branch in &wrapped_self.borrow().followers {
-                let from_branch = EventDAG::node_chains(branch);
+            for branch in wrapped_self.borrow().evaluation_order() {
+                let from_branch = EventDAG::node_chains(&branch);
                 for chain in from_branch {
                     let mut current = EventNodes::new();
-                    current.push(Rc::clone(&wrapped_self));
+                    current.push(Rc::clone(wrapped_self));
                     current.extend(chain);
                     result.push(current);
                 }
@@ -84,34 +570,132 @@ impl<T: Copy> EventDAG<T> {
     }
 
     /// Evaluate unique function chains represented by the given EventNode<T>, producing their
-    /// results as a vector OperationResults<T>.
+    /// results as a vector OperationResults<T>. Pruned chains produce no results.
     pub fn evaluate_chains(wrapped_self: &EventNode<T>, payload: T) -> OperationResults<T> {
-        let chains = EventDAG::node_chains(wrapped_self);
-        let mut results = OperationResults::new();
-        for chain in chains {
-            let mut current: T = payload;
-            for node in chain {
-                current = (node.borrow().operation)(current)
+        EventDAG::evaluate_chains_by_id(wrapped_self, payload).into_values().collect()
+    }
+
+    /// Evaluate unique function chains with the given EvaluationStrategy, producing the results
+    /// by chain id.
+    pub fn try_evaluate_with<S>(wrapped_self: &EventNode<T>, payload: T, strategy: &S) -> Result<ChainResults<T>, EvaluationFailure>
+    where S: EvaluationStrategy<T> + ?Sized {
+        strategy.evaluate(wrapped_self, payload)
+    }
+
+    /// Evaluate unique function chains as evaluate_chains, producing the results by the ids of
+    /// their chains. The ids of pruned chains are missing.
+    pub fn evaluate_chains_by_id(wrapped_self: &EventNode<T>, payload: T) -> ChainResults<T> {
+        EventDAG::evaluate_node_chains(&EventDAG::node_chains(wrapped_self), payload)
+    }
+
+    fn evaluate_node_chains(chains: &[EventNodes<T>], payload: T) -> ChainResults<T> {
+        let mut results = ChainResults::new();
+        for (chain_id, chain) in chains.iter().enumerate() {
+            let evaluated = catch_unwind(AssertUnwindSafe(|| {
+                chain.iter().fold(payload.clone(), |current, node| node.borrow().apply(current))
+            }));
+            match evaluated {
+                Ok(result) => { results.insert(chain_id, result); }
+                Err(payload) if is_pruned(&*payload) => {}
+                Err(payload) => resume_unwind(payload),
             }
-            results.push(current)
         }
         results
     }
 
+    /// Evaluate unique function chains as try_evaluate_chains, producing the results by the ids
+    /// of their chains.
+    pub fn try_evaluate_chains_by_id(wrapped_self: &EventNode<T>, payload: T) -> Result<ChainResults<T>, EvaluationFailure> {
+        let chains = EventDAG::node_chains(wrapped_self);
+        Ok(EventDAG::try_evaluate_indexed(&chains, |_| payload.clone())?.into_iter().collect())
+    }
+
+    /// Evaluate unique function chains as evaluate_chains, but catch a panicking operation and
+    /// describe it as an EvaluationFailure instead of unwinding through the caller.
+    pub fn try_evaluate_chains(wrapped_self: &EventNode<T>, payload: T) -> Result<OperationResults<T>, EvaluationFailure> {
+        EventDAG::try_evaluate_chains_from(wrapped_self, |_| payload.clone())
+    }
+
+    /// Evaluate unique function chains as try_evaluate_chains, starting each chain from the
+    /// payload given for its chain index.
+    pub fn try_evaluate_chains_from<F>(wrapped_self: &EventNode<T>, payload: F) -> Result<OperationResults<T>, EvaluationFailure>
+    where F: Fn(usize) -> T {
+        EventDAG::try_evaluate_node_chains_from(&EventDAG::node_chains(wrapped_self), payload)
+    }
+
+    /// Evaluate the given event chains, such as selected by chains_through, as
+    /// try_evaluate_chains. Failures are indexed within the given chains.
+    pub fn try_evaluate_node_chains(chains: &[EventNodes<T>], payload: T) -> Result<OperationResults<T>, EvaluationFailure> {
+        EventDAG::try_evaluate_node_chains_from(chains, |_| payload.clone())
+    }
+
+    fn try_evaluate_node_chains_from<F>(chains: &[EventNodes<T>], payload: F) -> Result<OperationResults<T>, EvaluationFailure>
+    where F: Fn(usize) -> T {
+        let indexed = EventDAG::try_evaluate_indexed(chains, payload)?;
+        Ok(indexed.into_iter().map(|(_, result)| result).collect())
+    }
+
+    /// Evaluate the given event chains, producing the results of the chains not pruned along
+    /// with their indices.
+    pub(crate) fn try_evaluate_indexed<F>(chains: &[EventNodes<T>], payload: F) -> Result<Vec<(usize, T)>, EvaluationFailure>
+    where F: Fn(usize) -> T {
+        let mut results = Vec::new();
+        'chains: for (chain_index, chain) in chains.iter().enumerate() {
+            let mut current: T = payload(chain_index);
+            for (operation_index, node) in chain.iter().enumerate() {
+                current = match catch_unwind(AssertUnwindSafe(|| node.borrow().apply(current))) {
+                    Ok(current) => current,
+                    Err(payload) if is_pruned(&*payload) => continue 'chains,
+                    Err(payload) => return Err(EvaluationFailure {
+                        chain: chain_index,
+                        operation: operation_index,
+                        label: node.borrow().label().map(str::to_string),
+                        error: panic_message(payload)
+                    }),
+                };
+            }
+            results.push((chain_index, current))
+        }
+        Ok(results)
+    }
+
+    /// Unique event chains following the given node, without the node itself.
+    fn tail_chains(node: &EventNode<T>) -> UniqueChains<T> {
+        EventDAG::node_chains(node).into_iter().map(|chain| chain[1..].to_vec()).collect()
+    }
+
+    /// Evaluate the unique function chains following the given EventNode<T>, which need not be
+    /// a root, from a payload standing for the state after the node, such as an intermediate
+    /// state saved from an earlier evaluation. The operation of the node itself is not applied.
+    pub fn evaluate_from(node: &EventNode<T>, payload: T) -> OperationResults<T> {
+        EventDAG::evaluate_node_chains(&EventDAG::tail_chains(node), payload).into_values().collect()
+    }
+
+    /// Evaluate as evaluate_from, describing a panicking operation as an EvaluationFailure. The
+    /// given node is at position 0 of the failing event chain and the chains are indexed within
+    /// the chains following the node.
+    pub fn try_evaluate_from(node: &EventNode<T>, payload: T) -> Result<OperationResults<T>, EvaluationFailure> {
+        EventDAG::try_evaluate_node_chains(&EventDAG::tail_chains(node), payload)
+            .map_err(|failure| EvaluationFailure { operation: failure.operation + 1, ..failure })
+    }
+
     /// Evaluate the total computation represented by this EventDAG<T>, producing its results
     /// as a vector OperationResults<T>. Recursive pre-order walkthrough is performed.
     pub fn evaluate_depth(&self, payload: T) -> OperationResults<T> {
         let mut results = OperationResults::new();
-        let current = (self.operation)(payload);
-        let extension = match &self.followers {
-            branches if branches.len() == 0 => {
+        let current = match catch_unwind(AssertUnwindSafe(|| self.apply(payload))) {
+            Ok(current) => current,
+            Err(payload) if is_pruned(&*payload) => return results,
+            Err(payload) => resume_unwind(payload),
+        };
+        let extension = match self.evaluation_order().as_slice() {
+            [] => {
                 vec![current]
             }
             branches => {
                 branches
                     .iter()
-                    .map(|branch| branch.borrow().evaluate_depth(current))
-                    .flatten()
+                    .flat_map(|branch| branch.borrow().evaluate_depth(current.clone()))
                     .collect()
             }
         };
@@ -171,7 +755,7 @@ mod tests {
         assert_eq!(chains[0].len(), 3);
         assert_eq!(chains[1].len(), 3);
 
-        let leafs = root.borrow().collect_leaf_nodes();
+        let leafs: EventNodes<i32> = root.leaves().collect();
         for leaf in leafs {
             let extension = EventDAG::new(Box::new(increment));
             leaf.borrow_mut().add_branch(extension);
@@ -186,7 +770,7 @@ mod tests {
     #[test]
     fn nodes_are_shareable() {
         let root = create_fixture();
-        let leafs = root.borrow().collect_leaf_nodes();
+        let leafs: EventNodes<i32> = root.leaves().collect();
         let extension = Rc::new(RefCell::new(EventDAG::new(Box::new(increment))));
         for leaf in leafs {
             leaf.borrow_mut().add_follower_node(&extension);
@@ -196,4 +780,241 @@ mod tests {
         assert_eq!(chains[0].len(), 4);
         assert_eq!(chains[1].len(), 4);
     }
+
+    #[test]
+    fn leaves_are_distinct_and_ordered() {
+        let root = EventDAG::new_node(Box::new(increment));
+        let leaves: EventNodes<i32> = root.leaves().collect();
+        assert_eq!(1, leaves.len());
+        assert!(Rc::ptr_eq(&root, &leaves[0]));
+
+        let root = create_fixture();
+        let first = Rc::clone(&root.borrow().followers()[0]);
+        let shared = EventDAG::new_node(Box::new(increment));
+        let late = EventDAG::new_node(Box::new(increment));
+        root.borrow_mut().add_follower_node(&late);
+        for leaf in first.borrow().followers().iter() {
+            leaf.borrow_mut().add_follower_node(&shared);
+        }
+        let leaves: EventNodes<i32> = root.leaves().collect();
+        assert_eq!(2, leaves.len());
+        assert!(Rc::ptr_eq(&shared, &leaves[0]) && Rc::ptr_eq(&late, &leaves[1]));
+    }
+
+    #[test]
+    fn branches_are_found_by_label() {
+        let root = create_fixture();
+        let first = Rc::clone(&root.borrow().followers()[0]);
+        first.borrow().followers()[1].borrow_mut().set_label("thinning");
+        assert!(Rc::ptr_eq(&first.borrow().followers()[1], &first.borrow().branch("thinning").unwrap()));
+        assert!(first.borrow().branch("clearcut").is_none());
+
+        first.borrow_mut().set_follower_storage(LabeledFollowers::new());
+        let clearcut = EventDAG::new_node(Box::new(|x: i32| x * 10));
+        clearcut.borrow_mut().set_label("thinning");
+        first.borrow_mut().add_labeled_follower("clearcut", &clearcut);
+        first.borrow_mut().add_follower_node(&EventDAG::new_node(Box::new(increment)));
+        assert_eq!(4, first.borrow().followers().len());
+        assert!(Rc::ptr_eq(&first.borrow().followers()[1], &first.borrow().branch("thinning").unwrap()));
+        assert!(Rc::ptr_eq(&clearcut, &first.borrow().branch("clearcut").unwrap()));
+        assert_eq!(vec![3, 3, 20, 3], EventDAG::evaluate_chains(&root, 0));
+
+        let kept = vec![Rc::clone(&clearcut), Rc::clone(&first.borrow().followers()[0])];
+        first.borrow_mut().set_followers(kept);
+        assert!(first.borrow().branch("thinning").is_none());
+        assert!(Rc::ptr_eq(&clearcut, &first.borrow().branch("clearcut").unwrap()));
+    }
+
+    #[test]
+    fn reachable_nodes_are_distinct() {
+        let root = create_fixture();
+        let nodes = EventDAG::reachable_nodes(&[Rc::clone(&root)]);
+        assert_eq!(4, nodes.len());
+        assert!(Rc::ptr_eq(&root, &nodes[0]));
+        let leaves: EventNodes<i32> = root.leaves().collect();
+        let extension = EventDAG::new_node(Box::new(increment));
+        for leaf in leaves.iter() {
+            leaf.borrow_mut().add_follower_node(&extension);
+        }
+        assert_eq!(3, EventDAG::reachable_nodes(&leaves).len());
+    }
+
+    #[test]
+    fn nodes_are_labeled_and_tagged() {
+        let root = create_fixture();
+        let mut node = root.borrow_mut();
+        assert_eq!(None, node.label());
+        node.set_label("grow");
+        node.add_tag("managed");
+        node.add_tag("managed");
+        assert_eq!((Some("grow"), &["managed"][..]), (node.label(), node.tags()));
+        assert!(node.has_tag("managed") && !node.has_tag("unmanaged"));
+        node.set_operation(Box::new(|x| x * 10));
+        drop(node);
+        assert_eq!(vec![12, 12], EventDAG::evaluate_chains(&root, 1));
+    }
+
+    #[test]
+    fn nodes_are_found_by_label_and_tag() {
+        let root = create_fixture();
+        let first = Rc::clone(&root.borrow().followers()[0]);
+        let leaves: EventNodes<i32> = first.leaves().collect();
+        first.borrow_mut().set_label("thinning");
+        leaves[1].borrow_mut().set_label("thinning");
+        for leaf in leaves.iter() {
+            leaf.borrow_mut().add_tag("late");
+        }
+        assert!(Rc::ptr_eq(&first, &EventDAG::find(&root, "thinning").unwrap()));
+        assert!(Rc::ptr_eq(&leaves[1], &EventDAG::find(&leaves[1], "thinning").unwrap()));
+        assert!(EventDAG::find(&root, "clearcut").is_none());
+        let late = EventDAG::find_all(&root, "late");
+        assert_eq!(2, late.len());
+        assert!(late.iter().zip(leaves.iter()).all(|(found, leaf)| Rc::ptr_eq(found, leaf)));
+        assert!(EventDAG::find_all(&root, "early").is_empty());
+    }
+
+    #[test]
+    fn chains_through_nodes_are_selected() {
+        let root = create_fixture();
+        let first = Rc::clone(&root.borrow().followers()[0]);
+        let leaf = Rc::clone(&first.borrow().followers()[1]);
+        leaf.borrow_mut().set_operation(Box::new(|x| x * 10));
+        leaf.borrow_mut().set_label("fertilization");
+        assert_eq!(2, EventDAG::chains_through(&root, &first).len());
+        let chains = EventDAG::chains_through(&root, "fertilization");
+        assert_eq!(1, chains.len());
+        assert!(Rc::ptr_eq(&leaf, &chains[0][2]));
+        assert_eq!(Ok(vec![20]), EventDAG::try_evaluate_node_chains(&chains, 0));
+        assert!(EventDAG::chains_through(&root, "clearcut").is_empty());
+    }
+
+    #[test]
+    fn chains_are_filtered_by_tags() {
+        let root = create_fixture();
+        let first = Rc::clone(&root.borrow().followers()[0]);
+        let leaves: EventNodes<i32> = first.leaves().collect();
+        first.borrow_mut().add_tag("thinning");
+        leaves[1].borrow_mut().add_tag("clearcut");
+        leaves[1].borrow_mut().set_operation(Box::new(|_| 0));
+        let thinned = ChainFilter { include: vec!["thinning"], exclude: Vec::new() };
+        assert_eq!(2, EventDAG::filtered_chains(&root, &thinned).len());
+        let filter = ChainFilter { exclude: vec!["clearcut"], ..thinned };
+        let chains = EventDAG::filtered_chains(&root, &filter);
+        assert_eq!(1, chains.len());
+        assert!(Rc::ptr_eq(&leaves[0], &chains[0][2]));
+        assert_eq!(Ok(vec![3]), EventDAG::try_evaluate_node_chains(&chains, 0));
+        assert!(EventDAG::filtered_chains(&root, &ChainFilter { include: vec!["fertilization"], exclude: Vec::new() }).is_empty());
+        assert_eq!(2, EventDAG::filtered_chains(&root, &ChainFilter::default()).len());
+    }
+
+    #[test]
+    fn cloned_payloads_are_evaluable() {
+        let root: EventNode<Vec<i32>> = EventDAG::new_node(Box::new(|mut trees: Vec<i32>| { trees.push(1); trees }));
+        for tree in [2, 3] {
+            root.borrow_mut().add_follower_node(&EventDAG::new_node(Box::new(move |mut trees: Vec<i32>| { trees.push(tree); trees })));
+        }
+        assert_eq!(vec![vec![1, 2], vec![1, 3]], EventDAG::evaluate_chains(&root, Vec::new()));
+        assert_eq!(vec![vec![1, 2], vec![1, 3]], root.borrow().evaluate_depth(Vec::new()));
+    }
+
+    #[test]
+    fn failures_are_described() {
+        let root = create_fixture();
+        let failing = EventDAG::new_node(Box::new(|x: i32| if x > 3 { panic!("too large: {x}") } else { x }));
+        root.borrow().followers()[0].borrow().followers()[1].borrow_mut().add_follower_node(&failing);
+        assert_eq!(Ok(vec![3]), EventDAG::try_evaluate_chains(&root.borrow().followers()[0].borrow().followers()[1], 2));
+        let failure = EventDAG::try_evaluate_chains(&root, 1).unwrap_err();
+        assert_eq!(EvaluationFailure { chain: 1, operation: 3, label: None, error: "too large: 4".to_string() }, failure);
+    }
+
+    #[test]
+    fn pruned_chains_have_no_results() {
+        let root = create_fixture();
+        let pruning = EventDAG::new_node(Box::new(|x: i32| if x > 2 { prune() } else { x }));
+        root.borrow().followers()[0].borrow().followers()[0].borrow_mut().add_follower_node(&pruning);
+        assert_eq!(vec![3], EventDAG::evaluate_chains(&root, 0));
+        assert_eq!(Ok(vec![3]), EventDAG::try_evaluate_chains(&root, 0));
+        assert_eq!(vec![3], root.borrow().evaluate_depth(0));
+        assert_eq!(vec![2, 2], EventDAG::evaluate_chains(&root, -1));
+    }
+
+    #[test]
+    fn chains_follow_follower_attachment_order() {
+        let root = EventDAG::new_node(Box::new(|x: i32| x));
+        let first = EventDAG::new_node(Box::new(|x: i32| x * 10 + 1));
+        let second = EventDAG::new_node(Box::new(|x: i32| x * 10 + 2));
+        for node in [&second, &first] {
+            root.borrow_mut().add_follower_node(node);
+        }
+        for leaf in [3, 4] {
+            first.borrow_mut().add_follower_node(&EventDAG::new_node(Box::new(move |x: i32| x * 10 + leaf)));
+        }
+        assert_eq!(vec![2, 13, 14], EventDAG::evaluate_chains(&root, 0));
+        assert_eq!(vec![2, 13, 14], root.borrow().evaluate_depth(0));
+    }
+
+    #[test]
+    fn followers_are_evaluated_by_priority() {
+        let root = EventDAG::new_node(Box::new(|x: i32| x));
+        for step in 1..=3 {
+            let node = EventDAG::new_node(Box::new(move |x: i32| x * 10 + step));
+            node.borrow_mut().set_label(if step == 1 { "grow" } else { "thin" });
+            root.borrow_mut().add_follower_node(&node);
+        }
+        root.borrow().followers()[2].borrow_mut().set_priority(1);
+        assert_eq!(vec![3, 1, 2], EventDAG::evaluate_chains(&root, 0));
+        assert_eq!(vec![3, 1, 2], root.borrow().evaluate_depth(0));
+        assert!(Rc::ptr_eq(&root.borrow().followers()[2], &root.leaves().next().unwrap()));
+        assert_eq!(2, EventDAG::prioritize(&root, "thin", 2));
+        assert_eq!(vec![2, 3, 1], EventDAG::evaluate_chains(&root, 0));
+        assert_eq!(vec![1, 2, 3], root.borrow().followers().iter().map(|node| node.borrow().apply(0)).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn results_are_kept_by_chain_id() {
+        let root = create_fixture();
+        let pruning = EventDAG::new_node(Box::new(|x: i32| if x > 2 { prune() } else { x }));
+        root.borrow().followers()[0].borrow().followers()[0].borrow_mut().add_follower_node(&pruning);
+        assert_eq!(ChainResults::from([(1, 3)]), EventDAG::evaluate_chains_by_id(&root, 0));
+        assert_eq!(Ok(ChainResults::from([(0, 2), (1, 2)])), EventDAG::try_evaluate_chains_by_id(&root, -1));
+    }
+
+    #[test]
+    fn tails_are_evaluable_from_interior_nodes() {
+        let root = create_fixture();
+        let interior = Rc::clone(&root.borrow().followers()[0]);
+        assert_eq!(vec![11, 11], EventDAG::evaluate_from(&interior, 10));
+        let leaf = Rc::clone(&interior.borrow().followers()[1]);
+        assert_eq!(vec![10], EventDAG::evaluate_from(&leaf, 10));
+
+        let failing = EventDAG::new_node(Box::new(|x: i32| if x > 10 { panic!("too large: {x}") } else { x }));
+        leaf.borrow_mut().add_follower_node(&failing);
+        assert_eq!(Ok(vec![5, 5]), EventDAG::try_evaluate_from(&interior, 4));
+        let failure = EventDAG::try_evaluate_from(&interior, 10).unwrap_err();
+        assert_eq!(EvaluationFailure { chain: 1, operation: 2, label: None, error: "too large: 11".to_string() }, failure);
+        assert_eq!(2, interior.borrow().followers().len());
+    }
+
+    #[test]
+    fn disabled_nodes_are_skipped_or_pruned() {
+        let root = create_fixture();
+        let first = Rc::clone(&root.borrow().followers()[0].borrow().followers()[0]);
+        first.borrow_mut().set_mode(NodeMode::Skipped);
+        assert_eq!(vec![2, 3], EventDAG::evaluate_chains(&root, 0));
+        assert_eq!(vec![2, 3], root.borrow().evaluate_depth(0));
+        first.borrow_mut().set_mode(NodeMode::Pruned);
+        assert_eq!(Ok(vec![3]), EventDAG::try_evaluate_chains(&root, 0));
+        assert_eq!(vec![3], root.borrow().evaluate_depth(0));
+        first.borrow_mut().set_mode(NodeMode::Enabled);
+        assert_eq!(vec![3, 3], EventDAG::evaluate_chains(&root, 0));
+    }
+
+    #[test]
+    fn chains_are_composed_into_single_operations() {
+        let root = EventDAG::new_node(Box::new(|x: i32| x));
+        let steps: OperationChain<i32> = vec![Box::new(increment), Box::new(|x| x * 10), Box::new(increment)];
+        root.borrow_mut().add_follower_node(&EventDAG::new_node(compose(steps)));
+        root.borrow_mut().add_follower_node(&EventDAG::new_node(compose(Vec::new())));
+        assert_eq!(vec![21, 1], EventDAG::evaluate_chains(&root, 1));
+    }
 }
@@ -0,0 +1,196 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::panic::resume_unwind;
+use std::rc::Rc;
+use evalexpr::{build_operator_tree, Context, ContextWithMutableVariables, EvalexprError, HashMapContext, Node, Value};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::{Map, Number};
+use crate::branching_generators::generator_map;
+use crate::configuration_utils::ParameterMap;
+use crate::event_graph::{BoxedOperation, EventDAG, EventNode, OperationChain};
+use crate::simulation_runner::{OperationParameters, RunnerError, SimulationDeclaration};
+
+/// Reasons for failing to parse expression operations or to compile a declaration over them.
+#[derive(Debug)]
+pub enum ExpressionError {
+    Parse { operation: String, error: EvalexprError },
+    Runner(RunnerError),
+}
+
+impl fmt::Display for ExpressionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExpressionError::Parse { operation, error } => write!(f, "expression of operation '{operation}': {error}"),
+            ExpressionError::Runner(err) => write!(f, "{err}"),
+        }
+    }
+}
+
+impl Error for ExpressionError {}
+
+impl From<RunnerError> for ExpressionError {
+    fn from(err: RunnerError) -> Self {
+        ExpressionError::Runner(err)
+    }
+}
+
+/// Trivial operations declared as assignments to the fields of the state, such as
+/// "volume = volume * 1.03; age = age + 5". Numeric, boolean and string fields of the state are
+/// visible by name, as are the operation parameters unless shadowed by a state field.
+pub struct ExpressionOperations {
+    expressions: HashMap<String, Rc<Node>>,
+}
+
+fn parameter_value(parameter: &str) -> Value {
+    if let Ok(int) = parameter.parse::<i64>() {
+        Value::Int(int)
+    } else if let Ok(float) = parameter.parse::<f64>() {
+        Value::Float(float)
+    } else if let Ok(boolean) = parameter.parse::<bool>() {
+        Value::Boolean(boolean)
+    } else {
+        Value::String(parameter.to_string())
+    }
+}
+
+fn field_value(field: &serde_json::Value) -> Option<Value> {
+    match field {
+        serde_json::Value::Number(number) => number.as_i64().map(Value::Int).or_else(|| number.as_f64().map(Value::Float)),
+        serde_json::Value::Bool(boolean) => Some(Value::Boolean(*boolean)),
+        serde_json::Value::String(string) => Some(Value::String(string.clone())),
+        _ => None,
+    }
+}
+
+fn json_value(value: &Value) -> Option<serde_json::Value> {
+    match value {
+        Value::Int(int) => Some(serde_json::Value::from(*int)),
+        Value::Float(float) => Number::from_f64(*float).map(serde_json::Value::Number),
+        Value::Boolean(boolean) => Some(serde_json::Value::Bool(*boolean)),
+        Value::String(string) => Some(serde_json::Value::String(string.clone())),
+        _ => None,
+    }
+}
+
+/// Evaluate the expression over the fields of the state, returning the state with its fields
+/// updated from the assigned variables.
+fn evaluate<T: Serialize + DeserializeOwned>(expression: &Node, parameters: &HashMapContext, state: T) -> Result<T, String> {
+    let mut fields: Map<String, serde_json::Value> = match serde_json::to_value(state).map_err(|err| err.to_string())? {
+        serde_json::Value::Object(fields) => fields,
+        _ => return Err("state is not a struct of named fields".to_string()),
+    };
+    let mut context = parameters.clone();
+    for (name, field) in fields.iter() {
+        if let Some(value) = field_value(field) {
+            context.set_value(name.clone(), value).map_err(|err| err.to_string())?;
+        }
+    }
+    expression.eval_with_context_mut(&mut context).map_err(|err| err.to_string())?;
+    for (name, field) in fields.iter_mut() {
+        if let Some(value) = context.get_value(name).filter(|_| field_value(field).is_some()) {
+            *field = json_value(value).ok_or_else(|| format!("field '{name}' assigned {value}"))?;
+        }
+    }
+    serde_json::from_value(serde_json::Value::Object(fields)).map_err(|err| err.to_string())
+}
+
+impl ExpressionOperations {
+    /// Parse the expressions given by operation name.
+    pub fn new<S: AsRef<str>>(expressions: &HashMap<&str, S>) -> Result<ExpressionOperations, ExpressionError> {
+        let expressions = expressions.iter()
+            .map(|(name, expression)| {
+                let node = build_operator_tree(expression.as_ref())
+                    .map_err(|error| ExpressionError::Parse { operation: name.to_string(), error })?;
+                Ok((name.to_string(), Rc::new(node)))
+            })
+            .collect::<Result<HashMap<String, Rc<Node>>, ExpressionError>>()?;
+        Ok(ExpressionOperations { expressions })
+    }
+
+    /// Names of the parsed operations.
+    pub fn names(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = self.expressions.keys().map(String::as_str).collect();
+        names.sort_unstable();
+        names
+    }
+
+    /// Bind the parameters into the named operation. Evaluation errors fail the event chain.
+    pub fn operation<T>(&self, name: &'static str, parameters: ParameterMap) -> Result<BoxedOperation<T>, ExpressionError>
+    where T: Serialize + DeserializeOwned + 'static {
+        let expression = Rc::clone(self.expressions.get(name).ok_or(RunnerError::UnknownOperation(name))?);
+        let mut context = HashMapContext::new();
+        for (key, parameter) in parameters {
+            context.set_value(key.to_string(), parameter_value(parameter)).expect("parameters are set once");
+        }
+        Ok(Box::new(move |state: T| {
+            evaluate(&expression, &context, state).unwrap_or_else(|err| resume_unwind(Box::new(format!("operation '{name}': {err}"))))
+        }))
+    }
+
+    /// Compile the declaration over the parsed operations into an event graph.
+    pub fn compile<T>(&self, declaration: &SimulationDeclaration, parameters: &OperationParameters) -> Result<EventNode<T>, ExpressionError>
+    where T: Clone + Serialize + DeserializeOwned + 'static {
+        let generators = generator_map::<T>();
+        let root: EventNode<T> = EventDAG::new_node(Box::new(|state| state));
+        let mut nodes = vec![root.clone()];
+        for (generator_name, operation_names) in declaration {
+            let generator_fn = generators.get(generator_name).ok_or(RunnerError::UnknownGenerator(generator_name))?;
+            let chain = operation_names.iter()
+                .map(|name| self.operation(name, parameters.get(name).cloned().unwrap_or_default()))
+                .collect::<Result<OperationChain<T>, ExpressionError>>()?;
+            nodes = generator_fn(nodes, chain).map_err(RunnerError::from)?;
+        }
+        Ok(root)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::Deserialize;
+    use super::*;
+
+    #[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+    struct Stand {
+        age: u32,
+        volume: f64,
+        thinned: bool,
+    }
+
+    fn operations() -> ExpressionOperations {
+        ExpressionOperations::new(&HashMap::from([
+            ("grow", "volume = volume * 1.5; age = age + years"),
+            ("thin", "volume = volume - removal; thinned = true"),
+        ])).unwrap()
+    }
+
+    #[test]
+    fn expression_operations_are_compiled() {
+        let operations = operations();
+        assert_eq!(vec!["grow", "thin"], operations.names());
+        let declaration = vec![("sequence", vec!["grow"]), ("alternatives", vec!["grow", "thin"])];
+        let parameters = OperationParameters::from([
+            ("grow", ParameterMap::from([("years", "5")])),
+            ("thin", ParameterMap::from([("removal", "10.0")])),
+        ]);
+        let root = operations.compile::<Stand>(&declaration, &parameters).unwrap();
+        let results = EventDAG::try_evaluate_chains(&root, Stand { age: 0, volume: 20.0, thinned: false }).unwrap();
+        assert_eq!(vec![
+            Stand { age: 10, volume: 45.0, thinned: false },
+            Stand { age: 5, volume: 20.0, thinned: true },
+        ], results);
+    }
+
+    #[test]
+    fn expression_errors_are_reported() {
+        let parsed = ExpressionOperations::new(&HashMap::from([("grow", "volume = (volume")]));
+        assert!(matches!(parsed, Err(ExpressionError::Parse { operation, .. }) if operation == "grow"));
+        let compiled = operations().compile::<Stand>(&vec![("sequence", vec!["clearcut"])], &OperationParameters::new());
+        assert!(matches!(compiled, Err(ExpressionError::Runner(RunnerError::UnknownOperation("clearcut")))));
+
+        let root = operations().compile::<Stand>(&vec![("sequence", vec!["thin"])], &OperationParameters::new()).unwrap();
+        let failure = EventDAG::try_evaluate_chains(&root, Stand { age: 0, volume: 20.0, thinned: false }).unwrap_err();
+        assert!(failure.error.starts_with("operation 'thin'"));
+    }
+}
@@ -0,0 +1,236 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::panic::resume_unwind;
+use std::rc::Rc;
+use crate::configuration_utils::ParameterMap;
+use crate::event_graph::BoxedOperation;
+
+/// Failure reported by an external model.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ModelError(pub String);
+
+impl fmt::Display for ModelError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl Error for ModelError {}
+
+/// Growth or yield model implemented outside of the engine, typically behind a foreign
+/// function interface. Predictions are requested for batches of states.
+pub trait ExternalModel<T> {
+    /// Prepare the model for predictions, such as initializing a foreign library.
+    fn prepare(&mut self) -> Result<(), ModelError> {
+        Ok(())
+    }
+
+    /// Predict the resulting state of each of the states, in the same order.
+    fn predict(&mut self, states: &[T], parameters: &ParameterMap) -> Result<Vec<T>, ModelError>;
+
+    /// Release the resources of the model after the last prediction.
+    fn teardown(&mut self) {}
+}
+
+/// Fingerprint of a state, equal for states the model predicts equally.
+pub type Fingerprint<T> = fn(&T) -> u64;
+
+/// Hits and misses of the prediction cache of a ModelAdapter.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CacheStatistics {
+    pub hits: u64,
+    pub misses: u64,
+}
+
+/// State fingerprint and sorted parameters of a cached prediction.
+type PredictionKey = (u64, Vec<(&'static str, &'static str)>);
+
+/// Predictions by state fingerprint and sorted parameters.
+struct PredictionCache<T> {
+    fingerprint: Fingerprint<T>,
+    predictions: HashMap<PredictionKey, T>,
+    statistics: CacheStatistics,
+}
+
+/// Adapter wrapping an ExternalModel into operations of the event graph. The model is prepared
+/// on construction and torn down when the adapter and all operations created from it are dropped.
+pub struct ModelAdapter<T> {
+    model: Rc<RefCell<Box<dyn ExternalModel<T>>>>,
+    batch_size: usize,
+    cache: Rc<RefCell<Option<PredictionCache<T>>>>,
+}
+
+impl<T: Clone + 'static> ModelAdapter<T> {
+    /// Prepare the model, calling it with at most batch_size states at a time.
+    pub fn new<M: ExternalModel<T> + 'static>(mut model: M, batch_size: usize) -> Result<ModelAdapter<T>, ModelError> {
+        model.prepare()?;
+        Ok(ModelAdapter {
+            model: Rc::new(RefCell::new(Box::new(model))),
+            batch_size: batch_size.max(1),
+            cache: Rc::new(RefCell::new(None)),
+        })
+    }
+
+    /// Cache predictions by the fingerprint of the predicted state and the parameters, so that
+    /// equal states reached on different branches are predicted once. Replaces an earlier cache,
+    /// also for the operations already created from the adapter.
+    pub fn set_cache(&self, fingerprint: Fingerprint<T>) {
+        *self.cache.borrow_mut() = Some(PredictionCache { fingerprint, predictions: HashMap::new(), statistics: CacheStatistics::default() });
+    }
+
+    /// Statistics of the prediction cache, if set.
+    pub fn cache_statistics(&self) -> Option<CacheStatistics> {
+        self.cache.borrow().as_ref().map(|cache| cache.statistics)
+    }
+
+    pub fn batch_size(&self) -> usize {
+        self.batch_size
+    }
+
+    /// Predict the resulting states of the states in batches of at most batch_size states, only
+    /// calling the model for the states missing from the cache if set.
+    pub fn predict(&self, states: &[T], parameters: &ParameterMap<'static>) -> Result<Vec<T>, ModelError> {
+        let mut cache = self.cache.borrow_mut();
+        let Some(cache) = cache.as_mut() else {
+            return self.predict_uncached(states, parameters);
+        };
+        let mut sorted: Vec<(&'static str, &'static str)> = parameters.iter().map(|(key, value)| (*key, *value)).collect();
+        sorted.sort_unstable();
+        let keys: Vec<PredictionKey> = states.iter()
+            .map(|state| ((cache.fingerprint)(state), sorted.clone()))
+            .collect();
+        let mut missing: Vec<T> = Vec::new();
+        let mut missing_keys = Vec::new();
+        for (state, key) in states.iter().zip(keys.iter()) {
+            if !cache.predictions.contains_key(key) && !missing_keys.contains(&key) {
+                missing.push(state.clone());
+                missing_keys.push(key);
+            }
+        }
+        let predicted = self.predict_uncached(&missing, parameters)?;
+        cache.statistics.misses += missing.len() as u64;
+        cache.statistics.hits += (states.len() - missing.len()) as u64;
+        for (key, state) in missing_keys.into_iter().zip(predicted) {
+            cache.predictions.insert(key.clone(), state);
+        }
+        Ok(keys.iter().map(|key| cache.predictions[key].clone()).collect())
+    }
+
+    fn predict_uncached(&self, states: &[T], parameters: &ParameterMap) -> Result<Vec<T>, ModelError> {
+        let mut model = self.model.borrow_mut();
+        let mut predicted = Vec::with_capacity(states.len());
+        for batch in states.chunks(self.batch_size) {
+            let results = model.predict(batch, parameters)?;
+            if results.len() != batch.len() {
+                return Err(ModelError(format!("model predicted {} states for {} states", results.len(), batch.len())));
+            }
+            predicted.extend(results);
+        }
+        Ok(predicted)
+    }
+
+    /// Operation predicting the resulting state with the given parameters. A model error fails
+    /// the event chain.
+    pub fn operation(&self, parameters: ParameterMap<'static>) -> BoxedOperation<T> {
+        let adapter = ModelAdapter { model: Rc::clone(&self.model), batch_size: self.batch_size, cache: Rc::clone(&self.cache) };
+        Box::new(move |state| match adapter.predict(&[state], &parameters) {
+            Ok(mut predicted) => predicted.swap_remove(0),
+            Err(err) => resume_unwind(Box::new(err.to_string())),
+        })
+    }
+}
+
+impl<T> Drop for ModelAdapter<T> {
+    fn drop(&mut self) {
+        if Rc::strong_count(&self.model) == 1 {
+            self.model.borrow_mut().teardown();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::branching_generators::alternatives;
+    use crate::event_graph::EventDAG;
+    use super::*;
+
+    /// Model multiplying volumes by a growth factor, recording its lifecycle and batch sizes.
+    struct Growth {
+        log: Rc<RefCell<Vec<String>>>,
+        prepared: bool,
+    }
+
+    impl ExternalModel<f64> for Growth {
+        fn prepare(&mut self) -> Result<(), ModelError> {
+            self.prepared = true;
+            self.log.borrow_mut().push("prepare".to_string());
+            Ok(())
+        }
+
+        fn predict(&mut self, states: &[f64], parameters: &ParameterMap) -> Result<Vec<f64>, ModelError> {
+            assert!(self.prepared);
+            let factor: f64 = parameters["factor"].parse().map_err(|_| ModelError("invalid factor".to_string()))?;
+            self.log.borrow_mut().push(format!("predict {}", states.len()));
+            match states.iter().any(|volume| *volume < 0.0) {
+                true => Err(ModelError("negative volume".to_string())),
+                false => Ok(states.iter().map(|volume| volume * factor).collect()),
+            }
+        }
+
+        fn teardown(&mut self) {
+            self.log.borrow_mut().push("teardown".to_string());
+        }
+    }
+
+    fn adapter(log: &Rc<RefCell<Vec<String>>>) -> ModelAdapter<f64> {
+        ModelAdapter::new(Growth { log: Rc::clone(log), prepared: false }, 2).unwrap()
+    }
+
+    #[test]
+    fn predictions_are_batched() {
+        let log = Rc::new(RefCell::new(Vec::new()));
+        let adapter = adapter(&log);
+        let predicted = adapter.predict(&[1.0, 2.0, 3.0, 4.0, 5.0], &ParameterMap::from([("factor", "2")])).unwrap();
+        assert_eq!(vec![2.0, 4.0, 6.0, 8.0, 10.0], predicted);
+        drop(adapter);
+        assert_eq!(vec!["prepare", "predict 2", "predict 2", "predict 1", "teardown"], *log.borrow());
+    }
+
+    #[test]
+    fn models_are_wrapped_as_operations() {
+        let log = Rc::new(RefCell::new(Vec::new()));
+        let adapter = adapter(&log);
+        let root = EventDAG::new_node(Box::new(|volume| volume));
+        alternatives(vec![Rc::clone(&root)], [
+            adapter.operation(ParameterMap::from([("factor", "2")])),
+            adapter.operation(ParameterMap::from([("factor", "3")])),
+        ]).unwrap();
+        drop(adapter);
+        assert_eq!(vec![20.0, 30.0], EventDAG::try_evaluate_chains(&root, 10.0).unwrap());
+        let failure = EventDAG::try_evaluate_chains(&root, -1.0).unwrap_err();
+        assert_eq!((0, 1, "negative volume".to_string()), (failure.chain, failure.operation, failure.error));
+        assert!(!log.borrow().contains(&"teardown".to_string()));
+        drop(root);
+        assert_eq!(Some(&"teardown".to_string()), log.borrow().last());
+    }
+
+    #[test]
+    fn cached_predictions_are_reused() {
+        let log = Rc::new(RefCell::new(Vec::new()));
+        let adapter = adapter(&log);
+        adapter.set_cache(|volume: &f64| volume.round() as u64);
+        let grow = ParameterMap::from([("factor", "2")]);
+        assert_eq!(vec![2.0, 4.0, 2.0], adapter.predict(&[1.0, 2.0, 1.0], &grow).unwrap());
+        assert_eq!(vec![4.0, 6.0], adapter.predict(&[2.0, 3.0], &grow).unwrap());
+        assert_eq!(vec![3.0], adapter.predict(&[1.0], &ParameterMap::from([("factor", "3")])).unwrap());
+        assert_eq!(Some(CacheStatistics { hits: 2, misses: 4 }), adapter.cache_statistics());
+        assert_eq!(vec!["prepare", "predict 2", "predict 1", "predict 1"], *log.borrow());
+
+        let root = EventDAG::new_node(Box::new(|volume| volume));
+        alternatives(vec![Rc::clone(&root)], [adapter.operation(grow.clone()), adapter.operation(grow)]).unwrap();
+        assert_eq!(vec![20.0, 20.0], EventDAG::try_evaluate_chains(&root, 10.0).unwrap());
+        assert_eq!(Some(CacheStatistics { hits: 3, misses: 5 }), adapter.cache_statistics());
+    }
+}
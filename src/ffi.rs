@@ -0,0 +1,314 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::ffi::{c_char, c_void, CStr, CString};
+use std::panic::{catch_unwind, resume_unwind, AssertUnwindSafe};
+use std::ptr;
+use serde::Deserialize;
+use crate::branching_generators::generator_map;
+use crate::event_graph::{BoxedOperation, EventDAG, EventNode, OperationChain};
+
+/// Operation implemented in C. Reads the state of state_size bytes and writes the resulting state
+/// of state_size bytes, given the parameters of the operation as a JSON object of strings and the
+/// user data given on registration. Returns 0 on success, other values failing the event chain.
+pub type MetsiOperation = unsafe extern "C" fn(
+    state: *const u8,
+    result: *mut u8,
+    state_size: usize,
+    parameters: *const c_char,
+    user_data: *mut c_void
+) -> i32;
+
+thread_local! {
+    /// Message of the latest failed call on the thread.
+    static LAST_ERROR: RefCell<CString> = RefCell::new(CString::default());
+    /// State buffers referred to by the BufferState handles of the thread.
+    static BUFFERS: RefCell<Vec<Box<[u8]>>> = const { RefCell::new(Vec::new()) };
+}
+
+fn set_error(message: String) {
+    let message = CString::new(message.replace('\0', " ")).expect("nul bytes replaced");
+    LAST_ERROR.with_borrow_mut(|error| *error = message);
+}
+
+/// Handle of a state buffer held by the evaluating thread until the evaluation completes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct BufferState(usize);
+
+impl BufferState {
+    fn store(buffer: Box<[u8]>) -> BufferState {
+        BUFFERS.with_borrow_mut(|buffers| {
+            buffers.push(buffer);
+            BufferState(buffers.len() - 1)
+        })
+    }
+
+    fn mark() -> usize {
+        BUFFERS.with_borrow(Vec::len)
+    }
+
+    fn release_from(mark: usize) {
+        BUFFERS.with_borrow_mut(|buffers| buffers.truncate(mark))
+    }
+}
+
+/// Operations registered for simulations of states of a fixed size in bytes.
+pub struct MetsiRegistry {
+    state_size: usize,
+    operations: HashMap<String, (MetsiOperation, *mut c_void)>,
+}
+
+/// Simulation compiled from a JSON configuration over the operations of a registry.
+pub struct MetsiSimulation {
+    state_size: usize,
+    root: EventNode<BufferState>,
+}
+
+/// Chain results of evaluating a simulation for a state.
+pub struct MetsiResults {
+    results: Vec<Box<[u8]>>,
+}
+
+/// JSON configuration of a simulation, such as
+/// {"declaration": [["sequence", ["grow"]], ["alternatives", ["grow", "thin"]]],
+///  "parameters": {"grow": {"increase": "5"}}}
+#[derive(Deserialize)]
+struct Configuration {
+    declaration: Vec<(String, Vec<String>)>,
+    #[serde(default)]
+    parameters: HashMap<String, HashMap<String, String>>,
+}
+
+fn c_operation(name: String, operation: MetsiOperation, user_data: *mut c_void, parameters: CString, state_size: usize) -> BoxedOperation<BufferState> {
+    Box::new(move |state: BufferState| {
+        let mut result = vec![0u8; state_size].into_boxed_slice();
+        // SAFETY: the buffers are of state_size bytes and the caller of metsi_registry_add
+        // guarantees the operation and user data to be valid for the operation.
+        let code = BUFFERS.with_borrow(|buffers| unsafe {
+            operation(buffers[state.0].as_ptr(), result.as_mut_ptr(), state_size, parameters.as_ptr(), user_data)
+        });
+        match code {
+            0 => BufferState::store(result),
+            code => resume_unwind(Box::new(format!("operation '{name}' failed with code {code}"))),
+        }
+    })
+}
+
+fn compile(registry: &MetsiRegistry, configuration: &str) -> Result<EventNode<BufferState>, String> {
+    let configuration: Configuration = serde_json::from_str(configuration).map_err(|err| err.to_string())?;
+    let generators = generator_map::<BufferState>();
+    let root: EventNode<BufferState> = EventDAG::new_node(Box::new(|state| state));
+    let mut nodes = vec![root.clone()];
+    for (generator_name, operation_names) in configuration.declaration {
+        let generator_fn = generators.get(generator_name.as_str())
+            .ok_or_else(|| format!("unknown generator '{generator_name}'"))?;
+        let chain = operation_names.into_iter().map(|name| {
+            let (operation, user_data) = *registry.operations.get(&name).ok_or_else(|| format!("unknown operation '{name}'"))?;
+            let parameters = configuration.parameters.get(&name).cloned().unwrap_or_default();
+            let parameters = CString::new(serde_json::to_string(&parameters).map_err(|err| err.to_string())?)
+                .map_err(|err| err.to_string())?;
+            Ok(c_operation(name, operation, user_data, parameters, registry.state_size))
+        }).collect::<Result<OperationChain<BufferState>, String>>()?;
+        nodes = generator_fn(nodes, chain).map_err(|err| err.to_string())?;
+    }
+    Ok(root)
+}
+
+/// Message of the latest failed call on the calling thread, valid until the next failing call.
+#[no_mangle]
+pub extern "C" fn metsi_last_error() -> *const c_char {
+    LAST_ERROR.with_borrow(|error| error.as_ptr())
+}
+
+/// Create a registry of operations for states of state_size bytes.
+#[no_mangle]
+pub extern "C" fn metsi_registry_new(state_size: usize) -> *mut MetsiRegistry {
+    Box::into_raw(Box::new(MetsiRegistry { state_size, operations: HashMap::new() }))
+}
+
+/// Register the operation under the given name, replacing an operation of the same name.
+/// Returns 0 on success.
+///
+/// # Safety
+/// registry must come from metsi_registry_new and name must be a nul-terminated string. The
+/// operation must be callable with the user data for as long as simulations compiled from the
+/// registry exist.
+#[no_mangle]
+pub unsafe extern "C" fn metsi_registry_add(registry: *mut MetsiRegistry, name: *const c_char, operation: MetsiOperation, user_data: *mut c_void) -> i32 {
+    if registry.is_null() || name.is_null() {
+        set_error("null registry or name".to_string());
+        return -1;
+    }
+    let name = match CStr::from_ptr(name).to_str() {
+        Ok(name) => name.to_string(),
+        Err(err) => {
+            set_error(err.to_string());
+            return -1;
+        }
+    };
+    (*registry).operations.insert(name, (operation, user_data));
+    0
+}
+
+/// # Safety
+/// registry must come from metsi_registry_new or be null.
+#[no_mangle]
+pub unsafe extern "C" fn metsi_registry_free(registry: *mut MetsiRegistry) {
+    if !registry.is_null() {
+        drop(Box::from_raw(registry));
+    }
+}
+
+/// Compile a simulation from the JSON configuration over the operations of the registry.
+/// Returns null on failure, described by metsi_last_error.
+///
+/// # Safety
+/// registry must come from metsi_registry_new and configuration must be a nul-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn metsi_simulation_new(registry: *const MetsiRegistry, configuration: *const c_char) -> *mut MetsiSimulation {
+    if registry.is_null() || configuration.is_null() {
+        set_error("null registry or configuration".to_string());
+        return ptr::null_mut();
+    }
+    let registry = &*registry;
+    let compiled = CStr::from_ptr(configuration).to_str()
+        .map_err(|err| err.to_string())
+        .and_then(|configuration| compile(registry, configuration));
+    match compiled {
+        Ok(root) => Box::into_raw(Box::new(MetsiSimulation { state_size: registry.state_size, root })),
+        Err(message) => {
+            set_error(message);
+            ptr::null_mut()
+        }
+    }
+}
+
+/// # Safety
+/// simulation must come from metsi_simulation_new or be null.
+#[no_mangle]
+pub unsafe extern "C" fn metsi_simulation_free(simulation: *mut MetsiSimulation) {
+    if !simulation.is_null() {
+        drop(Box::from_raw(simulation));
+    }
+}
+
+/// Evaluate the event chains of the simulation for the state. Returns the chain results, or null
+/// on failure described by metsi_last_error.
+///
+/// # Safety
+/// simulation must come from metsi_simulation_new and state must point to a state of the size of
+/// the registry the simulation was compiled from. A simulation must not be evaluated from
+/// several threads at once.
+#[no_mangle]
+pub unsafe extern "C" fn metsi_simulation_run(simulation: *const MetsiSimulation, state: *const u8) -> *mut MetsiResults {
+    if simulation.is_null() || state.is_null() {
+        set_error("null simulation or state".to_string());
+        return ptr::null_mut();
+    }
+    let simulation = &*simulation;
+    let initial: Box<[u8]> = std::slice::from_raw_parts(state, simulation.state_size).into();
+    let mark = BufferState::mark();
+    let evaluated = catch_unwind(AssertUnwindSafe(|| EventDAG::try_evaluate_chains(&simulation.root, BufferState::store(initial))));
+    let results = match evaluated {
+        Ok(Ok(results)) => Ok(BUFFERS.with_borrow(|buffers| results.iter().map(|result| buffers[result.0].clone()).collect())),
+        Ok(Err(failure)) => Err(format!("chain {}, {}: {}", failure.chain, failure.location(), failure.error)),
+        Err(_) => Err("evaluation panicked".to_string()),
+    };
+    BufferState::release_from(mark);
+    match results {
+        Ok(results) => Box::into_raw(Box::new(MetsiResults { results })),
+        Err(message) => {
+            set_error(message);
+            ptr::null_mut()
+        }
+    }
+}
+
+/// Number of chain results.
+///
+/// # Safety
+/// results must come from metsi_simulation_run.
+#[no_mangle]
+pub unsafe extern "C" fn metsi_results_count(results: *const MetsiResults) -> usize {
+    results.as_ref().map_or(0, |results| results.results.len())
+}
+
+/// Result state of the chain with the given index, null if out of range. The state is valid
+/// until the results are freed.
+///
+/// # Safety
+/// results must come from metsi_simulation_run.
+#[no_mangle]
+pub unsafe extern "C" fn metsi_results_get(results: *const MetsiResults, index: usize) -> *const u8 {
+    results.as_ref()
+        .and_then(|results| results.results.get(index))
+        .map_or(ptr::null(), |result| result.as_ptr())
+}
+
+/// # Safety
+/// results must come from metsi_simulation_run or be null.
+#[no_mangle]
+pub unsafe extern "C" fn metsi_results_free(results: *mut MetsiResults) {
+    if !results.is_null() {
+        drop(Box::from_raw(results));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    unsafe extern "C" fn grow(state: *const u8, result: *mut u8, state_size: usize, parameters: *const c_char, user_data: *mut c_void) -> i32 {
+        let parameters: HashMap<String, String> = serde_json::from_str(CStr::from_ptr(parameters).to_str().unwrap()).unwrap();
+        let increase: f64 = parameters.get("increase").map_or(1.0, |increase| increase.parse().unwrap());
+        *(user_data as *mut usize) += 1;
+        let volume = f64::from_ne_bytes(std::slice::from_raw_parts(state, state_size).try_into().unwrap());
+        ptr::copy_nonoverlapping((volume + increase).to_ne_bytes().as_ptr(), result, state_size);
+        0
+    }
+
+    unsafe extern "C" fn thin(state: *const u8, result: *mut u8, state_size: usize, _parameters: *const c_char, _user_data: *mut c_void) -> i32 {
+        let volume = f64::from_ne_bytes(std::slice::from_raw_parts(state, state_size).try_into().unwrap());
+        if volume < 10.0 {
+            return 3;
+        }
+        ptr::copy_nonoverlapping((volume / 2.0).to_ne_bytes().as_ptr(), result, state_size);
+        0
+    }
+
+    fn last_error() -> String {
+        unsafe { CStr::from_ptr(metsi_last_error()) }.to_str().unwrap().to_string()
+    }
+
+    #[test]
+    fn simulations_are_run_through_c_api() {
+        let mut calls = 0usize;
+        unsafe {
+            let registry = metsi_registry_new(8);
+            assert_eq!(0, metsi_registry_add(registry, c"grow".as_ptr(), grow, &mut calls as *mut usize as *mut c_void));
+            assert_eq!(0, metsi_registry_add(registry, c"thin".as_ptr(), thin, ptr::null_mut()));
+            let configuration = cr#"{"declaration": [["sequence", ["grow"]], ["alternatives", ["grow", "thin"]]], "parameters": {"grow": {"increase": "5"}}}"#;
+            let simulation = metsi_simulation_new(registry, configuration.as_ptr());
+            assert!(!simulation.is_null());
+
+            let results = metsi_simulation_run(simulation, 10.0f64.to_ne_bytes().as_ptr());
+            assert_eq!(2, metsi_results_count(results));
+            let volumes: Vec<f64> = (0..2)
+                .map(|index| f64::from_ne_bytes(std::slice::from_raw_parts(metsi_results_get(results, index), 8).try_into().unwrap()))
+                .collect();
+            assert_eq!(vec![20.0, 7.5], volumes);
+            assert!(metsi_results_get(results, 2).is_null());
+            assert_eq!(3, calls);
+            metsi_results_free(results);
+
+            assert!(metsi_simulation_run(simulation, 1.0f64.to_ne_bytes().as_ptr()).is_null());
+            assert_eq!("chain 1, operation 2: operation 'thin' failed with code 3", last_error());
+            assert_eq!(0, BufferState::mark());
+
+            assert!(metsi_simulation_new(registry, cr#"{"declaration": [["sequence", ["clearcut"]]]}"#.as_ptr()).is_null());
+            assert_eq!("unknown operation 'clearcut'", last_error());
+            assert!(metsi_simulation_new(registry, c"{".as_ptr()).is_null());
+            metsi_simulation_free(simulation);
+            metsi_registry_free(registry);
+        }
+    }
+}
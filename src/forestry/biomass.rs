@@ -0,0 +1,61 @@
+use super::{ForestStand, ReferenceTree, Species};
+
+/// Biomass equations of trees, used by the collectors to report the biomass of stands.
+/// Implement to use institution-specific equations in place of SimpleBiomass.
+pub trait BiomassModel {
+    /// Total dry biomass of a single tree in kilograms.
+    fn tree_biomass(&self, tree: &ReferenceTree) -> f64;
+
+    /// Total dry biomass of the reference trees of the stand in tonnes per hectare.
+    fn stand_biomass(&self, stand: &ForestStand) -> f64 {
+        stand.reference_trees.iter().map(|tree| self.tree_biomass(tree) * tree.stems_per_ha).sum::<f64>() / 1000.0
+    }
+}
+
+/// Biomass model converting stem volumes into total biomass with species group specific basic
+/// densities and a common expansion factor accounting for branches, foliage and roots.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SimpleBiomass {
+    /// Ratio of the total biomass to the stem biomass.
+    pub expansion_factor: f64,
+}
+
+impl SimpleBiomass {
+    /// Basic density of stem wood in kilograms per cubic metre.
+    pub fn basic_density(species: Species) -> f64 {
+        match species {
+            Species::Pine | Species::OtherConiferous => 400.0,
+            Species::Spruce => 385.0,
+            Species::Aspen | Species::GreyAlder | Species::BlackAlder => 360.0,
+            Species::SilverBirch | Species::DownyBirch | Species::OtherDeciduous => 490.0,
+        }
+    }
+}
+
+impl Default for SimpleBiomass {
+    fn default() -> SimpleBiomass {
+        SimpleBiomass { expansion_factor: 1.6 }
+    }
+}
+
+impl BiomassModel for SimpleBiomass {
+    fn tree_biomass(&self, tree: &ReferenceTree) -> f64 {
+        tree.tree_volume() * SimpleBiomass::basic_density(tree.species) * self.expansion_factor
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::forestry::tests::stand;
+    use super::*;
+
+    #[test]
+    fn biomass_is_computed_from_volume() {
+        let stand = stand();
+        let model = SimpleBiomass::default();
+        let pine = &stand.reference_trees[0];
+        assert!((model.tree_biomass(pine) - pine.tree_volume() * 400.0 * 1.6).abs() < 1e-9);
+        let expected = (pine.volume() * 400.0 + stand.reference_trees[1].volume() * 385.0) * 1.6 / 1000.0;
+        assert!((model.stand_biomass(&stand) - expected).abs() < 1e-9);
+    }
+}
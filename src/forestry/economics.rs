@@ -0,0 +1,123 @@
+use std::collections::BTreeMap;
+use serde::{Deserialize, Serialize};
+use crate::collectors::{CollectionPoint, Collector};
+use crate::post_processing::discounted;
+use super::{ForestStand, Removal};
+
+/// Income and cost of a harvesting operation.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct HarvestPrice {
+    /// Income per removed cubic metre.
+    #[serde(default)]
+    pub income: f64,
+    /// Cost per hectare of each harvest.
+    #[serde(default)]
+    pub cost: f64,
+}
+
+/// Economic parameters of the net present value calculation, typically read from the
+/// configuration.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Economics {
+    /// Yearly interest rate of discounting, such as 0.03.
+    pub discount_rate: f64,
+    /// Year to discount to, the year of the initial stand if not given.
+    #[serde(default)]
+    pub base_year: Option<i32>,
+    /// Prices by the names of the harvesting operations recorded in the removals. Removals of
+    /// other operations yield neither incomes nor costs.
+    pub prices: BTreeMap<String, HarvestPrice>,
+}
+
+impl Economics {
+    /// Net income per hectare of the removal, not discounted.
+    pub fn net_income(&self, removal: &Removal) -> f64 {
+        match self.prices.get(&removal.operation) {
+            Some(price) => removal.volume * price.income - price.cost,
+            None => 0.0,
+        }
+    }
+}
+
+/// Collect the net present value per hectare of each event chain, the incomes and costs of the
+/// removals made along the chain discounted to the base year.
+pub struct NpvCollector {
+    economics: Economics,
+    base_year: i32,
+    initial_removals: usize,
+    pub values: BTreeMap<usize, f64>,
+}
+
+impl NpvCollector {
+    /// Collector for event chains starting from the given stand. Removals of the initial stand
+    /// are not included.
+    pub fn new(economics: Economics, initial: &ForestStand) -> NpvCollector {
+        let base_year = economics.base_year.unwrap_or(initial.year);
+        NpvCollector { economics, base_year, initial_removals: initial.removals.len(), values: BTreeMap::new() }
+    }
+}
+
+impl<T: AsRef<ForestStand>> Collector<T> for NpvCollector {
+    fn point(&self) -> CollectionPoint {
+        CollectionPoint::ChainEnd
+    }
+
+    fn observe(&mut self, chain: usize, state: &T) {
+        let removals = &state.as_ref().removals[self.initial_removals..];
+        let value = removals.iter()
+            .map(|removal| {
+                let years = f64::from(removal.year - self.base_year);
+                discounted(self.economics.net_income(removal), self.economics.discount_rate, years)
+            })
+            .sum();
+        self.values.insert(chain, value);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::configuration_utils::ParameterMap;
+    use crate::forestry::operations;
+    use crate::forestry::tests::stand;
+    use crate::simulation_runner::{OperationParameters, SimulationRunner};
+    use super::*;
+
+    fn economics() -> Economics {
+        serde_json::from_str(r#"{
+            "discount_rate": 0.03,
+            "prices": {"thinning_from_below": {"income": 30, "cost": 200}, "clearcut": {"income": 50}}
+        }"#).unwrap()
+    }
+
+    #[test]
+    fn incomes_are_discounted() {
+        let economics = economics();
+        assert_eq!((None, 0.0), (economics.base_year, economics.prices["clearcut"].cost));
+        let removal = Removal { year: 2035, operation: "clearcut".to_string(), stems_per_ha: 500.0, basal_area: 30.0, volume: 300.0 };
+        assert_eq!(15000.0, economics.net_income(&removal));
+        assert_eq!(0.0, economics.net_income(&Removal { operation: "fertilization".to_string(), ..removal }));
+    }
+
+    #[test]
+    fn npv_is_collected_per_chain() {
+        let parameters = OperationParameters::from([
+            ("grow", ParameterMap::from([("years", "10")])),
+            ("thinning_from_below", ParameterMap::from([("removal_fraction", "0.3")])),
+        ]);
+        let declaration = vec![
+            ("alternatives", vec!["thinning_from_below", "grow"]),
+            ("sequence", vec!["grow", "clearcut"]),
+        ];
+        let runner = SimulationRunner::new(operations(), parameters, declaration).unwrap();
+        let initial = stand();
+        let mut collector = NpvCollector::new(economics(), &initial);
+        let results = runner.run_collecting(initial, &mut [&mut collector]).results;
+
+        let thinning = &results[0].removals;
+        let expected = thinning[0].volume * 30.0 - 200.0 + discounted(thinning[1].volume * 50.0, 0.03, 10.0);
+        assert!((collector.values[&0] - expected).abs() < 1e-9);
+        let clearcut = &results[1].removals[0];
+        assert_eq!(2045, clearcut.year);
+        assert!((collector.values[&1] - discounted(clearcut.volume * 50.0, 0.03, 20.0)).abs() < 1e-9);
+    }
+}
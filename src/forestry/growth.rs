@@ -0,0 +1,95 @@
+use crate::configuration_utils::ParameterMap;
+use super::{ForestStand, ReferenceTree};
+
+/// Diameter increment in centimetres per year of a tree without competition on an average site.
+const BASE_DIAMETER_INCREMENT: f64 = 0.5;
+/// Height increment in metres per year of a tree without competition on an average site.
+const BASE_HEIGHT_INCREMENT: f64 = 0.4;
+/// Height at which height growth ceases, in metres.
+const MAXIMUM_HEIGHT: f64 = 35.0;
+/// Temperature sum of an average site in degree days.
+const REFERENCE_DEGREE_DAYS: f64 = 1200.0;
+/// Basal area in square metres per hectare halving the increments.
+const HALVING_BASAL_AREA: f64 = 30.0;
+
+/// Value of the numeric parameter, or the default if not given. An unparseable value fails the
+/// event chain.
+pub(crate) fn parameter(params: &ParameterMap, name: &str, default: f64) -> f64 {
+    match params.get(name) {
+        Some(value) => value.parse().unwrap_or_else(|_| panic!("invalid value '{value}' of parameter '{name}'")),
+        None => default,
+    }
+}
+
+/// Relative growth of the site, 1 on an average site with site type 3 and the reference
+/// temperature sum.
+fn site_factor(stand: &ForestStand) -> f64 {
+    let fertility = f64::from(9u8.saturating_sub(stand.site_type)) / 6.0;
+    fertility * (stand.degree_days / REFERENCE_DEGREE_DAYS)
+}
+
+fn grow_tree(tree: &mut ReferenceTree, growth: f64, years: f64) {
+    tree.breast_height_diameter += BASE_DIAMETER_INCREMENT * growth * years;
+    let height_growth = (1.0 - tree.height / MAXIMUM_HEIGHT).max(0.0);
+    tree.height += BASE_HEIGHT_INCREMENT * growth * height_growth * years;
+    tree.biological_age += years;
+}
+
+/// Grow the reference trees of the stand with a simple increment model, scaling the diameter and
+/// height increments by the site fertility, the temperature sum and the competition of the stand
+/// basal area. Parameters:
+/// - years: length of the growth period in years, 5 by default
+/// - factor: multiplier of the increments for calibration, 1 by default
+pub fn grow(mut stand: ForestStand, params: ParameterMap) -> ForestStand {
+    let years = parameter(&params, "years", 5.0);
+    let factor = parameter(&params, "factor", 1.0);
+    let competition = 1.0 / (1.0 + stand.basal_area() / HALVING_BASAL_AREA);
+    let growth = factor * site_factor(&stand) * competition;
+    for tree in stand.reference_trees.iter_mut() {
+        grow_tree(tree, growth, years);
+    }
+    stand.year += years.round() as i32;
+    stand
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use crate::forestry::tests::stand;
+    use crate::forestry::operations;
+    use crate::simulation_runner::{OperationParameters, SimulationRunner};
+    use crate::event_graph::EventDAG;
+    use super::*;
+
+    #[test]
+    fn trees_grow() {
+        let stand = stand();
+        let basal_area = stand.basal_area();
+        let grown = grow(stand.clone(), ParameterMap::from([("years", "10")]));
+        assert_eq!(2035, grown.year);
+        let growth = 1.0 / (1.0 + basal_area / HALVING_BASAL_AREA);
+        let pine = &grown.reference_trees[0];
+        assert!((pine.breast_height_diameter - (20.0 + 5.0 * growth)).abs() < 1e-9);
+        assert!((pine.height - (18.0 + 4.0 * growth * (1.0 - 18.0 / 35.0))).abs() < 1e-9);
+        assert_eq!(50.0, pine.biological_age);
+        assert!(grown.volume() > stand.volume());
+
+        let poor = ForestStand { site_type: 6, ..stand.clone() };
+        assert!(grow(poor, ParameterMap::new()).volume() < grow(stand, ParameterMap::new()).volume());
+    }
+
+    #[test]
+    fn invalid_parameters_fail_the_chain() {
+        let root = EventDAG::new_node(Box::new(|stand| grow(stand, ParameterMap::from([("years", "five")]))));
+        let failure = EventDAG::try_evaluate_chains(&root, stand()).unwrap_err();
+        assert_eq!("invalid value 'five' of parameter 'years'", failure.error);
+    }
+
+    #[test]
+    fn growth_is_registered() {
+        let parameters = OperationParameters::from([("grow", HashMap::from([("years", "5")]))]);
+        let runner = SimulationRunner::new(operations(), parameters, vec![("sequence", vec!["grow", "grow"])]).unwrap();
+        let results = runner.run([stand()]).remove(0).results;
+        assert_eq!(vec![2035], results.iter().map(|stand| stand.year).collect::<Vec<_>>());
+    }
+}
@@ -0,0 +1,347 @@
+pub mod biomass;
+pub mod economics;
+pub mod growth;
+pub mod regeneration;
+pub mod thinning;
+pub mod yields;
+
+use std::collections::HashMap;
+use std::f64::consts::PI;
+use serde::{Deserialize, Serialize};
+use crate::configuration_utils::ParameteredOperation;
+use crate::output::{ColumnType, ColumnValue, RowMapping};
+use crate::simulation_runner::OperationRegistry;
+use crate::time_points::TimedState;
+
+/// Form factor converting the basal area times height of a tree into its stem volume.
+pub const FORM_FACTOR: f64 = 0.5;
+
+/// Tree species of the Finnish national forest inventory.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Species {
+    Pine,
+    Spruce,
+    SilverBirch,
+    DownyBirch,
+    Aspen,
+    GreyAlder,
+    BlackAlder,
+    OtherConiferous,
+    OtherDeciduous,
+}
+
+impl Species {
+    pub fn is_coniferous(self) -> bool {
+        matches!(self, Species::Pine | Species::Spruce | Species::OtherConiferous)
+    }
+}
+
+/// Reference tree representing stems_per_ha trees of equal dimensions per hectare.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ReferenceTree {
+    pub identifier: String,
+    pub species: Species,
+    /// Diameter at breast height in centimetres.
+    pub breast_height_diameter: f64,
+    /// Height in metres.
+    pub height: f64,
+    pub stems_per_ha: f64,
+    /// Biological age in years.
+    pub biological_age: f64,
+}
+
+impl ReferenceTree {
+    /// Basal area of a single tree in square metres.
+    pub fn tree_basal_area(&self) -> f64 {
+        PI * (self.breast_height_diameter / 200.0).powi(2)
+    }
+
+    /// Basal area of the represented trees in square metres per hectare.
+    pub fn basal_area(&self) -> f64 {
+        self.tree_basal_area() * self.stems_per_ha
+    }
+
+    /// Stem volume of a single tree in cubic metres.
+    pub fn tree_volume(&self) -> f64 {
+        FORM_FACTOR * self.tree_basal_area() * self.height
+    }
+
+    /// Stem volume of the represented trees in cubic metres per hectare.
+    pub fn volume(&self) -> f64 {
+        self.tree_volume() * self.stems_per_ha
+    }
+}
+
+/// Inventoried tree stratum described by its mean dimensions, such as from stand-level
+/// inventory data without measured reference trees.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TreeStratum {
+    pub identifier: String,
+    pub species: Species,
+    /// Basal area weighted mean diameter in centimetres.
+    pub mean_diameter: f64,
+    /// Basal area weighted mean height in metres.
+    pub mean_height: f64,
+    /// Basal area in square metres per hectare.
+    pub basal_area: f64,
+    pub stems_per_ha: f64,
+    /// Mean biological age in years.
+    pub mean_age: f64,
+}
+
+impl TreeStratum {
+    /// Stem volume of the stratum in cubic metres per hectare.
+    pub fn volume(&self) -> f64 {
+        FORM_FACTOR * self.basal_area * self.mean_height
+    }
+}
+
+/// Trees removed from a stand by a harvesting operation, per hectare.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Removal {
+    pub year: i32,
+    pub operation: String,
+    pub stems_per_ha: f64,
+    /// Basal area in square metres per hectare.
+    pub basal_area: f64,
+    /// Stem volume in cubic metres per hectare.
+    pub volume: f64,
+}
+
+/// Forest stand with its reference trees and inventoried strata, the standard simulation state
+/// of forestry simulations.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ForestStand {
+    pub identifier: String,
+    /// Calendar year of the state.
+    pub year: i32,
+    /// Area in hectares.
+    pub area: f64,
+    /// Site fertility class from 1 (most fertile) to 8.
+    pub site_type: u8,
+    /// Temperature sum of the location in degree days.
+    pub degree_days: f64,
+    pub reference_trees: Vec<ReferenceTree>,
+    #[serde(default)]
+    pub tree_strata: Vec<TreeStratum>,
+    /// Removals of the simulated harvests in the order of occurrence.
+    #[serde(default)]
+    pub removals: Vec<Removal>,
+}
+
+impl ForestStand {
+    pub fn stems_per_ha(&self) -> f64 {
+        self.reference_trees.iter().map(|tree| tree.stems_per_ha).sum()
+    }
+
+    /// Basal area of the reference trees in square metres per hectare.
+    pub fn basal_area(&self) -> f64 {
+        self.reference_trees.iter().map(ReferenceTree::basal_area).sum()
+    }
+
+    /// Stem volume of the reference trees in cubic metres per hectare.
+    pub fn volume(&self) -> f64 {
+        self.reference_trees.iter().map(ReferenceTree::volume).sum()
+    }
+
+    /// Total stem volume removed by the simulated harvests in cubic metres per hectare.
+    pub fn removed_volume(&self) -> f64 {
+        self.removals.iter().map(|removal| removal.volume).sum()
+    }
+
+    /// Basal area weighted mean diameter of the reference trees in centimetres, 0 without trees.
+    pub fn mean_diameter(&self) -> f64 {
+        let basal_area = self.basal_area();
+        match basal_area > 0.0 {
+            true => self.reference_trees.iter().map(|tree| tree.basal_area() * tree.breast_height_diameter).sum::<f64>() / basal_area,
+            false => 0.0,
+        }
+    }
+
+    /// Basal area weighted mean height of the reference trees in metres, 0 without trees.
+    pub fn mean_height(&self) -> f64 {
+        let basal_area = self.basal_area();
+        match basal_area > 0.0 {
+            true => self.reference_trees.iter().map(|tree| tree.basal_area() * tree.height).sum::<f64>() / basal_area,
+            false => 0.0,
+        }
+    }
+}
+
+impl AsRef<ForestStand> for ForestStand {
+    fn as_ref(&self) -> &ForestStand {
+        self
+    }
+}
+
+impl AsRef<ForestStand> for TimedState<ForestStand> {
+    fn as_ref(&self) -> &ForestStand {
+        &self.state
+    }
+}
+
+/// Registry of the built-in forestry operations by their declaration names.
+pub fn operations() -> OperationRegistry<ForestStand> {
+    HashMap::from([
+        ("grow", growth::grow as ParameteredOperation<ForestStand>),
+        ("thinning_from_below", thinning::thinning_from_below as ParameteredOperation<ForestStand>),
+        ("thinning_from_above", thinning::thinning_from_above as ParameteredOperation<ForestStand>),
+        ("clearcut", regeneration::clearcut as ParameteredOperation<ForestStand>),
+        ("planting", regeneration::planting as ParameteredOperation<ForestStand>),
+        ("natural_regeneration", regeneration::natural_regeneration as ParameteredOperation<ForestStand>),
+    ])
+}
+
+impl RowMapping for ReferenceTree {
+    fn column_names() -> Vec<&'static str> {
+        vec!["identifier", "species", "breast_height_diameter", "height", "stems_per_ha", "biological_age", "basal_area", "volume"]
+    }
+
+    fn column_values(&self) -> Vec<String> {
+        self.typed_values().iter().map(ColumnValue::to_string).collect()
+    }
+
+    fn column_types() -> Vec<ColumnType> {
+        let mut types = vec![ColumnType::Text, ColumnType::Text];
+        types.extend([ColumnType::Float; 6]);
+        types
+    }
+
+    fn typed_values(&self) -> Vec<ColumnValue> {
+        vec![
+            ColumnValue::Text(self.identifier.clone()),
+            ColumnValue::Text(format!("{:?}", self.species)),
+            ColumnValue::Float(self.breast_height_diameter),
+            ColumnValue::Float(self.height),
+            ColumnValue::Float(self.stems_per_ha),
+            ColumnValue::Float(self.biological_age),
+            ColumnValue::Float(self.basal_area()),
+            ColumnValue::Float(self.volume()),
+        ]
+    }
+}
+
+impl RowMapping for ForestStand {
+    fn column_names() -> Vec<&'static str> {
+        vec!["identifier", "year", "area", "site_type", "reference_trees", "stems_per_ha", "basal_area", "volume", "removed_volume"]
+    }
+
+    fn column_values(&self) -> Vec<String> {
+        self.typed_values().iter().map(ColumnValue::to_string).collect()
+    }
+
+    fn column_types() -> Vec<ColumnType> {
+        vec![
+            ColumnType::Text, ColumnType::Integer, ColumnType::Float, ColumnType::Integer,
+            ColumnType::Integer, ColumnType::Float, ColumnType::Float, ColumnType::Float, ColumnType::Float,
+        ]
+    }
+
+    fn typed_values(&self) -> Vec<ColumnValue> {
+        vec![
+            ColumnValue::Text(self.identifier.clone()),
+            ColumnValue::Integer(self.year.into()),
+            ColumnValue::Float(self.area),
+            ColumnValue::Integer(self.site_type.into()),
+            ColumnValue::Integer(self.reference_trees.len() as i64),
+            ColumnValue::Float(self.stems_per_ha()),
+            ColumnValue::Float(self.basal_area()),
+            ColumnValue::Float(self.volume()),
+            ColumnValue::Float(self.removed_volume()),
+        ]
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use crate::configuration_utils::{ParameterMap, ParameteredOperation};
+    use crate::simulation_runner::{OperationParameters, OperationRegistry, SimulationRunner};
+    use super::*;
+
+    pub(crate) fn tree(identifier: &str, species: Species, diameter: f64, height: f64, stems: f64) -> ReferenceTree {
+        ReferenceTree {
+            identifier: identifier.to_string(),
+            species,
+            breast_height_diameter: diameter,
+            height,
+            stems_per_ha: stems,
+            biological_age: 40.0,
+        }
+    }
+
+    pub(crate) fn stand() -> ForestStand {
+        ForestStand {
+            identifier: "1".to_string(),
+            year: 2025,
+            area: 2.0,
+            site_type: 3,
+            degree_days: 1200.0,
+            reference_trees: vec![
+                tree("1-1", Species::Pine, 20.0, 18.0, 400.0),
+                tree("1-2", Species::Spruce, 10.0, 9.0, 600.0),
+            ],
+            tree_strata: Vec::new(),
+            removals: Vec::new(),
+        }
+    }
+
+    fn approx(expected: f64, found: f64) {
+        assert!((expected - found).abs() < 1e-9, "expected {expected}, found {found}");
+    }
+
+    #[test]
+    fn stand_aggregates_are_computed() {
+        let stand = stand();
+        approx(1000.0, stand.stems_per_ha());
+        approx(PI * (0.01 * 400.0 + 0.0025 * 600.0), stand.basal_area());
+        approx(0.5 * PI * (0.01 * 400.0 * 18.0 + 0.0025 * 600.0 * 9.0), stand.volume());
+        approx((0.01 * 400.0 * 18.0 + 0.0025 * 600.0 * 9.0) / (0.01 * 400.0 + 0.0025 * 600.0), stand.mean_height());
+        approx((0.01 * 400.0 * 20.0 + 0.0025 * 600.0 * 10.0) / (0.01 * 400.0 + 0.0025 * 600.0), stand.mean_diameter());
+        let bare = ForestStand { reference_trees: Vec::new(), ..stand };
+        approx(0.0, bare.mean_height());
+        approx(0.0, bare.mean_diameter());
+    }
+
+    #[test]
+    fn stands_are_projected_into_rows() {
+        let stand = stand();
+        assert_eq!(ForestStand::column_names().len(), ForestStand::column_types().len());
+        assert_eq!(vec!["1", "2025", "2", "3", "2", "1000"], stand.column_values()[..6]);
+        assert_eq!(ColumnValue::Text("Spruce".to_string()), stand.reference_trees[1].typed_values()[1]);
+        assert_eq!(ReferenceTree::column_names().len(), ReferenceTree::column_types().len());
+    }
+
+    #[test]
+    fn stands_are_serializable() {
+        let stand = stand();
+        let json = serde_json::to_string(&stand).unwrap();
+        assert!(json.contains("\"species\":\"pine\""));
+        assert_eq!(stand, serde_json::from_str(&json).unwrap());
+    }
+
+    fn grow_older(mut stand: ForestStand, params: ParameterMap) -> ForestStand {
+        let years: i32 = params["years"].parse().unwrap();
+        stand.year += years;
+        stand.reference_trees.iter_mut().for_each(|tree| tree.biological_age += f64::from(years));
+        stand
+    }
+
+    fn remove_spruce(mut stand: ForestStand, _params: ParameterMap) -> ForestStand {
+        stand.reference_trees.retain(|tree| tree.species != Species::Spruce);
+        stand
+    }
+
+    #[test]
+    fn stands_are_simulated() {
+        let operations: OperationRegistry<ForestStand> = HashMap::from([
+            ("grow_older", grow_older as ParameteredOperation<ForestStand>),
+            ("remove_spruce", remove_spruce as ParameteredOperation<ForestStand>),
+        ]);
+        let parameters = OperationParameters::from([("grow_older", ParameterMap::from([("years", "5")]))]);
+        let declaration = vec![("sequence", vec!["grow_older"]), ("alternatives", vec!["grow_older", "remove_spruce"])];
+        let runner = SimulationRunner::new(operations, parameters, declaration).unwrap();
+        let results = runner.run([stand()]).remove(0).results;
+        assert_eq!(vec![(2035, 2), (2030, 1)], results.iter().map(|stand| (stand.year, stand.reference_trees.len())).collect::<Vec<_>>());
+    }
+}
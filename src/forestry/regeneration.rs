@@ -0,0 +1,140 @@
+use serde_json::Value;
+use crate::configuration_utils::ParameterMap;
+use crate::simulation_runner::{Precondition, PreconditionFailure, Preconditions};
+use super::growth::parameter;
+use super::{ForestStand, ReferenceTree, Removal, Species};
+
+/// Minimum basal area weighted mean diameter in centimetres of a stand to clearcut.
+pub const MINIMUM_CLEARCUT_DIAMETER: f64 = 25.0;
+
+/// Whether the stand is mature enough for a clearcut.
+pub fn mature(stand: &ForestStand) -> bool {
+    stand.mean_diameter() >= MINIMUM_CLEARCUT_DIAMETER
+}
+
+/// Whether the stand is without trees to regenerate.
+pub fn bare(stand: &ForestStand) -> bool {
+    stand.reference_trees.is_empty() && stand.tree_strata.is_empty()
+}
+
+/// Preconditions of the clearcut and the regeneration operations, handling immature stands
+/// and stands with trees as given.
+pub fn preconditions(on_failure: PreconditionFailure) -> Preconditions<ForestStand> {
+    Preconditions::from([
+        ("clearcut", Precondition { check: mature, on_failure }),
+        ("planting", Precondition { check: bare, on_failure }),
+        ("natural_regeneration", Precondition { check: bare, on_failure }),
+    ])
+}
+
+/// Remove all reference trees and inventoried strata of the stand, recording the removal.
+pub fn clearcut(mut stand: ForestStand, _params: ParameterMap) -> ForestStand {
+    let trees = std::mem::take(&mut stand.reference_trees);
+    let strata = std::mem::take(&mut stand.tree_strata);
+    let removal = Removal {
+        year: stand.year,
+        operation: "clearcut".to_string(),
+        stems_per_ha: trees.iter().map(|tree| tree.stems_per_ha).chain(strata.iter().map(|stratum| stratum.stems_per_ha)).sum(),
+        basal_area: trees.iter().map(ReferenceTree::basal_area).chain(strata.iter().map(|stratum| stratum.basal_area)).sum(),
+        volume: trees.iter().map(ReferenceTree::volume).chain(strata.iter().map(|stratum| stratum.volume())).sum(),
+    };
+    if removal.stems_per_ha > 0.0 {
+        stand.removals.push(removal);
+    }
+    stand
+}
+
+fn species(params: &ParameterMap, default: Species) -> Species {
+    match params.get("species") {
+        Some(value) => serde_json::from_value(Value::String(value.to_string()))
+            .unwrap_or_else(|_| panic!("invalid value '{value}' of parameter 'species'")),
+        None => default,
+    }
+}
+
+/// Establish a seedling reference tree, the parameters defaulting to the given species, density and age.
+fn regenerate(mut stand: ForestStand, params: ParameterMap, default_species: Species, default_stems: f64, default_age: f64) -> ForestStand {
+    let tree = ReferenceTree {
+        identifier: format!("{}-{}", stand.identifier, stand.reference_trees.len() + 1),
+        species: species(&params, default_species),
+        breast_height_diameter: 0.0,
+        height: parameter(&params, "height", 0.3),
+        stems_per_ha: parameter(&params, "stems_per_ha", default_stems),
+        biological_age: parameter(&params, "age", default_age),
+    };
+    stand.reference_trees.push(tree);
+    stand
+}
+
+/// Plant seedlings, by default 2000 pines per hectare aged 2 years. Parameters as in
+/// natural_regeneration.
+pub fn planting(stand: ForestStand, params: ParameterMap) -> ForestStand {
+    regenerate(stand, params, Species::Pine, 2000.0, 2.0)
+}
+
+/// Regenerate naturally, by default 3000 downy birches per hectare aged 0 years. Parameters:
+/// - species: species of the seedlings in snake case, such as silver_birch
+/// - stems_per_ha: density of the seedlings
+/// - height: height of the seedlings in metres, 0.3 by default
+/// - age: biological age of the seedlings in years
+pub fn natural_regeneration(stand: ForestStand, params: ParameterMap) -> ForestStand {
+    regenerate(stand, params, Species::DownyBirch, 3000.0, 0.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::event_graph::EventDAG;
+    use crate::forestry::operations;
+    use crate::forestry::tests::{stand, tree};
+    use crate::simulation_runner::{OperationParameters, SimulationRunner};
+    use super::*;
+
+    #[test]
+    fn clearcut_removes_all_trees() {
+        let stand = stand();
+        let harvested = clearcut(stand.clone(), ParameterMap::new());
+        assert!(bare(&harvested));
+        assert_eq!(1, harvested.removals.len());
+        let removal = &harvested.removals[0];
+        assert_eq!((2025, "clearcut", 1000.0), (removal.year, removal.operation.as_str(), removal.stems_per_ha));
+        assert!((stand.volume() - harvested.removed_volume()).abs() < 1e-9);
+        assert!(clearcut(harvested, ParameterMap::new()).removals.len() == 1);
+    }
+
+    #[test]
+    fn bare_stands_are_regenerated() {
+        let bare_stand = clearcut(stand(), ParameterMap::new());
+        let planted = planting(bare_stand.clone(), ParameterMap::from([("species", "spruce"), ("stems_per_ha", "1800")]));
+        let seedling = &planted.reference_trees[0];
+        assert_eq!(("1-1", Species::Spruce, 1800.0, 2.0), (seedling.identifier.as_str(), seedling.species, seedling.stems_per_ha, seedling.biological_age));
+        let regenerated = natural_regeneration(bare_stand.clone(), ParameterMap::new());
+        assert_eq!((Species::DownyBirch, 3000.0), (regenerated.reference_trees[0].species, regenerated.reference_trees[0].stems_per_ha));
+        assert_eq!(1, regenerated.removals.len());
+
+        let root = EventDAG::new_node(Box::new(|stand| planting(stand, ParameterMap::from([("species", "oak")]))));
+        let failure = EventDAG::try_evaluate_chains(&root, bare_stand).unwrap_err();
+        assert_eq!("invalid value 'oak' of parameter 'species'", failure.error);
+    }
+
+    #[test]
+    fn rotations_are_simulated() {
+        let parameters = OperationParameters::from([("grow", ParameterMap::from([("years", "10")]))]);
+        let declaration = vec![
+            ("sequence", vec!["clearcut", "planting"]),
+            ("sequence", vec!["grow", "grow"]),
+            ("sequence", vec!["clearcut", "planting"]),
+        ];
+        let mut runner = SimulationRunner::new(operations(), parameters, declaration).unwrap();
+        runner.set_preconditions(preconditions(PreconditionFailure::Skip)).unwrap();
+
+        let young = ForestStand { reference_trees: vec![tree("1-1", Species::Pine, 20.0, 16.0, 600.0)], ..stand() };
+        let mature_stand = ForestStand { reference_trees: vec![tree("1-1", Species::Pine, 30.0, 22.0, 500.0)], ..stand() };
+        let results = runner.run([young, mature_stand]);
+        let young = &results[0].results[0];
+        assert_eq!((1, 1), (young.removals.len(), young.reference_trees.len()));
+        assert_eq!(2045, young.removals[0].year);
+        let mature_stand = &results[1].results[0];
+        assert_eq!(vec![2025], mature_stand.removals.iter().map(|removal| removal.year).collect::<Vec<_>>());
+        assert_eq!(22.0, mature_stand.reference_trees[0].biological_age);
+    }
+}
@@ -0,0 +1,144 @@
+use crate::configuration_utils::ParameterMap;
+use crate::simulation_runner::{Precondition, PreconditionFailure, Preconditions};
+use super::growth::parameter;
+use super::{ForestStand, Removal};
+
+/// Minimum basal area in square metres per hectare of a stand to thin.
+pub const MINIMUM_THINNING_BASAL_AREA: f64 = 15.0;
+/// Minimum mean height in metres of a stand to thin.
+pub const MINIMUM_THINNING_HEIGHT: f64 = 10.0;
+
+/// Whether the stand is developed enough for thinning.
+pub fn thinnable(stand: &ForestStand) -> bool {
+    stand.basal_area() >= MINIMUM_THINNING_BASAL_AREA && stand.mean_height() >= MINIMUM_THINNING_HEIGHT
+}
+
+/// Preconditions of the thinning operations, handling stands not thinnable as given.
+pub fn preconditions(on_failure: PreconditionFailure) -> Preconditions<ForestStand> {
+    Preconditions::from([
+        ("thinning_from_below", Precondition { check: thinnable, on_failure }),
+        ("thinning_from_above", Precondition { check: thinnable, on_failure }),
+    ])
+}
+
+/// Basal area to remove from the stand, given either as the target basal area after thinning or
+/// as the fraction of the basal area to remove.
+fn removal_target(stand: &ForestStand, operation: &str, params: &ParameterMap) -> f64 {
+    let basal_area = stand.basal_area();
+    match (params.contains_key("target_basal_area"), params.contains_key("removal_fraction")) {
+        (true, false) => (basal_area - parameter(params, "target_basal_area", basal_area)).max(0.0),
+        (false, true) => basal_area * parameter(params, "removal_fraction", 0.0).clamp(0.0, 1.0),
+        _ => panic!("operation '{operation}' requires either parameter 'target_basal_area' or 'removal_fraction'"),
+    }
+}
+
+/// Remove stems from the reference trees in order of diameter, from the smallest or the
+/// largest, until the removal target is reached, recording the removal into the stand.
+fn thin(mut stand: ForestStand, operation: &str, params: ParameterMap, from_above: bool) -> ForestStand {
+    let mut remaining = removal_target(&stand, operation, &params);
+    let mut order: Vec<usize> = (0..stand.reference_trees.len()).collect();
+    order.sort_by(|a, b| {
+        let (a, b) = (&stand.reference_trees[*a], &stand.reference_trees[*b]);
+        a.breast_height_diameter.total_cmp(&b.breast_height_diameter)
+    });
+    if from_above {
+        order.reverse();
+    }
+    let mut removal = Removal { year: stand.year, operation: operation.to_string(), stems_per_ha: 0.0, basal_area: 0.0, volume: 0.0 };
+    for index in order {
+        let tree = &mut stand.reference_trees[index];
+        let tree_basal_area = tree.tree_basal_area();
+        if remaining <= 0.0 {
+            break;
+        }
+        if tree_basal_area <= 0.0 {
+            continue;
+        }
+        let stems = tree.stems_per_ha.min(remaining / tree_basal_area);
+        tree.stems_per_ha -= stems;
+        removal.stems_per_ha += stems;
+        removal.basal_area += stems * tree_basal_area;
+        removal.volume += stems * tree.tree_volume();
+        remaining -= stems * tree_basal_area;
+    }
+    stand.reference_trees.retain(|tree| tree.stems_per_ha > 0.0);
+    if removal.stems_per_ha > 0.0 {
+        stand.removals.push(removal);
+    }
+    stand
+}
+
+/// Thin the smallest trees first. Parameters, exactly one of which is required:
+/// - target_basal_area: basal area in square metres per hectare to leave
+/// - removal_fraction: fraction of the basal area to remove
+pub fn thinning_from_below(stand: ForestStand, params: ParameterMap) -> ForestStand {
+    thin(stand, "thinning_from_below", params, false)
+}
+
+/// Thin the largest trees first, with the parameters of thinning_from_below.
+pub fn thinning_from_above(stand: ForestStand, params: ParameterMap) -> ForestStand {
+    thin(stand, "thinning_from_above", params, true)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::event_graph::EventDAG;
+    use crate::forestry::{operations, Species};
+    use crate::forestry::tests::{stand, tree};
+    use crate::simulation_runner::{OperationParameters, SimulationRunner};
+    use super::*;
+
+    fn approx(expected: f64, found: f64) {
+        assert!((expected - found).abs() < 1e-9, "expected {expected}, found {found}");
+    }
+
+    #[test]
+    fn thinning_from_below_removes_small_trees() {
+        let stand = stand();
+        let thinned = thinning_from_below(stand.clone(), ParameterMap::from([("target_basal_area", "12")]));
+        approx(12.0, thinned.basal_area());
+        assert_eq!(1, thinned.reference_trees.len());
+        let removal = &thinned.removals[0];
+        assert_eq!((2025, "thinning_from_below"), (removal.year, removal.operation.as_str()));
+        approx(stand.basal_area() - 12.0, removal.basal_area);
+        approx(stand.volume() - thinned.volume(), removal.volume);
+        approx(removal.volume, thinned.removed_volume());
+    }
+
+    #[test]
+    fn thinning_from_above_removes_large_trees() {
+        let stand = stand();
+        let thinned = thinning_from_above(stand.clone(), ParameterMap::from([("removal_fraction", "0.25")]));
+        approx(0.75 * stand.basal_area(), thinned.basal_area());
+        approx(600.0, thinned.reference_trees[1].stems_per_ha);
+        assert!(thinned.reference_trees[0].stems_per_ha < 400.0);
+
+        let untouched = thinning_from_above(stand.clone(), ParameterMap::from([("target_basal_area", "100")]));
+        assert_eq!(stand, untouched);
+    }
+
+    #[test]
+    fn thinning_requires_a_target() {
+        let root = EventDAG::new_node(Box::new(|stand| thinning_from_below(stand, ParameterMap::new())));
+        let failure = EventDAG::try_evaluate_chains(&root, stand()).unwrap_err();
+        assert_eq!("operation 'thinning_from_below' requires either parameter 'target_basal_area' or 'removal_fraction'", failure.error);
+    }
+
+    #[test]
+    fn thinning_is_guarded_by_preconditions() {
+        let parameters = OperationParameters::from([
+            ("thinning_from_below", ParameterMap::from([("removal_fraction", "0.3")])),
+            ("thinning_from_above", ParameterMap::from([("removal_fraction", "0.3")])),
+        ]);
+        let declaration = vec![("alternatives", vec!["thinning_from_below", "thinning_from_above"])];
+        let mut runner = SimulationRunner::new(operations(), parameters, declaration).unwrap();
+        runner.set_preconditions(preconditions(PreconditionFailure::Prune)).unwrap();
+
+        let results = runner.run([stand()]).remove(0).results;
+        assert_eq!(2, results.len());
+        assert!(results.iter().all(|thinned| thinned.removals.len() == 1));
+        let young = ForestStand { reference_trees: vec![tree("1-1", Species::Pine, 8.0, 6.0, 2000.0)], ..stand() };
+        assert!(!thinnable(&young));
+        assert!(runner.run([young]).remove(0).results.is_empty());
+    }
+}
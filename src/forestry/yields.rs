@@ -0,0 +1,200 @@
+use std::collections::BTreeMap;
+use serde::{Deserialize, Serialize};
+use crate::collectors::{CollectionPoint, Collector};
+use crate::output::{ColumnType, ColumnValue, RowMapping};
+use super::biomass::{BiomassModel, SimpleBiomass};
+use super::ForestStand;
+
+/// Volumes of a stand in a simulated year, in cubic metres per hectare.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct YieldRecord {
+    pub year: i32,
+    /// Standing volume at the end of the year.
+    pub standing_volume: f64,
+    /// Volume removed since the previous year.
+    pub removal_volume: f64,
+    /// Growth of the standing volume since the previous year, including the removed volume.
+    pub increment: f64,
+    /// Total dry biomass at the end of the year in tonnes per hectare.
+    pub biomass: f64,
+}
+
+impl RowMapping for YieldRecord {
+    fn column_names() -> Vec<&'static str> {
+        vec!["year", "standing_volume", "removal_volume", "increment", "biomass"]
+    }
+
+    fn column_values(&self) -> Vec<String> {
+        self.typed_values().iter().map(ColumnValue::to_string).collect()
+    }
+
+    fn column_types() -> Vec<ColumnType> {
+        vec![ColumnType::Integer, ColumnType::Float, ColumnType::Float, ColumnType::Float, ColumnType::Float]
+    }
+
+    fn typed_values(&self) -> Vec<ColumnValue> {
+        vec![
+            ColumnValue::Integer(self.year.into()),
+            ColumnValue::Float(self.standing_volume),
+            ColumnValue::Float(self.removal_volume),
+            ColumnValue::Float(self.increment),
+            ColumnValue::Float(self.biomass),
+        ]
+    }
+}
+
+/// Standing volume and number of recorded removals of a stand.
+#[derive(Debug, Clone, Copy)]
+struct Volumes {
+    standing: f64,
+    removals: usize,
+}
+
+impl Volumes {
+    fn of(stand: &ForestStand) -> Volumes {
+        Volumes { standing: stand.volume(), removals: stand.removals.len() }
+    }
+}
+
+/// Volumes at the end of the previous year and after the latest observation of an event chain.
+#[derive(Debug, Clone, Copy)]
+struct Progress {
+    year: i32,
+    baseline: Volumes,
+    latest: Volumes,
+}
+
+/// Collect a YieldRecord for each simulated year of the stand of each event chain, the record
+/// of a year reflecting the state after its last operation. The series feed the exporters
+/// writing series of RowMapping records. The biomass is computed with SimpleBiomass unless set.
+pub struct YieldCollector {
+    initial: Progress,
+    biomass_model: Box<dyn BiomassModel>,
+    progress: BTreeMap<usize, Progress>,
+    pub series: BTreeMap<usize, Vec<YieldRecord>>,
+}
+
+impl YieldCollector {
+    /// Collector for event chains starting from the given stand.
+    pub fn new(initial: &ForestStand) -> YieldCollector {
+        let volumes = Volumes::of(initial);
+        YieldCollector {
+            initial: Progress { year: initial.year, baseline: volumes, latest: volumes },
+            biomass_model: Box::new(SimpleBiomass::default()),
+            progress: BTreeMap::new(),
+            series: BTreeMap::new(),
+        }
+    }
+
+    /// Compute the biomass of the records with the given model.
+    pub fn set_biomass_model<M: BiomassModel + 'static>(&mut self, model: M) {
+        self.biomass_model = Box::new(model);
+    }
+}
+
+impl<T: AsRef<ForestStand>> Collector<T> for YieldCollector {
+    fn point(&self) -> CollectionPoint {
+        CollectionPoint::Operation
+    }
+
+    fn observe(&mut self, chain: usize, state: &T) {
+        let stand = state.as_ref();
+        let progress = self.progress.entry(chain).or_insert(self.initial);
+        if stand.year != progress.year {
+            progress.year = stand.year;
+            progress.baseline = progress.latest;
+        }
+        progress.latest = Volumes::of(stand);
+        let removal_volume: f64 = stand.removals[progress.baseline.removals..].iter().map(|removal| removal.volume).sum();
+        let record = YieldRecord {
+            year: stand.year,
+            standing_volume: progress.latest.standing,
+            removal_volume,
+            increment: progress.latest.standing - progress.baseline.standing + removal_volume,
+            biomass: self.biomass_model.stand_biomass(stand),
+        };
+        let records = self.series.entry(chain).or_default();
+        match records.last_mut() {
+            Some(last) if last.year == record.year => *last = record,
+            _ => records.push(record),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::configuration_utils::ParameterMap;
+    use crate::forestry::growth::grow;
+    use crate::forestry::{operations, ReferenceTree};
+    use crate::forestry::tests::stand;
+    use crate::output::csv::CsvWriter;
+    use crate::simulation_runner::{OperationParameters, SimulationRunner};
+    use crate::time_points::TimedState;
+    use super::*;
+
+    fn approx(expected: f64, found: f64) {
+        assert!((expected - found).abs() < 1e-9, "expected {expected}, found {found}");
+    }
+
+    #[test]
+    fn yields_are_collected_by_year() {
+        let parameters = OperationParameters::from([
+            ("grow", ParameterMap::from([("years", "5")])),
+            ("thinning_from_below", ParameterMap::from([("removal_fraction", "0.3")])),
+        ]);
+        let declaration = vec![
+            ("alternatives", vec!["grow", "thinning_from_below"]),
+            ("sequence", vec!["grow", "thinning_from_below"]),
+        ];
+        let runner = SimulationRunner::new(operations(), parameters, declaration).unwrap();
+        let initial = stand();
+        let mut collector = YieldCollector::new(&initial);
+        let results = runner.run_collecting(initial.clone(), &mut [&mut collector]).results;
+
+        let grown = &collector.series[&0];
+        assert_eq!(vec![2025, 2030, 2035], grown.iter().map(|record| record.year).collect::<Vec<_>>());
+        approx(initial.volume(), grown[0].standing_volume);
+        approx(0.0, grown[0].increment);
+        approx(0.0, grown[1].removal_volume);
+        approx(grown[1].standing_volume - initial.volume(), grown[1].increment);
+        approx(results[0].volume(), grown[2].standing_volume);
+        approx(results[0].removed_volume(), grown[2].removal_volume);
+        approx(grown[2].standing_volume - grown[1].standing_volume + grown[2].removal_volume, grown[2].increment);
+
+        let thinned = &collector.series[&1];
+        assert_eq!(vec![2025, 2030], thinned.iter().map(|record| record.year).collect::<Vec<_>>());
+        approx(0.0, thinned[0].increment);
+        approx(initial.volume() - thinned[0].standing_volume, thinned[0].removal_volume);
+        approx(results[1].removed_volume(), thinned.iter().map(|record| record.removal_volume).sum());
+        approx(SimpleBiomass::default().stand_biomass(&results[1]), thinned[1].biomass);
+    }
+
+    struct Carbon;
+
+    impl BiomassModel for Carbon {
+        fn tree_biomass(&self, tree: &ReferenceTree) -> f64 {
+            tree.height
+        }
+    }
+
+    #[test]
+    fn biomass_models_are_swappable() {
+        let initial = stand();
+        let mut collector = YieldCollector::new(&initial);
+        collector.set_biomass_model(Carbon);
+        Collector::observe(&mut collector, 0, &initial);
+        approx((18.0 * 400.0 + 9.0 * 600.0) / 1000.0, collector.series[&0][0].biomass);
+    }
+
+    #[test]
+    fn yields_are_exported() {
+        let initial = stand();
+        let mut collector = YieldCollector::new(&initial);
+        let timed = TimedState { time: 2030, state: grow(initial, ParameterMap::new()) };
+        Collector::observe(&mut collector, 0, &timed);
+        let mut writer = CsvWriter::new(Vec::new());
+        writer.write_series(&"1", &collector.series).unwrap();
+        let csv = String::from_utf8(writer.into_inner()).unwrap();
+        assert!(csv.starts_with("entity,chain,step,year,standing_volume,removal_volume,increment,biomass\n1,0,0,2030,"));
+    }
+}
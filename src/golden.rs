@@ -0,0 +1,165 @@
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+use serde::Serialize;
+use serde_json::Value;
+
+/// Environment variable which, when set, makes golden file comparisons rewrite the golden files
+/// with the current results instead of comparing against them.
+pub const UPDATE_VARIABLE: &str = "METSI_UPDATE_GOLDEN";
+
+/// Difference between the golden and the current results at a path within the results.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Difference {
+    pub path: String,
+    pub expected: String,
+    pub found: String,
+}
+
+/// Failure of a golden file comparison.
+#[derive(Debug)]
+pub enum GoldenError {
+    Io(io::Error),
+    Json(serde_json::Error),
+    Mismatch(Vec<Difference>),
+}
+
+impl fmt::Display for GoldenError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GoldenError::Io(err) => write!(f, "{err}"),
+            GoldenError::Json(err) => write!(f, "{err}"),
+            GoldenError::Mismatch(differences) => {
+                writeln!(f, "{} differences to golden file:", differences.len())?;
+                for difference in differences {
+                    writeln!(f, "  {}: expected {}, found {}", difference.path, difference.expected, difference.found)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl Error for GoldenError {}
+
+impl From<io::Error> for GoldenError {
+    fn from(err: io::Error) -> Self {
+        GoldenError::Io(err)
+    }
+}
+
+impl From<serde_json::Error> for GoldenError {
+    fn from(err: serde_json::Error) -> Self {
+        GoldenError::Json(err)
+    }
+}
+
+/// Compare the results against the golden file, numbers being equal within the given absolute
+/// tolerance. Results are serialized as JSON with object keys in sorted order. A missing golden
+/// file is written from the results, as are all golden files when UPDATE_VARIABLE is set.
+pub fn compare_golden<P: AsRef<Path>, R: Serialize>(path: P, results: &R, tolerance: f64) -> Result<(), GoldenError> {
+    let path = path.as_ref();
+    let current = serde_json::to_value(results)?;
+    if std::env::var_os(UPDATE_VARIABLE).is_some() || !path.exists() {
+        if let Some(directory) = path.parent() {
+            fs::create_dir_all(directory)?;
+        }
+        let mut text = serde_json::to_string_pretty(&current)?;
+        text.push('\n');
+        return Ok(fs::write(path, text)?);
+    }
+    let golden: Value = serde_json::from_str(&fs::read_to_string(path)?)?;
+    let mut differences = Vec::new();
+    compare(String::new(), &golden, &current, tolerance, &mut differences);
+    match differences.is_empty() {
+        true => Ok(()),
+        false => Err(GoldenError::Mismatch(differences)),
+    }
+}
+
+/// Assert that the results match the golden file as compare_golden does, panicking with the
+/// differences otherwise.
+pub fn assert_golden<P: AsRef<Path>, R: Serialize>(path: P, results: &R, tolerance: f64) {
+    if let Err(err) = compare_golden(path.as_ref(), results, tolerance) {
+        panic!("{}: {err}", path.as_ref().display());
+    }
+}
+
+fn compare(path: String, expected: &Value, found: &Value, tolerance: f64, differences: &mut Vec<Difference>) {
+    let child = |key: &dyn fmt::Display| if path.is_empty() { key.to_string() } else { format!("{path}.{key}") };
+    match (expected, found) {
+        (Value::Number(expected), Value::Number(found)) if expected != found => {
+            let (expected, found) = (expected.as_f64().unwrap_or_default(), found.as_f64().unwrap_or_default());
+            if (expected - found).abs() > tolerance {
+                differences.push(Difference { path, expected: expected.to_string(), found: found.to_string() });
+            }
+        }
+        (Value::Array(expected), Value::Array(found)) => {
+            for index in 0..expected.len().max(found.len()) {
+                let path = format!("{path}[{index}]");
+                match (expected.get(index), found.get(index)) {
+                    (Some(expected), Some(found)) => compare(path, expected, found, tolerance, differences),
+                    (expected, found) => differences.push(Difference { path, expected: describe(expected), found: describe(found) }),
+                }
+            }
+        }
+        (Value::Object(expected), Value::Object(found)) => {
+            let mut keys: Vec<&String> = expected.keys().chain(found.keys()).collect();
+            keys.sort();
+            keys.dedup();
+            for key in keys {
+                match (expected.get(key), found.get(key)) {
+                    (Some(expected), Some(found)) => compare(child(key), expected, found, tolerance, differences),
+                    (expected, found) => differences.push(Difference { path: child(key), expected: describe(expected), found: describe(found) }),
+                }
+            }
+        }
+        (Value::Number(_), Value::Number(_)) => {}
+        (expected, found) if expected != found =>
+            differences.push(Difference { path, expected: expected.to_string(), found: found.to_string() }),
+        _ => {}
+    }
+}
+
+fn describe(value: Option<&Value>) -> String {
+    value.map_or("nothing".to_string(), Value::to_string)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+    use super::*;
+
+    #[test]
+    fn results_are_compared_within_tolerance() {
+        let directory = tempfile::tempdir().unwrap();
+        let path = directory.path().join("results.json");
+        let golden = BTreeMap::from([("a", vec![1.0, 2.0]), ("b", vec![3.0])]);
+        compare_golden(&path, &golden, 1e-6).unwrap();
+        assert!(fs::read_to_string(&path).unwrap().contains("\"a\": ["));
+
+        let close = BTreeMap::from([("a", vec![1.0, 2.0 + 1e-9]), ("b", vec![3.0])]);
+        assert_golden(&path, &close, 1e-6);
+
+        let changed = BTreeMap::from([("a", vec![1.0, 2.5]), ("c", vec![3.0])]);
+        let err = compare_golden(&path, &changed, 1e-6).unwrap_err();
+        assert_eq!(
+            "3 differences to golden file:\n  a[1]: expected 2, found 2.5\n  b: expected [3.0], found nothing\n  c: expected nothing, found [3.0]\n",
+            err.to_string()
+        );
+    }
+
+    #[test]
+    fn structural_differences_are_reported() {
+        let mut differences = Vec::new();
+        let expected = serde_json::json!([{"state": 1, "name": "pine"}, {"state": 2}]);
+        let found = serde_json::json!([{"state": 1, "name": "spruce"}]);
+        compare(String::new(), &expected, &found, 0.0, &mut differences);
+        assert_eq!(vec![
+            Difference { path: "[0].name".to_string(), expected: "\"pine\"".to_string(), found: "\"spruce\"".to_string() },
+            Difference { path: "[1]".to_string(), expected: "{\"state\":2}".to_string(), found: "nothing".to_string() }
+        ], differences);
+    }
+}
@@ -0,0 +1,182 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap, VecDeque};
+use std::fmt;
+use std::rc::Rc;
+use serde::Serialize;
+use crate::event_graph::EventNode;
+
+/// Position of a node in an event graph as the follower indices leading to it from the root,
+/// along the path on which a breadth-first walk first reaches it.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
+pub struct NodePath(pub Vec<usize>);
+
+impl fmt::Display for NodePath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "root")?;
+        for index in &self.0 {
+            write!(f, "/{index}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Node present in only one of the compared graphs.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct DiffNode {
+    pub path: NodePath,
+    pub label: Option<&'static str>,
+}
+
+/// Node whose label differs between the compared graphs.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Relabel {
+    pub path: NodePath,
+    pub before: Option<&'static str>,
+    pub after: Option<&'static str>,
+}
+
+/// Follower attachment between the nodes at the given positions.
+pub type DiffEdge = (NodePath, NodePath);
+
+/// Structural differences between two event graphs, nodes matched by their positions. Inserting
+/// a node into a sequence moves the nodes following it, which are reported as removed and added.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct GraphDiff {
+    pub added_nodes: Vec<DiffNode>,
+    pub removed_nodes: Vec<DiffNode>,
+    pub relabeled_nodes: Vec<Relabel>,
+    pub added_edges: Vec<DiffEdge>,
+    pub removed_edges: Vec<DiffEdge>,
+}
+
+impl GraphDiff {
+    /// Whether the graphs are structurally equal.
+    pub fn is_empty(&self) -> bool {
+        self.added_nodes.is_empty() && self.removed_nodes.is_empty() && self.relabeled_nodes.is_empty()
+            && self.added_edges.is_empty() && self.removed_edges.is_empty()
+    }
+}
+
+fn label(label: Option<&str>) -> &str {
+    label.unwrap_or("-")
+}
+
+impl fmt::Display for GraphDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for node in &self.removed_nodes {
+            writeln!(f, "- node {} {}", node.path, label(node.label))?;
+        }
+        for node in &self.added_nodes {
+            writeln!(f, "+ node {} {}", node.path, label(node.label))?;
+        }
+        for relabel in &self.relabeled_nodes {
+            writeln!(f, "~ node {} {} -> {}", relabel.path, label(relabel.before), label(relabel.after))?;
+        }
+        for (from, to) in &self.removed_edges {
+            writeln!(f, "- edge {from} -> {to}")?;
+        }
+        for (from, to) in &self.added_edges {
+            writeln!(f, "+ edge {from} -> {to}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Labels of the nodes and the edges of a graph by node position.
+type Structure = (BTreeMap<NodePath, Option<&'static str>>, BTreeSet<DiffEdge>);
+
+fn structure<T: Clone>(root: &EventNode<T>) -> Structure {
+    let mut positions: HashMap<*const (), NodePath> = HashMap::from([(root.as_ptr() as *const (), NodePath(Vec::new()))]);
+    let mut labels = BTreeMap::new();
+    let mut edges = BTreeSet::new();
+    let mut queue = VecDeque::from([(Rc::clone(root), NodePath(Vec::new()))]);
+    while let Some((node, path)) = queue.pop_front() {
+        let node = node.borrow();
+        labels.insert(path.clone(), node.label());
+        for (index, follower) in node.followers().iter().enumerate() {
+            let key = follower.as_ptr() as *const ();
+            let follower_path = positions.entry(key).or_insert_with(|| {
+                let mut follower_path = path.0.clone();
+                follower_path.push(index);
+                queue.push_back((Rc::clone(follower), NodePath(follower_path.clone())));
+                NodePath(follower_path)
+            });
+            edges.insert((path.clone(), follower_path.clone()));
+        }
+    }
+    (labels, edges)
+}
+
+/// Compare the structures of the event graphs starting from the given roots, reporting the
+/// nodes and edges of the second graph added, removed or relabeled relative to the first.
+pub fn diff<T: Clone>(a: &EventNode<T>, b: &EventNode<T>) -> GraphDiff {
+    let (labels_a, edges_a) = structure(a);
+    let (labels_b, edges_b) = structure(b);
+    let mut result = GraphDiff::default();
+    for (path, before) in &labels_a {
+        match labels_b.get(path) {
+            None => result.removed_nodes.push(DiffNode { path: path.clone(), label: *before }),
+            Some(after) if after != before => result.relabeled_nodes.push(Relabel { path: path.clone(), before: *before, after: *after }),
+            Some(_) => {}
+        }
+    }
+    result.added_nodes = labels_b.iter()
+        .filter(|(path, _)| !labels_a.contains_key(*path))
+        .map(|(path, label)| DiffNode { path: path.clone(), label: *label })
+        .collect();
+    result.removed_edges = edges_a.difference(&edges_b).cloned().collect();
+    result.added_edges = edges_b.difference(&edges_a).cloned().collect();
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use crate::configuration_utils::{ParameterMap, ParameteredOperation};
+    use crate::simulation_runner::{OperationParameters, OperationRegistry, SimulationDeclaration, SimulationRunner};
+    use super::*;
+
+    fn identity(value: i32, _params: ParameterMap) -> i32 {
+        value
+    }
+
+    fn graph(declaration: SimulationDeclaration) -> EventNode<i32> {
+        let operations: OperationRegistry<i32> = HashMap::from([
+            ("grow", identity as ParameteredOperation<i32>),
+            ("thin", identity as ParameteredOperation<i32>),
+            ("clearcut", identity as ParameteredOperation<i32>),
+        ]);
+        let runner = SimulationRunner::new(operations, OperationParameters::new(), declaration).unwrap();
+        Rc::clone(runner.root())
+    }
+
+    #[test]
+    fn equal_graphs_have_no_differences() {
+        let declaration = vec![("sequence", vec!["grow"]), ("alternatives", vec!["thin", "clearcut"]), ("sequence", vec!["grow"])];
+        let difference = diff(&graph(declaration.clone()), &graph(declaration));
+        assert!(difference.is_empty());
+        assert_eq!("", difference.to_string());
+    }
+
+    #[test]
+    fn differences_are_reported() {
+        let a = graph(vec![("sequence", vec!["grow"]), ("alternatives", vec!["thin", "clearcut"]), ("sequence", vec!["grow"])]);
+        let b = graph(vec![("sequence", vec!["grow"]), ("alternatives", vec!["grow", "clearcut", "thin"])]);
+        let difference = diff(&a, &b);
+        assert_eq!(vec![DiffNode { path: NodePath(vec![0, 0, 0]), label: Some("grow") }], difference.removed_nodes);
+        assert_eq!(vec![DiffNode { path: NodePath(vec![0, 2]), label: Some("thin") }], difference.added_nodes);
+        assert_eq!(vec![Relabel { path: NodePath(vec![0, 0]), before: Some("thin"), after: Some("grow") }], difference.relabeled_nodes);
+        assert_eq!(vec![
+            (NodePath(vec![0, 0]), NodePath(vec![0, 0, 0])),
+            (NodePath(vec![0, 1]), NodePath(vec![0, 0, 0])),
+        ], difference.removed_edges);
+        assert_eq!(vec![(NodePath(vec![0]), NodePath(vec![0, 2]))], difference.added_edges);
+        assert_eq!("\
+- node root/0/0/0 grow
++ node root/0/2 thin
+~ node root/0/0 thin -> grow
+- edge root/0/0 -> root/0/0/0
+- edge root/0/1 -> root/0/0/0
++ edge root/0 -> root/0/2
+", difference.to_string());
+    }
+}
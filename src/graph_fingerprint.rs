@@ -0,0 +1,153 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::hash::Hasher;
+use serde::{Deserialize, Serialize};
+use crate::event_graph::{EventNode, NodeMode};
+use crate::provenance::EffectiveParameters;
+use crate::stable_hash::StableHasher;
+
+/// Canonical fingerprint of an event graph, displayed as 16 hex digits. Graphs of equal
+/// fingerprints produce the same event chains, of the same labels and bound parameters, in the
+/// same chain order, however they were constructed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct GraphFingerprint(pub u64);
+
+impl fmt::Display for GraphFingerprint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:016x}", self.0)
+    }
+}
+
+/// Fingerprint of the event graph from its topology, the labels, modes, probabilities, layers and
+/// tags of its nodes, the branch labels of their followers and the parameters bound to the node
+/// labels by the lookup. Followers are fingerprinted in evaluation order, so the fingerprint
+/// changes when chain ids change. Operations are identified by their labels only; the operations
+/// of unlabeled nodes do not affect the fingerprint.
+pub fn fingerprint<T: Clone, F>(root: &EventNode<T>, parameters: F) -> GraphFingerprint
+where F: Fn(&'static str) -> Option<EffectiveParameters> {
+    fingerprint_to_depth(root, usize::MAX, parameters)
+}
+
+/// Fingerprint of the event graph as fingerprint, covering only the nodes within the given
+/// depth, the root being at depth 0. Graphs sharing their structure up to the depth have equal
+/// fingerprints up to the depth.
+pub fn fingerprint_to_depth<T: Clone, F>(root: &EventNode<T>, depth: usize, parameters: F) -> GraphFingerprint
+where F: Fn(&'static str) -> Option<EffectiveParameters> {
+    GraphFingerprint(node_hash(root, depth, &parameters, &mut HashMap::new()))
+}
+
+fn write_str(hasher: &mut StableHasher, value: Option<&str>) {
+    match value {
+        Some(value) => {
+            hasher.write(&(value.len() as u64).to_le_bytes());
+            hasher.write(value.as_bytes());
+        }
+        None => hasher.write(&u64::MAX.to_le_bytes()),
+    }
+}
+
+/// Hash of the node and the nodes following it down to the remaining depth, memoized by node and
+/// remaining depth for graphs joining their branches.
+fn node_hash<T: Clone, F>(node: &EventNode<T>, depth: usize, parameters: &F, hashes: &mut HashMap<(*const (), usize), u64>) -> u64
+where F: Fn(&'static str) -> Option<EffectiveParameters> {
+    let key = (node.as_ptr() as *const (), depth);
+    if let Some(hash) = hashes.get(&key) {
+        return *hash;
+    }
+    let mut hasher = StableHasher::new();
+    let followers = {
+        let node = node.borrow();
+        write_str(&mut hasher, node.label());
+        hasher.write_u8(match node.mode() {
+            NodeMode::Enabled => 0,
+            NodeMode::Skipped => 1,
+            NodeMode::Pruned => 2,
+        });
+        hasher.write(&node.probability().to_bits().to_le_bytes());
+        hasher.write(&node.layer().map_or(i64::MIN, i64::from).to_le_bytes());
+        let mut tags = node.tags().to_vec();
+        tags.sort();
+        hasher.write(&(tags.len() as u64).to_le_bytes());
+        for tag in tags {
+            write_str(&mut hasher, Some(tag));
+        }
+        let bound = node.label().and_then(parameters);
+        hasher.write_u8(u8::from(bound.is_some()));
+        for (name, value) in bound.iter().flatten() {
+            write_str(&mut hasher, Some(name));
+            write_str(&mut hasher, Some(value));
+        }
+        let followers = if depth == 0 { Vec::new() } else { node.evaluation_order() };
+        hasher.write(&(followers.len() as u64).to_le_bytes());
+        for follower in &followers {
+            write_str(&mut hasher, node.branch_label(follower));
+        }
+        followers
+    };
+    for follower in &followers {
+        hasher.write(&node_hash(follower, depth - 1, parameters, hashes).to_le_bytes());
+    }
+    let hash = hasher.finish();
+    hashes.insert(key, hash);
+    hash
+}
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+    use crate::branching_generators::{alternatives, sequence};
+    use crate::event_graph::{BoxedOperation, EventDAG};
+    use super::*;
+
+    fn labeled(label: &'static str) -> BoxedOperation<i32> {
+        Box::new(move |x| x + label.len() as i32)
+    }
+
+    /// Root followed by alternatives of the labels, each followed by the sequence of labels.
+    fn graph(alternative: &[&'static str], following: &[&'static str]) -> EventNode<i32> {
+        let root = EventDAG::new_node(Box::new(|x: i32| x));
+        let branches = alternatives(vec![Rc::clone(&root)], alternative.iter().map(|label| labeled(label))).unwrap();
+        for (branch, label) in branches.iter().zip(alternative) {
+            branch.borrow_mut().set_label(label);
+        }
+        let mut frontier = branches;
+        for label in following {
+            frontier = sequence(frontier, [labeled(label)]).unwrap();
+            frontier[0].borrow_mut().set_label(label);
+        }
+        root
+    }
+
+    fn no_parameters(_: &'static str) -> Option<EffectiveParameters> {
+        None
+    }
+
+    #[test]
+    fn equal_simulations_have_equal_fingerprints() {
+        let fingerprint_of = |root: &EventNode<i32>| fingerprint(root, no_parameters);
+        assert_eq!(fingerprint_of(&graph(&["thin", "clearcut"], &["grow"])), fingerprint_of(&graph(&["thin", "clearcut"], &["grow"])));
+        assert_ne!(fingerprint_of(&graph(&["thin", "clearcut"], &["grow"])), fingerprint_of(&graph(&["clearcut", "thin"], &["grow"])));
+        assert_ne!(fingerprint_of(&graph(&["thin", "clearcut"], &["grow"])), fingerprint_of(&graph(&["thin", "clearcut"], &["grow", "grow"])));
+
+        let root = graph(&["thin", "clearcut"], &["grow"]);
+        let before = fingerprint_of(&root);
+        root.borrow().followers()[0].borrow_mut().set_mode(NodeMode::Skipped);
+        assert_ne!(before, fingerprint_of(&root));
+        assert_eq!(16, before.to_string().len());
+    }
+
+    #[test]
+    fn bound_parameters_and_depth_are_fingerprinted() {
+        let root = graph(&["thin", "clearcut"], &["grow"]);
+        let ratio = |ratio: &'static str| move |operation: &'static str| {
+            (operation == "thin").then(|| EffectiveParameters::from([("ratio", ratio)]))
+        };
+        assert_eq!(fingerprint(&root, ratio("0.3")), fingerprint(&root, ratio("0.3")));
+        assert_ne!(fingerprint(&root, ratio("0.3")), fingerprint(&root, ratio("0.5")));
+
+        let other = graph(&["thin", "clearcut"], &["fertilize"]);
+        assert_eq!(fingerprint_to_depth(&root, 1, no_parameters), fingerprint_to_depth(&other, 1, no_parameters));
+        assert_ne!(fingerprint_to_depth(&root, 2, no_parameters), fingerprint_to_depth(&other, 2, no_parameters));
+        assert_eq!(fingerprint(&root, no_parameters), fingerprint_to_depth(&root, 2, no_parameters));
+    }
+}
@@ -0,0 +1,177 @@
+use std::collections::{BTreeMap, HashSet};
+use std::rc::Rc;
+use crate::event_graph::{BoxedOperation, EventDAG, EventNode, EventNodes, NodeMode};
+
+/// Factory of the replacement operations of a rewrite, called once per rewritten node.
+pub type OperationFactory<T> = Box<dyn Fn() -> BoxedOperation<T>>;
+/// Pattern selecting the nodes to rewrite.
+pub type NodePattern<T> = Box<dyn Fn(&EventDAG<T>) -> bool>;
+/// Number of nodes rewritten by each rule, by rule name.
+pub type RewriteCounts = BTreeMap<&'static str, usize>;
+
+/// Rewrite of the nodes matching a RewriteRule.
+pub enum Rewrite<T> {
+    /// Splice the node out of the graph, its predecessors followed directly by its followers.
+    /// The root and leaf nodes are not removed, as their removal would change the event chains.
+    Remove,
+    /// Replace the operation of the node, relabeling the node.
+    Replace { label: &'static str, operation: OperationFactory<T> },
+}
+
+/// Rule rewriting the nodes matching its pattern, for optimization passes and scenario surgery
+/// on generated event graphs.
+pub struct RewriteRule<T> {
+    name: &'static str,
+    pattern: NodePattern<T>,
+    rewrite: Rewrite<T>,
+}
+
+impl<T: Clone + 'static> RewriteRule<T> {
+    pub fn new<P: Fn(&EventDAG<T>) -> bool + 'static>(name: &'static str, pattern: P, rewrite: Rewrite<T>) -> RewriteRule<T> {
+        RewriteRule { name, pattern: Box::new(pattern), rewrite }
+    }
+
+    /// Remove the nodes disabled as identities, collapsing consecutive identities.
+    pub fn collapse_identities() -> RewriteRule<T> {
+        RewriteRule::new("collapse_identities", |node| node.mode() == NodeMode::Skipped, Rewrite::Remove)
+    }
+
+    /// Replace the operation of the nodes labeled with the given label, optionally only of the
+    /// nodes with the given tag.
+    pub fn replace_operation<F>(label: &'static str, tag: Option<&'static str>, replacement: &'static str, operation: F) -> RewriteRule<T>
+    where F: Fn() -> BoxedOperation<T> + 'static {
+        let pattern = move |node: &EventDAG<T>| node.label() == Some(label) && tag.is_none_or(|tag| node.has_tag(tag));
+        RewriteRule::new("replace_operation", pattern, Rewrite::Replace { label: replacement, operation: Box::new(operation) })
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+}
+
+fn key<T>(node: &EventNode<T>) -> *const () {
+    node.as_ptr() as *const ()
+}
+
+/// Followers of the node with the removed nodes replaced by their followers, without duplicates.
+fn spliced<T: Clone>(followers: &[EventNode<T>], removed: &HashSet<*const ()>, result: &mut EventNodes<T>) {
+    for follower in followers {
+        if removed.contains(&key(follower)) {
+            spliced(follower.borrow().followers(), removed, result);
+        } else if !result.iter().any(|existing| Rc::ptr_eq(existing, follower)) {
+            result.push(Rc::clone(follower));
+        }
+    }
+}
+
+/// Apply the rules in the given order over the graph starting from the given root, each rule
+/// rewriting all of its matching nodes in place. Returns the number of rewritten nodes by rule.
+/// Nodes shared by several branches are rewritten once, for all of the branches. Rewriting the
+/// graph of a SimulationRunner applies until the runner recompiles its graph.
+pub fn rewrite<T: Clone + 'static>(root: &EventNode<T>, rules: &[RewriteRule<T>]) -> RewriteCounts {
+    let mut counts = RewriteCounts::new();
+    for rule in rules {
+        let nodes = EventDAG::reachable_nodes(&[Rc::clone(root)]);
+        let mut removed: HashSet<*const ()> = HashSet::new();
+        let mut count = 0;
+        for node in nodes.iter().filter(|node| (rule.pattern)(&node.borrow())) {
+            match &rule.rewrite {
+                Rewrite::Remove => {
+                    if !Rc::ptr_eq(node, root) && !node.borrow().followers().is_empty() {
+                        removed.insert(key(node));
+                        count += 1;
+                    }
+                }
+                Rewrite::Replace { label, operation } => {
+                    let mut node = node.borrow_mut();
+                    node.set_operation(operation());
+                    node.set_label(label);
+                    count += 1;
+                }
+            }
+        }
+        if !removed.is_empty() {
+            for node in nodes.iter().filter(|node| !removed.contains(&key(node))) {
+                let mut followers = EventNodes::new();
+                spliced(node.borrow().followers(), &removed, &mut followers);
+                node.borrow_mut().set_followers(followers);
+            }
+        }
+        *counts.entry(rule.name).or_default() += count;
+    }
+    counts
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use crate::configuration_utils::{ParameterMap, ParameteredOperation};
+    use crate::graph_statistics::GraphStatistics;
+    use crate::simulation_runner::{OperationParameters, OperationRegistry, SimulationRunner};
+    use super::*;
+
+    fn increment(value: i32, _params: ParameterMap) -> i32 {
+        value + 1
+    }
+
+    fn double(value: i32, _params: ParameterMap) -> i32 {
+        value * 2
+    }
+
+    fn runner() -> SimulationRunner<i32> {
+        let operations: OperationRegistry<i32> = HashMap::from([
+            ("increment", increment as ParameteredOperation<i32>),
+            ("double", double as ParameteredOperation<i32>),
+        ]);
+        let declaration = vec![
+            ("sequence", vec!["increment", "increment", "increment"]),
+            ("alternatives", vec!["increment", "double"]),
+            ("sequence", vec!["increment"]),
+        ];
+        SimulationRunner::new(operations, OperationParameters::new(), declaration).unwrap()
+    }
+
+    #[test]
+    fn identities_are_collapsed() {
+        let runner = runner();
+        let nodes = EventDAG::reachable_nodes(&[Rc::clone(runner.root())]);
+        for node in [&nodes[1], &nodes[2], &nodes[4], &nodes[6]] {
+            node.borrow_mut().set_mode(NodeMode::Skipped);
+        }
+        assert_eq!(vec![2, 4], runner.run([1])[0].results);
+
+        let counts = rewrite(runner.root(), &[RewriteRule::collapse_identities()]);
+        assert_eq!(RewriteCounts::from([("collapse_identities", 3)]), counts);
+        assert_eq!(vec![2, 4], runner.run([1])[0].results);
+        let statistics = GraphStatistics::of(runner.root());
+        assert_eq!((4, 4, 2), (statistics.nodes, statistics.depth, statistics.chains));
+        assert_eq!(Some("increment"), runner.root().borrow().followers()[0].borrow().label());
+    }
+
+    #[test]
+    fn operations_are_replaced_under_tag() {
+        let runner = runner();
+        let nodes = EventDAG::reachable_nodes(&[Rc::clone(runner.root())]);
+        nodes[4].borrow_mut().add_tag("experiment");
+        let rules = [
+            RewriteRule::replace_operation("increment", Some("experiment"), "decrement", || Box::new(|value: i32| value - 1)),
+            RewriteRule::new("disable_doubling", |node| node.label() == Some("double"), Rewrite::Replace {
+                label: "identity",
+                operation: Box::new(|| Box::new(|value| value)),
+            }),
+        ];
+        let counts = rewrite(runner.root(), &rules);
+        assert_eq!(RewriteCounts::from([("disable_doubling", 1), ("replace_operation", 1)]), counts);
+        assert_eq!(Some("decrement"), nodes[4].borrow().label());
+        assert_eq!(vec![3, 4], runner.run([0])[0].results);
+    }
+
+    #[test]
+    fn roots_and_leaves_are_kept() {
+        let runner = runner();
+        let rule = RewriteRule::new("remove_all", |_| true, Rewrite::Remove);
+        assert_eq!(RewriteCounts::from([("remove_all", 5)]), rewrite(runner.root(), &[rule]));
+        assert_eq!(vec![1], runner.run([0])[0].results);
+        assert_eq!(2, GraphStatistics::of(runner.root()).nodes);
+    }
+}
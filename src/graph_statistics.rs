@@ -0,0 +1,87 @@
+use std::collections::HashMap;
+use std::fmt;
+use crate::event_graph::EventNode;
+
+/// Structural statistics of an event graph, computed without evaluating it or enumerating its
+/// event chains.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GraphStatistics {
+    /// Number of distinct EventNodes.
+    pub nodes: usize,
+    /// Number of follower attachments between EventNodes.
+    pub edges: usize,
+    /// Number of distinct EventNodes without followers.
+    pub leaves: usize,
+    /// Number of EventNodes in the longest event chain.
+    pub depth: usize,
+    /// Number of unique event chains, saturating at usize::MAX.
+    pub chains: usize,
+}
+
+impl GraphStatistics {
+    /// Compute the statistics of the graph starting from the given EventNode.
+    pub fn of<T: Clone>(root: &EventNode<T>) -> GraphStatistics {
+        let mut visited: HashMap<*const (), (usize, usize)> = HashMap::new();
+        let mut statistics = GraphStatistics { nodes: 0, edges: 0, leaves: 0, depth: 0, chains: 0 };
+        let (depth, chains) = GraphStatistics::visit(root, &mut visited, &mut statistics);
+        statistics.depth = depth;
+        statistics.chains = chains;
+        statistics
+    }
+
+    /// Visit the node once, returning its depth and chain count memoized by node identity.
+    fn visit<T: Clone>(node: &EventNode<T>, visited: &mut HashMap<*const (), (usize, usize)>, statistics: &mut GraphStatistics) -> (usize, usize) {
+        let key = node.as_ptr() as *const ();
+        if let Some(memoized) = visited.get(&key) {
+            return *memoized;
+        }
+        let borrowed = node.borrow();
+        let followers = borrowed.followers();
+        statistics.nodes += 1;
+        statistics.edges += followers.len();
+        let result = if followers.is_empty() {
+            statistics.leaves += 1;
+            (1, 1)
+        } else {
+            followers.iter()
+                .map(|follower| GraphStatistics::visit(follower, visited, statistics))
+                .fold((0, 0), |(depth, chains): (usize, usize), (follower_depth, follower_chains)| {
+                    (depth.max(follower_depth + 1), chains.saturating_add(follower_chains))
+                })
+        };
+        visited.insert(key, result);
+        result
+    }
+}
+
+impl fmt::Display for GraphStatistics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "nodes: {}", self.nodes)?;
+        writeln!(f, "edges: {}", self.edges)?;
+        writeln!(f, "leaves: {}", self.leaves)?;
+        writeln!(f, "depth: {}", self.depth)?;
+        write!(f, "chains: {}", self.chains)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::branching_generators::{alternatives, sequence};
+    use crate::event_graph::{BoxedOperation, EventDAG};
+    use super::*;
+
+    fn ops(count: usize) -> Vec<BoxedOperation<i32>> {
+        (0..count).map(|_| -> BoxedOperation<i32> { Box::new(|x| x + 1) }).collect()
+    }
+
+    #[test]
+    fn statistics_are_computed() {
+        let root = EventDAG::new_node(Box::new(|x: i32| x));
+        let first = alternatives(vec![root.clone()], ops(3)).unwrap();
+        let second = sequence(first, ops(2)).unwrap();
+        alternatives(second, ops(4)).unwrap();
+        let statistics = GraphStatistics::of(&root);
+        assert_eq!(GraphStatistics { nodes: 10, edges: 11, leaves: 4, depth: 5, chains: 12 }, statistics);
+        assert_eq!(EventDAG::node_chains(&root).len(), statistics.chains);
+    }
+}
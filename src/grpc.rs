@@ -0,0 +1,230 @@
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{mpsc as std_mpsc, Arc, Mutex};
+use std::thread;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use tokio::sync::{mpsc, oneshot};
+use tokio_stream::wrappers::ReceiverStream;
+use tonic::transport::Server;
+use tonic::{Request, Response, Status};
+use crate::graph_statistics::GraphStatistics;
+use crate::simulation_runner::{compile_owned, OperationRegistry, OwnedParameters};
+use crate::streaming::{stream_chains, ResultSender};
+
+/// Messages and service definitions generated from proto/metsi.proto.
+pub mod proto {
+    tonic::include_proto!("metsi");
+}
+
+use proto::evaluation_server::{Evaluation, EvaluationServer};
+use proto::{ChainResult, CompileReply, CompileRequest, EvaluateRequest, ReleaseReply, ReleaseRequest};
+
+/// Number of chain results buffered ahead of the client.
+const RESULT_BUFFER: usize = 64;
+
+type ResultStream = mpsc::Sender<Result<ChainResult, Status>>;
+
+/// Evaluation of a state of a compiled simulation, streaming the chain results into the sender.
+struct EvaluationJob<T> {
+    state: T,
+    results: ResultStream,
+}
+
+/// Compiled simulation kept by the service. Event graphs are not shareable across threads, so
+/// each simulation is compiled once in a thread of its own, which owns the graph and evaluates
+/// the states sent to it in turn until the simulation is released.
+struct CompiledSimulation<T> {
+    jobs: std_mpsc::Sender<EvaluationJob<T>>,
+}
+
+/// gRPC evaluation service over the registered operations, keeping compiled simulations until
+/// released for evaluating states exchanged as JSON documents of T.
+pub struct EvaluationService<T> {
+    operations: Arc<OperationRegistry<T>>,
+    simulations: Mutex<HashMap<u64, Arc<CompiledSimulation<T>>>>,
+    next_simulation: AtomicU64,
+}
+
+impl<T> EvaluationService<T> {
+    pub fn new(operations: OperationRegistry<T>) -> EvaluationService<T> {
+        EvaluationService {
+            operations: Arc::new(operations),
+            simulations: Mutex::new(HashMap::new()),
+            next_simulation: AtomicU64::new(1),
+        }
+    }
+}
+
+impl<T: Clone + Serialize + DeserializeOwned + Send + 'static> EvaluationService<T> {
+    /// Serve the evaluation service at the address until the server fails.
+    pub async fn serve(self, address: SocketAddr) -> Result<(), tonic::transport::Error> {
+        Server::builder().add_service(EvaluationServer::new(self)).serve(address).await
+    }
+}
+
+/// Sender of the chain results of an evaluation as JSON documents into the result stream.
+struct ChainSender<'a>(&'a ResultStream);
+
+impl<T: Serialize> ResultSender<(usize, T)> for ChainSender<'_> {
+    fn send_result(&self, (chain, result): (usize, T)) -> bool {
+        let sent = serde_json::to_string(&result)
+            .map(|state| ChainResult { chain: chain as u64, state })
+            .map_err(|err| Status::internal(err.to_string()));
+        let serialized = sent.is_ok();
+        self.0.blocking_send(sent).is_ok() && serialized
+    }
+}
+
+/// Compile the simulation in a thread of its own, reporting the number of its event chains once
+/// compiled and then evaluating the jobs sent to it until their senders are dropped.
+fn spawn_simulation<T>(operations: Arc<OperationRegistry<T>>, request: CompileRequest, compiled: oneshot::Sender<Result<u64, Status>>) -> std_mpsc::Sender<EvaluationJob<T>>
+where T: Clone + Serialize + Send + 'static {
+    let (jobs, received) = std_mpsc::channel::<EvaluationJob<T>>();
+    thread::spawn(move || {
+        let declaration: Vec<(String, Vec<String>)> = request.declaration.into_iter().map(|generator| (generator.generator, generator.operations)).collect();
+        let parameters: OwnedParameters = request.parameters.into_iter().map(|(operation, parameters)| (operation, parameters.values)).collect();
+        let root = match compile_owned(&operations, &parameters, &declaration) {
+            Ok(root) => root,
+            Err(err) => {
+                let _ = compiled.send(Err(Status::invalid_argument(err)));
+                return;
+            }
+        };
+        if compiled.send(Ok(GraphStatistics::of(&root).chains as u64)).is_err() {
+            return;
+        }
+        for job in received {
+            if let Err(failure) = stream_chains(&root, job.state, &ChainSender(&job.results)) {
+                let _ = job.results.blocking_send(Err(Status::aborted(
+                    format!("chain {}, {}: {}", failure.chain, failure.location(), failure.error)
+                )));
+            }
+        }
+    });
+    jobs
+}
+
+#[tonic::async_trait]
+impl<T: Clone + Serialize + DeserializeOwned + Send + 'static> Evaluation for EvaluationService<T> {
+    async fn compile_simulation(&self, request: Request<CompileRequest>) -> Result<Response<CompileReply>, Status> {
+        let (compiled, chains) = oneshot::channel();
+        let jobs = spawn_simulation(Arc::clone(&self.operations), request.into_inner(), compiled);
+        let chains = chains.await.map_err(|_| Status::internal("compiling the simulation failed"))??;
+        let id = self.next_simulation.fetch_add(1, Ordering::Relaxed);
+        self.simulations.lock().expect("simulations are not poisoned").insert(id, Arc::new(CompiledSimulation { jobs }));
+        Ok(Response::new(CompileReply { simulation: id, chains }))
+    }
+
+    type EvaluateStream = ReceiverStream<Result<ChainResult, Status>>;
+
+    /// Evaluate the state in the thread of the simulation, streaming each chain result with its
+    /// chain id as soon as the chain completes. Evaluations of a simulation take turns.
+    async fn evaluate(&self, request: Request<EvaluateRequest>) -> Result<Response<Self::EvaluateStream>, Status> {
+        let request = request.into_inner();
+        let simulation = self.simulations.lock().expect("simulations are not poisoned")
+            .get(&request.simulation).cloned()
+            .ok_or_else(|| Status::not_found(format!("unknown simulation {}", request.simulation)))?;
+        let state: T = serde_json::from_str(&request.state).map_err(|err| Status::invalid_argument(err.to_string()))?;
+        let (results, receiver) = mpsc::channel(RESULT_BUFFER);
+        simulation.jobs.send(EvaluationJob { state, results })
+            .map_err(|_| Status::internal(format!("simulation {} is no longer evaluated", request.simulation)))?;
+        Ok(Response::new(ReceiverStream::new(receiver)))
+    }
+
+    async fn release_simulation(&self, request: Request<ReleaseRequest>) -> Result<Response<ReleaseReply>, Status> {
+        let simulation = request.into_inner().simulation;
+        match self.simulations.lock().expect("simulations are not poisoned").remove(&simulation) {
+            Some(_) => Ok(Response::new(ReleaseReply {})),
+            None => Err(Status::not_found(format!("unknown simulation {simulation}"))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::net::TcpListener;
+    use tokio_stream::wrappers::TcpListenerStream;
+    use tokio_stream::StreamExt;
+    use crate::configuration_utils::{ParameterMap, ParameteredOperation};
+    use crate::event_graph::prune;
+    use super::proto::evaluation_client::EvaluationClient;
+    use super::proto::{Generator, Parameters};
+    use super::*;
+
+    fn increment(val: i32, params: ParameterMap) -> i32 {
+        val + params.get("increase").unwrap().parse::<i32>().unwrap()
+    }
+
+    fn decrement(val: i32, params: ParameterMap) -> i32 {
+        val - params.get("decrease").unwrap().parse::<i32>().unwrap()
+    }
+
+    fn cap(val: i32, params: ParameterMap) -> i32 {
+        match val > params.get("limit").unwrap().parse::<i32>().unwrap() {
+            true => prune(),
+            false => val,
+        }
+    }
+
+    fn generator(generator: &str, operations: &[&str]) -> Generator {
+        Generator { generator: generator.to_string(), operations: operations.iter().map(|name| name.to_string()).collect() }
+    }
+
+    #[tokio::test]
+    async fn simulations_are_compiled_and_evaluated() {
+        let operations: OperationRegistry<i32> = HashMap::from([
+            ("increment", increment as ParameteredOperation<i32>),
+            ("decrement", decrement as ParameteredOperation<i32>),
+            ("cap", cap as ParameteredOperation<i32>)
+        ]);
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(Server::builder()
+            .add_service(EvaluationServer::new(EvaluationService::new(operations)))
+            .serve_with_incoming(TcpListenerStream::new(listener)));
+        let mut client = EvaluationClient::connect(format!("http://{address}")).await.unwrap();
+
+        let parameters = |key: &str, value: &str| Parameters { values: HashMap::from([(key.to_string(), value.to_string())]) };
+        let reply = client.compile_simulation(CompileRequest {
+            declaration: vec![generator("sequence", &["increment"]), generator("alternatives", &["increment", "decrement"])],
+            parameters: HashMap::from([
+                ("increment".to_string(), parameters("increase", "2")),
+                ("decrement".to_string(), parameters("decrease", "1")),
+            ]),
+        }).await.unwrap().into_inner();
+        assert_eq!(2, reply.chains);
+
+        let request = EvaluateRequest { simulation: reply.simulation, state: "10".to_string() };
+        let results: Vec<ChainResult> = client.evaluate(request).await.unwrap().into_inner()
+            .map(Result::unwrap).collect().await;
+        assert_eq!(vec![(0, "14"), (1, "11")], results.iter().map(|result| (result.chain, result.state.as_str())).collect::<Vec<_>>());
+        let request = EvaluateRequest { simulation: reply.simulation, state: "0".to_string() };
+        assert_eq!(2, client.evaluate(request).await.unwrap().into_inner().collect::<Vec<_>>().await.len());
+
+        let capped = client.compile_simulation(CompileRequest {
+            declaration: vec![generator("alternatives", &["increment", "decrement"]), generator("sequence", &["cap"])],
+            parameters: HashMap::from([
+                ("increment".to_string(), parameters("increase", "2")),
+                ("decrement".to_string(), parameters("decrease", "1")),
+                ("cap".to_string(), parameters("limit", "10")),
+            ]),
+        }).await.unwrap().into_inner();
+        let request = EvaluateRequest { simulation: capped.simulation, state: "10".to_string() };
+        let results: Vec<ChainResult> = client.evaluate(request).await.unwrap().into_inner()
+            .map(Result::unwrap).collect().await;
+        assert_eq!(vec![(1, "9")], results.iter().map(|result| (result.chain, result.state.as_str())).collect::<Vec<_>>());
+
+        let unknown = client.compile_simulation(CompileRequest { declaration: vec![generator("sequence", &["clearcut"])], parameters: HashMap::new() }).await;
+        assert_eq!(tonic::Code::InvalidArgument, unknown.unwrap_err().code());
+        let missing = client.evaluate(EvaluateRequest { simulation: 99, state: "10".to_string() }).await;
+        assert_eq!(tonic::Code::NotFound, missing.unwrap_err().code());
+
+        client.release_simulation(ReleaseRequest { simulation: reply.simulation }).await.unwrap();
+        let released = client.evaluate(EvaluateRequest { simulation: reply.simulation, state: "10".to_string() }).await;
+        assert_eq!(tonic::Code::NotFound, released.unwrap_err().code());
+        let released = client.release_simulation(ReleaseRequest { simulation: reply.simulation }).await;
+        assert_eq!(tonic::Code::NotFound, released.unwrap_err().code());
+    }
+}
@@ -0,0 +1,128 @@
+use std::io;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::routing::post;
+use axum::{Json, Router};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use tokio::net::TcpListener;
+use crate::batch_runner::{run_batch, EntityFailure, EntityResults};
+use crate::simulation_runner::{compile_owned, OperationRegistry, OwnedParameters};
+
+/// Body of a request to the /run endpoint: a simulation declaration over the registered
+/// operations, the operation parameters and the entities to evaluate.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct RunRequest<T> {
+    pub declaration: Vec<(String, Vec<String>)>,
+    #[serde(default)]
+    pub parameters: OwnedParameters,
+    pub entities: Vec<(String, T)>,
+}
+
+/// Body of a successful response of the /run endpoint.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RunResponse<T> {
+    pub results: EntityResults<String, T>,
+    pub failures: Vec<EntityFailure<String>>,
+}
+
+/// Body of a failed response.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ErrorResponse {
+    pub error: String,
+}
+
+type ErrorReply = (StatusCode, Json<ErrorResponse>);
+
+fn error_reply(status: StatusCode, error: String) -> ErrorReply {
+    (status, Json(ErrorResponse { error }))
+}
+
+struct ServiceState<T> {
+    operations: OperationRegistry<T>,
+}
+
+async fn run<T>(State(service): State<Arc<ServiceState<T>>>, Json(request): Json<RunRequest<T>>) -> Result<Json<RunResponse<T>>, ErrorReply>
+where T: Clone + Serialize + DeserializeOwned + Send + 'static {
+    // Event graphs are not shareable across threads, so the simulation is compiled and evaluated
+    // in a blocking thread of its own. The graph owns the names and parameters of the request,
+    // which are freed with it.
+    let evaluated = tokio::task::spawn_blocking(move || {
+        compile_owned(&service.operations, &request.parameters, &request.declaration)
+            .map(|root| run_batch(&root, request.entities))
+    }).await;
+    match evaluated {
+        Ok(Ok(outcome)) => Ok(Json(RunResponse { results: outcome.results, failures: outcome.failures })),
+        Ok(Err(err)) => Err(error_reply(StatusCode::BAD_REQUEST, err)),
+        Err(err) => Err(error_reply(StatusCode::INTERNAL_SERVER_ERROR, err.to_string())),
+    }
+}
+
+/// HTTP routes evaluating simulations over the registered operations, with states exchanged as
+/// JSON documents of T. POST /run accepts a RunRequest and replies with a RunResponse.
+pub fn router<T>(operations: OperationRegistry<T>) -> Router
+where T: Clone + Serialize + DeserializeOwned + Send + 'static {
+    let service = Arc::new(ServiceState { operations });
+    Router::new().route("/run", post(run::<T>)).with_state(service)
+}
+
+/// Serve the routes of router at the address until the server fails.
+pub async fn serve<T>(operations: OperationRegistry<T>, address: SocketAddr) -> io::Result<()>
+where T: Clone + Serialize + DeserializeOwned + Send + 'static {
+    let listener = TcpListener::bind(address).await?;
+    axum::serve(listener, router(operations)).await
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::io::{Read, Write};
+    use std::net::TcpStream;
+    use crate::configuration_utils::{ParameterMap, ParameteredOperation};
+    use super::*;
+
+    fn increment(val: i32, params: ParameterMap) -> i32 {
+        val + params.get("increase").unwrap().parse::<i32>().unwrap()
+    }
+
+    fn decrement(val: i32, params: ParameterMap) -> i32 {
+        val - params.get("decrease").unwrap().parse::<i32>().unwrap()
+    }
+
+    /// Post the body to the path, returning the status line and the response body.
+    fn post_json(address: SocketAddr, path: &str, body: &str) -> (String, String) {
+        let mut stream = TcpStream::connect(address).unwrap();
+        write!(stream, "POST {path} HTTP/1.1\r\nHost: localhost\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}", body.len()).unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        let (head, body) = response.split_once("\r\n\r\n").unwrap();
+        (head.lines().next().unwrap().to_string(), body.to_string())
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn simulations_are_run_over_http() {
+        let operations: OperationRegistry<i32> = HashMap::from([
+            ("increment", increment as ParameteredOperation<i32>),
+            ("decrement", decrement as ParameteredOperation<i32>)
+        ]);
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, router(operations)).await });
+
+        let request = r#"{
+            "declaration": [["sequence", ["increment"]], ["alternatives", ["increment", "decrement"]]],
+            "parameters": {"increment": {"increase": "2"}, "decrement": {"decrease": "1"}},
+            "entities": [["a", 10], ["b", 20]]
+        }"#;
+        let (status, body) = tokio::task::spawn_blocking(move || post_json(address, "/run", request)).await.unwrap();
+        assert_eq!("HTTP/1.1 200 OK", status);
+        assert_eq!(r#"{"results":{"a":{"0":14,"1":11},"b":{"0":24,"1":21}},"failures":[]}"#, body);
+
+        let request = r#"{"declaration": [["sequence", ["clearcut"]]], "entities": []}"#;
+        let (status, body) = tokio::task::spawn_blocking(move || post_json(address, "/run", request)).await.unwrap();
+        assert_eq!("HTTP/1.1 400 Bad Request", status);
+        assert_eq!(r#"{"error":"unknown operation 'clearcut'"}"#, body);
+    }
+}
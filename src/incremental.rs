@@ -0,0 +1,153 @@
+use std::collections::HashMap;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::rc::Rc;
+use crate::event_graph::{is_pruned, panic_message, EvaluationFailure, EventNode};
+use crate::graph_statistics::GraphStatistics;
+
+/// Cached output state of a node, by the identifier of the event chain prefix leading to the
+/// node and the revision of the node. Pruned prefixes have no state.
+type PrefixCache<T> = HashMap<(usize, u64), (usize, Option<T>)>;
+
+/// Repeated evaluation of an event graph under modification, such as in interactive
+/// experimentation. The states after each prefix of the event chains are cached, so that
+/// evaluating again only applies the operations of the nodes modified since the previous
+/// evaluation, the nodes following them and the newly attached nodes. Nodes are modified through
+/// set_operation and set_mode, and the followers of nodes may be changed freely. Assumes
+/// deterministic operations.
+pub struct IncrementalEvaluation<T> {
+    root: EventNode<T>,
+    initial_state: T,
+    cache: PrefixCache<T>,
+    next_prefix: usize,
+    applications: usize,
+}
+
+impl<T: Clone> IncrementalEvaluation<T> {
+    pub fn new(root: &EventNode<T>, initial_state: T) -> IncrementalEvaluation<T> {
+        IncrementalEvaluation { root: Rc::clone(root), initial_state, cache: HashMap::new(), next_prefix: 1, applications: 0 }
+    }
+
+    /// Evaluate from another initial state, discarding the cached states.
+    pub fn set_initial_state(&mut self, initial_state: T) {
+        self.initial_state = initial_state;
+        self.cache.clear();
+    }
+
+    /// Number of operations applied by the latest evaluation.
+    pub fn applications(&self) -> usize {
+        self.applications
+    }
+
+    /// Evaluate the unique event chains as EventDAG::try_evaluate_chains, reusing the states
+    /// cached by the previous evaluation for the unmodified prefixes of the chains.
+    pub fn evaluate(&mut self) -> Result<Vec<T>, EvaluationFailure> {
+        self.applications = 0;
+        let mut evaluation = Evaluation { results: Vec::new(), chain: 0, cache: PrefixCache::new() };
+        let root = Rc::clone(&self.root);
+        let outcome = self.visit(&root, 0, self.initial_state.clone(), 0, &mut evaluation);
+        if outcome.is_err() {
+            evaluation.cache.extend(self.cache.drain());
+        }
+        self.cache = evaluation.cache;
+        outcome.map(|_| evaluation.results)
+    }
+
+    fn visit(&mut self, node: &EventNode<T>, prefix: usize, state: T, depth: usize, evaluation: &mut Evaluation<T>) -> Result<(), EvaluationFailure> {
+        let borrowed = node.borrow();
+        let key = (prefix, borrowed.revision());
+        let cached = evaluation.cache.get(&key).cloned().or_else(|| self.cache.remove(&key));
+        let (id, output) = match cached {
+            Some(cached) => cached,
+            None => {
+                self.applications += 1;
+                let output = match catch_unwind(AssertUnwindSafe(|| borrowed.apply(state))) {
+                    Ok(output) => Some(output),
+                    Err(payload) if is_pruned(&*payload) => None,
+                    Err(payload) => return Err(EvaluationFailure { chain: evaluation.chain, operation: depth, label: borrowed.label().map(str::to_string), error: panic_message(payload) }),
+                };
+                self.next_prefix += 1;
+                (self.next_prefix, output)
+            }
+        };
+        evaluation.cache.insert(key, (id, output.clone()));
+        match output {
+            None => evaluation.chain += GraphStatistics::of(node).chains,
+            Some(output) if borrowed.followers().is_empty() => {
+                evaluation.results.push(output);
+                evaluation.chain += 1;
+            }
+            Some(output) => {
+                for follower in borrowed.evaluation_order() {
+                    self.visit(&follower, id, output.clone(), depth + 1, evaluation)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Progress of a single evaluation.
+struct Evaluation<T> {
+    results: Vec<T>,
+    chain: usize,
+    cache: PrefixCache<T>,
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::branching_generators::{alternatives, sequence};
+    use crate::event_graph::{prune, BoxedOperation, EventDAG, NodeMode};
+    use super::*;
+
+    fn ops(count: usize) -> Vec<BoxedOperation<i32>> {
+        (0..count).map(|_| -> BoxedOperation<i32> { Box::new(|x| x + 1) }).collect()
+    }
+
+    #[test]
+    fn unmodified_prefixes_are_reused() {
+        let root = EventDAG::new_node(Box::new(|x: i32| x));
+        let first = sequence(vec![root.clone()], ops(2)).unwrap();
+        let branches = alternatives(first.clone(), ops(2)).unwrap();
+        sequence(branches.clone(), ops(1)).unwrap();
+        let mut evaluation = IncrementalEvaluation::new(&root, 0);
+        assert_eq!(Ok(vec![4, 4]), evaluation.evaluate());
+        assert_eq!(7, evaluation.applications());
+        assert_eq!(Ok(vec![4, 4]), evaluation.evaluate());
+        assert_eq!(0, evaluation.applications());
+
+        branches[1].borrow_mut().set_operation(Box::new(|x| x * 10));
+        assert_eq!(Ok(vec![4, 21]), evaluation.evaluate());
+        assert_eq!(2, evaluation.applications());
+        assert_eq!(EventDAG::evaluate_chains(&root, 0), evaluation.evaluate().unwrap());
+
+        alternatives(first, ops(1)).unwrap();
+        assert_eq!(Ok(vec![4, 21, 3]), evaluation.evaluate());
+        assert_eq!(1, evaluation.applications());
+
+        branches[1].borrow_mut().set_priority(1);
+        assert_eq!(Ok(vec![21, 4, 3]), evaluation.evaluate());
+        assert_eq!(0, evaluation.applications());
+
+        evaluation.set_initial_state(1);
+        assert_eq!(Ok(vec![31, 5, 4]), evaluation.evaluate());
+        assert_eq!(8, evaluation.applications());
+    }
+
+    #[test]
+    fn pruning_and_failures_are_evaluated() {
+        let root = EventDAG::new_node(Box::new(|x: i32| x));
+        let branches = alternatives(vec![root.clone()], ops(3)).unwrap();
+        let mut evaluation = IncrementalEvaluation::new(&root, 0);
+        assert_eq!(Ok(vec![1, 1, 1]), evaluation.evaluate());
+        branches[0].borrow_mut().set_mode(NodeMode::Pruned);
+        assert_eq!(Ok(vec![1, 1]), evaluation.evaluate());
+        assert_eq!(1, evaluation.applications());
+
+        branches[2].borrow_mut().set_operation(Box::new(|x| if x == 0 { panic!("zero") } else { x }));
+        let failure = evaluation.evaluate().unwrap_err();
+        assert_eq!(EvaluationFailure { chain: 2, operation: 1, label: None, error: "zero".to_string() }, failure);
+        branches[2].borrow_mut().set_operation(Box::new(|x| if x == 0 { prune() } else { x }));
+        assert_eq!(Ok(vec![1]), evaluation.evaluate());
+        assert_eq!(1, evaluation.applications());
+    }
+}
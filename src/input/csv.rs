@@ -0,0 +1,241 @@
+use std::io::Read;
+use std::marker::PhantomData;
+use serde::de::value::{Error as ValueError, MapDeserializer};
+use serde::de::{DeserializeOwned, Deserializer, Error as _, IntoDeserializer, Visitor};
+use serde::forward_to_deserialize_any;
+use super::{Entities, InputError, InputReader};
+
+/// CsvReader reads entities from CSV with a header row. Each row is deserialized into the state
+/// by column name, the entity id being read from the id column. Fields are parsed into the
+/// types requested by the state, an empty field reading as None for optional values. A state
+/// which is not a struct or map is read from the single column other than the id column.
+pub struct CsvReader<R, K, T> {
+    reader: R,
+    id_column: String,
+    entities: PhantomData<(K, T)>,
+}
+
+impl<R: Read, K: DeserializeOwned, T: DeserializeOwned> CsvReader<R, K, T> {
+    pub fn new(reader: R, id_column: &str) -> CsvReader<R, K, T> {
+        CsvReader { reader, id_column: id_column.to_string(), entities: PhantomData }
+    }
+}
+
+impl<R: Read, K: DeserializeOwned, T: DeserializeOwned> InputReader<K, T> for CsvReader<R, K, T> {
+    fn read_entities(&mut self) -> Result<Entities<K, T>, InputError> {
+        let mut text = String::new();
+        self.reader.read_to_string(&mut text)?;
+        let mut records = parse_records(strip_comments(&text)).into_iter();
+        let columns = records.next().unwrap_or_default();
+        let id = columns.iter().position(|column| *column == self.id_column);
+        records.enumerate().map(|(index, fields)| {
+            let record = index + 1;
+            let error = |err: ValueError| InputError::Record { record, message: err.to_string() };
+            if fields.len() != columns.len() {
+                let message = format!("expected {} fields, found {}", columns.len(), fields.len());
+                return Err(InputError::Record { record, message });
+            }
+            let id = id.ok_or_else(|| InputError::MissingId { record, id: self.id_column.clone() })?;
+            let entity = K::deserialize(Field(&fields[id])).map_err(error)?;
+            let state = T::deserialize(Row { columns: &columns, fields: &fields, id }).map_err(error)?;
+            Ok((entity, state))
+        }).collect()
+    }
+}
+
+/// CSV text following the leading `#` comment lines, such as the run metadata of written outputs.
+fn strip_comments(text: &str) -> &str {
+    let mut rest = text;
+    while rest.starts_with('#') {
+        rest = rest.split_once('\n').map_or("", |(_, rest)| rest);
+    }
+    rest
+}
+
+/// Split CSV text into records of fields, honouring quoted fields. Blank lines are skipped.
+fn parse_records(text: &str) -> Vec<Vec<String>> {
+    let mut records = Vec::new();
+    let mut record = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                chars.next();
+                field.push('"');
+            }
+            '"' if quoted => quoted = false,
+            '"' if field.is_empty() => quoted = true,
+            ',' if !quoted => record.push(std::mem::take(&mut field)),
+            '\r' if !quoted && chars.peek() == Some(&'\n') => {}
+            '\n' if !quoted => {
+                if !record.is_empty() || !field.is_empty() {
+                    record.push(std::mem::take(&mut field));
+                    records.push(std::mem::take(&mut record));
+                }
+            }
+            c => field.push(c),
+        }
+    }
+    if !record.is_empty() || !field.is_empty() {
+        record.push(field);
+        records.push(record);
+    }
+    records
+}
+
+/// Deserializer of a single CSV field, parsing the field into the requested type.
+struct Field<'a>(&'a str);
+
+macro_rules! parsed {
+    ($($method:ident => $visit:ident),*) => {
+        $(
+            fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, ValueError> {
+                match self.0.trim().parse() {
+                    Ok(value) => visitor.$visit(value),
+                    Err(err) => Err(ValueError::custom(format!("invalid value '{}': {err}", self.0))),
+                }
+            }
+        )*
+    };
+}
+
+impl<'de> Deserializer<'de> for Field<'_> {
+    type Error = ValueError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, ValueError> {
+        if let Ok(value) = self.0.parse::<bool>() {
+            visitor.visit_bool(value)
+        } else if let Ok(value) = self.0.parse::<i64>() {
+            visitor.visit_i64(value)
+        } else if let Ok(value) = self.0.parse::<f64>() {
+            visitor.visit_f64(value)
+        } else if self.0.is_empty() {
+            visitor.visit_unit()
+        } else {
+            visitor.visit_str(self.0)
+        }
+    }
+
+    parsed!(deserialize_bool => visit_bool, deserialize_i8 => visit_i8, deserialize_i16 => visit_i16,
+        deserialize_i32 => visit_i32, deserialize_i64 => visit_i64, deserialize_u8 => visit_u8,
+        deserialize_u16 => visit_u16, deserialize_u32 => visit_u32, deserialize_u64 => visit_u64,
+        deserialize_f32 => visit_f32, deserialize_f64 => visit_f64, deserialize_char => visit_char);
+
+    fn deserialize_str<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, ValueError> {
+        visitor.visit_str(self.0)
+    }
+
+    fn deserialize_string<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, ValueError> {
+        visitor.visit_str(self.0)
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, ValueError> {
+        if self.0.is_empty() { visitor.visit_none() } else { visitor.visit_some(self) }
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(self, _name: &'static str, visitor: V) -> Result<V::Value, ValueError> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(self, name: &'static str, variants: &'static [&'static str], visitor: V) -> Result<V::Value, ValueError> {
+        self.0.into_deserializer().deserialize_enum(name, variants, visitor)
+    }
+
+    forward_to_deserialize_any! { i128 u128 bytes byte_buf unit unit_struct seq tuple tuple_struct map struct identifier ignored_any }
+}
+
+impl<'de, 'a> IntoDeserializer<'de, ValueError> for Field<'a> {
+    type Deserializer = Field<'a>;
+
+    fn into_deserializer(self) -> Field<'a> {
+        self
+    }
+}
+
+/// Deserializer of a CSV row, as a map of column names to fields or as its single value field.
+struct Row<'a> {
+    columns: &'a [String],
+    fields: &'a [String],
+    id: usize,
+}
+
+impl Row<'_> {
+    fn value(&self) -> Result<Field<'_>, ValueError> {
+        let mut values = self.fields.iter().enumerate().filter(|(index, _)| *index != self.id);
+        match (values.next(), values.next()) {
+            (Some((_, value)), None) => Ok(Field(value)),
+            _ => Err(ValueError::custom("expected a single value column")),
+        }
+    }
+}
+
+macro_rules! single_value {
+    ($($method:ident),*) => {
+        $(
+            fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, ValueError> {
+                self.value()?.$method(visitor)
+            }
+        )*
+    };
+}
+
+impl<'de> Deserializer<'de> for Row<'_> {
+    type Error = ValueError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, ValueError> {
+        let entries = self.columns.iter().map(String::as_str).zip(self.fields.iter().map(|field| Field(field)));
+        visitor.visit_map(MapDeserializer::new(entries))
+    }
+
+    single_value!(deserialize_bool, deserialize_i8, deserialize_i16, deserialize_i32, deserialize_i64,
+        deserialize_u8, deserialize_u16, deserialize_u32, deserialize_u64, deserialize_f32, deserialize_f64,
+        deserialize_char, deserialize_str, deserialize_string, deserialize_option);
+
+    forward_to_deserialize_any! { i128 u128 bytes byte_buf unit unit_struct newtype_struct seq tuple tuple_struct map struct enum identifier ignored_any }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::Deserialize;
+    use super::*;
+
+    #[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+    enum Species { Pine, Spruce }
+
+    #[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+    struct Stand { age: i32, volume: f64, species: Species, site: Option<u8> }
+
+    #[test]
+    fn rows_are_deserialized_by_column() {
+        let input = "id,volume,age,species,site\r\nA1,120.5,40,Pine,3\r\n\r\n\"A,2\",80,25,Spruce,\r\n";
+        let entities: Entities<String, Stand> = CsvReader::new(input.as_bytes(), "id").read_entities().unwrap();
+        assert_eq!(vec![
+            ("A1".to_string(), Stand { age: 40, volume: 120.5, species: Species::Pine, site: Some(3) }),
+            ("A,2".to_string(), Stand { age: 25, volume: 80.0, species: Species::Spruce, site: None })
+        ], entities);
+    }
+
+    #[test]
+    fn single_values_are_deserialized() {
+        let input = "value,entity\n10,1\n20,2";
+        let entities: Entities<u32, i32> = CsvReader::new(input.as_bytes(), "entity").read_entities().unwrap();
+        assert_eq!(vec![(1, 10), (2, 20)], entities);
+
+        let commented = "# run_id: run-1\n# seed: 42\nvalue,entity\n10,1";
+        let entities: Entities<u32, i32> = CsvReader::new(commented.as_bytes(), "entity").read_entities().unwrap();
+        assert_eq!(vec![(1, 10)], entities);
+    }
+
+    #[test]
+    fn invalid_rows_are_reported() {
+        let input = "id,value\n1,10\n2,ten";
+        let result: Result<Entities<u32, i32>, InputError> = CsvReader::new(input.as_bytes(), "id").read_entities();
+        assert_eq!("record 2: invalid value 'ten': invalid digit found in string", result.unwrap_err().to_string());
+        let result: Result<Entities<u32, i32>, InputError> = CsvReader::new(input.as_bytes(), "stand").read_entities();
+        assert!(matches!(result, Err(InputError::MissingId { record: 1, .. })));
+        let result: Result<Entities<u32, i32>, InputError> = CsvReader::new("id,value\n1".as_bytes(), "id").read_entities();
+        assert!(matches!(result, Err(InputError::Record { record: 1, .. })));
+    }
+}
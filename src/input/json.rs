@@ -0,0 +1,95 @@
+use std::io::Read;
+use std::marker::PhantomData;
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use super::{Entities, InputError, InputReader};
+
+/// JsonReader reads entities from a JSON array of objects or from JSON Lines, one object per
+/// line. Each object is deserialized into the state, the entity id being read from the id
+/// field. A state which is not a struct or map is read from the single field other than the id.
+pub struct JsonReader<R, K, T> {
+    reader: R,
+    id_field: String,
+    entities: PhantomData<(K, T)>,
+}
+
+impl<R: Read, K: DeserializeOwned, T: DeserializeOwned> JsonReader<R, K, T> {
+    pub fn new(reader: R, id_field: &str) -> JsonReader<R, K, T> {
+        JsonReader { reader, id_field: id_field.to_string(), entities: PhantomData }
+    }
+
+    fn read_entity(&self, record: usize, value: Value) -> Result<(K, T), InputError> {
+        let error = |err: serde_json::Error| InputError::Record { record, message: err.to_string() };
+        let id = value.get(&self.id_field)
+            .ok_or_else(|| InputError::MissingId { record, id: self.id_field.clone() })?;
+        let entity = K::deserialize(id).map_err(error)?;
+        let state = match T::deserialize(&value) {
+            Ok(state) => state,
+            Err(err) => {
+                let mut values = value.as_object().into_iter().flatten().filter(|(field, _)| **field != self.id_field);
+                match (values.next(), values.next()) {
+                    (Some((_, single)), None) => T::deserialize(single).map_err(error)?,
+                    _ => return Err(error(err)),
+                }
+            }
+        };
+        Ok((entity, state))
+    }
+}
+
+impl<R: Read, K: DeserializeOwned, T: DeserializeOwned> InputReader<K, T> for JsonReader<R, K, T> {
+    fn read_entities(&mut self) -> Result<Entities<K, T>, InputError> {
+        let mut text = String::new();
+        self.reader.read_to_string(&mut text)?;
+        let values: Vec<Value> = if text.trim_start().starts_with('[') {
+            serde_json::from_str(&text).map_err(|err| InputError::Record { record: 1, message: err.to_string() })?
+        } else {
+            serde_json::Deserializer::from_str(&text).into_iter::<Value>()
+                .enumerate()
+                .map(|(index, value)| value.map_err(|err| InputError::Record { record: index + 1, message: err.to_string() }))
+                .collect::<Result<Vec<Value>, InputError>>()?
+        };
+        values.into_iter().enumerate().map(|(index, value)| self.read_entity(index + 1, value)).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::Deserialize;
+    use super::*;
+
+    #[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+    struct Stand { id: u32, age: i32, volume: f64 }
+
+    #[test]
+    fn arrays_and_lines_are_read() {
+        let array = r#"[{"id": 1, "age": 40, "volume": 120.5}, {"id": 2, "age": 25, "volume": 80.0}]"#;
+        let lines = "{\"id\": 1, \"age\": 40, \"volume\": 120.5}\n\n{\"id\": 2, \"age\": 25, \"volume\": 80.0}\n";
+        let expected = vec![
+            (1, Stand { id: 1, age: 40, volume: 120.5 }),
+            (2, Stand { id: 2, age: 25, volume: 80.0 })
+        ];
+        let entities: Entities<u32, Stand> = JsonReader::new(array.as_bytes(), "id").read_entities().unwrap();
+        assert_eq!(expected, entities);
+        let entities: Entities<u32, Stand> = JsonReader::new(lines.as_bytes(), "id").read_entities().unwrap();
+        assert_eq!(expected, entities);
+    }
+
+    #[test]
+    fn single_values_are_read() {
+        let input = r#"{"entity": "a", "value": 10}
+{"entity": "b", "value": 20}"#;
+        let entities: Entities<String, i32> = JsonReader::new(input.as_bytes(), "entity").read_entities().unwrap();
+        assert_eq!(vec![("a".to_string(), 10), ("b".to_string(), 20)], entities);
+    }
+
+    #[test]
+    fn invalid_records_are_reported() {
+        let input = r#"{"id": 1, "age": 40, "volume": 120.5}
+{"id": 2, "age": "old", "volume": 80.0}"#;
+        let result: Result<Entities<u32, Stand>, InputError> = JsonReader::new(input.as_bytes(), "id").read_entities();
+        assert!(matches!(result, Err(InputError::Record { record: 2, .. })));
+        let result: Result<Entities<u32, Stand>, InputError> = JsonReader::new(input.as_bytes(), "stand").read_entities();
+        assert!(matches!(result, Err(InputError::MissingId { record: 1, .. })));
+    }
+}
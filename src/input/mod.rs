@@ -0,0 +1,52 @@
+pub mod csv;
+pub mod json;
+
+use std::error::Error;
+use std::fmt;
+use std::io;
+
+/// Initial states of entities keyed by entity id, in input order, as consumed by the batch
+/// runners.
+pub type Entities<K, T> = Vec<(K, T)>;
+
+/// Failure reading initial states of entities.
+#[derive(Debug)]
+pub enum InputError {
+    Io(io::Error),
+    /// The record at the given 1-based position could not be deserialized.
+    Record { record: usize, message: String },
+    /// The record at the given 1-based position has no value for the entity id.
+    MissingId { record: usize, id: String },
+}
+
+impl fmt::Display for InputError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InputError::Io(err) => write!(f, "{err}"),
+            InputError::Record { record, message } => write!(f, "record {record}: {message}"),
+            InputError::MissingId { record, id } => write!(f, "record {record}: missing entity id '{id}'"),
+        }
+    }
+}
+
+impl Error for InputError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            InputError::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for InputError {
+    fn from(err: io::Error) -> Self {
+        InputError::Io(err)
+    }
+}
+
+/// InputReader reads the initial states of entities for a batch run. Implement it for input
+/// formats other than the built-in CSV and JSON readers.
+pub trait InputReader<K, T> {
+    /// Read all entities of the input, keyed by their entity id.
+    fn read_entities(&mut self) -> Result<Entities<K, T>, InputError>;
+}
@@ -1,6 +1,73 @@
 #![crate_type = "lib"]
 #![crate_name = "metsi_rust"]
 
+extern crate self as metsi_rust;
+
 pub mod event_graph;
 pub mod branching_generators;
 pub mod configuration_utils;
+pub mod simulation_runner;
+pub mod time_points;
+pub mod batch_runner;
+pub mod collectors;
+pub mod output;
+pub mod input;
+pub mod stable_hash;
+pub mod state_hash;
+pub mod manifest;
+pub mod provenance;
+pub mod rng;
+pub mod stochastic;
+pub mod ops;
+pub mod graph_statistics;
+pub mod graph_rewriting;
+pub mod graph_diff;
+pub mod graph_fingerprint;
+pub mod lint;
+pub mod incremental;
+pub mod warm_start;
+pub mod stepwise;
+pub mod evaluation_strategy;
+pub mod streaming;
+pub mod operation_cache;
+pub mod classification;
+pub mod probability;
+pub mod decisions;
+pub mod decision_log;
+pub mod snapshots;
+pub mod pareto;
+pub mod sweep;
+pub mod optimization;
+pub mod search;
+pub mod scheduler;
+pub mod cli;
+pub mod run_summary;
+pub mod metrics;
+pub mod checkpoint;
+pub mod post_processing;
+pub mod golden;
+pub mod run_comparison;
+pub mod replication;
+pub mod external_model;
+#[cfg(feature = "forestry")]
+pub mod forestry;
+#[cfg(feature = "lua")]
+pub mod lua;
+#[cfg(feature = "rhai")]
+pub mod rhai;
+#[cfg(feature = "plugins")]
+pub mod plugins;
+#[cfg(feature = "expressions")]
+pub mod expressions;
+#[cfg(feature = "grpc")]
+pub mod grpc;
+#[cfg(feature = "http")]
+pub mod http;
+#[cfg(feature = "yaml")]
+pub mod control;
+#[cfg(feature = "yaml")]
+pub mod control_schema;
+#[cfg(feature = "python")]
+pub mod python;
+#[cfg(feature = "ffi")]
+pub mod ffi;
@@ -0,0 +1,119 @@
+use std::collections::{BTreeSet, HashSet};
+use std::fmt;
+use std::rc::Rc;
+use crate::event_graph::{EventDAG, EventNode, NodeMode};
+use crate::graph_statistics::GraphStatistics;
+
+/// Default number of event chains above which a configuration is warned about.
+pub const CHAIN_COUNT_WARNING: usize = 100_000;
+
+/// Warning about a configuration which compiles but likely does not do what was intended.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Lint {
+    /// Parameters are given for an operation never referenced by the declaration.
+    UnusedParameters(&'static str),
+    /// A registered operation is never referenced by the declaration.
+    UnreferencedOperation(&'static str),
+    /// Nodes of the event graph only reachable through pruned or zero-probability nodes.
+    UnreachableNodes(usize),
+    /// The event graph has more event chains than the limit.
+    LargeChainCount { chains: usize, limit: usize },
+}
+
+impl fmt::Display for Lint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Lint::UnusedParameters(name) => write!(f, "parameters given for operation '{name}' which is never referenced"),
+            Lint::UnreferencedOperation(name) => write!(f, "operation '{name}' is registered but never referenced"),
+            Lint::UnreachableNodes(nodes) => write!(f, "{nodes} nodes are unreachable through pruned or zero-probability branches"),
+            Lint::LargeChainCount { chains, limit } => write!(f, "{chains} event chains exceed the warning limit of {limit}"),
+        }
+    }
+}
+
+/// Warn about the operations registered or given parameters but never referenced, in name order.
+pub fn lint_references<'a>(
+    registered: impl IntoIterator<Item = &'a &'static str>,
+    parameterized: impl IntoIterator<Item = &'a &'static str>,
+    referenced: &[&'static str]
+) -> Vec<Lint> {
+    let unreferenced = |names: BTreeSet<&'static str>| names.into_iter().filter(|name| !referenced.contains(name)).collect::<Vec<_>>();
+    let registered: BTreeSet<&'static str> = registered.into_iter().copied().collect();
+    let parameterized: BTreeSet<&'static str> = parameterized.into_iter().copied().filter(|name| registered.contains(name)).collect();
+    let mut lints: Vec<Lint> = unreferenced(parameterized).into_iter().map(Lint::UnusedParameters).collect();
+    lints.extend(unreferenced(registered).into_iter().map(Lint::UnreferencedOperation));
+    lints
+}
+
+/// Warn about the unreachable branches of the event graph and about its chain count exceeding
+/// the given limit.
+pub fn lint_graph<T: Clone>(root: &EventNode<T>, chain_limit: usize) -> Vec<Lint> {
+    let mut lints = Vec::new();
+    let unreachable = unreachable_nodes(root);
+    if unreachable > 0 {
+        lints.push(Lint::UnreachableNodes(unreachable));
+    }
+    let chains = GraphStatistics::of(root).chains;
+    if chains > chain_limit {
+        lints.push(Lint::LargeChainCount { chains, limit: chain_limit });
+    }
+    lints
+}
+
+/// Whether event chains can pass through the node.
+fn passable<T: Clone>(node: &EventNode<T>) -> bool {
+    let node = node.borrow();
+    node.mode() != NodeMode::Pruned && node.probability() > 0.0
+}
+
+/// Number of nodes of the event graph reached only through nodes which are pruned or have a
+/// zero probability, including those nodes.
+pub fn unreachable_nodes<T: Clone>(root: &EventNode<T>) -> usize {
+    let mut reached: HashSet<*const ()> = HashSet::new();
+    let mut stack: Vec<EventNode<T>> = vec![Rc::clone(root)];
+    while let Some(node) = stack.pop() {
+        if passable(&node) && reached.insert(node.as_ptr() as *const ()) {
+            stack.extend(node.borrow().followers().iter().cloned());
+        }
+    }
+    EventDAG::reachable_nodes(&[Rc::clone(root)]).len() - reached.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::branching_generators::{alternatives, sequence};
+    use crate::event_graph::BoxedOperation;
+    use super::*;
+
+    fn add(amount: i32) -> BoxedOperation<i32> {
+        Box::new(move |x| x + amount)
+    }
+
+    #[test]
+    fn unreachable_branches_and_large_graphs_are_warned_about() {
+        let root = EventDAG::new_node(add(0));
+        let branches = alternatives(vec![Rc::clone(&root)], [add(1), add(2), add(3)]).unwrap();
+        let ends = sequence(branches.clone(), [add(10), add(20)]).unwrap();
+        alternatives(ends, [add(1), add(2)]).unwrap();
+        assert_eq!(Vec::<Lint>::new(), lint_graph(&root, 6));
+        assert_eq!(vec![Lint::LargeChainCount { chains: 6, limit: 5 }], lint_graph(&root, 5));
+
+        branches[0].borrow_mut().set_mode(NodeMode::Pruned);
+        branches[1].borrow_mut().set_probability(0.0);
+        // The sequence joining the branches stays reachable through the third branch.
+        assert_eq!(vec![Lint::UnreachableNodes(2)], lint_graph(&root, 6));
+        branches[2].borrow_mut().set_mode(NodeMode::Pruned);
+        assert_eq!(7, unreachable_nodes(&root));
+    }
+
+    #[test]
+    fn unreferenced_operations_are_warned_about() {
+        let lints = lint_references(&["grow", "thin", "clearcut"], &["thin", "grow", "fertilize"], &["grow"]);
+        assert_eq!(vec![
+            Lint::UnusedParameters("thin"),
+            Lint::UnreferencedOperation("clearcut"),
+            Lint::UnreferencedOperation("thin"),
+        ], lints);
+        assert_eq!("parameters given for operation 'thin' which is never referenced", lints[0].to_string());
+    }
+}
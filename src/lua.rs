@@ -0,0 +1,194 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::panic::resume_unwind;
+use std::path::Path;
+use mlua::{Function, Lua, LuaSerdeExt};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use crate::branching_generators::generator_map;
+use crate::configuration_utils::ParameterMap;
+use crate::event_graph::{BoxedOperation, EventDAG, EventNode, OperationChain};
+use crate::simulation_runner::{OperationParameters, RunnerError, SimulationDeclaration};
+
+/// Reasons for failing to load Lua operations or to compile a declaration over them.
+#[derive(Debug)]
+pub enum ScriptError {
+    Io(io::Error),
+    /// The script of the operation failed to load or did not evaluate into a function.
+    Lua { operation: String, error: mlua::Error },
+    Runner(RunnerError),
+}
+
+impl fmt::Display for ScriptError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ScriptError::Io(err) => write!(f, "{err}"),
+            ScriptError::Lua { operation, error } => write!(f, "script of operation '{operation}': {error}"),
+            ScriptError::Runner(err) => write!(f, "{err}"),
+        }
+    }
+}
+
+impl Error for ScriptError {}
+
+impl From<io::Error> for ScriptError {
+    fn from(err: io::Error) -> Self {
+        ScriptError::Io(err)
+    }
+}
+
+impl From<RunnerError> for ScriptError {
+    fn from(err: RunnerError) -> Self {
+        ScriptError::Runner(err)
+    }
+}
+
+/// Operations defined as Lua scripts. Each script evaluates into a function taking the state and
+/// a table of the operation parameters and returning the resulting state, such as
+///
+/// return function(state, parameters)
+///     state.volume = state.volume + tonumber(parameters.increase)
+///     return state
+/// end
+///
+/// States are passed into Lua through their serde representation.
+pub struct LuaOperations {
+    lua: Lua,
+    functions: HashMap<String, Function>,
+}
+
+impl LuaOperations {
+    /// Load the scripts given by operation name.
+    pub fn new<S: AsRef<str>>(scripts: &HashMap<&str, S>) -> Result<LuaOperations, ScriptError> {
+        let lua = Lua::new();
+        let functions = scripts.iter()
+            .map(|(name, script)| {
+                let function = lua.load(script.as_ref()).set_name(*name).eval::<Function>()
+                    .map_err(|error| ScriptError::Lua { operation: name.to_string(), error })?;
+                Ok((name.to_string(), function))
+            })
+            .collect::<Result<HashMap<String, Function>, ScriptError>>()?;
+        Ok(LuaOperations { lua, functions })
+    }
+
+    /// Load the .lua files of the directory, each defining the operation named by its file stem.
+    pub fn from_directory<P: AsRef<Path>>(directory: P) -> Result<LuaOperations, ScriptError> {
+        let mut scripts = HashMap::new();
+        for entry in fs::read_dir(directory)? {
+            let path = entry?.path();
+            if path.extension().is_some_and(|extension| extension == "lua") {
+                if let Some(name) = path.file_stem().and_then(|stem| stem.to_str()) {
+                    scripts.insert(name.to_string(), fs::read_to_string(&path)?);
+                }
+            }
+        }
+        LuaOperations::new(&scripts.iter().map(|(name, script)| (name.as_str(), script)).collect())
+    }
+
+    /// Names of the loaded operations.
+    pub fn names(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = self.functions.keys().map(String::as_str).collect();
+        names.sort_unstable();
+        names
+    }
+
+    /// Bind the parameters into the named operation. A Lua error raised by the script fails the
+    /// event chain with the error as its message.
+    pub fn operation<T>(&self, name: &'static str, parameters: ParameterMap) -> Result<BoxedOperation<T>, ScriptError>
+    where T: Serialize + DeserializeOwned + 'static {
+        let function = self.functions.get(name).ok_or(RunnerError::UnknownOperation(name))?.clone();
+        let lua = self.lua.clone();
+        let parameters = lua.create_table_from(parameters)
+            .map_err(|error| ScriptError::Lua { operation: name.to_string(), error })?;
+        Ok(Box::new(move |state: T| {
+            let called = lua.to_value(&state)
+                .and_then(|state| function.call((state, &parameters)))
+                .and_then(|result| lua.from_value(result));
+            match called {
+                Ok(result) => result,
+                Err(err) => resume_unwind(Box::new(err.to_string())),
+            }
+        }))
+    }
+
+    /// Compile the declaration over the loaded operations into an event graph.
+    pub fn compile<T>(&self, declaration: &SimulationDeclaration, parameters: &OperationParameters) -> Result<EventNode<T>, ScriptError>
+    where T: Clone + Serialize + DeserializeOwned + 'static {
+        let generators = generator_map::<T>();
+        let root: EventNode<T> = EventDAG::new_node(Box::new(|state| state));
+        let mut nodes = vec![root.clone()];
+        for (generator_name, operation_names) in declaration {
+            let generator_fn = generators.get(generator_name).ok_or(RunnerError::UnknownGenerator(generator_name))?;
+            let chain = operation_names.iter()
+                .map(|name| self.operation(name, parameters.get(name).cloned().unwrap_or_default()))
+                .collect::<Result<OperationChain<T>, ScriptError>>()?;
+            nodes = generator_fn(nodes, chain).map_err(RunnerError::from)?;
+        }
+        Ok(root)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::Deserialize;
+    use super::*;
+
+    #[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+    struct Stand {
+        age: u32,
+        volume: f64,
+    }
+
+    const GROW: &str = "
+        return function(stand, parameters)
+            stand.age = stand.age + 5
+            stand.volume = stand.volume + tonumber(parameters.increase)
+            return stand
+        end";
+
+    const THIN: &str = "
+        return function(stand, parameters)
+            if stand.volume < 20 then error('too little volume') end
+            stand.volume = stand.volume / 2
+            return stand
+        end";
+
+    #[test]
+    fn lua_operations_are_compiled() {
+        let operations = LuaOperations::new(&HashMap::from([("grow", GROW), ("thin", THIN)])).unwrap();
+        assert_eq!(vec!["grow", "thin"], operations.names());
+        let declaration = vec![("sequence", vec!["grow"]), ("alternatives", vec!["grow", "thin"])];
+        let parameters = OperationParameters::from([("grow", ParameterMap::from([("increase", "10")]))]);
+        let root = operations.compile::<Stand>(&declaration, &parameters).unwrap();
+
+        let results = EventDAG::try_evaluate_chains(&root, Stand { age: 0, volume: 15.0 }).unwrap();
+        assert_eq!(vec![Stand { age: 10, volume: 35.0 }, Stand { age: 5, volume: 12.5 }], results);
+
+        let failure = EventDAG::try_evaluate_chains(&root, Stand { age: 0, volume: 0.0 }).unwrap_err();
+        assert_eq!((1, 2), (failure.chain, failure.operation));
+        assert!(failure.error.contains("too little volume"));
+    }
+
+    #[test]
+    fn script_errors_are_reported() {
+        let failed = LuaOperations::new(&HashMap::from([("grow", "return 1 +")]));
+        assert!(matches!(failed, Err(ScriptError::Lua { operation, .. }) if operation == "grow"));
+
+        let operations = LuaOperations::new(&HashMap::from([("grow", GROW)])).unwrap();
+        let compiled = operations.compile::<Stand>(&vec![("sequence", vec!["clearcut"])], &OperationParameters::new());
+        assert!(matches!(compiled, Err(ScriptError::Runner(RunnerError::UnknownOperation("clearcut")))));
+    }
+
+    #[test]
+    fn scripts_are_loaded_from_directory() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let directory = tempdir.path();
+        fs::write(directory.join("grow.lua"), GROW).unwrap();
+        fs::write(directory.join("notes.txt"), "not a script").unwrap();
+        let operations = LuaOperations::from_directory(directory).unwrap();
+        assert_eq!(vec!["grow"], operations.names());
+    }
+}
@@ -0,0 +1,170 @@
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::hash::Hash;
+use std::io::{BufWriter, Result, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use serde::{Deserialize, Serialize};
+use crate::simulation_runner::{OperationParameters, SimulationDeclaration};
+use crate::stable_hash::StableHasher;
+
+pub type OperationVersions = BTreeMap<&'static str, &'static str>;
+
+/// RunManifest records what is needed to trace simulation outputs back to the exact simulation
+/// definition that produced them.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RunManifest {
+    pub crate_version: String,
+    pub config_hash: String,
+    /// Versions of the registered operations, "unversioned" when no version was declared.
+    pub operation_versions: BTreeMap<String, String>,
+    pub seeds: Vec<u64>,
+    /// Unix timestamp in seconds of starting the run.
+    pub started_at: u64,
+    /// Unix timestamp in seconds of finishing the run.
+    pub finished_at: Option<u64>,
+}
+
+/// Metadata identifying the run which produced an output, for embedding into the output so that
+/// it remains interpretable when copied apart from its manifest.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RunMetadata {
+    pub run_id: String,
+    pub config_hash: String,
+    pub seed: Option<u64>,
+    pub crate_version: String,
+}
+
+impl RunMetadata {
+    /// Construct metadata of a run of this crate version.
+    pub fn new(run_id: &str, config_hash: String, seed: Option<u64>) -> RunMetadata {
+        RunMetadata { run_id: run_id.to_string(), config_hash, seed, crate_version: env!("CARGO_PKG_VERSION").to_string() }
+    }
+
+    /// Metadata entries as keys and values, an unknown seed being left out.
+    pub fn entries(&self) -> Vec<(&'static str, String)> {
+        let mut entries = vec![("run_id", self.run_id.clone()), ("config_hash", self.config_hash.clone())];
+        entries.extend(self.seed.map(|seed| ("seed", seed.to_string())));
+        entries.push(("crate_version", self.crate_version.clone()));
+        entries
+    }
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |elapsed| elapsed.as_secs())
+}
+
+/// Stable hash of a simulation declaration and operation parameters, independent of the
+/// iteration order of the parameter maps. Sections are hashed with their lengths, so that the
+/// boundaries between operations and their parameters affect the hash.
+pub fn config_hash(declaration: &SimulationDeclaration, parameters: &OperationParameters) -> String {
+    let mut hasher = StableHasher::new();
+    declaration.len().hash(&mut hasher);
+    for (generator, operations) in declaration {
+        generator.hash(&mut hasher);
+        operations.hash(&mut hasher);
+    }
+    let sorted: BTreeMap<_, BTreeMap<_, _>> = parameters.iter()
+        .map(|(operation, params)| (operation, params.iter().collect()))
+        .collect();
+    sorted.len().hash(&mut hasher);
+    for (operation, params) in sorted {
+        operation.hash(&mut hasher);
+        params.len().hash(&mut hasher);
+        for (key, value) in params {
+            key.hash(&mut hasher);
+            value.hash(&mut hasher);
+        }
+    }
+    hasher.hex()
+}
+
+impl RunManifest {
+    /// Construct a RunManifest for a run starting now.
+    pub fn new<'a, I>(config_hash: String, operations: I, versions: &OperationVersions, seeds: Vec<u64>) -> RunManifest
+    where I: IntoIterator<Item = &'a &'static str> {
+        let operation_versions = operations.into_iter()
+            .map(|name| (name.to_string(), versions.get(name).unwrap_or(&"unversioned").to_string()))
+            .collect();
+        RunManifest {
+            crate_version: env!("CARGO_PKG_VERSION").to_string(),
+            config_hash,
+            operation_versions,
+            seeds,
+            started_at: unix_now(),
+            finished_at: None,
+        }
+    }
+
+    /// Metadata of the run for embedding into its outputs, with the seed of a single-seed run.
+    pub fn metadata(&self, run_id: &str) -> RunMetadata {
+        let seed = match self.seeds.as_slice() {
+            [seed] => Some(*seed),
+            _ => None,
+        };
+        RunMetadata { run_id: run_id.to_string(), config_hash: self.config_hash.clone(), seed, crate_version: self.crate_version.clone() }
+    }
+
+    /// Mark the run as finished now.
+    pub fn finish(&mut self) {
+        self.finished_at = Some(unix_now());
+    }
+
+    pub fn write_json<W: Write>(&self, writer: W) -> Result<()> {
+        serde_json::to_writer_pretty(writer, self)?;
+        Ok(())
+    }
+
+    /// Write the manifest as JSON next to the given output file, into a file named after the
+    /// output file with a .manifest.json suffix. Returns the path of the written manifest.
+    pub fn write_sidecar(&self, output: &Path) -> Result<PathBuf> {
+        let mut name = output.file_name().unwrap_or_default().to_os_string();
+        name.push(".manifest.json");
+        let path = output.with_file_name(name);
+        let mut writer = BufWriter::new(File::create(&path)?);
+        self.write_json(&mut writer)?;
+        writer.flush()?;
+        Ok(path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::configuration_utils::ParameterMap;
+    use super::*;
+
+    #[test]
+    fn config_hash_tracks_configuration() {
+        let declaration = vec![("sequence", vec!["grow", "thin"])];
+        let parameters = OperationParameters::from([("thin", ParameterMap::from([("ratio", "0.3"), ("method", "below")]))]);
+        let hash = config_hash(&declaration, &parameters);
+        assert_eq!(hash, config_hash(&declaration, &parameters.clone()));
+        let changed = OperationParameters::from([("thin", ParameterMap::from([("ratio", "0.4"), ("method", "below")]))]);
+        assert_ne!(hash, config_hash(&declaration, &changed));
+        assert_ne!(hash, config_hash(&vec![("sequence", vec!["thin", "grow"])], &parameters));
+
+        let unbound_grow = OperationParameters::from([("grow", ParameterMap::new()), ("thin", ParameterMap::from([("ratio", "x")]))]);
+        let bound_grow = OperationParameters::from([("grow", ParameterMap::from([("thin", "ratio")])), ("x", ParameterMap::new())]);
+        assert_ne!(config_hash(&declaration, &unbound_grow), config_hash(&declaration, &bound_grow));
+    }
+
+    #[test]
+    fn manifest_is_written_beside_output() {
+        let versions = OperationVersions::from([("grow", "1.2")]);
+        let mut manifest = RunManifest::new("abc".to_string(), &["grow", "thin"], &versions, vec![42]);
+        manifest.finish();
+        assert_eq!("1.2", manifest.operation_versions["grow"]);
+        assert_eq!("unversioned", manifest.operation_versions["thin"]);
+
+        let directory = tempfile::tempdir().unwrap();
+        let path = manifest.write_sidecar(&directory.path().join("trajectories.csv")).unwrap();
+        assert_eq!("trajectories.csv.manifest.json", path.file_name().unwrap());
+        let read: RunManifest = serde_json::from_reader(File::open(&path).unwrap()).unwrap();
+        assert_eq!(manifest, read);
+
+        let metadata = manifest.metadata("run-1");
+        assert_eq!(RunMetadata::new("run-1", "abc".to_string(), Some(42)), metadata);
+        assert_eq!(vec!["run_id", "config_hash", "seed", "crate_version"], metadata.entries().iter().map(|entry| entry.0).collect::<Vec<_>>());
+        assert_eq!(None, RunManifest::new("abc".to_string(), &[], &versions, vec![1, 2]).metadata("run-2").seed);
+    }
+}
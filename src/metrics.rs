@@ -0,0 +1,89 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use crate::event_graph::BoxedOperation;
+
+/// Counters reported during graph construction and evaluation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Counter {
+    /// Operations applied into a payload.
+    OperationsApplied,
+    /// Alternative branches created beyond the first follower of each EventNode, counted for
+    /// each event graph compiled, such as the graphs of the worker threads of parallel runs.
+    BranchesCreated,
+    /// Event chains left unevaluated due to pruning.
+    ChainsPruned,
+    /// Failed entity evaluations.
+    Errors,
+}
+
+impl Counter {
+    pub const ALL: [Counter; 4] = [Counter::OperationsApplied, Counter::BranchesCreated, Counter::ChainsPruned, Counter::Errors];
+
+    /// Conventional metric name of the counter.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Counter::OperationsApplied => "operations_applied",
+            Counter::BranchesCreated => "branches_created",
+            Counter::ChainsPruned => "chains_pruned",
+            Counter::Errors => "errors",
+        }
+    }
+}
+
+/// Metrics receives counter increments. Embedding applications implement it to forward the
+/// counters into their metrics backend.
+pub trait Metrics: Send + Sync {
+    fn increment(&self, counter: Counter, amount: u64);
+}
+
+/// Metrics implementation holding the counters in memory.
+#[derive(Debug, Default)]
+pub struct AtomicMetrics {
+    counters: [AtomicU64; 4],
+}
+
+impl AtomicMetrics {
+    pub fn new() -> AtomicMetrics {
+        AtomicMetrics::default()
+    }
+
+    /// Current value of the counter.
+    pub fn get(&self, counter: Counter) -> u64 {
+        self.counters[counter as usize].load(Ordering::Relaxed)
+    }
+}
+
+impl Metrics for AtomicMetrics {
+    fn increment(&self, counter: Counter, amount: u64) {
+        self.counters[counter as usize].fetch_add(amount, Ordering::Relaxed);
+    }
+}
+
+/// Wrap a named operation to count its applications into the metrics.
+pub fn counted<T: 'static>(metrics: Arc<dyn Metrics>) -> impl Fn(&'static str, BoxedOperation<T>) -> BoxedOperation<T> {
+    move |_name, operation| {
+        let metrics = Arc::clone(&metrics);
+        Box::new(move |payload| {
+            metrics.increment(Counter::OperationsApplied, 1);
+            operation(payload)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn operations_are_counted() {
+        let metrics = Arc::new(AtomicMetrics::new());
+        let wrap = counted::<i32>(metrics.clone());
+        let operation = wrap("increment", Box::new(|x| x + 1));
+        assert_eq!(3, operation(operation(1)));
+        metrics.increment(Counter::Errors, 2);
+        assert_eq!(2, metrics.get(Counter::OperationsApplied));
+        assert_eq!(2, metrics.get(Counter::Errors));
+        assert_eq!(0, metrics.get(Counter::ChainsPruned));
+        assert_eq!(vec!["operations_applied", "branches_created", "chains_pruned", "errors"], Counter::ALL.map(|counter| counter.name()));
+    }
+}
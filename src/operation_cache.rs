@@ -0,0 +1,118 @@
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::rc::Rc;
+use crate::event_graph::BoxedOperation;
+use crate::external_model::CacheStatistics;
+use crate::stable_hash::StableHasher;
+
+/// Key of a state, equal for states the operations process equally.
+pub type StateKey<T> = fn(&T) -> u64;
+
+/// StateKey of hashable states.
+pub fn hash_key<T: Hash>(state: &T) -> u64 {
+    let mut hasher = StableHasher::new();
+    state.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Memoized outputs of named deterministic operations by the keys of their input states, so
+/// that operations applied to equal states on different branches compute once.
+pub struct OperationCache<T> {
+    key: StateKey<T>,
+    outputs: RefCell<HashMap<(&'static str, u64), T>>,
+    statistics: Cell<CacheStatistics>,
+}
+
+impl<T: Clone> OperationCache<T> {
+    pub fn new(key: StateKey<T>) -> OperationCache<T> {
+        OperationCache { key, outputs: RefCell::new(HashMap::new()), statistics: Cell::new(CacheStatistics::default()) }
+    }
+
+    pub fn key(&self) -> StateKey<T> {
+        self.key
+    }
+
+    pub fn statistics(&self) -> CacheStatistics {
+        self.statistics.get()
+    }
+
+    /// Number of memoized outputs.
+    pub fn len(&self) -> usize {
+        self.outputs.borrow().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.outputs.borrow().is_empty()
+    }
+
+    pub fn clear(&self) {
+        self.outputs.borrow_mut().clear();
+        self.statistics.set(CacheStatistics::default());
+    }
+
+    /// Output of the named operation for the state, applying the operation if not memoized.
+    /// Pruning and failing operations are not memoized.
+    fn apply(&self, name: &'static str, operation: &BoxedOperation<T>, state: T) -> T {
+        let key = (name, (self.key)(&state));
+        let mut statistics = self.statistics.get();
+        if let Some(output) = self.outputs.borrow().get(&key) {
+            statistics.hits += 1;
+            self.statistics.set(statistics);
+            return output.clone();
+        }
+        let output = operation(state);
+        statistics.misses += 1;
+        self.statistics.set(statistics);
+        self.outputs.borrow_mut().insert(key, output.clone());
+        output
+    }
+}
+
+/// Wrap a named operation to memoize its outputs into the shared cache. The operation name
+/// identifies the operation, so it must stand for a single operation and its parameters.
+pub fn cached<T: Clone + 'static>(cache: Rc<OperationCache<T>>) -> impl Fn(&'static str, BoxedOperation<T>) -> BoxedOperation<T> {
+    move |name, operation| {
+        let cache = Rc::clone(&cache);
+        Box::new(move |state| cache.apply(name, &operation, state))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::branching_generators::{alternatives, sequence};
+    use crate::event_graph::{prune, EventDAG};
+    use super::*;
+
+    #[test]
+    fn equal_states_are_computed_once() {
+        let calls = Rc::new(Cell::new(0));
+        let cache = Rc::new(OperationCache::new(hash_key::<i32>));
+        let wrap = cached(Rc::clone(&cache));
+        let counting = Rc::clone(&calls);
+        let square = move || -> BoxedOperation<i32> {
+            let calls = Rc::clone(&counting);
+            wrap("square", Box::new(move |x| { calls.set(calls.get() + 1); x * x }))
+        };
+        let root = EventDAG::new_node(Box::new(|x: i32| x));
+        let branches = alternatives(vec![root.clone()], [Box::new(|x| x + 1) as BoxedOperation<i32>, Box::new(|x| 3 - x), Box::new(|x| x - 1)]).unwrap();
+        sequence(branches, [square()]).unwrap();
+        assert_eq!(vec![4, 4, 0], EventDAG::evaluate_chains(&root, 1));
+        assert_eq!(2, calls.get());
+        assert_eq!(CacheStatistics { hits: 1, misses: 2 }, cache.statistics());
+        assert_eq!(2, cache.len());
+        cache.clear();
+        assert!(cache.is_empty());
+    }
+
+    #[test]
+    fn pruned_states_are_not_memoized() {
+        let cache = Rc::new(OperationCache::new(hash_key::<i32>));
+        let operation = cached(Rc::clone(&cache))("prune", Box::new(|x: i32| if x < 0 { prune() } else { x }));
+        let root = EventDAG::new_node(operation);
+        assert!(EventDAG::evaluate_chains(&root, -1).is_empty());
+        assert!(EventDAG::evaluate_chains(&root, -1).is_empty());
+        assert_eq!(CacheStatistics { hits: 0, misses: 0 }, cache.statistics());
+        assert_eq!(hash_key(&1), hash_key(&1));
+    }
+}
@@ -0,0 +1,56 @@
+use crate::event_graph::{compose, BoxedOperation};
+
+/// Create an operation passing the payload through unchanged, such as for a do-nothing
+/// alternative.
+pub fn identity<T: 'static>() -> BoxedOperation<T> {
+    Box::new(|payload| payload)
+}
+
+/// Create an operation applying the given operation only to payloads accepted by the predicate,
+/// passing other payloads through unchanged.
+pub fn when<T: 'static, P: Fn(&T) -> bool + 'static>(predicate: P, operation: BoxedOperation<T>) -> BoxedOperation<T> {
+    Box::new(move |payload| if predicate(&payload) { operation(payload) } else { payload })
+}
+
+/// Create an operation applying the first operation and then the second one.
+pub fn then<T: 'static>(first: BoxedOperation<T>, second: BoxedOperation<T>) -> BoxedOperation<T> {
+    compose(vec![first, second])
+}
+
+/// Create an operation transforming a field of the payload with the given function, the field
+/// accessed with the given getter and setter.
+pub fn map_field<T: 'static, V: 'static, F: Fn(V) -> V + 'static>(getter: fn(&T) -> V, setter: fn(T, V) -> T, function: F) -> BoxedOperation<T> {
+    Box::new(move |payload| {
+        let value = function(getter(&payload));
+        setter(payload, value)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    struct Stand {
+        age: i32,
+        volume: f64,
+    }
+
+    fn volume(stand: &Stand) -> f64 { stand.volume }
+    fn set_volume(stand: Stand, volume: f64) -> Stand { Stand { volume, ..stand } }
+    fn age(stand: &Stand) -> i32 { stand.age }
+    fn set_age(stand: Stand, age: i32) -> Stand { Stand { age, ..stand } }
+
+    #[test]
+    fn combinators_glue_operations() {
+        let stand = Stand { age: 40, volume: 200.0 };
+        assert_eq!(stand, identity()(stand));
+
+        let grow = then(map_field(age, set_age, |age| age + 5), map_field(volume, set_volume, |volume| volume * 1.5));
+        assert_eq!(Stand { age: 45, volume: 300.0 }, grow(stand));
+
+        let thin = when(|stand: &Stand| stand.volume > 250.0, map_field(volume, set_volume, |volume| volume * 0.5));
+        assert_eq!(stand, thin(stand));
+        assert_eq!(Stand { age: 45, volume: 150.0 }, then(grow, thin)(stand));
+    }
+}
@@ -0,0 +1,213 @@
+use std::collections::HashMap;
+use crate::decisions::{decisions, DecidedResult};
+use crate::event_graph::{chain_count, EvaluationFailure, EventDAG, EventNode, EventNodes};
+use crate::pareto::Objective;
+use crate::rng::RngStream;
+
+/// Settings of hill climbing over the alternatives of an event graph.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct HillClimbing {
+    /// Number of climbs started from randomly chosen chains after the climb from the first chain.
+    pub restarts: usize,
+    /// Maximum number of distinct event chains evaluated over all climbs.
+    pub max_evaluations: usize,
+    /// Stream drawing the starting chains of the restarts.
+    pub stream: RngStream,
+}
+
+impl Default for HillClimbing {
+    fn default() -> HillClimbing {
+        HillClimbing { restarts: 0, max_evaluations: 1000, stream: RngStream::new(0) }
+    }
+}
+
+/// Best found event chain with its score and decision path.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Optimum<T> {
+    pub score: f64,
+    pub best: DecidedResult<T>,
+    /// Number of distinct event chains evaluated.
+    pub evaluations: usize,
+}
+
+/// Event chain chosen by the follower indices taken at its branching nodes, in evaluation order.
+struct Walk<T> {
+    choices: Vec<usize>,
+    /// Number of followers at each of the branching nodes.
+    branches: Vec<usize>,
+    chain: EventNodes<T>,
+}
+
+/// Walk from the root into a leaf, choosing the followers at the branching nodes by index.
+fn walk<T: Clone>(root: &EventNode<T>, mut choose: impl FnMut(usize, usize) -> usize) -> Walk<T> {
+    let mut walk = Walk { choices: Vec::new(), branches: Vec::new(), chain: vec![EventNode::clone(root)] };
+    loop {
+        let followers = walk.chain.last().unwrap().borrow().evaluation_order();
+        let next = match followers.len() {
+            0 => return walk,
+            1 => 0,
+            branches => {
+                let choice = choose(walk.choices.len(), branches).min(branches - 1);
+                walk.choices.push(choice);
+                walk.branches.push(branches);
+                choice
+            }
+        };
+        walk.chain.push(EventNode::clone(&followers[next]));
+    }
+}
+
+/// Chain id of the event chain, the number of chains preceding it in evaluation order.
+fn chain_id<T: Clone>(chain: &[EventNode<T>], counts: &mut HashMap<*const (), usize>) -> usize {
+    chain.windows(2).map(|pair| {
+        pair[0].borrow().evaluation_order().iter()
+            .take_while(|follower| !EventNode::ptr_eq(follower, &pair[1]))
+            .map(|follower| chain_count(follower, counts))
+            .fold(0, usize::saturating_add)
+    }).fold(0, usize::saturating_add)
+}
+
+/// State of a hill climbing run.
+struct Climb<'a, T> {
+    root: &'a EventNode<T>,
+    payload: T,
+    objective: Objective<T>,
+    max_evaluations: usize,
+    scores: HashMap<Vec<usize>, Option<f64>>,
+    counts: HashMap<*const (), usize>,
+    best: Option<(f64, DecidedResult<T>)>,
+}
+
+impl<T: Clone> Climb<'_, T> {
+    /// Score of the chain of the choices, None for a pruned chain or an exhausted budget.
+    fn score(&mut self, choices: &[usize]) -> Result<Option<(f64, Walk<T>)>, EvaluationFailure> {
+        let walk = walk(self.root, |index, _| choices.get(index).copied().unwrap_or_default());
+        if let Some(score) = self.scores.get(&walk.choices) {
+            return Ok(score.map(|score| (score, walk)));
+        }
+        if self.scores.len() >= self.max_evaluations {
+            return Ok(None);
+        }
+        let chain = chain_id(&walk.chain, &mut self.counts);
+        let evaluated = EventDAG::try_evaluate_indexed(std::slice::from_ref(&walk.chain), |_| self.payload.clone())
+            .map_err(|failure| EvaluationFailure { chain, ..failure })?;
+        let score = evaluated.into_iter().next().map(|(_, result)| {
+            let score = (self.objective)(&result);
+            if self.best.as_ref().is_none_or(|(best, _)| score > *best) {
+                self.best = Some((score, DecidedResult { chain, decisions: decisions(&walk.chain), result }));
+            }
+            score
+        });
+        self.scores.insert(walk.choices.clone(), score);
+        Ok(score.map(|score| (score, walk)))
+    }
+
+    /// Climb from the chain of the choices into a chain scoring better than all of its
+    /// neighbors, the chains taking another follower at a single branching node.
+    fn climb(&mut self, choices: Vec<usize>) -> Result<(), EvaluationFailure> {
+        let mut current = self.score(&choices)?;
+        let mut choices = choices;
+        loop {
+            let (branches, taken) = match &current {
+                Some((_, walk)) => (walk.branches.clone(), walk.choices.clone()),
+                None => {
+                    let walk = walk(self.root, |index, _| choices.get(index).copied().unwrap_or_default());
+                    (walk.branches, walk.choices)
+                }
+            };
+            let mut best_neighbor: Option<(f64, Walk<T>)> = None;
+            for (index, count) in branches.iter().enumerate() {
+                for choice in (0..*count).filter(|choice| *choice != taken[index]) {
+                    let mut neighbor = taken.clone();
+                    neighbor[index] = choice;
+                    if let Some((score, walk)) = self.score(&neighbor)? {
+                        if best_neighbor.as_ref().is_none_or(|(best, _)| score > *best) {
+                            best_neighbor = Some((score, walk));
+                        }
+                    }
+                }
+            }
+            match best_neighbor {
+                Some((score, walk)) if current.as_ref().is_none_or(|(current, _)| score > *current) => {
+                    choices = walk.choices.clone();
+                    current = Some((score, walk));
+                }
+                _ => return Ok(()),
+            }
+        }
+    }
+}
+
+/// Search for the event chain maximizing the objective by hill climbing over the followers
+/// taken at the branching nodes, without enumerating all event chains. Climbs start from the
+/// first chain and from randomly chosen chains for each restart, ending when no neighboring
+/// chain scores better or the evaluation budget is exhausted. Returns the best evaluated chain
+/// with its decision path, or None if all evaluated chains were pruned.
+pub fn hill_climb<T: Clone>(root: &EventNode<T>, payload: T, objective: Objective<T>, settings: &HillClimbing) -> Result<Option<Optimum<T>>, EvaluationFailure> {
+    let mut climb = Climb {
+        root,
+        payload,
+        objective,
+        max_evaluations: settings.max_evaluations,
+        scores: HashMap::new(),
+        counts: HashMap::new(),
+        best: None,
+    };
+    climb.climb(Vec::new())?;
+    let mut stream = settings.stream;
+    for _ in 0..settings.restarts {
+        let start = walk(root, |_, branches| (stream.next_u64() % branches as u64) as usize);
+        climb.climb(start.choices)?;
+    }
+    let evaluations = climb.scores.len();
+    Ok(climb.best.map(|(score, best)| Optimum { score, best, evaluations }))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+    use crate::branching_generators::alternatives;
+    use crate::event_graph::{prune, BoxedOperation};
+    use super::*;
+
+    /// Three periods of choosing among adding 1, 2 or 3, where adding 3 twice in a row prunes.
+    fn periods() -> EventNode<(i32, i32)> {
+        let root = EventDAG::new_node(Box::new(|state: (i32, i32)| state));
+        let mut frontier = vec![Rc::clone(&root)];
+        for _ in 0..3 {
+            let operations: Vec<BoxedOperation<(i32, i32)>> = (1..=3)
+                .map(|step| -> BoxedOperation<(i32, i32)> {
+                    Box::new(move |(total, previous): (i32, i32)| match step == 3 && previous == 3 {
+                        true => prune(),
+                        false => (total + step, step),
+                    })
+                })
+                .collect();
+            frontier = alternatives(frontier, operations).unwrap();
+        }
+        root
+    }
+
+    fn total(state: &(i32, i32)) -> f64 { state.0 as f64 }
+
+    #[test]
+    fn climbing_finds_the_best_chain() {
+        let optimum = hill_climb(&periods(), (0, 0), total, &HillClimbing::default()).unwrap().unwrap();
+        assert_eq!(8.0, optimum.score);
+        let results = EventDAG::try_evaluate_chains_by_id(&periods(), (0, 0)).unwrap();
+        assert_eq!(results[&optimum.best.chain], optimum.best.result);
+        assert_eq!(vec!["#2", "#1", "#2"], optimum.best.program());
+        assert!(optimum.evaluations < 27);
+    }
+
+    #[test]
+    fn budget_limits_evaluations() {
+        let settings = HillClimbing { max_evaluations: 3, restarts: 2, ..HillClimbing::default() };
+        let optimum = hill_climb(&periods(), (0, 0), total, &settings).unwrap().unwrap();
+        assert_eq!(3, optimum.evaluations);
+        assert_eq!(5.0, optimum.score);
+        let failing = EventDAG::new_node(Box::new(|_: (i32, i32)| -> (i32, i32) { panic!("failed") }));
+        let failure = hill_climb(&failing, (0, 0), total, &HillClimbing::default()).unwrap_err();
+        assert_eq!("failed", failure.error);
+    }
+}
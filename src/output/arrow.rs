@@ -0,0 +1,114 @@
+use std::collections::BTreeMap;
+use std::fmt::Display;
+use std::sync::Arc;
+use arrow_array::{ArrayRef, BooleanArray, Float64Array, Int64Array, RecordBatch, StringArray, UInt64Array};
+use arrow_schema::{ArrowError, DataType, Field, Schema, SchemaRef};
+use crate::event_graph::ChainResults;
+use super::{ColumnType, ColumnValue, RowMapping};
+
+fn data_type(column_type: ColumnType) -> DataType {
+    match column_type {
+        ColumnType::Integer => DataType::Int64,
+        ColumnType::Float => DataType::Float64,
+        ColumnType::Boolean => DataType::Boolean,
+        ColumnType::Text => DataType::Utf8,
+    }
+}
+
+/// Arrow schema of tables keyed by the given key columns, followed by the columns of the state.
+fn schema<T: RowMapping>(keys: &[&str]) -> SchemaRef {
+    let key_fields = keys.iter()
+        .map(|key| Field::new(*key, if *key == "entity" { DataType::Utf8 } else { DataType::UInt64 }, false));
+    let state_fields = T::column_names().into_iter().zip(T::column_types())
+        .map(|(name, column_type)| Field::new(name, data_type(column_type), true));
+    Arc::new(Schema::new(key_fields.chain(state_fields).collect::<Vec<Field>>()))
+}
+
+/// Arrow schema of chain results: entity, chain and the columns of the state.
+pub fn results_schema<T: RowMapping>() -> SchemaRef {
+    schema::<T>(&["entity", "chain"])
+}
+
+/// Arrow schema of collected time series: entity, chain, step and the columns of the state.
+pub fn series_schema<T: RowMapping>() -> SchemaRef {
+    schema::<T>(&["entity", "chain", "step"])
+}
+
+/// Build an Arrow array of a single state column from typed values.
+fn state_column(column_type: ColumnType, values: Vec<ColumnValue>) -> ArrayRef {
+    match column_type {
+        ColumnType::Integer => Arc::new(values.into_iter()
+            .map(|value| match value { ColumnValue::Integer(value) => Some(value), _ => None })
+            .collect::<Int64Array>()),
+        ColumnType::Float => Arc::new(values.into_iter()
+            .map(|value| match value { ColumnValue::Float(value) => Some(value), _ => None })
+            .collect::<Float64Array>()),
+        ColumnType::Boolean => Arc::new(values.into_iter()
+            .map(|value| match value { ColumnValue::Boolean(value) => Some(value), _ => None })
+            .collect::<BooleanArray>()),
+        ColumnType::Text => Arc::new(values.into_iter()
+            .map(|value| match value { ColumnValue::Text(value) => Some(value), _ => None })
+            .collect::<StringArray>()),
+    }
+}
+
+/// Build a RecordBatch from key columns and the states of each row.
+fn batch<T: RowMapping>(schema: SchemaRef, mut columns: Vec<ArrayRef>, states: Vec<&T>) -> Result<RecordBatch, ArrowError> {
+    let types = T::column_types();
+    let mut values: Vec<Vec<ColumnValue>> = vec![Vec::with_capacity(states.len()); types.len()];
+    for state in states {
+        for (column, value) in state.typed_values().into_iter().enumerate() {
+            values[column].push(value);
+        }
+    }
+    columns.extend(types.into_iter().zip(values).map(|(column_type, values)| state_column(column_type, values)));
+    RecordBatch::try_new(schema, columns)
+}
+
+/// Build a RecordBatch of the chain results of an entity, one row per event chain.
+pub fn results_batch<K: Display, T: RowMapping>(entity: &K, results: &ChainResults<T>) -> Result<RecordBatch, ArrowError> {
+    let entities: ArrayRef = Arc::new(StringArray::from(vec![entity.to_string(); results.len()]));
+    let chains: ArrayRef = Arc::new(UInt64Array::from_iter_values(results.keys().map(|chain| *chain as u64)));
+    batch(results_schema::<T>(), vec![entities, chains], results.values().collect())
+}
+
+/// Build a RecordBatch of the collected time series of an entity, one row per observation of
+/// each event chain.
+pub fn series_batch<K: Display, T: RowMapping>(entity: &K, series: &BTreeMap<usize, Vec<T>>) -> Result<RecordBatch, ArrowError> {
+    let rows: Vec<(u64, u64, &T)> = series.iter()
+        .flat_map(|(chain, states)| states.iter().enumerate().map(|(step, state)| (*chain as u64, step as u64, state)))
+        .collect();
+    let entities: ArrayRef = Arc::new(StringArray::from(vec![entity.to_string(); rows.len()]));
+    let chains: ArrayRef = Arc::new(UInt64Array::from_iter_values(rows.iter().map(|row| row.0)));
+    let steps: ArrayRef = Arc::new(UInt64Array::from_iter_values(rows.iter().map(|row| row.1)));
+    batch(series_schema::<T>(), vec![entities, chains, steps], rows.into_iter().map(|row| row.2).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use arrow_array::cast::AsArray;
+    use arrow_array::types::{Int64Type, UInt64Type};
+    use crate::time_points::TimedState;
+    use super::*;
+
+    #[test]
+    fn results_are_batched() {
+        let batch = results_batch(&"a", &ChainResults::from([(0, 1.5), (2, 2.5)])).unwrap();
+        assert_eq!(2, batch.num_rows());
+        assert_eq!(vec![0, 2], batch.column(1).as_primitive::<UInt64Type>().values().to_vec());
+        assert_eq!(vec!["entity", "chain", "value"], batch.schema().fields().iter().map(|field| field.name().as_str()).collect::<Vec<_>>());
+        assert_eq!(&DataType::Float64, batch.column(2).data_type());
+    }
+
+    #[test]
+    fn series_are_batched() {
+        let series = BTreeMap::from([
+            (0, vec![TimedState { time: 2020, state: 1 }, TimedState { time: 2025, state: 2 }]),
+            (1, vec![TimedState { time: 2020, state: 3 }])
+        ]);
+        let batch = series_batch(&7, &series).unwrap();
+        assert_eq!(3, batch.num_rows());
+        let times = batch.column(3).as_primitive::<Int64Type>();
+        assert_eq!(vec![2020, 2025, 2020], times.values().to_vec());
+    }
+}
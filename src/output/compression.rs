@@ -0,0 +1,144 @@
+use std::fs::File;
+use std::io::{BufWriter, Error, ErrorKind, Result, Write};
+use std::path::Path;
+
+/// Compression of an output stream.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Compression {
+    #[default]
+    None,
+    /// gzip, requiring the gzip feature.
+    Gzip,
+    /// Zstandard, requiring the zstd feature.
+    Zstd,
+}
+
+impl Compression {
+    /// Compression by the extension of the path: gzip for .gz, Zstandard for .zst and none
+    /// otherwise.
+    pub fn from_path(path: &Path) -> Compression {
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some("gz") => Compression::Gzip,
+            Some("zst" | "zstd") => Compression::Zstd,
+            _ => Compression::None,
+        }
+    }
+}
+
+/// Output stream compressing what is written into the underlying writer, for passing to the
+/// CSV and JSON Lines writers. The stream is to be finished after writing to complete the
+/// compressed data.
+pub enum CompressedWriter<W: Write> {
+    Plain(W),
+    #[cfg(feature = "gzip")]
+    Gzip(flate2::write::GzEncoder<W>),
+    #[cfg(feature = "zstd")]
+    Zstd(zstd::Encoder<'static, W>),
+}
+
+impl<W: Write> CompressedWriter<W> {
+    /// Compress into the writer, failing for compressions of features not enabled.
+    pub fn new(writer: W, compression: Compression) -> Result<CompressedWriter<W>> {
+        match compression {
+            Compression::None => Ok(CompressedWriter::Plain(writer)),
+            #[cfg(feature = "gzip")]
+            Compression::Gzip => Ok(CompressedWriter::Gzip(flate2::write::GzEncoder::new(writer, flate2::Compression::default()))),
+            #[cfg(feature = "zstd")]
+            Compression::Zstd => Ok(CompressedWriter::Zstd(zstd::Encoder::new(writer, 0)?)),
+            #[allow(unreachable_patterns)]
+            unsupported => Err(Error::new(ErrorKind::Unsupported, format!("{unsupported:?} compression requires its feature"))),
+        }
+    }
+
+    /// Complete the compressed data, returning the underlying writer flushed.
+    #[allow(clippy::infallible_destructuring_match)]
+    pub fn finish(self) -> Result<W> {
+        let mut writer = match self {
+            CompressedWriter::Plain(writer) => writer,
+            #[cfg(feature = "gzip")]
+            CompressedWriter::Gzip(encoder) => encoder.finish()?,
+            #[cfg(feature = "zstd")]
+            CompressedWriter::Zstd(encoder) => encoder.finish()?,
+        };
+        writer.flush()?;
+        Ok(writer)
+    }
+}
+
+impl CompressedWriter<BufWriter<File>> {
+    /// Create the output file, compressed as given or by the extension of the path if not
+    /// given, such as gzip for results.csv.gz.
+    pub fn create<P: AsRef<Path>>(path: P, compression: Option<Compression>) -> Result<CompressedWriter<BufWriter<File>>> {
+        let path = path.as_ref();
+        let compression = compression.unwrap_or_else(|| Compression::from_path(path));
+        CompressedWriter::new(BufWriter::new(File::create(path)?), compression)
+    }
+}
+
+impl<W: Write> Write for CompressedWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        match self {
+            CompressedWriter::Plain(writer) => writer.write(buf),
+            #[cfg(feature = "gzip")]
+            CompressedWriter::Gzip(encoder) => encoder.write(buf),
+            #[cfg(feature = "zstd")]
+            CompressedWriter::Zstd(encoder) => encoder.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        match self {
+            CompressedWriter::Plain(writer) => writer.flush(),
+            #[cfg(feature = "gzip")]
+            CompressedWriter::Gzip(encoder) => encoder.flush(),
+            #[cfg(feature = "zstd")]
+            CompressedWriter::Zstd(encoder) => encoder.flush(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(any(feature = "gzip", feature = "zstd"))]
+    use std::io::Read;
+    use crate::event_graph::ChainResults;
+    use crate::output::csv::CsvWriter;
+    use super::*;
+
+    fn written(compression: Compression) -> Result<Vec<u8>> {
+        let mut writer = CsvWriter::new(CompressedWriter::new(Vec::new(), compression)?);
+        writer.write_results(&"a", &ChainResults::from([(0, 1), (1, 2)])).unwrap();
+        writer.into_inner().finish()
+    }
+
+    #[test]
+    fn compression_follows_the_extension() {
+        assert_eq!(Compression::Gzip, Compression::from_path(Path::new("results.csv.gz")));
+        assert_eq!(Compression::Zstd, Compression::from_path(Path::new("series.jsonl.zst")));
+        assert_eq!(Compression::None, Compression::from_path(Path::new("results.csv")));
+        assert_eq!(b"entity,chain,value\na,0,1\na,1,2\n".to_vec(), written(Compression::None).unwrap());
+    }
+
+    #[cfg(feature = "gzip")]
+    #[test]
+    fn gzip_streams_are_written() {
+        let mut decoded = String::new();
+        flate2::read::GzDecoder::new(written(Compression::Gzip).unwrap().as_slice()).read_to_string(&mut decoded).unwrap();
+        assert_eq!("entity,chain,value\na,0,1\na,1,2\n", decoded);
+    }
+
+    #[cfg(feature = "zstd")]
+    #[test]
+    fn zstd_streams_are_written() {
+        let mut decoded = String::new();
+        zstd::Decoder::new(written(Compression::Zstd).unwrap().as_slice()).unwrap().read_to_string(&mut decoded).unwrap();
+        assert_eq!("entity,chain,value\na,0,1\na,1,2\n", decoded);
+    }
+
+    #[cfg(not(all(feature = "gzip", feature = "zstd")))]
+    #[test]
+    fn compressions_of_disabled_features_fail() {
+        let results = [written(Compression::Gzip), written(Compression::Zstd)];
+        assert!(results.iter().any(|result| matches!(result, Err(error) if error.kind() == ErrorKind::Unsupported)));
+    }
+}
@@ -0,0 +1,174 @@
+use std::collections::BTreeMap;
+use std::fmt::Display;
+use std::io::{Error, ErrorKind, Result, Write};
+use std::marker::PhantomData;
+use crate::event_graph::ChainResults;
+use crate::manifest::RunMetadata;
+use super::RowMapping;
+
+/// CsvWriter writes chain results or collected time series of states as CSV rows, with the
+/// state projected into columns by its RowMapping. A single CsvWriter writes a single table, so
+/// results and time series should be written with separate writers.
+pub struct CsvWriter<W, T> {
+    writer: W,
+    columns: Vec<usize>,
+    metadata: Option<RunMetadata>,
+    header_written: bool,
+    state: PhantomData<T>,
+}
+
+/// Quote a CSV field if it contains separators, quotes or line breaks.
+fn escape(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+impl<W: Write, T: RowMapping> CsvWriter<W, T> {
+    /// Construct a CsvWriter writing all columns of the state.
+    pub fn new(writer: W) -> CsvWriter<W, T> {
+        CsvWriter { writer, columns: (0..T::column_names().len()).collect(), metadata: None, header_written: false, state: PhantomData }
+    }
+
+    /// Construct a CsvWriter writing only the named columns of the state, in the given order.
+    pub fn with_columns(writer: W, columns: &[&str]) -> Result<CsvWriter<W, T>> {
+        let names = T::column_names();
+        let columns = columns.iter()
+            .map(|column| names.iter().position(|name| name == column)
+                .ok_or_else(|| Error::new(ErrorKind::InvalidInput, format!("unknown column '{column}'"))))
+            .collect::<Result<Vec<usize>>>()?;
+        Ok(CsvWriter { writer, columns, metadata: None, header_written: false, state: PhantomData })
+    }
+
+    /// Embed the run metadata as `# key: value` comment lines preceding the header. Takes
+    /// effect when set before writing the first rows.
+    pub fn set_metadata(&mut self, metadata: RunMetadata) {
+        self.metadata = Some(metadata);
+    }
+
+    fn write_row<I: IntoIterator<Item = String>>(&mut self, fields: I) -> Result<()> {
+        let row = fields.into_iter().map(|field| escape(&field)).collect::<Vec<String>>().join(",");
+        writeln!(self.writer, "{row}")
+    }
+
+    fn write_header(&mut self, keys: &[&str]) -> Result<()> {
+        if !self.header_written {
+            for (key, value) in self.metadata.iter().flat_map(RunMetadata::entries) {
+                writeln!(self.writer, "# {key}: {value}")?;
+            }
+            let names = T::column_names();
+            let header = keys.iter().map(|key| key.to_string())
+                .chain(self.columns.iter().map(|column| names[*column].to_string()))
+                .collect::<Vec<String>>();
+            self.write_row(header)?;
+            self.header_written = true;
+        }
+        Ok(())
+    }
+
+    fn write_state(&mut self, keys: Vec<String>, state: &T) -> Result<()> {
+        let values = state.column_values();
+        let fields = keys.into_iter()
+            .chain(self.columns.iter().map(|column| values[*column].clone()))
+            .collect::<Vec<String>>();
+        self.write_row(fields)
+    }
+
+    /// Write the given rows as such, without entity or chain columns.
+    pub fn write_rows(&mut self, rows: &[T]) -> Result<()> {
+        self.write_header(&[])?;
+        for row in rows {
+            self.write_state(Vec::new(), row)?;
+        }
+        Ok(())
+    }
+
+    /// Write the chain results of an entity, one row per event chain.
+    pub fn write_results<K: Display>(&mut self, entity: &K, results: &ChainResults<T>) -> Result<()> {
+        self.write_header(&["entity", "chain"])?;
+        for (chain, state) in results {
+            self.write_state(vec![entity.to_string(), chain.to_string()], state)?;
+        }
+        Ok(())
+    }
+
+    /// Write the collected time series of an entity, one row per observation of each event chain.
+    pub fn write_series<K: Display>(&mut self, entity: &K, series: &BTreeMap<usize, Vec<T>>) -> Result<()> {
+        self.write_header(&["entity", "chain", "step"])?;
+        for (chain, states) in series {
+            for (step, state) in states.iter().enumerate() {
+                self.write_state(vec![entity.to_string(), chain.to_string(), step.to_string()], state)?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> Result<()> {
+        self.writer.flush()
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::time_points::TimedState;
+    use super::*;
+
+    struct Stand {
+        name: &'static str,
+        volume: f64,
+    }
+
+    impl RowMapping for Stand {
+        fn column_names() -> Vec<&'static str> {
+            vec!["name", "volume"]
+        }
+
+        fn column_values(&self) -> Vec<String> {
+            vec![self.name.to_string(), self.volume.to_string()]
+        }
+    }
+
+    #[test]
+    fn results_are_written() {
+        let mut writer = CsvWriter::new(Vec::new());
+        writer.write_results(&"a", &ChainResults::from([(0, Stand { name: "spruce, mixed", volume: 120.5 }), (2, Stand { name: "pine", volume: 80.0 })])).unwrap();
+        writer.write_results(&"b", &ChainResults::from([(0, Stand { name: "\"birch\"", volume: 10.0 })])).unwrap();
+        let output = String::from_utf8(writer.into_inner()).unwrap();
+        assert_eq!("entity,chain,name,volume\na,0,\"spruce, mixed\",120.5\na,2,pine,80\nb,0,\"\"\"birch\"\"\",10\n", output);
+    }
+
+    #[test]
+    fn run_metadata_precedes_the_header() {
+        let mut writer = CsvWriter::new(Vec::new());
+        writer.set_metadata(RunMetadata::new("run-1", "abc".to_string(), Some(42)));
+        writer.write_results(&"a", &ChainResults::from([(0, Stand { name: "pine", volume: 80.0 })])).unwrap();
+        let expected = format!("# run_id: run-1\n# config_hash: abc\n# seed: 42\n# crate_version: {}\nentity,chain,name,volume\na,0,pine,80\n", env!("CARGO_PKG_VERSION"));
+        assert_eq!(expected, String::from_utf8(writer.into_inner()).unwrap());
+    }
+
+    #[test]
+    fn columns_are_projected() {
+        let mut writer = CsvWriter::with_columns(Vec::new(), &["volume"]).unwrap();
+        writer.write_results(&1, &ChainResults::from([(0, Stand { name: "pine", volume: 80.0 })])).unwrap();
+        assert_eq!("entity,chain,volume\n1,0,80\n", String::from_utf8(writer.into_inner()).unwrap());
+        assert!(CsvWriter::<Vec<u8>, Stand>::with_columns(Vec::new(), &["age"]).is_err());
+    }
+
+    #[test]
+    fn series_are_written() {
+        let mut writer = CsvWriter::new(Vec::new());
+        let series = BTreeMap::from([
+            (0, vec![TimedState { time: 2020, state: 1 }, TimedState { time: 2025, state: 2 }]),
+            (1, vec![TimedState { time: 2020, state: 3 }])
+        ]);
+        writer.write_series(&7, &series).unwrap();
+        let output = String::from_utf8(writer.into_inner()).unwrap();
+        assert_eq!("entity,chain,step,time,value\n7,0,0,2020,1\n7,0,1,2025,2\n7,1,0,2020,3\n", output);
+    }
+}
@@ -0,0 +1,133 @@
+use std::collections::BTreeMap;
+use std::fmt::Display;
+use std::io::Write;
+use std::marker::PhantomData;
+use arrow_array::RecordBatch;
+use arrow_ipc::writer::{FileWriter, StreamWriter};
+use arrow_schema::{ArrowError, SchemaRef};
+use crate::event_graph::ChainResults;
+use super::arrow::{results_batch, results_schema, series_batch, series_schema};
+use super::RowMapping;
+
+/// Arrow IPC format written by an IpcWriter.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IpcFormat {
+    /// Streaming format, readable batch by batch while being written, such as by
+    /// `pyarrow.ipc.open_stream` over a pipe or a socket.
+    Stream,
+    /// File format, also known as Feather, with a footer allowing random access.
+    File,
+}
+
+enum Inner<W: Write> {
+    Stream(StreamWriter<W>),
+    File(FileWriter<W>),
+}
+
+/// IpcWriter writes either chain results or collected time series of states in the Arrow IPC
+/// format, with the state projected into typed columns by its RowMapping. Each write is flushed
+/// through to the underlying writer, so readers receive the batches as entities are simulated.
+pub struct IpcWriter<W: Write, T> {
+    writer: Inner<W>,
+    series: bool,
+    state: PhantomData<T>,
+}
+
+impl<W: Write, T: RowMapping> IpcWriter<W, T> {
+    fn new(writer: W, format: IpcFormat, schema: SchemaRef, series: bool) -> Result<IpcWriter<W, T>, ArrowError> {
+        let writer = match format {
+            IpcFormat::Stream => Inner::Stream(StreamWriter::try_new(writer, &schema)?),
+            IpcFormat::File => Inner::File(FileWriter::try_new(writer, &schema)?),
+        };
+        Ok(IpcWriter { writer, series, state: PhantomData })
+    }
+
+    /// Construct an IpcWriter for chain results.
+    pub fn for_results(writer: W, format: IpcFormat) -> Result<IpcWriter<W, T>, ArrowError> {
+        IpcWriter::new(writer, format, results_schema::<T>(), false)
+    }
+
+    /// Construct an IpcWriter for collected time series.
+    pub fn for_series(writer: W, format: IpcFormat) -> Result<IpcWriter<W, T>, ArrowError> {
+        IpcWriter::new(writer, format, series_schema::<T>(), true)
+    }
+
+    fn write(&mut self, batch: &RecordBatch) -> Result<(), ArrowError> {
+        match &mut self.writer {
+            Inner::Stream(writer) => {
+                writer.write(batch)?;
+                writer.flush()
+            },
+            Inner::File(writer) => {
+                writer.write(batch)?;
+                writer.flush()
+            },
+        }
+    }
+
+    /// Write the chain results of an entity, one row per event chain.
+    pub fn write_results<K: Display>(&mut self, entity: &K, results: &ChainResults<T>) -> Result<(), ArrowError> {
+        if self.series {
+            return Err(ArrowError::InvalidArgumentError("writer was constructed for time series".to_string()));
+        }
+        self.write(&results_batch(entity, results)?)
+    }
+
+    /// Write the collected time series of an entity, one row per observation of each event chain.
+    pub fn write_series<K: Display>(&mut self, entity: &K, series: &BTreeMap<usize, Vec<T>>) -> Result<(), ArrowError> {
+        if !self.series {
+            return Err(ArrowError::InvalidArgumentError("writer was constructed for chain results".to_string()));
+        }
+        self.write(&series_batch(entity, series)?)
+    }
+
+    /// Finish the output, writing the end of the stream or the footer of the file, and return the
+    /// underlying writer.
+    pub fn finish(self) -> Result<W, ArrowError> {
+        match self.writer {
+            Inner::Stream(mut writer) => {
+                writer.finish()?;
+                writer.into_inner()
+            },
+            Inner::File(mut writer) => {
+                writer.finish()?;
+                writer.into_inner()
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+    use arrow_ipc::reader::{FileReader, StreamReader};
+    use crate::time_points::TimedState;
+    use super::*;
+
+    #[test]
+    fn streamed_batches_are_readable_before_finishing() {
+        let mut writer = IpcWriter::for_results(Vec::new(), IpcFormat::Stream).unwrap();
+        writer.write_results(&"a", &ChainResults::from([(0, 1.0), (1, 2.0)])).unwrap();
+        assert!(writer.write_series(&"b", &BTreeMap::from([(0, vec![3.0])])).is_err());
+        let Inner::Stream(stream) = &writer.writer else { panic!("stream expected") };
+        let written = stream.get_ref().clone();
+        let mut reader = StreamReader::try_new(Cursor::new(written), None).unwrap();
+        assert_eq!(2, reader.next().unwrap().unwrap().num_rows());
+
+        writer.write_results(&"b", &ChainResults::from([(0, 3.0)])).unwrap();
+        let reader = StreamReader::try_new(Cursor::new(writer.finish().unwrap()), None).unwrap();
+        let rows: usize = reader.map(|batch| batch.unwrap().num_rows()).sum();
+        assert_eq!(3, rows);
+    }
+
+    #[test]
+    fn series_are_written_to_files() {
+        let mut writer = IpcWriter::for_series(Vec::new(), IpcFormat::File).unwrap();
+        writer.write_series(&"a", &BTreeMap::from([(0, vec![TimedState { time: 0, state: 1 }, TimedState { time: 5, state: 2 }])])).unwrap();
+        let reader = FileReader::try_new(Cursor::new(writer.finish().unwrap()), None).unwrap();
+        assert_eq!(vec!["entity", "chain", "step", "time", "value"],
+            reader.schema().fields().iter().map(|field| field.name().as_str()).collect::<Vec<_>>());
+        let rows: usize = reader.map(|batch| batch.unwrap().num_rows()).sum();
+        assert_eq!(2, rows);
+    }
+}
@@ -0,0 +1,100 @@
+use std::collections::BTreeMap;
+use std::io::{Result, Write};
+use serde::Serialize;
+use serde_json::Value;
+use crate::event_graph::ChainResults;
+use crate::manifest::RunMetadata;
+
+pub type Metadata = BTreeMap<String, Value>;
+
+impl From<&RunMetadata> for Metadata {
+    fn from(metadata: &RunMetadata) -> Metadata {
+        let mut entries = Metadata::from([
+            ("run_id".to_string(), Value::from(metadata.run_id.clone())),
+            ("config_hash".to_string(), Value::from(metadata.config_hash.clone())),
+            ("crate_version".to_string(), Value::from(metadata.crate_version.clone())),
+        ]);
+        entries.extend(metadata.seed.map(|seed| ("seed".to_string(), Value::from(seed))));
+        entries
+    }
+}
+
+#[derive(Serialize)]
+struct ChainRecord<'a, K, T> {
+    entity: &'a K,
+    chain: usize,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    metadata: &'a Metadata,
+    result: &'a T,
+}
+
+/// JsonlWriter streams chain results as JSON Lines, one JSON object per event chain carrying the
+/// entity id, chain id, metadata and the serialized result state.
+pub struct JsonlWriter<W> {
+    writer: W,
+    metadata: Metadata,
+}
+
+impl<W: Write> JsonlWriter<W> {
+    pub fn new(writer: W) -> JsonlWriter<W> {
+        JsonlWriter { writer, metadata: Metadata::new() }
+    }
+
+    /// Construct a JsonlWriter attaching the given metadata into every written record.
+    pub fn with_metadata(writer: W, metadata: Metadata) -> JsonlWriter<W> {
+        JsonlWriter { writer, metadata }
+    }
+
+    /// Write the chain results of an entity, one line per event chain.
+    pub fn write_results<K: Serialize, T: Serialize>(&mut self, entity: &K, results: &ChainResults<T>) -> Result<()> {
+        for (chain, result) in results {
+            let record = ChainRecord { entity, chain: *chain, metadata: &self.metadata, result };
+            serde_json::to_writer(&mut self.writer, &record)?;
+            self.writer.write_all(b"\n")?;
+        }
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> Result<()> {
+        self.writer.flush()
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use crate::time_points::TimedState;
+    use super::*;
+
+    #[test]
+    fn results_are_written_as_lines() {
+        let mut writer = JsonlWriter::new(Vec::new());
+        writer.write_results(&"stand-1", &ChainResults::from([(0, 1.5), (3, 2.0)])).unwrap();
+        let output = String::from_utf8(writer.into_inner()).unwrap();
+        assert_eq!("{\"entity\":\"stand-1\",\"chain\":0,\"result\":1.5}\n{\"entity\":\"stand-1\",\"chain\":3,\"result\":2.0}\n", output);
+    }
+
+    #[test]
+    fn metadata_is_attached() {
+        let metadata = Metadata::from([("scenario".to_string(), json!("baseline"))]);
+        let mut writer = JsonlWriter::with_metadata(Vec::new(), metadata);
+        writer.write_results(&3, &ChainResults::from([(0, TimedState { time: 2030, state: 4 })])).unwrap();
+        let output = String::from_utf8(writer.into_inner()).unwrap();
+        let record: Value = serde_json::from_str(output.trim_end()).unwrap();
+        assert_eq!(json!({
+            "entity": 3,
+            "chain": 0,
+            "metadata": { "scenario": "baseline" },
+            "result": { "time": 2030, "state": 4 }
+        }), record);
+
+        let mut metadata = Metadata::from(&RunMetadata::new("run-1", "abc".to_string(), Some(42)));
+        metadata.insert("scenario".to_string(), json!("baseline"));
+        assert_eq!((json!("run-1"), json!(42)), (metadata["run_id"].clone(), metadata["seed"].clone()));
+        assert!(!Metadata::from(&RunMetadata::new("run-2", "abc".to_string(), None)).contains_key("seed"));
+    }
+}
@@ -0,0 +1,109 @@
+use std::collections::BTreeMap;
+use std::fmt::Display;
+use crate::time_points::{TimePoint, TimedState};
+use super::{ColumnType, ColumnValue, RowMapping};
+
+/// Tidy long-format row holding a single variable value of a trajectory at a time point.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LongRow {
+    pub entity: String,
+    pub chain: usize,
+    pub time: TimePoint,
+    pub variable: &'static str,
+    pub value: ColumnValue,
+}
+
+/// Convert collected trajectories of an entity into long-format rows, one row per variable of
+/// each observed state. Variables are the RowMapping columns of the state.
+pub fn long_format_rows<K: Display, T: RowMapping>(entity: &K, series: &BTreeMap<usize, Vec<TimedState<T>>>) -> Vec<LongRow> {
+    let variables = T::column_names();
+    let entity = entity.to_string();
+    series.iter()
+        .flat_map(|(chain, states)| states.iter().flat_map(|timed| {
+            variables.iter().zip(timed.state.typed_values()).map(|(variable, value)| LongRow {
+                entity: entity.clone(),
+                chain: *chain,
+                time: timed.time,
+                variable,
+                value
+            })
+        }))
+        .collect()
+}
+
+/// Long-format rows map into entity, chain, time, variable and value columns. In typed outputs
+/// the value column is a float column, with integers converted and other values left empty.
+impl RowMapping for LongRow {
+    fn column_names() -> Vec<&'static str> {
+        vec!["entity", "chain", "time", "variable", "value"]
+    }
+
+    fn column_values(&self) -> Vec<String> {
+        vec![self.entity.clone(), self.chain.to_string(), self.time.to_string(), self.variable.to_string(), self.value.to_string()]
+    }
+
+    fn column_types() -> Vec<ColumnType> {
+        vec![ColumnType::Text, ColumnType::Integer, ColumnType::Integer, ColumnType::Text, ColumnType::Float]
+    }
+
+    fn typed_values(&self) -> Vec<ColumnValue> {
+        let value = match &self.value {
+            ColumnValue::Integer(value) => ColumnValue::Float(*value as f64),
+            value => value.clone(),
+        };
+        vec![
+            ColumnValue::Text(self.entity.clone()),
+            ColumnValue::Integer(self.chain as i64),
+            ColumnValue::Integer(self.time.into()),
+            ColumnValue::Text(self.variable.to_string()),
+            value
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::output::csv::CsvWriter;
+    use super::*;
+
+    #[derive(Clone, Copy)]
+    struct Stand {
+        age: i32,
+        volume: f64,
+    }
+
+    impl RowMapping for Stand {
+        fn column_names() -> Vec<&'static str> {
+            vec!["age", "volume"]
+        }
+
+        fn column_values(&self) -> Vec<String> {
+            vec![self.age.to_string(), self.volume.to_string()]
+        }
+
+        fn column_types() -> Vec<ColumnType> {
+            vec![ColumnType::Integer, ColumnType::Float]
+        }
+
+        fn typed_values(&self) -> Vec<ColumnValue> {
+            vec![ColumnValue::Integer(self.age.into()), ColumnValue::Float(self.volume)]
+        }
+    }
+
+    #[test]
+    fn trajectories_are_exported_in_long_format() {
+        let series = BTreeMap::from([(1, vec![
+            TimedState { time: 2020, state: Stand { age: 30, volume: 100.0 } },
+            TimedState { time: 2025, state: Stand { age: 35, volume: 120.5 } }
+        ])]);
+        let rows = long_format_rows(&"stand-1", &series);
+        assert_eq!(4, rows.len());
+        assert_eq!(LongRow { entity: "stand-1".to_string(), chain: 1, time: 2025, variable: "volume", value: ColumnValue::Float(120.5) }, rows[3]);
+        assert_eq!(ColumnValue::Float(30.0), rows[0].typed_values()[4]);
+
+        let mut writer = CsvWriter::new(Vec::new());
+        writer.write_rows(&rows).unwrap();
+        let output = String::from_utf8(writer.into_inner()).unwrap();
+        assert_eq!("entity,chain,time,variable,value\nstand-1,1,2020,age,30\nstand-1,1,2020,volume,100\nstand-1,1,2025,age,35\nstand-1,1,2025,volume,120.5\n", output);
+    }
+}
@@ -0,0 +1,121 @@
+pub mod csv;
+pub mod jsonl;
+pub mod long_format;
+pub mod compression;
+#[cfg(feature = "arrow")]
+pub mod arrow;
+#[cfg(feature = "parquet")]
+pub mod parquet;
+#[cfg(feature = "ipc")]
+pub mod ipc;
+#[cfg(feature = "polars")]
+pub mod polars;
+#[cfg(feature = "sqlite")]
+pub mod sqlite;
+#[cfg(feature = "netcdf")]
+pub mod netcdf;
+
+use std::fmt;
+use crate::time_points::TimedState;
+
+/// Types of tabular output columns.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColumnType {
+    Integer,
+    Float,
+    Boolean,
+    Text,
+}
+
+/// Typed value of a tabular output column.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ColumnValue {
+    Integer(i64),
+    Float(f64),
+    Boolean(bool),
+    Text(String),
+}
+
+impl fmt::Display for ColumnValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ColumnValue::Integer(value) => write!(f, "{value}"),
+            ColumnValue::Float(value) => write!(f, "{value}"),
+            ColumnValue::Boolean(value) => write!(f, "{value}"),
+            ColumnValue::Text(value) => write!(f, "{value}"),
+        }
+    }
+}
+
+/// RowMapping projects a state into named columns of a tabular output.
+pub trait RowMapping {
+    /// Names of the columns produced from the state.
+    fn column_names() -> Vec<&'static str>;
+    /// Column values of the state in the order of column_names.
+    fn column_values(&self) -> Vec<String>;
+
+    /// Types of the columns in the order of column_names. Typed outputs treat all columns as
+    /// text unless overridden.
+    fn column_types() -> Vec<ColumnType> {
+        vec![ColumnType::Text; Self::column_names().len()]
+    }
+
+    /// Typed column values of the state in the order of column_names, matching column_types.
+    fn typed_values(&self) -> Vec<ColumnValue> {
+        self.column_values().into_iter().map(ColumnValue::Text).collect()
+    }
+}
+
+macro_rules! primitive_row_mapping {
+    ($column_type:ident, $($primitive:ty),*) => {
+        $(
+            impl RowMapping for $primitive {
+                fn column_names() -> Vec<&'static str> {
+                    vec!["value"]
+                }
+
+                fn column_values(&self) -> Vec<String> {
+                    vec![self.to_string()]
+                }
+
+                fn column_types() -> Vec<ColumnType> {
+                    vec![ColumnType::$column_type]
+                }
+
+                fn typed_values(&self) -> Vec<ColumnValue> {
+                    vec![ColumnValue::$column_type((*self).into())]
+                }
+            }
+        )*
+    };
+}
+
+primitive_row_mapping!(Integer, i32, i64, u32);
+primitive_row_mapping!(Float, f32, f64);
+primitive_row_mapping!(Boolean, bool);
+
+impl<T: RowMapping> RowMapping for TimedState<T> {
+    fn column_names() -> Vec<&'static str> {
+        let mut names = vec!["time"];
+        names.extend(T::column_names());
+        names
+    }
+
+    fn column_values(&self) -> Vec<String> {
+        let mut values = vec![self.time.to_string()];
+        values.extend(self.state.column_values());
+        values
+    }
+
+    fn column_types() -> Vec<ColumnType> {
+        let mut types = vec![ColumnType::Integer];
+        types.extend(T::column_types());
+        types
+    }
+
+    fn typed_values(&self) -> Vec<ColumnValue> {
+        let mut values = vec![ColumnValue::Integer(self.time.into())];
+        values.extend(self.state.typed_values());
+        values
+    }
+}
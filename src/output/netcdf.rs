@@ -0,0 +1,189 @@
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt::{self, Display};
+use std::marker::PhantomData;
+use std::path::{Path, PathBuf};
+use netcdf3::{DataSet, FileWriter, InvalidDataSet, Version, WriteError, NC_FILL_F64};
+use crate::manifest::RunMetadata;
+use crate::time_points::{TimePoint, TimedState};
+use super::{ColumnValue, RowMapping};
+
+/// Reasons for failing to archive trajectories into a netCDF file.
+#[derive(Debug)]
+pub enum NetcdfError {
+    Definition(InvalidDataSet),
+    Write(WriteError),
+    /// A state was observed at a time point not among the time points of the archive.
+    UnknownTimePoint(TimePoint),
+    /// An event chain beyond the chain count of the archive.
+    ChainOutOfRange(usize),
+}
+
+impl fmt::Display for NetcdfError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NetcdfError::Definition(err) => write!(f, "{err}"),
+            NetcdfError::Write(err) => write!(f, "netCDF write failed: {err:?}"),
+            NetcdfError::UnknownTimePoint(time) => write!(f, "time point {time} is not archived"),
+            NetcdfError::ChainOutOfRange(chain) => write!(f, "event chain {chain} exceeds the archived chain count"),
+        }
+    }
+}
+
+impl Error for NetcdfError {}
+
+impl From<InvalidDataSet> for NetcdfError {
+    fn from(err: InvalidDataSet) -> Self {
+        NetcdfError::Definition(err)
+    }
+}
+
+impl From<WriteError> for NetcdfError {
+    fn from(err: WriteError) -> Self {
+        NetcdfError::Write(err)
+    }
+}
+
+/// NetcdfWriter archives collected trajectories into a netCDF classic file as a four-dimensional
+/// array `trajectories(entity, chain, time, variable)` of doubles, the variables being the
+/// RowMapping columns of the state. The file also holds the coordinate variables `time`,
+/// `entity` and `variable`, the latter two as character arrays, and the run metadata as global
+/// attributes.
+///
+/// Integer and boolean values are converted to doubles. Text values, and the time points and
+/// event chains an entity was not observed at, hold the netCDF fill value, which readers mask
+/// as missing. As netCDF classic files are written whole, the trajectories are kept in memory
+/// until the writer is closed.
+pub struct NetcdfWriter<T> {
+    path: PathBuf,
+    times: Vec<TimePoint>,
+    chains: usize,
+    entities: Vec<String>,
+    values: Vec<f64>,
+    metadata: Vec<(&'static str, String)>,
+    state: PhantomData<T>,
+}
+
+fn numeric(value: ColumnValue) -> f64 {
+    match value {
+        ColumnValue::Integer(value) => value as f64,
+        ColumnValue::Float(value) => value,
+        ColumnValue::Boolean(value) => f64::from(u8::from(value)),
+        ColumnValue::Text(_) => NC_FILL_F64,
+    }
+}
+
+/// Character array of the names padded to the longest name.
+fn characters(names: &[String]) -> (usize, Vec<u8>) {
+    let length = names.iter().map(String::len).max().unwrap_or(0).max(1);
+    let characters = names.iter()
+        .flat_map(|name| name.bytes().chain(std::iter::repeat(0)).take(length))
+        .collect();
+    (length, characters)
+}
+
+impl<T: RowMapping> NetcdfWriter<T> {
+    /// Construct a NetcdfWriter archiving into the file the given time points of the given
+    /// number of event chains of each entity.
+    pub fn new<P: AsRef<Path>>(path: P, times: &[TimePoint], chains: usize) -> NetcdfWriter<T> {
+        NetcdfWriter {
+            path: path.as_ref().to_path_buf(),
+            times: times.to_vec(),
+            chains,
+            entities: Vec::new(),
+            values: Vec::new(),
+            metadata: Vec::new(),
+            state: PhantomData,
+        }
+    }
+
+    /// Embed the run metadata as global attributes of the file.
+    pub fn set_metadata(&mut self, metadata: &RunMetadata) {
+        self.metadata = metadata.entries();
+    }
+
+    /// Add the collected trajectories of an entity.
+    pub fn write_series<K: Display>(&mut self, entity: &K, series: &BTreeMap<usize, Vec<TimedState<T>>>) -> Result<(), NetcdfError> {
+        let variables = T::column_names().len();
+        let mut values = vec![NC_FILL_F64; self.chains * self.times.len() * variables];
+        for (chain, states) in series {
+            if *chain >= self.chains {
+                return Err(NetcdfError::ChainOutOfRange(*chain));
+            }
+            for timed in states {
+                let time = self.times.iter().position(|time| *time == timed.time).ok_or(NetcdfError::UnknownTimePoint(timed.time))?;
+                let offset = (chain * self.times.len() + time) * variables;
+                for (variable, value) in timed.state.typed_values().into_iter().enumerate() {
+                    values[offset + variable] = numeric(value);
+                }
+            }
+        }
+        self.entities.push(entity.to_string());
+        self.values.extend(values);
+        Ok(())
+    }
+
+    /// Write the netCDF file.
+    pub fn close(self) -> Result<(), NetcdfError> {
+        let variables: Vec<String> = T::column_names().into_iter().map(str::to_string).collect();
+        let (entity_length, entities) = characters(&self.entities);
+        let (variable_length, variable_names) = characters(&variables);
+
+        let mut data_set = DataSet::new();
+        data_set.set_unlimited_dim("entity", self.entities.len())?;
+        data_set.add_fixed_dim("chain", self.chains)?;
+        data_set.add_fixed_dim("time", self.times.len())?;
+        data_set.add_fixed_dim("variable", variables.len())?;
+        data_set.add_fixed_dim("entity_length", entity_length)?;
+        data_set.add_fixed_dim("variable_length", variable_length)?;
+        data_set.add_var_i32("time", &["time"])?;
+        data_set.add_var_u8("entity", &["entity", "entity_length"])?;
+        data_set.add_var_u8("variable", &["variable", "variable_length"])?;
+        data_set.add_var_f64("trajectories", &["entity", "chain", "time", "variable"])?;
+        data_set.add_var_attr_f64("trajectories", "_FillValue", vec![NC_FILL_F64])?;
+        for (key, value) in &self.metadata {
+            data_set.add_global_attr_string(key, value)?;
+        }
+
+        let mut writer = FileWriter::open(&self.path)?;
+        writer.set_def(&data_set, Version::Offset64Bit, 0)?;
+        writer.write_var_i32("time", &self.times)?;
+        writer.write_var_u8("entity", &entities)?;
+        writer.write_var_u8("variable", &variable_names)?;
+        writer.write_var_f64("trajectories", &self.values)?;
+        writer.close()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use netcdf3::FileReader;
+    use super::*;
+
+    #[test]
+    fn trajectories_are_archived_as_arrays() {
+        let directory = tempfile::tempdir().unwrap();
+        let path = directory.path().join("trajectories.nc");
+        let mut writer = NetcdfWriter::new(&path, &[0, 5, 10], 2);
+        writer.set_metadata(&RunMetadata::new("run-1", "abc".to_string(), None));
+        writer.write_series(&"a", &BTreeMap::from([
+            (0, vec![TimedState { time: 0, state: 1.0 }, TimedState { time: 10, state: 3.0 }]),
+            (1, vec![TimedState { time: 5, state: 2.0 }]),
+        ])).unwrap();
+        writer.write_series(&"b", &BTreeMap::from([(0, vec![TimedState { time: 0, state: 4.0 }])])).unwrap();
+        assert!(matches!(writer.write_series(&"c", &BTreeMap::from([(2, vec![])])), Err(NetcdfError::ChainOutOfRange(2))));
+        assert!(matches!(writer.write_series(&"c", &BTreeMap::from([(0, vec![TimedState { time: 3, state: 0.0 }])])),
+            Err(NetcdfError::UnknownTimePoint(3))));
+        writer.close().unwrap();
+
+        let mut reader = FileReader::open(&path).unwrap();
+        let values = reader.read_var("trajectories").unwrap().get_f64_into().unwrap();
+        let entities = reader.read_var("entity").unwrap().get_u8_into().unwrap();
+        assert_eq!("run-1", reader.data_set().get_global_attr_as_string("run_id").unwrap());
+        assert_eq!(vec![2, 2, 3, 1], reader.data_set().get_var("trajectories").unwrap().get_dims().iter().map(|dim| dim.size()).collect::<Vec<_>>());
+        assert_eq!(b"ab".to_vec(), entities);
+        let observed: Vec<Option<f64>> = values.into_iter().map(|value| Some(value).filter(|value| *value != NC_FILL_F64)).collect();
+        assert_eq!(vec![Some(1.0), None, Some(3.0), None, Some(2.0), None, Some(4.0), None, None, None, None, None], observed);
+    }
+}
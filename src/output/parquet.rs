@@ -0,0 +1,104 @@
+use std::collections::BTreeMap;
+use std::fmt::Display;
+use std::io::Write;
+use std::marker::PhantomData;
+use arrow_schema::SchemaRef;
+use parquet::arrow::ArrowWriter;
+use parquet::errors::{ParquetError, Result};
+use parquet::file::metadata::{KeyValue, ParquetMetaData};
+use super::arrow::{results_batch, results_schema, series_batch, series_schema};
+use crate::event_graph::ChainResults;
+use crate::manifest::RunMetadata;
+use super::RowMapping;
+
+/// ParquetWriter writes either chain results or collected time series of states into a Parquet
+/// file, with the state projected into typed columns by its RowMapping.
+pub struct ParquetWriter<W: Write + Send, T> {
+    writer: ArrowWriter<W>,
+    series: bool,
+    state: PhantomData<T>,
+}
+
+impl<W: Write + Send, T: RowMapping> ParquetWriter<W, T> {
+    fn new(writer: W, schema: SchemaRef, series: bool) -> Result<ParquetWriter<W, T>> {
+        Ok(ParquetWriter { writer: ArrowWriter::try_new(writer, schema, None)?, series, state: PhantomData })
+    }
+
+    /// Construct a ParquetWriter for chain results.
+    pub fn for_results(writer: W) -> Result<ParquetWriter<W, T>> {
+        ParquetWriter::new(writer, results_schema::<T>(), false)
+    }
+
+    /// Construct a ParquetWriter for collected time series.
+    pub fn for_series(writer: W) -> Result<ParquetWriter<W, T>> {
+        ParquetWriter::new(writer, series_schema::<T>(), true)
+    }
+
+    /// Embed the run metadata into the key-value metadata of the file footer.
+    pub fn set_metadata(&mut self, metadata: &RunMetadata) {
+        for (key, value) in metadata.entries() {
+            self.writer.append_key_value_metadata(KeyValue::new(key.to_string(), value));
+        }
+    }
+
+    /// Write the chain results of an entity, one row per event chain.
+    pub fn write_results<K: Display>(&mut self, entity: &K, results: &ChainResults<T>) -> Result<()> {
+        if self.series {
+            return Err(ParquetError::General("writer was constructed for time series".to_string()));
+        }
+        self.writer.write(&results_batch(entity, results)?)
+    }
+
+    /// Write the collected time series of an entity, one row per observation of each event chain.
+    pub fn write_series<K: Display>(&mut self, entity: &K, series: &BTreeMap<usize, Vec<T>>) -> Result<()> {
+        if !self.series {
+            return Err(ParquetError::General("writer was constructed for chain results".to_string()));
+        }
+        self.writer.write(&series_batch(entity, series)?)
+    }
+
+    /// Finish the Parquet file, writing its footer.
+    pub fn close(self) -> Result<ParquetMetaData> {
+        self.writer.close()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::File;
+    use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+    use super::*;
+
+    #[test]
+    fn results_are_written() {
+        let directory = tempfile::tempdir().unwrap();
+        let path = directory.path().join("results.parquet");
+        let mut writer = ParquetWriter::for_results(File::create(&path).unwrap()).unwrap();
+        writer.write_results(&"a", &ChainResults::from([(0, 1.0), (1, 2.0)])).unwrap();
+        writer.write_results(&"b", &ChainResults::from([(0, 3.0)])).unwrap();
+        assert!(writer.write_series(&"c", &BTreeMap::from([(0, vec![4.0])])).is_err());
+        let metadata = writer.close().unwrap();
+        assert_eq!(3, metadata.file_metadata().num_rows());
+
+        let reader = ParquetRecordBatchReaderBuilder::try_new(File::open(&path).unwrap()).unwrap().build().unwrap();
+        let rows: usize = reader.map(|batch| batch.unwrap().num_rows()).sum();
+        assert_eq!(3, rows);
+    }
+
+    #[test]
+    fn run_metadata_is_embedded() {
+        let mut writer = ParquetWriter::for_results(Vec::new()).unwrap();
+        writer.set_metadata(&RunMetadata::new("run-1", "abc".to_string(), None));
+        writer.write_results(&"a", &ChainResults::from([(0, 1.0)])).unwrap();
+        let metadata = writer.close().unwrap();
+        let entries: Vec<(String, Option<String>)> = metadata.file_metadata().key_value_metadata().unwrap().iter()
+            .filter(|entry| entry.key != "ARROW:schema")
+            .map(|entry| (entry.key.clone(), entry.value.clone()))
+            .collect();
+        assert_eq!(vec![
+            ("run_id".to_string(), Some("run-1".to_string())),
+            ("config_hash".to_string(), Some("abc".to_string())),
+            ("crate_version".to_string(), Some(env!("CARGO_PKG_VERSION").to_string())),
+        ], entries);
+    }
+}
@@ -0,0 +1,92 @@
+use std::collections::BTreeMap;
+use std::fmt::Display;
+use polars::prelude::{Column, DataFrame, PolarsResult};
+use crate::batch_runner::EntityResults;
+use super::{ColumnType, ColumnValue, RowMapping};
+
+/// Build a polars Column of a single state column from typed values.
+fn state_column(name: &str, column_type: ColumnType, values: Vec<ColumnValue>) -> Column {
+    match column_type {
+        ColumnType::Integer => Column::new(name.into(), values.into_iter()
+            .map(|value| match value { ColumnValue::Integer(value) => Some(value), _ => None })
+            .collect::<Vec<Option<i64>>>()),
+        ColumnType::Float => Column::new(name.into(), values.into_iter()
+            .map(|value| match value { ColumnValue::Float(value) => Some(value), _ => None })
+            .collect::<Vec<Option<f64>>>()),
+        ColumnType::Boolean => Column::new(name.into(), values.into_iter()
+            .map(|value| match value { ColumnValue::Boolean(value) => Some(value), _ => None })
+            .collect::<Vec<Option<bool>>>()),
+        ColumnType::Text => Column::new(name.into(), values.into_iter()
+            .map(|value| match value { ColumnValue::Text(value) => Some(value), _ => None })
+            .collect::<Vec<Option<String>>>()),
+    }
+}
+
+/// Build a DataFrame from key columns and the states of each row.
+fn frame<T: RowMapping>(mut columns: Vec<Column>, states: Vec<&T>) -> PolarsResult<DataFrame> {
+    let height = states.len();
+    let types = T::column_types();
+    let mut values: Vec<Vec<ColumnValue>> = vec![Vec::with_capacity(height); types.len()];
+    for state in states {
+        for (column, value) in state.typed_values().into_iter().enumerate() {
+            values[column].push(value);
+        }
+    }
+    columns.extend(T::column_names().into_iter().zip(types).zip(values)
+        .map(|((name, column_type), values)| state_column(name, column_type, values)));
+    DataFrame::new(height, columns)
+}
+
+/// Build a DataFrame of batch results, one row per event chain of each entity, with columns
+/// entity, chain and the columns of the state.
+pub fn results_frame<K: Display, T: RowMapping>(results: &EntityResults<K, T>) -> PolarsResult<DataFrame> {
+    let rows: Vec<(String, u64, &T)> = results.iter()
+        .flat_map(|(entity, states)| states.iter()
+            .map(move |(chain, state)| (entity.to_string(), *chain as u64, state)))
+        .collect();
+    let entities = Column::new("entity".into(), rows.iter().map(|row| row.0.as_str()).collect::<Vec<&str>>());
+    let chains = Column::new("chain".into(), rows.iter().map(|row| row.1).collect::<Vec<u64>>());
+    frame(vec![entities, chains], rows.iter().map(|row| row.2).collect())
+}
+
+/// Build a DataFrame of collected time series keyed by entity, one row per observation of each
+/// event chain, with columns entity, chain, step and the columns of the state.
+pub fn series_frame<K: Display, T: RowMapping>(series: &BTreeMap<K, BTreeMap<usize, Vec<T>>>) -> PolarsResult<DataFrame> {
+    let rows: Vec<(String, u64, u64, &T)> = series.iter()
+        .flat_map(|(entity, chains)| chains.iter()
+            .flat_map(move |(chain, states)| states.iter().enumerate()
+                .map(move |(step, state)| (entity.to_string(), *chain as u64, step as u64, state))))
+        .collect();
+    let entities = Column::new("entity".into(), rows.iter().map(|row| row.0.as_str()).collect::<Vec<&str>>());
+    let chains = Column::new("chain".into(), rows.iter().map(|row| row.1).collect::<Vec<u64>>());
+    let steps = Column::new("step".into(), rows.iter().map(|row| row.2).collect::<Vec<u64>>());
+    frame(vec![entities, chains, steps], rows.iter().map(|row| row.3).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::event_graph::ChainResults;
+    use crate::time_points::TimedState;
+    use super::*;
+
+    #[test]
+    fn results_are_framed() {
+        let results = EntityResults::from([("a", ChainResults::from([(0, 1.5), (2, 2.5)])), ("b", ChainResults::from([(0, 3.5)]))]);
+        let frame = results_frame(&results).unwrap();
+        assert_eq!((3, 3), frame.shape());
+        assert_eq!(vec!["entity", "chain", "value"], frame.get_column_names().iter().map(|name| name.as_str()).collect::<Vec<_>>());
+        assert_eq!(vec![Some(1.5), Some(2.5), Some(3.5)], frame.column("value").unwrap().f64().unwrap().to_vec());
+        assert_eq!(vec![Some(0), Some(2), Some(0)], frame.column("chain").unwrap().u64().unwrap().to_vec());
+    }
+
+    #[test]
+    fn series_are_framed() {
+        let series = BTreeMap::from([(1, BTreeMap::from([
+            (0, vec![TimedState { time: 2020, state: 1 }, TimedState { time: 2025, state: 2 }]),
+            (1, vec![TimedState { time: 2020, state: 3 }])
+        ]))]);
+        let frame = series_frame(&series).unwrap();
+        assert_eq!((3, 5), frame.shape());
+        assert_eq!(vec![Some(2020), Some(2025), Some(2020)], frame.column("time").unwrap().i64().unwrap().to_vec());
+    }
+}
@@ -0,0 +1,144 @@
+use std::collections::BTreeMap;
+use std::fmt::Display;
+use std::path::Path;
+use rusqlite::types::Value;
+use rusqlite::{params_from_iter, Connection, Result};
+use crate::event_graph::ChainResults;
+use crate::manifest::RunMetadata;
+use super::{ColumnType, ColumnValue, RowMapping};
+
+/// SqliteWriter writes chain results, collected time series and run metadata into tables of a
+/// SQLite database, with the state projected into typed columns by its RowMapping:
+///
+/// - `run_metadata(key TEXT PRIMARY KEY, value TEXT NOT NULL)`
+/// - `results(entity TEXT NOT NULL, chain INTEGER NOT NULL, <state columns>)`
+/// - `trajectories(entity TEXT NOT NULL, chain INTEGER NOT NULL, step INTEGER NOT NULL, <state columns>)`
+///
+/// Integer and boolean columns are INTEGER, float columns REAL and text columns TEXT. The result
+/// and trajectory tables are created on first write and indexed by entity and chain.
+pub struct SqliteWriter {
+    connection: Connection,
+}
+
+fn sql_type(column_type: ColumnType) -> &'static str {
+    match column_type {
+        ColumnType::Integer | ColumnType::Boolean => "INTEGER",
+        ColumnType::Float => "REAL",
+        ColumnType::Text => "TEXT",
+    }
+}
+
+fn sql_value(value: ColumnValue) -> Value {
+    match value {
+        ColumnValue::Integer(value) => Value::Integer(value),
+        ColumnValue::Float(value) => Value::Real(value),
+        ColumnValue::Boolean(value) => Value::Integer(value.into()),
+        ColumnValue::Text(value) => Value::Text(value),
+    }
+}
+
+fn quoted(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+impl SqliteWriter {
+    /// Construct a SqliteWriter over the connection, creating the run metadata table.
+    pub fn new(connection: Connection) -> Result<SqliteWriter> {
+        connection.execute("CREATE TABLE IF NOT EXISTS run_metadata (key TEXT PRIMARY KEY, value TEXT NOT NULL)", ())?;
+        Ok(SqliteWriter { connection })
+    }
+
+    /// Open or create the database file.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<SqliteWriter> {
+        SqliteWriter::new(Connection::open(path)?)
+    }
+
+    /// Record the run metadata, replacing entries of a previous run.
+    pub fn set_metadata(&mut self, metadata: &RunMetadata) -> Result<()> {
+        let transaction = self.connection.transaction()?;
+        transaction.execute("DELETE FROM run_metadata", ())?;
+        for (key, value) in metadata.entries() {
+            transaction.execute("INSERT INTO run_metadata (key, value) VALUES (?1, ?2)", (key, value))?;
+        }
+        transaction.commit()
+    }
+
+    /// Create the table keyed by the given key columns, followed by the columns of the state.
+    fn create_table<T: RowMapping>(&self, table: &str, keys: &[&str]) -> Result<()> {
+        let columns: Vec<String> = keys.iter()
+            .map(|key| format!("{key} {} NOT NULL", if *key == "entity" { "TEXT" } else { "INTEGER" }))
+            .chain(T::column_names().into_iter().zip(T::column_types())
+                .map(|(name, column_type)| format!("{} {}", quoted(name), sql_type(column_type))))
+            .collect();
+        self.connection.execute_batch(&format!(
+            "CREATE TABLE IF NOT EXISTS {table} ({});\nCREATE INDEX IF NOT EXISTS {table}_entity_chain ON {table} (entity, chain);",
+            columns.join(", ")
+        ))
+    }
+
+    /// Insert rows of key values and states into the table within a single transaction.
+    fn insert<'a, T: RowMapping + 'a>(&mut self, table: &str, keys: &[&str], rows: impl Iterator<Item = (Vec<Value>, &'a T)>) -> Result<()> {
+        self.create_table::<T>(table, keys)?;
+        let columns: Vec<String> = keys.iter().map(|key| key.to_string())
+            .chain(T::column_names().into_iter().map(quoted))
+            .collect();
+        let placeholders = vec!["?"; columns.len()].join(", ");
+        let transaction = self.connection.transaction()?;
+        {
+            let mut statement = transaction.prepare(&format!("INSERT INTO {table} ({}) VALUES ({placeholders})", columns.join(", ")))?;
+            for (mut values, state) in rows {
+                values.extend(state.typed_values().into_iter().map(sql_value));
+                statement.execute(params_from_iter(values))?;
+            }
+        }
+        transaction.commit()
+    }
+
+    /// Write the chain results of an entity, one row per event chain.
+    pub fn write_results<K: Display, T: RowMapping>(&mut self, entity: &K, results: &ChainResults<T>) -> Result<()> {
+        let entity = entity.to_string();
+        let rows = results.iter()
+            .map(|(chain, state)| (vec![Value::Text(entity.clone()), Value::Integer(*chain as i64)], state));
+        self.insert("results", &["entity", "chain"], rows)
+    }
+
+    /// Write the collected time series of an entity, one row per observation of each event chain.
+    pub fn write_series<K: Display, T: RowMapping>(&mut self, entity: &K, series: &BTreeMap<usize, Vec<T>>) -> Result<()> {
+        let entity = entity.to_string();
+        let rows = series.iter()
+            .flat_map(|(chain, states)| states.iter().enumerate().map(move |(step, state)| (*chain, step, state)))
+            .map(|(chain, step, state)| (vec![Value::Text(entity.clone()), Value::Integer(chain as i64), Value::Integer(step as i64)], state));
+        self.insert("trajectories", &["entity", "chain", "step"], rows)
+    }
+
+    /// Return the underlying connection.
+    pub fn into_inner(self) -> Connection {
+        self.connection
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::time_points::TimedState;
+    use super::*;
+
+    #[test]
+    fn results_trajectories_and_metadata_are_queryable() {
+        let mut writer = SqliteWriter::new(Connection::open_in_memory().unwrap()).unwrap();
+        writer.set_metadata(&RunMetadata::new("run-1", "abc".to_string(), Some(7))).unwrap();
+        writer.write_results(&"a", &ChainResults::from([(0, 1.5), (2, 2.5)])).unwrap();
+        writer.write_results(&"b", &ChainResults::from([(0, 3.0)])).unwrap();
+        let series = BTreeMap::from([(0, vec![TimedState { time: 0, state: true }, TimedState { time: 5, state: false }])]);
+        writer.write_series(&"a", &series).unwrap();
+        let connection = writer.into_inner();
+
+        let total: f64 = connection.query_row("SELECT sum(value) FROM results WHERE chain < 1", (), |row| row.get(0)).unwrap();
+        assert_eq!(4.5, total);
+        let rows: Vec<(i64, i64, bool)> = connection.prepare("SELECT step, time, value FROM trajectories ORDER BY step").unwrap()
+            .query_map((), |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?))).unwrap()
+            .collect::<Result<_>>().unwrap();
+        assert_eq!(vec![(0, 0, true), (1, 5, false)], rows);
+        let seed: String = connection.query_row("SELECT value FROM run_metadata WHERE key = 'seed'", (), |row| row.get(0)).unwrap();
+        assert_eq!("7", seed);
+    }
+}
@@ -0,0 +1,83 @@
+use crate::collectors::Projector;
+use crate::decisions::DecidedResult;
+
+/// Objective to maximize. Objectives to minimize are negated.
+pub type Objective<T> = Projector<T, f64>;
+
+/// Check whether the first objective values dominate the second, being at least as good in all
+/// objectives and better in some.
+fn dominates(first: &[f64], second: &[f64]) -> bool {
+    first.iter().zip(second).all(|(a, b)| a >= b) && first.iter().zip(second).any(|(a, b)| a > b)
+}
+
+/// Select the non-dominated chain results over the given objectives along with their decision
+/// paths, in the order of the given results. Results are consumed one at a time, so that a
+/// stream of results needs not be kept in memory beyond the front. Results with equal
+/// objective values are all kept.
+pub fn select_pareto<T, I>(results: I, objectives: &[Objective<T>]) -> Vec<DecidedResult<T>>
+where I: IntoIterator<Item = DecidedResult<T>> {
+    let mut front: Vec<(Vec<f64>, DecidedResult<T>)> = Vec::new();
+    for result in results {
+        let values: Vec<f64> = objectives.iter().map(|objective| objective(&result.result)).collect();
+        if front.iter().any(|(existing, _)| dominates(existing, &values)) {
+            continue;
+        }
+        front.retain(|(existing, _)| !dominates(&values, existing));
+        front.push((values, result));
+    }
+    front.into_iter().map(|(_, result)| result).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+    use crate::branching_generators::alternatives;
+    use crate::decisions::try_evaluate_decided;
+    use crate::event_graph::{BoxedOperation, EventDAG};
+    use super::*;
+
+    /// Net present value and carbon storage of a management program.
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    struct Outcome {
+        npv: f64,
+        carbon: f64,
+    }
+
+    fn npv(outcome: &Outcome) -> f64 { outcome.npv }
+    fn carbon(outcome: &Outcome) -> f64 { outcome.carbon }
+
+    fn results(outcomes: &[(f64, f64)]) -> Vec<DecidedResult<Outcome>> {
+        outcomes.iter().enumerate()
+            .map(|(chain, (npv, carbon))| DecidedResult { chain, decisions: Vec::new(), result: Outcome { npv: *npv, carbon: *carbon } })
+            .collect()
+    }
+
+    #[test]
+    fn dominated_results_are_dropped() {
+        let outcomes = results(&[(100.0, 10.0), (50.0, 50.0), (40.0, 40.0), (120.0, 5.0), (100.0, 12.0), (50.0, 50.0)]);
+        let front = select_pareto(outcomes, &[npv, carbon]);
+        assert_eq!(vec![1, 3, 4, 5], front.iter().map(|selected| selected.chain).collect::<Vec<_>>());
+        let richest = select_pareto(results(&[(1.0, 0.0), (3.0, 0.0), (2.0, 9.0)]), &[npv]);
+        assert_eq!(vec![1], richest.iter().map(|selected| selected.chain).collect::<Vec<_>>());
+        assert!(select_pareto(Vec::new(), &[npv, carbon]).is_empty());
+    }
+
+    #[test]
+    fn front_of_evaluated_chains_keeps_decisions() {
+        let root = EventDAG::new_node(Box::new(|outcome: Outcome| outcome));
+        let programs: Vec<BoxedOperation<Outcome>> = vec![
+            Box::new(|outcome| Outcome { npv: outcome.npv + 10.0, ..outcome }),
+            Box::new(|outcome| Outcome { carbon: outcome.carbon + 10.0, ..outcome }),
+            Box::new(|outcome| Outcome { npv: outcome.npv + 5.0, carbon: outcome.carbon + 5.0 }),
+            Box::new(|outcome| Outcome { npv: outcome.npv + 1.0, carbon: outcome.carbon + 1.0 }),
+        ];
+        let nodes = alternatives(vec![Rc::clone(&root)], programs).unwrap();
+        for (node, label) in nodes.iter().zip(["harvest", "reserve", "thinning", "fertilization"]) {
+            node.borrow_mut().set_label(label);
+        }
+        let results = try_evaluate_decided(&root, Outcome { npv: 0.0, carbon: 0.0 }).unwrap();
+        let front = select_pareto(results, &[npv, carbon]);
+        let programs: Vec<Vec<String>> = front.iter().map(|selected| selected.program()).collect();
+        assert_eq!(vec![vec!["harvest"], vec!["reserve"], vec!["thinning"]], programs);
+    }
+}
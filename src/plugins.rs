@@ -0,0 +1,198 @@
+use std::any::TypeId;
+use std::collections::HashMap;
+use std::error::Error;
+use std::ffi::OsStr;
+use std::fmt;
+use libloading::Library;
+use crate::configuration_utils::ParameteredOperation;
+use crate::simulation_runner::OperationRegistry;
+
+/// Version of the plugin declaration layout, increased on incompatible changes.
+pub const PLUGIN_ABI_VERSION: u32 = 1;
+pub const CRATE_VERSION: &str = env!("CARGO_PKG_VERSION");
+pub const RUSTC_VERSION: &str = env!("METSI_RUSTC_VERSION");
+/// Symbol of the PluginDeclaration exported by plugins with export_plugin.
+pub const DECLARATION_SYMBOL: &[u8] = b"METSI_PLUGIN_DECLARATION\0";
+
+/// Declaration exported by an operation plugin, checked for compatibility before registering
+/// the operations of the plugin.
+#[repr(C)]
+pub struct PluginDeclaration {
+    pub abi_version: u32,
+    pub crate_version: &'static str,
+    pub rustc_version: &'static str,
+    pub register: fn(&mut PluginRegistrar),
+}
+
+/// Export the registering function of an operation plugin, built as a cdylib against the same
+/// version of this crate and the same compiler as the engine loading it.
+///
+/// fn register(registrar: &mut PluginRegistrar) {
+///     registrar.register::<Stand>("grow", grow);
+/// }
+/// export_plugin!(register);
+#[macro_export]
+macro_rules! export_plugin {
+    ($register:expr) => {
+        #[no_mangle]
+        pub static METSI_PLUGIN_DECLARATION: $crate::plugins::PluginDeclaration = $crate::plugins::PluginDeclaration {
+            abi_version: $crate::plugins::PLUGIN_ABI_VERSION,
+            crate_version: $crate::plugins::CRATE_VERSION,
+            rustc_version: $crate::plugins::RUSTC_VERSION,
+            register: $register,
+        };
+    };
+}
+
+/// Reasons for failing to load an operation plugin or to collect its operations.
+#[derive(Debug)]
+pub enum PluginError {
+    Load(libloading::Error),
+    /// The plugin was built against an incompatible declaration layout, crate or compiler.
+    Incompatible { plugin: String, found: String, expected: String },
+    /// The operation is registered by several plugins for the same state type.
+    Duplicate(&'static str),
+}
+
+impl fmt::Display for PluginError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PluginError::Load(err) => write!(f, "{err}"),
+            PluginError::Incompatible { plugin, found, expected } =>
+                write!(f, "plugin '{plugin}' is built for {found}, expected {expected}"),
+            PluginError::Duplicate(name) => write!(f, "operation '{name}' is registered by several plugins"),
+        }
+    }
+}
+
+impl Error for PluginError {}
+
+impl From<libloading::Error> for PluginError {
+    fn from(err: libloading::Error) -> Self {
+        PluginError::Load(err)
+    }
+}
+
+/// Names and type-erased function pointers of operations registered for a state type.
+type ErasedOperations = Vec<(&'static str, fn())>;
+
+/// Operations registered by plugins, by TypeId of their state type. Operations are held as
+/// type-erased function pointers and only handed out for the state type they were registered
+/// for. TypeIds agree across the engine and its plugins as they are checked to be built with the
+/// same compiler against the same version of this crate.
+#[derive(Default)]
+pub struct PluginRegistrar {
+    operations: HashMap<TypeId, ErasedOperations>,
+}
+
+impl PluginRegistrar {
+    /// Register the operation for states of type T.
+    pub fn register<T: 'static>(&mut self, name: &'static str, operation: ParameteredOperation<'static, T>) {
+        // SAFETY: function pointers have the same size and are only transmuted back into
+        // ParameteredOperation<T> for the same T, by its TypeId.
+        let erased = unsafe { std::mem::transmute::<ParameteredOperation<'static, T>, fn()>(operation) };
+        self.operations.entry(TypeId::of::<T>()).or_default().push((name, erased));
+    }
+}
+
+fn check(plugin: &str, declaration: &PluginDeclaration) -> Result<(), PluginError> {
+    let found = (declaration.abi_version, declaration.crate_version, declaration.rustc_version);
+    match found == (PLUGIN_ABI_VERSION, CRATE_VERSION, RUSTC_VERSION) {
+        true => Ok(()),
+        false => Err(PluginError::Incompatible {
+            plugin: plugin.to_string(),
+            found: format!("ABI {} of metsi-rust {} with {}", found.0, found.1, found.2),
+            expected: format!("ABI {PLUGIN_ABI_VERSION} of metsi-rust {CRATE_VERSION} with {RUSTC_VERSION}"),
+        }),
+    }
+}
+
+/// Operations loaded from plugin shared libraries. Loaded libraries are never unloaded, so the
+/// collected operations stay valid for the rest of the process.
+#[derive(Default)]
+pub struct OperationPlugins {
+    registrar: PluginRegistrar,
+}
+
+impl OperationPlugins {
+    pub fn new() -> OperationPlugins {
+        OperationPlugins::default()
+    }
+
+    /// Load the plugin library at the path and register its operations.
+    ///
+    /// # Safety
+    /// Loading a library runs its initialization code. The library must export its declaration
+    /// with export_plugin, as the exported symbol is trusted to be a PluginDeclaration.
+    pub unsafe fn load<P: AsRef<OsStr>>(&mut self, path: P) -> Result<(), PluginError> {
+        let library = Library::new(path.as_ref())?;
+        let declaration = *library.get::<*const PluginDeclaration>(DECLARATION_SYMBOL)?;
+        self.add(&path.as_ref().to_string_lossy(), &*declaration)?;
+        std::mem::forget(library);
+        Ok(())
+    }
+
+    /// Register the operations of a plugin declaration, after checking its compatibility.
+    pub fn add(&mut self, plugin: &str, declaration: &PluginDeclaration) -> Result<(), PluginError> {
+        check(plugin, declaration)?;
+        (declaration.register)(&mut self.registrar);
+        Ok(())
+    }
+
+    /// Operations registered by the loaded plugins for states of type T.
+    pub fn registry<T: 'static>(&self) -> Result<OperationRegistry<T>, PluginError> {
+        let mut registry = OperationRegistry::new();
+        for (name, erased) in self.registrar.operations.get(&TypeId::of::<T>()).into_iter().flatten() {
+            // SAFETY: registered from a ParameteredOperation<T> of the same TypeId.
+            let operation = unsafe { std::mem::transmute::<fn(), ParameteredOperation<'static, T>>(*erased) };
+            if registry.insert(*name, operation).is_some() {
+                return Err(PluginError::Duplicate(name));
+            }
+        }
+        Ok(registry)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::configuration_utils::ParameterMap;
+    use super::*;
+
+    fn increment(val: i32, params: ParameterMap) -> i32 {
+        val + params.get("increase").unwrap().parse::<i32>().unwrap()
+    }
+
+    fn scale(val: f64, params: ParameterMap) -> f64 {
+        val * params.get("factor").unwrap().parse::<f64>().unwrap()
+    }
+
+    fn register(registrar: &mut PluginRegistrar) {
+        registrar.register::<i32>("increment", increment);
+        registrar.register::<f64>("scale", scale);
+    }
+
+    export_plugin!(register);
+
+    #[test]
+    fn operations_are_registered_by_state_type() {
+        let mut plugins = OperationPlugins::new();
+        plugins.add("growth", &METSI_PLUGIN_DECLARATION).unwrap();
+        let registry = plugins.registry::<i32>().unwrap();
+        assert_eq!(vec![&"increment"], registry.keys().collect::<Vec<_>>());
+        assert_eq!(12, registry["increment"](10, ParameterMap::from([("increase", "2")])));
+        assert_eq!(5.0, plugins.registry::<f64>().unwrap()["scale"](2.0, ParameterMap::from([("factor", "2.5")])));
+        assert!(plugins.registry::<u8>().unwrap().is_empty());
+
+        plugins.add("growth", &METSI_PLUGIN_DECLARATION).unwrap();
+        assert!(matches!(plugins.registry::<i32>(), Err(PluginError::Duplicate("increment"))));
+    }
+
+    #[test]
+    fn incompatible_plugins_are_rejected() {
+        let declaration = PluginDeclaration { crate_version: "0.0.1", ..METSI_PLUGIN_DECLARATION };
+        let mut plugins = OperationPlugins::new();
+        assert!(matches!(plugins.add("old", &declaration), Err(PluginError::Incompatible { .. })));
+        assert!(plugins.registry::<i32>().unwrap().is_empty());
+        assert!(matches!(unsafe { plugins.load("/nonexistent/libgrowth.so") }, Err(PluginError::Load(_))));
+    }
+}
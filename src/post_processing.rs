@@ -0,0 +1,117 @@
+use std::collections::BTreeMap;
+use serde::{Deserialize, Serialize};
+use crate::batch_runner::BatchOutcome;
+use crate::event_graph::ChainResults;
+
+/// Derived variable computed from the result state of a single event chain.
+pub type ChainVariable<T> = Box<dyn Fn(&T) -> f64 + Send + Sync>;
+/// Derived variable computed across the result states of all event chains of an entity, keyed
+/// by chain id.
+pub type EntityVariable<T> = Box<dyn Fn(&ChainResults<T>) -> f64 + Send + Sync>;
+/// Derived variable values by variable name.
+pub type DerivedValues = BTreeMap<String, f64>;
+
+/// Derived variables of the chain results of a single entity.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct DerivedResults {
+    /// Chain variables of each event chain by chain id.
+    pub chains: ChainResults<DerivedValues>,
+    /// Entity variables computed across the event chains.
+    pub entity: DerivedValues,
+}
+
+/// PostProcessor computes registered derived variables from chain results before export. It
+/// processes the results of one entity at a time, so it is usable while streaming results.
+pub struct PostProcessor<T> {
+    chain_variables: Vec<(String, ChainVariable<T>)>,
+    entity_variables: Vec<(String, EntityVariable<T>)>,
+}
+
+impl<T> Default for PostProcessor<T> {
+    fn default() -> Self {
+        PostProcessor { chain_variables: Vec::new(), entity_variables: Vec::new() }
+    }
+}
+
+impl<T> PostProcessor<T> {
+    pub fn new() -> PostProcessor<T> {
+        PostProcessor::default()
+    }
+
+    /// Register a variable computed from the result of each event chain. A variable registered
+    /// again under the same name replaces the earlier one.
+    pub fn add_chain_variable<F: Fn(&T) -> f64 + Send + Sync + 'static>(&mut self, name: &str, variable: F) {
+        self.chain_variables.retain(|(existing, _)| existing != name);
+        self.chain_variables.push((name.to_string(), Box::new(variable)));
+    }
+
+    /// Register a variable computed across the results of all event chains of an entity. A
+    /// variable registered again under the same name replaces the earlier one.
+    pub fn add_entity_variable<F: Fn(&ChainResults<T>) -> f64 + Send + Sync + 'static>(&mut self, name: &str, variable: F) {
+        self.entity_variables.retain(|(existing, _)| existing != name);
+        self.entity_variables.push((name.to_string(), Box::new(variable)));
+    }
+
+    /// Compute the derived variables of the chain results of a single entity.
+    pub fn process(&self, results: &ChainResults<T>) -> DerivedResults {
+        let chains = results.iter()
+            .map(|(chain, state)| (*chain, self.chain_variables.iter()
+                .map(|(name, variable)| (name.clone(), variable(state)))
+                .collect()))
+            .collect();
+        let entity = self.entity_variables.iter()
+            .map(|(name, variable)| (name.clone(), variable(results)))
+            .collect();
+        DerivedResults { chains, entity }
+    }
+
+    /// Compute the derived variables of each entity in the batch outcome.
+    pub fn process_outcome<K: Ord + Clone>(&self, outcome: &BatchOutcome<K, T>) -> BTreeMap<K, DerivedResults> {
+        outcome.results.iter()
+            .map(|(entity, results)| (entity.clone(), self.process(results)))
+            .collect()
+    }
+}
+
+/// Discount a value realized after the given number of years into its present value with the
+/// given yearly interest rate, for derived variables such as discounted income.
+pub fn discounted(value: f64, rate: f64, years: f64) -> f64 {
+    value / (1.0 + rate).powf(years)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::batch_runner::run_batch;
+    use crate::event_graph::EventDAG;
+    use crate::time_points::TimedState;
+    use super::*;
+
+    #[test]
+    fn variables_are_derived() {
+        let mut processor: PostProcessor<TimedState<f64>> = PostProcessor::new();
+        processor.add_chain_variable("mean_annual_increment", |result| result.state / result.time as f64);
+        processor.add_chain_variable("discounted_income", |result| discounted(result.state, 0.03, result.time as f64));
+        processor.add_entity_variable("chains", |results| results.len() as f64);
+        processor.add_entity_variable("best_volume", |results| results.values().map(|result| result.state).fold(f64::MIN, f64::max));
+
+        let results = ChainResults::from([(0, TimedState { time: 20, state: 100.0 }), (2, TimedState { time: 40, state: 300.0 })]);
+        let derived = processor.process(&results);
+        assert_eq!(2, derived.chains.len());
+        assert_eq!(5.0, derived.chains[&0]["mean_annual_increment"]);
+        assert_eq!(7.5, derived.chains[&2]["mean_annual_increment"]);
+        assert!((derived.chains[&0]["discounted_income"] - 55.3676).abs() < 1e-4);
+        assert_eq!(DerivedValues::from([("best_volume".to_string(), 300.0), ("chains".to_string(), 2.0)]), derived.entity);
+    }
+
+    #[test]
+    fn outcomes_are_processed() {
+        let root = EventDAG::new_node(Box::new(|x: i32| x * 2));
+        let mut processor: PostProcessor<i32> = PostProcessor::new();
+        processor.add_chain_variable("value", |result| *result as f64);
+        processor.add_chain_variable("value", |result| *result as f64 + 0.5);
+        let derived = processor.process_outcome(&run_batch(&root, [(1, 1), (2, 5)]));
+        assert_eq!(10.5, derived[&2].chains[&0]["value"]);
+        assert_eq!(1, derived[&1].chains[&0].len());
+        assert!(derived[&1].entity.is_empty());
+    }
+}
@@ -0,0 +1,146 @@
+use serde::{Deserialize, Serialize};
+use crate::collectors::Projector;
+use crate::event_graph::{EvaluationFailure, EventDAG, EventNode, EventNodes};
+
+/// Result of an event chain weighted by the joint probability of the events of the chain.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WeightedResult<T> {
+    /// Index of the event chain in evaluation order.
+    pub chain: usize,
+    pub probability: f64,
+    pub result: T,
+}
+
+/// Probability weighted expectation of a variable over the outcomes of weighted event chains.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct Expectation {
+    /// Total probability of the chains producing results, below 1 when chains are pruned.
+    pub probability: f64,
+    /// Expected value given that the chain produces a result.
+    pub mean: f64,
+    pub variance: f64,
+}
+
+impl Expectation {
+    /// Expectation of the projected results, conditioned on the chains producing results. All
+    /// values are 0 for a total probability of 0.
+    pub fn of<T>(results: &[WeightedResult<T>], projector: Projector<T, f64>) -> Expectation {
+        let values: Vec<(f64, f64)> = results.iter().map(|weighted| (weighted.probability, projector(&weighted.result))).collect();
+        Expectation::of_values(&values)
+    }
+
+    /// Expectation of the values weighted by the probabilities of the given pairs.
+    fn of_values(values: &[(f64, f64)]) -> Expectation {
+        let probability: f64 = values.iter().map(|(probability, _)| probability).sum();
+        if probability <= 0.0 {
+            return Expectation::default();
+        }
+        let mean = values.iter().map(|(probability, value)| probability * value).sum::<f64>() / probability;
+        let variance = values.iter()
+            .map(|(probability, value)| probability * (value - mean).powi(2))
+            .sum::<f64>() / probability;
+        Expectation { probability, mean, variance }
+    }
+
+    pub fn std_dev(&self) -> f64 {
+        self.variance.sqrt()
+    }
+}
+
+/// Joint probability of the events of the chain, the product of the node probabilities along it.
+/// A node shared by several chains contributes to each of them.
+pub fn chain_probability<T: Clone>(chain: &[EventNode<T>]) -> f64 {
+    chain.iter().map(|node| node.borrow().probability()).product()
+}
+
+/// Joint probabilities of the unique event chains starting from the given root, in chain id order.
+pub fn chain_probabilities<T: Clone>(root: &EventNode<T>) -> Vec<f64> {
+    EventDAG::node_chains(root).iter().map(|chain| chain_probability(chain)).collect()
+}
+
+/// Evaluate the unique event chains as EventDAG::try_evaluate_chains, weighting the results by
+/// the joint probabilities of their chains so that the results form a discrete distribution of
+/// outcomes. Pruned chains produce no results, leaving their probabilities unaccounted for.
+pub fn try_evaluate_weighted<T: Clone>(root: &EventNode<T>, payload: T) -> Result<Vec<WeightedResult<T>>, EvaluationFailure> {
+    let chains: Vec<EventNodes<T>> = EventDAG::node_chains(root);
+    let results = EventDAG::try_evaluate_indexed(&chains, |_| payload.clone())?;
+    Ok(results.into_iter()
+        .map(|(chain, result)| WeightedResult { chain, probability: chain_probability(&chains[chain]), result })
+        .collect())
+}
+
+/// Evaluate the unique event chains weighted as try_evaluate_weighted, combining the projected
+/// results into their expectation without keeping the results of the chains. Each result is
+/// projected as soon as its chain is evaluated, keeping only the projected values.
+pub fn expected_value<T: Clone>(root: &EventNode<T>, payload: T, projector: Projector<T, f64>) -> Result<Expectation, EvaluationFailure> {
+    let mut values = Vec::new();
+    for (index, chain) in EventDAG::node_chains(root).iter().enumerate() {
+        let evaluated = EventDAG::try_evaluate_indexed(std::slice::from_ref(chain), |_| payload.clone())
+            .map_err(|failure| EvaluationFailure { chain: index, ..failure })?;
+        values.extend(evaluated.into_iter().map(|(_, result)| (chain_probability(chain), projector(&result))));
+    }
+    Ok(Expectation::of_values(&values))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+    use crate::branching_generators::{alternatives, sequence};
+    use crate::event_graph::{prune, BoxedOperation};
+    use super::*;
+
+    /// Storm damage with probability 0.2 in each of two periods, the nodes of the second period
+    /// shared by both outcomes of the first.
+    fn storms() -> EventNode<i32> {
+        let root = EventDAG::new_node(Box::new(|x: i32| x));
+        let mut frontier = vec![Rc::clone(&root)];
+        for _ in 0..2 {
+            let outcomes: Vec<BoxedOperation<i32>> = vec![Box::new(|x| x + 10), Box::new(|x| if x < 0 { prune() } else { x / 2 })];
+            frontier = alternatives(frontier, outcomes).unwrap();
+            frontier[0].borrow_mut().set_probability(0.8);
+            frontier[1].borrow_mut().set_probability(0.2);
+        }
+        sequence(frontier, [Box::new(|x| x) as BoxedOperation<i32>]).unwrap();
+        root
+    }
+
+    #[test]
+    fn chain_probabilities_are_joint() {
+        let probabilities = chain_probabilities(&storms());
+        assert_eq!(4, probabilities.len());
+        for (probability, expected) in probabilities.iter().zip([0.64, 0.16, 0.16, 0.04]) {
+            assert!((probability - expected).abs() < 1e-12);
+        }
+        assert!((probabilities.iter().sum::<f64>() - 1.0).abs() < 1e-12);
+    }
+
+    fn as_float(x: &i32) -> f64 { *x as f64 }
+
+    #[test]
+    fn expectations_are_weighted() {
+        let expectation = expected_value(&storms(), 0, as_float).unwrap();
+        assert!((expectation.probability - 1.0).abs() < 1e-12);
+        assert!((expectation.mean - 15.2).abs() < 1e-12);
+        assert!((expectation.variance - 44.96).abs() < 1e-9);
+
+        let pruned = expected_value(&storms(), -30, as_float).unwrap();
+        assert!((pruned.probability - 0.64).abs() < 1e-12);
+        assert_eq!((-10.0, 0.0), (pruned.mean, pruned.variance));
+        assert_eq!(Expectation::default(), Expectation::of::<i32>(&[], as_float));
+
+        let failing = storms();
+        EventDAG::node_chains(&failing)[1][2].borrow_mut().set_operation(Box::new(|_| panic!("failed")));
+        let failure = expected_value(&failing, 0, as_float).unwrap_err();
+        assert_eq!((1, 2), (failure.chain, failure.operation));
+    }
+
+    #[test]
+    fn results_are_weighted() {
+        let results = try_evaluate_weighted(&storms(), 0).unwrap();
+        assert_eq!(vec![20, 5, 10, 0], results.iter().map(|weighted| weighted.result).collect::<Vec<_>>());
+        assert!((results[3].probability - 0.04).abs() < 1e-12);
+
+        let pruned = try_evaluate_weighted(&storms(), -30).unwrap();
+        assert_eq!(vec![0], pruned.iter().map(|weighted| weighted.chain).collect::<Vec<_>>());
+    }
+}
@@ -0,0 +1,146 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use serde::Serialize;
+use crate::configuration_utils::ParameterMap;
+use crate::event_graph::{ChainResults, EventDAG, EventNode, NodeMode};
+use crate::output::{ColumnType, ColumnValue, RowMapping};
+
+/// Effective parameter values by parameter name, in name order.
+pub type EffectiveParameters = BTreeMap<&'static str, &'static str>;
+
+/// Operation applied along an event chain with the effective values of its parameters.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct AppliedOperation {
+    /// Position of the operation within the event chain, the starting node being 0.
+    pub position: usize,
+    pub operation: &'static str,
+    pub parameters: EffectiveParameters,
+}
+
+/// Operations applied along an event chain, displayed as `thin(ratio=0.3) > grow()`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct ChainProvenance(pub Vec<AppliedOperation>);
+
+/// Provenance of the event chains by chain id.
+pub type ParameterProvenance = BTreeMap<usize, ChainProvenance>;
+
+impl fmt::Display for AppliedOperation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let parameters: Vec<String> = self.parameters.iter().map(|(name, value)| format!("{name}={value}")).collect();
+        write!(f, "{}({})", self.operation, parameters.join(","))
+    }
+}
+
+impl fmt::Display for ChainProvenance {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let operations: Vec<String> = self.0.iter().map(|operation| operation.to_string()).collect();
+        write!(f, "{}", operations.join(" > "))
+    }
+}
+
+/// Effective parameters of an operation: the given parameters over the defaults.
+pub fn effective_parameters(given: Option<&ParameterMap<'static>>, defaults: &[(&'static str, &'static str)]) -> EffectiveParameters {
+    let mut parameters: EffectiveParameters = defaults.iter().copied().collect();
+    parameters.extend(given.into_iter().flat_map(|given| given.iter().map(|(name, value)| (*name, *value))));
+    parameters
+}
+
+/// Provenance of the event chains of the graph, the operations being identified by the labels of
+/// their nodes and their effective parameters given by the lookup. Unlabeled nodes, nodes
+/// skipped by their mode and labels without parameters, such as the root, are left out.
+pub fn chain_provenance<T: Clone, F>(root: &EventNode<T>, parameters: F) -> ParameterProvenance
+where F: Fn(&'static str) -> Option<EffectiveParameters> {
+    EventDAG::node_chains(root).iter().enumerate()
+        .map(|(chain, nodes)| {
+            let applied = nodes.iter().enumerate()
+                .filter_map(|(position, node)| {
+                    let node = node.borrow();
+                    let operation = node.label().filter(|_| node.mode() != NodeMode::Skipped)?;
+                    Some(AppliedOperation { position, operation, parameters: parameters(operation)? })
+                })
+                .collect();
+            (chain, ChainProvenance(applied))
+        })
+        .collect()
+}
+
+/// State of a result row with the provenance of its event chain, for self-describing outputs.
+/// Rows have the columns of the state followed by a parameters column.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct WithProvenance<T> {
+    pub state: T,
+    pub parameters: String,
+}
+
+impl<T> WithProvenance<T> {
+    /// Pair the chain results with the provenance of their event chains by chain id.
+    pub fn of_results(results: ChainResults<T>, provenance: &ParameterProvenance) -> ChainResults<WithProvenance<T>> {
+        results.into_iter()
+            .map(|(chain, state)| (chain, WithProvenance { state, parameters: provenance.get(&chain).map_or(String::new(), |chain| chain.to_string()) }))
+            .collect()
+    }
+}
+
+impl<T: RowMapping> RowMapping for WithProvenance<T> {
+    fn column_names() -> Vec<&'static str> {
+        let mut names = T::column_names();
+        names.push("parameters");
+        names
+    }
+
+    fn column_values(&self) -> Vec<String> {
+        let mut values = self.state.column_values();
+        values.push(self.parameters.clone());
+        values
+    }
+
+    fn column_types() -> Vec<ColumnType> {
+        let mut types = T::column_types();
+        types.push(ColumnType::Text);
+        types
+    }
+
+    fn typed_values(&self) -> Vec<ColumnValue> {
+        let mut values = self.state.typed_values();
+        values.push(ColumnValue::Text(self.parameters.clone()));
+        values
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+    use crate::branching_generators::{alternatives, sequence};
+    use crate::event_graph::BoxedOperation;
+    use crate::output::csv::CsvWriter;
+    use super::*;
+
+    fn add(amount: i32) -> BoxedOperation<i32> {
+        Box::new(move |x| x + amount)
+    }
+
+    #[test]
+    fn chains_record_the_effective_parameters_of_their_operations() {
+        let root = EventDAG::new_node(add(0));
+        let branches = alternatives(vec![Rc::clone(&root)], [add(1), add(2)]).unwrap();
+        branches[0].borrow_mut().set_label("thin");
+        branches[1].borrow_mut().set_label("clearcut");
+        let ends = sequence(branches, [add(10)]).unwrap();
+        ends[0].borrow_mut().set_label("grow");
+
+        let given = ParameterMap::from([("ratio", "0.3")]);
+        let lookup = |operation: &'static str| match operation {
+            "thin" => Some(effective_parameters(Some(&given), &[("ratio", "0.5"), ("from", "below")])),
+            _ => Some(effective_parameters(None, &[])),
+        };
+        let provenance = chain_provenance(&root, lookup);
+        assert_eq!("thin(from=below,ratio=0.3) > grow()", provenance[&0].to_string());
+        assert_eq!(vec![1, 2], provenance[&1].0.iter().map(|applied| applied.position).collect::<Vec<_>>());
+
+        let rows = WithProvenance::of_results(EventDAG::evaluate_chains_by_id(&root, 0), &provenance);
+        let mut writer = CsvWriter::new(Vec::new());
+        writer.write_results(&"a", &rows).unwrap();
+        assert_eq!("entity,chain,value,parameters\na,0,11,\"thin(from=below,ratio=0.3) > grow()\"\na,1,12,clearcut() > grow()\n",
+            String::from_utf8(writer.into_inner()).unwrap());
+    }
+}
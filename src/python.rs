@@ -0,0 +1,315 @@
+use std::cell::RefCell;
+use std::panic::resume_unwind;
+use pyo3::exceptions::{PyRuntimeError, PyValueError};
+use rayon::prelude::*;
+use rayon::ThreadPoolBuilder;
+use pyo3::prelude::*;
+use pyo3::types::{PyDict, PyList, PyTuple};
+use crate::branching_generators::generator_map;
+use crate::event_graph::{BoxedOperation, EvaluationFailure, EventDAG, EventNode, OperationChain};
+use crate::graph_statistics::GraphStatistics;
+
+thread_local! {
+    /// Python state objects referred to by the PyState handles of the thread.
+    static STATES: RefCell<Vec<Py<PyAny>>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Handle of a Python state object held by the evaluating thread. Event graphs clone their
+/// payloads into branches, which Python objects only allow while attached to the interpreter,
+/// so Python objects are passed through the graph by handle.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PyState(usize);
+
+impl PyState {
+    fn store(object: Py<PyAny>) -> PyState {
+        STATES.with_borrow_mut(|states| {
+            states.push(object);
+            PyState(states.len() - 1)
+        })
+    }
+
+    fn object(self, py: Python<'_>) -> Py<PyAny> {
+        STATES.with_borrow(|states| states[self.0].clone_ref(py))
+    }
+
+    /// Number of state objects held by the thread, for releasing the objects stored after it.
+    fn mark() -> usize {
+        STATES.with_borrow(Vec::len)
+    }
+
+    fn release_from(mark: usize) {
+        STATES.with_borrow_mut(|states| states.truncate(mark))
+    }
+}
+
+/// Python callable declared as an operation, with its parameters.
+struct PythonOperation {
+    callable: Py<PyAny>,
+    parameters: Py<PyDict>,
+}
+
+/// Declaration of generators over Python operations, shareable across threads for compiling an
+/// event graph in each evaluating thread.
+struct PythonDeclaration {
+    generators: Vec<(String, Vec<PythonOperation>)>,
+    /// Whether operations are called with their parameters as keyword arguments instead of a
+    /// parameter dict.
+    keyword_parameters: bool,
+}
+
+impl PythonDeclaration {
+    /// Wrap the Python operation into an operation of the event graph. The operation attaches
+    /// into the interpreter for the duration of the call only. A raised Python exception fails
+    /// the event chain with the exception as its error.
+    fn bind(&self, py: Python<'_>, operation: &PythonOperation) -> BoxedOperation<PyState> {
+        let callable = operation.callable.clone_ref(py);
+        let parameters = operation.parameters.clone_ref(py);
+        let keyword_parameters = self.keyword_parameters;
+        Box::new(move |state: PyState| Python::attach(|py| {
+            let called = match keyword_parameters {
+                true => callable.call(py, (state.object(py),), Some(parameters.bind(py))),
+                false => callable.call1(py, (state.object(py), parameters.clone_ref(py))),
+            };
+            match called {
+                Ok(result) => PyState::store(result),
+                Err(err) => resume_unwind(Box::new(err.to_string())),
+            }
+        }))
+    }
+
+    fn compile(&self, py: Python<'_>) -> PyResult<EventNode<PyState>> {
+        let generators = generator_map::<PyState>();
+        let root: EventNode<PyState> = EventDAG::new_node(Box::new(|state| state));
+        let mut nodes = vec![root.clone()];
+        for (generator_name, operations) in self.generators.iter() {
+            let generator_fn = generators.get(generator_name.as_str())
+                .ok_or_else(|| PyValueError::new_err(format!("unknown generator '{generator_name}'")))?;
+            let chain: OperationChain<PyState> = operations.iter().map(|operation| self.bind(py, operation)).collect();
+            nodes = generator_fn(nodes, chain).map_err(|err| PyValueError::new_err(err.to_string()))?;
+        }
+        Ok(root)
+    }
+}
+
+/// Evaluate the event chains for the state, releasing the intermediate states afterwards. The
+/// graph is walked without attaching into the interpreter, operations attaching for their calls.
+fn evaluate(root: &EventNode<PyState>, state: Py<PyAny>) -> Result<Vec<Py<PyAny>>, EvaluationFailure> {
+    let mark = PyState::mark();
+    let evaluated = EventDAG::try_evaluate_chains(root, PyState::store(state));
+    Python::attach(|py| {
+        let evaluated = evaluated.map(|results| results.into_iter().map(|result| result.object(py)).collect());
+        PyState::release_from(mark);
+        evaluated
+    })
+}
+
+fn failure_error(failure: &EvaluationFailure) -> PyErr {
+    PyRuntimeError::new_err(format!("chain {}, {}: {}", failure.chain, failure.location(), failure.error))
+}
+
+/// Simulation compiled from a declaration of generators over Python operations, evaluating
+/// Python state objects.
+#[pyclass(unsendable, module = "metsi_rust")]
+pub struct Simulation {
+    declaration: PythonDeclaration,
+    root: EventNode<PyState>,
+}
+
+#[pymethods]
+impl Simulation {
+    /// Compile the declaration of (generator, [operation]) pairs over the operations dict of
+    /// Python callables. Operations are called with the state and a dict of their parameters, or
+    /// with their parameters as keyword arguments if keyword_parameters is set.
+    #[new]
+    #[pyo3(signature = (operations, declaration, parameters = None, keyword_parameters = false))]
+    fn new(
+        py: Python<'_>,
+        operations: &Bound<'_, PyDict>,
+        declaration: Vec<(String, Vec<String>)>,
+        parameters: Option<&Bound<'_, PyDict>>,
+        keyword_parameters: bool
+    ) -> PyResult<Simulation> {
+        let generators = declaration.into_iter().map(|(generator_name, operation_names)| {
+            let operations = operation_names.iter().map(|name| {
+                let callable = operations.get_item(name)?
+                    .ok_or_else(|| PyValueError::new_err(format!("unknown operation '{name}'")))?;
+                let parameters = match parameters.map(|parameters| parameters.get_item(name)).transpose()?.flatten() {
+                    Some(given) => given.cast::<PyDict>()?.copy()?,
+                    None => PyDict::new(py),
+                };
+                Ok(PythonOperation { callable: callable.unbind(), parameters: parameters.unbind() })
+            }).collect::<PyResult<Vec<PythonOperation>>>()?;
+            Ok((generator_name, operations))
+        }).collect::<PyResult<_>>()?;
+        let declaration = PythonDeclaration { generators, keyword_parameters };
+        let root = declaration.compile(py)?;
+        Ok(Simulation { declaration, root })
+    }
+
+    /// Evaluate the event chains for the state, returning the list of chain results.
+    fn run(&self, state: Py<PyAny>) -> PyResult<Vec<Py<PyAny>>> {
+        evaluate(&self.root, state).map_err(|failure| failure_error(&failure))
+    }
+
+    /// Evaluate the event chains for each of the states over the given number of worker threads,
+    /// isolating failing states. The interpreter is released while evaluating, operations
+    /// attaching for their calls. Returns the dict of chain results by state index and the list
+    /// of failures as (index, chain, operation, error) tuples.
+    #[pyo3(signature = (states, threads = 1))]
+    fn run_batch<'py>(&self, py: Python<'py>, states: Vec<Py<PyAny>>, threads: usize) -> PyResult<(Bound<'py, PyDict>, Bound<'py, PyList>)> {
+        let declaration = &self.declaration;
+        let evaluated = py.detach(|| {
+            let pool = ThreadPoolBuilder::new().num_threads(threads).build()?;
+            let factory = || Python::attach(|py| declaration.compile(py).expect("declaration compiled on construction"));
+            Ok::<_, rayon::ThreadPoolBuildError>(pool.install(|| {
+                states.into_par_iter()
+                    .enumerate()
+                    .map_init(factory, |root, (index, state)| (index, evaluate(root, state)))
+                    .collect::<Vec<_>>()
+            }))
+        }).map_err(|err| PyRuntimeError::new_err(err.to_string()))?;
+        let results = PyDict::new(py);
+        let failures = PyList::empty(py);
+        for (index, evaluation) in evaluated {
+            match evaluation {
+                Ok(chain_results) => results.set_item(index, chain_results)?,
+                Err(failure) => failures.append(PyTuple::new(py, [
+                    index.into_pyobject(py)?.into_any(),
+                    failure.chain.into_pyobject(py)?.into_any(),
+                    failure.operation.into_pyobject(py)?.into_any(),
+                    failure.error.into_pyobject(py)?.into_any()
+                ])?)?,
+            }
+        }
+        Ok((results, failures))
+    }
+
+    /// Statistics of the compiled event graph as a dict.
+    fn statistics<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        let statistics = GraphStatistics::of(&self.root);
+        let dict = PyDict::new(py);
+        dict.set_item("nodes", statistics.nodes)?;
+        dict.set_item("edges", statistics.edges)?;
+        dict.set_item("leaves", statistics.leaves)?;
+        dict.set_item("depth", statistics.depth)?;
+        dict.set_item("chains", statistics.chains)?;
+        Ok(dict)
+    }
+}
+
+/// Python module of the simulation engine.
+#[pymodule]
+#[pyo3(name = "metsi_rust")]
+fn python_module(module: &Bound<'_, PyModule>) -> PyResult<()> {
+    module.add_class::<Simulation>()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ffi::CString;
+    use super::*;
+
+    const OPERATIONS: &str = "
+def grow(state, parameters):
+    return {**state, 'volume': state['volume'] + float(parameters.get('increase', '1'))}
+
+def fertilize(state, /, **parameters):
+    return {**state, 'volume': state['volume'] * float(parameters['factor'])}
+
+def thin(state, parameters):
+    if state['volume'] < 10:
+        raise ValueError('too little volume to thin')
+    return {**state, 'volume': state['volume'] / 2}
+";
+
+    fn create_simulation(py: Python<'_>) -> PyResult<Simulation> {
+        let code = CString::new(OPERATIONS).unwrap();
+        let module = PyModule::from_code(py, &code, c"operations.py", c"operations")?;
+        let operations = PyDict::new(py);
+        operations.set_item("grow", module.getattr("grow")?)?;
+        operations.set_item("thin", module.getattr("thin")?)?;
+        let parameters = PyDict::new(py);
+        let grow_parameters = PyDict::new(py);
+        grow_parameters.set_item("increase", "5")?;
+        parameters.set_item("grow", grow_parameters)?;
+        let declaration = vec![
+            ("sequence".to_string(), vec!["grow".to_string()]),
+            ("alternatives".to_string(), vec!["grow".to_string(), "thin".to_string()])
+        ];
+        Simulation::new(py, &operations, declaration, Some(&parameters), false)
+    }
+
+    fn volume(py: Python<'_>, state: &Py<PyAny>) -> f64 {
+        state.bind(py).get_item("volume").unwrap().extract().unwrap()
+    }
+
+    #[test]
+    fn python_operations_are_evaluated() {
+        Python::initialize();
+        Python::attach(|py| {
+            let simulation = create_simulation(py).unwrap();
+            assert_eq!(2, simulation.statistics(py).unwrap().get_item("leaves").unwrap().unwrap().extract::<usize>().unwrap());
+
+            let state = PyDict::new(py);
+            state.set_item("volume", 10.0).unwrap();
+            let results = simulation.run(state.clone().into_any().unbind()).unwrap();
+            assert_eq!(vec![20.0, 7.5], results.iter().map(|result| volume(py, result)).collect::<Vec<f64>>());
+            assert_eq!(0, PyState::mark());
+
+            let small = PyDict::new(py);
+            small.set_item("volume", 1.0).unwrap();
+            let err = simulation.run(small.clone().into_any().unbind()).unwrap_err();
+            assert_eq!("RuntimeError: chain 1, operation 2: ValueError: too little volume to thin", err.to_string());
+
+            let (results, failures) = simulation.run_batch(py, vec![state.into_any().unbind(), small.into_any().unbind()], 1).unwrap();
+            assert_eq!(1, results.len());
+            let failure: (usize, usize, usize, String) = failures.get_item(0).unwrap().extract().unwrap();
+            assert_eq!((1, 1, 2, "ValueError: too little volume to thin".to_string()), failure);
+        });
+    }
+
+    #[test]
+    fn unknown_names_are_rejected() {
+        Python::initialize();
+        Python::attach(|py| {
+            let operations = PyDict::new(py);
+            let declaration = vec![("sequence".to_string(), vec!["grow".to_string()])];
+            let err = Simulation::new(py, &operations, declaration, None, false).err().unwrap();
+            assert_eq!("ValueError: unknown operation 'grow'", err.to_string());
+            let declaration = vec![("repeat".to_string(), vec![])];
+            let err = Simulation::new(py, &operations, declaration, None, false).err().unwrap();
+            assert_eq!("ValueError: unknown generator 'repeat'", err.to_string());
+        });
+    }
+
+    #[test]
+    fn batches_are_evaluated_in_parallel() {
+        Python::initialize();
+        Python::attach(|py| {
+            let code = CString::new(OPERATIONS).unwrap();
+            let module = PyModule::from_code(py, &code, c"operations.py", c"operations").unwrap();
+            let operations = PyDict::new(py);
+            operations.set_item("fertilize", module.getattr("fertilize").unwrap()).unwrap();
+            let parameters = PyDict::new(py);
+            let fertilize_parameters = PyDict::new(py);
+            fertilize_parameters.set_item("factor", "1.5").unwrap();
+            parameters.set_item("fertilize", fertilize_parameters).unwrap();
+            let declaration = vec![("alternatives".to_string(), vec!["fertilize".to_string(), "fertilize".to_string()])];
+            let simulation = Simulation::new(py, &operations, declaration, Some(&parameters), true).unwrap();
+
+            let states: Vec<Py<PyAny>> = (0..20).map(|volume| {
+                let state = PyDict::new(py);
+                state.set_item("volume", volume as f64).unwrap();
+                state.into_any().unbind()
+            }).collect();
+            let states_again = states.iter().map(|state| state.clone_ref(py)).collect();
+            let (results, failures) = simulation.run_batch(py, states, 4).unwrap();
+            assert!(failures.is_empty());
+            assert_eq!(20, results.len());
+            let chains: Vec<Py<PyAny>> = results.get_item(10).unwrap().unwrap().extract().unwrap();
+            assert_eq!(vec![15.0, 15.0], chains.iter().map(|result| volume(py, result)).collect::<Vec<f64>>());
+            let (serial, _) = simulation.run_batch(py, states_again, 1).unwrap();
+            assert_eq!(results.repr().unwrap().to_string(), serial.repr().unwrap().to_string());
+        });
+    }
+}
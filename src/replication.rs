@@ -0,0 +1,188 @@
+use std::collections::BTreeMap;
+use std::hash::Hash;
+use rayon::prelude::*;
+use rayon::ThreadPoolBuildError;
+use serde::{Deserialize, Serialize};
+use crate::batch_runner::{BatchOutcome, Threads};
+use crate::collectors::Projector;
+use crate::event_graph::{ChainResults, EvaluationFailure, EventNode};
+use crate::rng::{try_evaluate_seeded_chains, RngStream, Stochastic};
+
+/// Standard normal quantile for two-sided 95% confidence intervals.
+pub const Z_95: f64 = 1.959_963_984_540_054;
+
+/// Chain results of each replication of a stochastic simulation, in replication order.
+pub type Replications<T> = Vec<ChainResults<Stochastic<T>>>;
+
+/// Results of an event chain by replication index, replications pruning the chain leaving no
+/// result.
+pub type ReplicatedResults<T> = BTreeMap<usize, Stochastic<T>>;
+
+/// Summary statistics of a variable across replications.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct ReplicationSummary {
+    pub count: usize,
+    pub mean: f64,
+    /// Sample variance, 0 for fewer than two values.
+    pub variance: f64,
+}
+
+impl ReplicationSummary {
+    /// Summarize the given values.
+    pub fn of<I: IntoIterator<Item = f64>>(values: I) -> ReplicationSummary {
+        // Welford's online algorithm for numerically stable variance.
+        let (count, mean, squares) = values.into_iter().fold((0usize, 0.0f64, 0.0f64), |(count, mean, squares), value| {
+            let count = count + 1;
+            let delta = value - mean;
+            let mean = mean + delta / count as f64;
+            (count, mean, squares + delta * (value - mean))
+        });
+        let variance = if count > 1 { squares / (count - 1) as f64 } else { 0.0 };
+        ReplicationSummary { count, mean, variance }
+    }
+
+    pub fn std_dev(&self) -> f64 {
+        self.variance.sqrt()
+    }
+
+    /// Standard error of the mean.
+    pub fn std_error(&self) -> f64 {
+        match self.count {
+            0 => 0.0,
+            count => (self.variance / count as f64).sqrt(),
+        }
+    }
+
+    /// Normal approximation confidence interval of the mean with the given standard normal
+    /// quantile, such as Z_95.
+    pub fn confidence_interval(&self, z: f64) -> (f64, f64) {
+        let margin = z * self.std_error();
+        (self.mean - margin, self.mean + margin)
+    }
+}
+
+/// Summarize values by position across replications, such as values by chain or by time point.
+/// Positions missing from some replications are summarized over the replications having them.
+pub fn summarize_positions<V: AsRef<[f64]>>(replications: &[V]) -> Vec<ReplicationSummary> {
+    let positions = replications.iter().map(|values| values.as_ref().len()).max().unwrap_or(0);
+    (0..positions)
+        .map(|position| ReplicationSummary::of(replications.iter().filter_map(|values| values.as_ref().get(position).copied())))
+        .collect()
+}
+
+/// Summarize the projected variable of each event chain across replications by chain id, chains
+/// pruned in some replications being summarized over the replications having them.
+pub fn summarize_chains<T>(replications: &Replications<T>, projector: Projector<T, f64>) -> BTreeMap<usize, ReplicationSummary> {
+    let mut values: BTreeMap<usize, Vec<f64>> = BTreeMap::new();
+    for results in replications {
+        for (chain, result) in results {
+            values.entry(*chain).or_default().push(projector(&result.state));
+        }
+    }
+    values.into_iter().map(|(chain, values)| (chain, ReplicationSummary::of(values))).collect()
+}
+
+/// Regroup the chain results of indexed replications by chain id.
+fn by_chain<T, I: IntoIterator<Item = (usize, ChainResults<Stochastic<T>>)>>(replications: I) -> ChainResults<ReplicatedResults<T>> {
+    let mut chains = ChainResults::new();
+    for (replication, results) in replications {
+        for (chain, result) in results {
+            chains.entry(chain).or_insert_with(ReplicatedResults::new).insert(replication, result);
+        }
+    }
+    chains
+}
+
+/// Evaluate the event graph of stochastic payloads the given number of times for the state.
+/// Each replication evaluates with a random number stream derived from the given stream by
+/// replication index, its nodes deriving their streams from that as in try_evaluate_seeded_chains.
+pub fn run_replications<T: Clone>(root: &EventNode<Stochastic<T>>, state: T, stream: RngStream, replications: usize) -> Result<Replications<T>, EvaluationFailure> {
+    (0..replications)
+        .map(|replication| try_evaluate_seeded_chains(root, state.clone(), stream.derive(replication as u64)))
+        .collect()
+}
+
+/// Replicate the evaluation of each of the given independent entities as run_replications does,
+/// with the entity streams derived from the master seed by entity id as in run_seeded_batch. The
+/// results of each chain of an entity are collected by replication index.
+pub fn run_replicated_batch<K, T, I>(root: &EventNode<Stochastic<T>>, entities: I, seed: u64, replications: usize) -> BatchOutcome<K, ReplicatedResults<T>>
+where K: Ord + Hash, T: Clone, I: IntoIterator<Item = (K, T)> {
+    let master = RngStream::new(seed);
+    entities.into_iter()
+        .map(|(id, state)| {
+            let stream = master.derive_for(&id);
+            (id, run_replications(root, state, stream, replications).map(|evaluated| by_chain(evaluated.into_iter().enumerate())))
+        })
+        .collect()
+}
+
+/// Replicate the evaluation of each of the given independent entities in parallel as
+/// run_replicated_batch does. Results do not depend on the number of worker threads.
+pub fn run_replicated_batch_parallel<'p, K, T, F>(graph_factory: F, entities: Vec<(K, T)>, threads: impl Into<Threads<'p>>, seed: u64, replications: usize) -> Result<BatchOutcome<K, ReplicatedResults<T>>, ThreadPoolBuildError>
+where K: Ord + Hash + Send, T: Clone + Send, F: Fn() -> EventNode<Stochastic<T>> + Sync {
+    let master = RngStream::new(seed);
+    let evaluated = threads.into().install(|| {
+        entities.into_par_iter()
+            .map_init(&graph_factory, |root, (id, state)| {
+                let stream = master.derive_for(&id);
+                (id, run_replications(root, state, stream, replications).map(|evaluated| by_chain(evaluated.into_iter().enumerate())))
+            })
+            .collect::<Vec<_>>()
+    })?;
+    Ok(evaluated.into_iter().collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::branching_generators::alternatives;
+    use crate::event_graph::{BoxedOperation, EventDAG};
+    use crate::stochastic::{lift, normal_noise};
+    use super::*;
+
+    fn create_graph() -> EventNode<Stochastic<f64>> {
+        let root = EventDAG::new_node(lift(Box::new(|x: f64| x)));
+        let operations: Vec<BoxedOperation<Stochastic<f64>>> = vec![
+            lift(Box::new(|x: f64| x + 10.0)),
+            normal_noise(|x: &f64| *x, |_, x| x, 2.0)
+        ];
+        alternatives(vec![root.clone()], operations).unwrap();
+        root
+    }
+
+    #[test]
+    fn values_are_summarized() {
+        let summary = ReplicationSummary::of([2.0, 4.0, 4.0, 4.0, 5.0, 5.0, 7.0, 9.0]);
+        assert_eq!(8, summary.count);
+        assert_eq!(5.0, summary.mean);
+        assert!((summary.variance - 32.0 / 7.0).abs() < 1e-12);
+        let (low, high) = summary.confidence_interval(Z_95);
+        assert!((high - low - 2.0 * Z_95 * (32.0 / 7.0 / 8.0f64).sqrt()).abs() < 1e-12);
+        assert_eq!(ReplicationSummary { count: 1, mean: 3.0, variance: 0.0 }, ReplicationSummary::of([3.0]));
+
+        let positions = summarize_positions(&[vec![1.0, 2.0], vec![3.0]]);
+        assert_eq!(vec![ReplicationSummary { count: 2, mean: 2.0, variance: 2.0 }, ReplicationSummary { count: 1, mean: 2.0, variance: 0.0 }], positions);
+    }
+
+    #[test]
+    fn replications_vary_by_seed() {
+        let root = create_graph();
+        let replications = run_replications(&root, 1.0, RngStream::new(7), 200).unwrap();
+        assert_eq!(200, replications.len());
+        assert_ne!(replications[0][&1].state, replications[1][&1].state);
+
+        let summaries = summarize_chains(&replications, |x| *x);
+        assert_eq!(ReplicationSummary { count: 200, mean: 11.0, variance: 0.0 }, summaries[&0]);
+        let (low, high) = summaries[&1].confidence_interval(Z_95);
+        assert!(low < 1.0 && 1.0 < high, "{low} .. {high}");
+        assert!((summaries[&1].std_dev() - 2.0).abs() < 0.5);
+    }
+
+    #[test]
+    fn replicated_batches_are_reproducible() {
+        let root = create_graph();
+        let entities = vec![(1, 1.0), (2, 2.0), (3, 3.0)];
+        let outcome = run_replicated_batch(&root, entities.clone(), 11, 5);
+        assert_eq!(vec![5, 5], outcome.results[&2].values().map(ReplicatedResults::len).collect::<Vec<_>>());
+        assert_eq!(outcome, run_replicated_batch_parallel(create_graph, entities, 2, 11, 5).unwrap());
+    }
+}
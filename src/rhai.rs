@@ -0,0 +1,204 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::panic::resume_unwind;
+use std::rc::Rc;
+use rhai::serde::{from_dynamic, to_dynamic};
+use rhai::{Dynamic, Engine, ParseError, Scope, AST};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use crate::branching_generators::generator_map;
+use crate::configuration_utils::ParameterMap;
+use crate::event_graph::{prune, BoxedOperation, EventDAG, EventNode, OperationChain};
+use crate::simulation_runner::{OperationParameters, PreconditionFailure, RunnerError, SimulationDeclaration};
+
+/// Guard predicates by operation name, with the handling of the operation when the predicate
+/// does not hold.
+pub type RhaiGuards = HashMap<&'static str, (&'static str, PreconditionFailure)>;
+
+/// Reasons for failing to compile Rhai scripts or a declaration over them.
+#[derive(Debug)]
+pub enum ScriptError {
+    /// The script of the operation, or its guard predicate, failed to parse.
+    Parse { operation: String, error: ParseError },
+    Runner(RunnerError),
+}
+
+impl fmt::Display for ScriptError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ScriptError::Parse { operation, error } => write!(f, "script of operation '{operation}': {error}"),
+            ScriptError::Runner(err) => write!(f, "{err}"),
+        }
+    }
+}
+
+impl Error for ScriptError {}
+
+impl From<RunnerError> for ScriptError {
+    fn from(err: RunnerError) -> Self {
+        ScriptError::Runner(err)
+    }
+}
+
+/// Operations defined as Rhai scripts, small enough to be written inside configuration files.
+/// A script sees the state as `state` and the operation parameters as the `parameters` map,
+/// and evaluates into the resulting state, such as
+///
+/// state.volume += parse_float(parameters.increase); state
+///
+/// States are passed into Rhai through their serde representation.
+pub struct RhaiOperations {
+    engine: Rc<Engine>,
+    scripts: HashMap<String, Rc<AST>>,
+}
+
+/// Evaluate the script for the state, failing the event chain on script errors.
+fn evaluate<T: Serialize>(engine: &Engine, script: &AST, state: &T, parameters: &Dynamic) -> Dynamic {
+    let mut scope = Scope::new();
+    let evaluated = to_dynamic(state).and_then(|state| {
+        scope.push("state", state).push("parameters", parameters.clone());
+        engine.eval_ast_with_scope::<Dynamic>(&mut scope, script)
+    });
+    match evaluated {
+        Ok(result) => result,
+        Err(err) => resume_unwind(Box::new(err.to_string())),
+    }
+}
+
+impl RhaiOperations {
+    /// Compile the scripts given by operation name.
+    pub fn new<S: AsRef<str>>(scripts: &HashMap<&str, S>) -> Result<RhaiOperations, ScriptError> {
+        let engine = Engine::new();
+        let scripts = scripts.iter()
+            .map(|(name, script)| {
+                let ast = engine.compile(script.as_ref())
+                    .map_err(|error| ScriptError::Parse { operation: name.to_string(), error })?;
+                Ok((name.to_string(), Rc::new(ast)))
+            })
+            .collect::<Result<HashMap<String, Rc<AST>>, ScriptError>>()?;
+        Ok(RhaiOperations { engine: Rc::new(engine), scripts })
+    }
+
+    /// Names of the compiled operations.
+    pub fn names(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = self.scripts.keys().map(String::as_str).collect();
+        names.sort_unstable();
+        names
+    }
+
+    /// Bind the parameters into the named operation. Script errors fail the event chain.
+    pub fn operation<T>(&self, name: &'static str, parameters: ParameterMap) -> Result<BoxedOperation<T>, ScriptError>
+    where T: Serialize + DeserializeOwned + 'static {
+        let script = Rc::clone(self.scripts.get(name).ok_or(RunnerError::UnknownOperation(name))?);
+        let engine = Rc::clone(&self.engine);
+        let parameters = to_dynamic(parameters).expect("string maps convert into Rhai maps");
+        Ok(Box::new(move |state: T| {
+            let result = evaluate(&engine, &script, &state, &parameters);
+            from_dynamic(&result).unwrap_or_else(|err| resume_unwind(Box::new(format!("operation '{name}' result: {err}"))))
+        }))
+    }
+
+    /// Guard the operation by the predicate script over `state`.
+    fn guarded<T>(&self, name: &'static str, guard: (&'static str, PreconditionFailure), operation: BoxedOperation<T>) -> Result<BoxedOperation<T>, ScriptError>
+    where T: Serialize + 'static {
+        let (predicate, on_failure) = guard;
+        let predicate = self.engine.compile_expression(predicate)
+            .map_err(|error| ScriptError::Parse { operation: name.to_string(), error })?;
+        let engine = Rc::clone(&self.engine);
+        Ok(Box::new(move |state: T| {
+            let holds = evaluate(&engine, &predicate, &state, &Dynamic::UNIT).as_bool()
+                .unwrap_or_else(|found| resume_unwind(Box::new(format!("guard of operation '{name}' evaluated into {found}"))));
+            match (holds, on_failure) {
+                (true, _) => operation(state),
+                (false, PreconditionFailure::Skip) => state,
+                (false, PreconditionFailure::Prune) => prune(),
+            }
+        }))
+    }
+
+    /// Compile the declaration over the compiled operations into an event graph, guarding
+    /// operations by their guard predicates.
+    pub fn compile<T>(&self, declaration: &SimulationDeclaration, parameters: &OperationParameters, guards: &RhaiGuards) -> Result<EventNode<T>, ScriptError>
+    where T: Clone + Serialize + DeserializeOwned + 'static {
+        let generators = generator_map::<T>();
+        let root: EventNode<T> = EventDAG::new_node(Box::new(|state| state));
+        let mut nodes = vec![root.clone()];
+        for (generator_name, operation_names) in declaration {
+            let generator_fn = generators.get(generator_name).ok_or(RunnerError::UnknownGenerator(generator_name))?;
+            let chain = operation_names.iter()
+                .map(|name| {
+                    let operation = self.operation(name, parameters.get(name).cloned().unwrap_or_default())?;
+                    match guards.get(name) {
+                        Some(guard) => self.guarded(name, *guard, operation),
+                        None => Ok(operation),
+                    }
+                })
+                .collect::<Result<OperationChain<T>, ScriptError>>()?;
+            nodes = generator_fn(nodes, chain).map_err(RunnerError::from)?;
+        }
+        Ok(root)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::Deserialize;
+    use super::*;
+
+    #[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+    struct Stand {
+        age: u32,
+        volume: f64,
+    }
+
+    const GROW: &str = "state.age += 5; state.volume += parse_float(parameters.increase); state";
+    const THIN: &str = "if state.volume < 20.0 { throw \"too little volume\" } state.volume /= 2.0; state";
+
+    fn operations() -> RhaiOperations {
+        RhaiOperations::new(&HashMap::from([("grow", GROW), ("thin", THIN)])).unwrap()
+    }
+
+    #[test]
+    fn rhai_operations_are_compiled() {
+        let operations = operations();
+        assert_eq!(vec!["grow", "thin"], operations.names());
+        let declaration = vec![("sequence", vec!["grow"]), ("alternatives", vec!["grow", "thin"])];
+        let parameters = OperationParameters::from([("grow", ParameterMap::from([("increase", "10")]))]);
+        let root = operations.compile::<Stand>(&declaration, &parameters, &RhaiGuards::new()).unwrap();
+
+        let results = EventDAG::try_evaluate_chains(&root, Stand { age: 0, volume: 15.0 }).unwrap();
+        assert_eq!(vec![Stand { age: 10, volume: 35.0 }, Stand { age: 5, volume: 12.5 }], results);
+
+        let failure = EventDAG::try_evaluate_chains(&root, Stand { age: 0, volume: 0.0 }).unwrap_err();
+        assert_eq!((1, 2), (failure.chain, failure.operation));
+        assert!(failure.error.contains("too little volume"));
+    }
+
+    #[test]
+    fn guards_skip_or_prune() {
+        let operations = operations();
+        let declaration = vec![("sequence", vec!["grow"]), ("alternatives", vec!["grow", "thin"])];
+        let parameters = OperationParameters::from([("grow", ParameterMap::from([("increase", "10")]))]);
+        let guards = RhaiGuards::from([("thin", ("state.volume >= 50.0", PreconditionFailure::Prune))]);
+        let root = operations.compile::<Stand>(&declaration, &parameters, &guards).unwrap();
+        assert_eq!(vec![Stand { age: 10, volume: 35.0 }], EventDAG::try_evaluate_chains(&root, Stand { age: 0, volume: 15.0 }).unwrap());
+
+        let guards = RhaiGuards::from([("grow", ("state.age < 5", PreconditionFailure::Skip))]);
+        let root = operations.compile::<Stand>(&declaration, &parameters, &guards).unwrap();
+        let results = EventDAG::try_evaluate_chains(&root, Stand { age: 0, volume: 15.0 }).unwrap();
+        assert_eq!(vec![Stand { age: 5, volume: 25.0 }, Stand { age: 5, volume: 12.5 }], results);
+    }
+
+    #[test]
+    fn script_errors_are_reported() {
+        let failed = RhaiOperations::new(&HashMap::from([("grow", "state +")]));
+        assert!(matches!(failed, Err(ScriptError::Parse { operation, .. }) if operation == "grow"));
+
+        let compiled = operations().compile::<Stand>(&vec![("sequence", vec!["clearcut"])], &OperationParameters::new(), &RhaiGuards::new());
+        assert!(matches!(compiled, Err(ScriptError::Runner(RunnerError::UnknownOperation("clearcut")))));
+        let guards = RhaiGuards::from([("grow", ("state.age <", PreconditionFailure::Skip))]);
+        let compiled = operations().compile::<Stand>(&vec![("sequence", vec!["grow"])], &OperationParameters::new(), &guards);
+        assert!(matches!(compiled, Err(ScriptError::Parse { operation, .. }) if operation == "grow"));
+    }
+}
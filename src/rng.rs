@@ -0,0 +1,182 @@
+use std::hash::{Hash, Hasher};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::rc::Rc;
+use serde::{Deserialize, Serialize};
+use crate::event_graph::{is_pruned, panic_message, ChainResults, EvaluationFailure, EventDAG, EventNode};
+use crate::stable_hash::StableHasher;
+
+const GOLDEN_GAMMA: u64 = 0x9e3779b97f4a7c15;
+
+fn mix(mut z: u64) -> u64 {
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+    z ^ (z >> 31)
+}
+
+/// RngStream is a SplitMix64 random number stream. Independent streams are derived from a parent
+/// stream by key, so that a master seed deterministically yields a stream per entity and per
+/// event chain regardless of evaluation order or thread count. The stream is Copy and thus
+/// travels inside simulation payloads.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct RngStream {
+    state: u64,
+}
+
+impl RngStream {
+    pub fn new(seed: u64) -> RngStream {
+        RngStream { state: mix(seed) }
+    }
+
+    /// Derive an independent stream for the given key without advancing this stream.
+    pub fn derive(&self, key: u64) -> RngStream {
+        RngStream { state: mix(self.state ^ mix(key.wrapping_add(GOLDEN_GAMMA))) }
+    }
+
+    /// Derive an independent stream for the given entity id. Entity ids are hashed with the
+    /// StableHasher, so string ids derive identical streams across platforms.
+    pub fn derive_for<K: Hash + ?Sized>(&self, entity: &K) -> RngStream {
+        let mut hasher = StableHasher::new();
+        entity.hash(&mut hasher);
+        self.derive(hasher.finish())
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(GOLDEN_GAMMA);
+        mix(self.state)
+    }
+
+    /// Next uniformly distributed value in the range [0, 1).
+    pub fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 * (1.0 / (1u64 << 53) as f64)
+    }
+
+    /// Next standard normally distributed value, drawn with the Box-Muller transform.
+    pub fn next_normal(&mut self) -> f64 {
+        let u1 = 1.0 - self.next_f64();
+        let u2 = self.next_f64();
+        (-2.0 * u1.ln()).sqrt() * (2.0 * std::f64::consts::PI * u2).cos()
+    }
+}
+
+/// Simulation payload carrying the random number stream of its event chain alongside the
+/// simulated state. Operations draw random numbers from the stream in the payload.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Stochastic<T> {
+    pub rng: RngStream,
+    pub state: T,
+}
+
+/// Key of the node within its event chain: its branch label among the followers of the previous
+/// node, or its own label, and its occurrence among the followers sharing that label.
+fn step_key<T: Clone>(previous: Option<&EventNode<T>>, node: &EventNode<T>) -> u64 {
+    let step = match previous {
+        None => (node.borrow().label(), 0),
+        Some(previous) => {
+            let previous = previous.borrow();
+            let label = previous.branch_label(node);
+            let occurrence = previous.followers().iter()
+                .take_while(|follower| !Rc::ptr_eq(follower, node))
+                .filter(|follower| previous.branch_label(follower) == label)
+                .count();
+            (label, occurrence)
+        }
+    };
+    let mut hasher = StableHasher::new();
+    step.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Evaluate unique event chains starting from the given EventNode with random number streams
+/// derived from the given entity stream. Each node applies with a stream derived along the path
+/// of branch labels leading to it, unlabeled followers keyed by their attachment order, so nodes
+/// shared by several chains draw the same numbers in each of them and adding or removing
+/// alternatives does not change the draws of the other chains. Panicking operations are described
+/// as an EvaluationFailure.
+pub fn try_evaluate_seeded_chains<T: Clone>(root: &EventNode<Stochastic<T>>, state: T, stream: RngStream) -> Result<ChainResults<Stochastic<T>>, EvaluationFailure> {
+    let mut results = ChainResults::new();
+    'chains: for (chain_index, chain) in EventDAG::node_chains(root).iter().enumerate() {
+        let mut current = Stochastic { rng: stream, state: state.clone() };
+        for (operation_index, node) in chain.iter().enumerate() {
+            let rng = current.rng.derive(step_key(operation_index.checked_sub(1).map(|previous| &chain[previous]), node));
+            let payload = Stochastic { rng, state: current.state };
+            current = match catch_unwind(AssertUnwindSafe(|| node.borrow().apply(payload))) {
+                Ok(result) => Stochastic { rng, state: result.state },
+                Err(payload) if is_pruned(&*payload) => continue 'chains,
+                Err(payload) => return Err(EvaluationFailure {
+                    chain: chain_index,
+                    operation: operation_index,
+                    label: node.borrow().label().map(str::to_string),
+                    error: panic_message(payload)
+                }),
+            };
+        }
+        results.insert(chain_index, current);
+    }
+    Ok(results)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::branching_generators::alternatives;
+    use crate::event_graph::BoxedOperation;
+    use super::*;
+
+    #[test]
+    fn streams_are_deterministic_and_independent() {
+        let master = RngStream::new(42);
+        let mut first = master.derive_for("stand-1");
+        let mut again = RngStream::new(42).derive_for("stand-1");
+        let mut other = master.derive_for("stand-2");
+        let draws: Vec<u64> = (0..5).map(|_| first.next_u64()).collect();
+        assert_eq!(draws, (0..5).map(|_| again.next_u64()).collect::<Vec<u64>>());
+        assert_ne!(draws, (0..5).map(|_| other.next_u64()).collect::<Vec<u64>>());
+        assert!((0..1000).map(|_| first.next_f64()).all(|value| (0.0..1.0).contains(&value)));
+        let mean = (0..10000).map(|_| first.next_normal()).sum::<f64>() / 10000.0;
+        assert!(mean.abs() < 0.05);
+    }
+
+    #[test]
+    fn chains_draw_from_own_streams() {
+        let root = EventDAG::new_node(Box::new(|payload: Stochastic<u64>| payload));
+        let draw = || -> BoxedOperation<Stochastic<u64>> {
+            Box::new(|mut payload: Stochastic<u64>| Stochastic { state: payload.rng.next_u64(), ..payload })
+        };
+        alternatives(vec![root.clone()], [draw(), draw()]).unwrap();
+        let stream = RngStream::new(7).derive_for(&1);
+        let results = try_evaluate_seeded_chains(&root, 0, stream).unwrap();
+        assert_ne!(results[&0].state, results[&1].state);
+        assert_eq!(results, try_evaluate_seeded_chains(&root, 0, stream).unwrap());
+    }
+
+    /// Draws appended to the state by a root drawing once and labeled alternatives drawing once.
+    fn labeled_draws(labels: &[&'static str]) -> EventNode<Stochastic<Vec<u64>>> {
+        let draw = || -> BoxedOperation<Stochastic<Vec<u64>>> {
+            Box::new(|mut payload: Stochastic<Vec<u64>>| {
+                let draw = payload.rng.next_u64();
+                payload.state.push(draw);
+                payload
+            })
+        };
+        let root = EventDAG::new_node(draw());
+        let branches = alternatives(vec![root.clone()], labels.iter().map(|_| draw())).unwrap();
+        for (branch, label) in branches.iter().zip(labels) {
+            branch.borrow_mut().set_label(label);
+        }
+        root
+    }
+
+    #[test]
+    fn draws_follow_the_branch_labels() {
+        let stream = RngStream::new(7);
+        let results = try_evaluate_seeded_chains(&labeled_draws(&["thin", "clearcut"]), Vec::new(), stream).unwrap();
+        assert_eq!(results[&0].state[0], results[&1].state[0]);
+        assert_ne!(results[&0].state[1], results[&1].state[1]);
+
+        let extended = try_evaluate_seeded_chains(&labeled_draws(&["fertilize", "thin", "clearcut"]), Vec::new(), stream).unwrap();
+        assert_eq!(results[&0].state, extended[&1].state);
+        assert_eq!(results[&1].state, extended[&2].state);
+        let duplicated = try_evaluate_seeded_chains(&labeled_draws(&["thin", "thin"]), Vec::new(), stream).unwrap();
+        assert_eq!(results[&0].state, duplicated[&0].state);
+        assert_ne!(duplicated[&0].state, duplicated[&1].state);
+    }
+}
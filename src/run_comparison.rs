@@ -0,0 +1,179 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use serde::Serialize;
+use crate::batch_runner::EntityResults;
+use crate::event_graph::ChainResults;
+use crate::collectors::Projector;
+
+/// Projected variable compared between runs.
+pub type ComparedVariable<T> = (&'static str, Projector<T, f64>);
+
+/// Difference of a variable in a single event chain between the baseline and candidate runs.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ChainDifference<K> {
+    pub entity: K,
+    pub chain: usize,
+    pub variable: &'static str,
+    pub baseline: f64,
+    pub candidate: f64,
+}
+
+impl<K> ChainDifference<K> {
+    /// Candidate value less the baseline value.
+    pub fn difference(&self) -> f64 {
+        self.candidate - self.baseline
+    }
+}
+
+/// Aggregate differences of a variable over the event chains present in both runs.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+pub struct VariableSummary {
+    pub compared: usize,
+    pub changed: usize,
+    pub baseline_total: f64,
+    pub candidate_total: f64,
+    pub max_abs_difference: f64,
+}
+
+impl VariableSummary {
+    /// Mean difference of the candidate from the baseline over the compared event chains.
+    pub fn mean_difference(&self) -> f64 {
+        match self.compared {
+            0 => 0.0,
+            compared => (self.candidate_total - self.baseline_total) / compared as f64,
+        }
+    }
+}
+
+/// Comparison of the results of two runs. Event chains are matched by entity id and chain id,
+/// so the runs should evaluate the same event graph structure.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RunComparison<K> {
+    /// Differences of the event chains whose variables changed.
+    pub chains: Vec<ChainDifference<K>>,
+    pub variables: BTreeMap<&'static str, VariableSummary>,
+    /// Entity ids and chain ids present only in the baseline run.
+    pub only_in_baseline: Vec<(K, usize)>,
+    /// Entity ids and chain ids present only in the candidate run.
+    pub only_in_candidate: Vec<(K, usize)>,
+}
+
+impl<K: Ord + Clone> RunComparison<K> {
+    /// Compare the given variables of the candidate run results against the baseline run.
+    pub fn of<T>(baseline: &EntityResults<K, T>, candidate: &EntityResults<K, T>, variables: &[ComparedVariable<T>]) -> RunComparison<K> {
+        let mut comparison = RunComparison {
+            chains: Vec::new(),
+            variables: variables.iter().map(|(name, _)| (*name, VariableSummary::default())).collect(),
+            only_in_baseline: Vec::new(),
+            only_in_candidate: Vec::new(),
+        };
+        let empty = ChainResults::new();
+        let mut entities: Vec<&K> = baseline.keys().chain(candidate.keys()).collect();
+        entities.sort();
+        entities.dedup();
+        for entity in entities {
+            let baseline_results = baseline.get(entity).unwrap_or(&empty);
+            let candidate_results = candidate.get(entity).unwrap_or(&empty);
+            for (chain, baseline_state) in baseline_results.iter() {
+                let Some(candidate_state) = candidate_results.get(chain) else {
+                    comparison.only_in_baseline.push((entity.clone(), *chain));
+                    continue;
+                };
+                for (variable, projector) in variables {
+                    let (baseline, candidate) = (projector(baseline_state), projector(candidate_state));
+                    let summary = comparison.variables.get_mut(variable).expect("variable summaries initialized");
+                    summary.compared += 1;
+                    summary.baseline_total += baseline;
+                    summary.candidate_total += candidate;
+                    if baseline != candidate {
+                        summary.changed += 1;
+                        summary.max_abs_difference = summary.max_abs_difference.max((candidate - baseline).abs());
+                        comparison.chains.push(ChainDifference { entity: entity.clone(), chain: *chain, variable, baseline, candidate });
+                    }
+                }
+            }
+            comparison.only_in_candidate.extend(candidate_results.keys()
+                .filter(|chain| !baseline_results.contains_key(chain))
+                .map(|chain| (entity.clone(), *chain)));
+        }
+        comparison
+    }
+
+    /// Check whether the runs have the same event chains with equal variables.
+    pub fn is_identical(&self) -> bool {
+        self.chains.is_empty() && self.only_in_baseline.is_empty() && self.only_in_candidate.is_empty()
+    }
+}
+
+impl<K: fmt::Display> fmt::Display for RunComparison<K> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (variable, summary) in self.variables.iter() {
+            writeln!(
+                f,
+                "{variable}: {} of {} chains changed, total {} -> {}, mean difference {}, max abs difference {}",
+                summary.changed, summary.compared, summary.baseline_total, summary.candidate_total,
+                summary.mean_difference(), summary.max_abs_difference
+            )?;
+        }
+        for difference in self.chains.iter() {
+            writeln!(
+                f,
+                "entity {} chain {} {}: {} -> {} ({:+})",
+                difference.entity, difference.chain, difference.variable,
+                difference.baseline, difference.candidate, difference.difference()
+            )?;
+        }
+        for (entity, chain) in self.only_in_baseline.iter() {
+            writeln!(f, "entity {entity} chain {chain} only in baseline")?;
+        }
+        for (entity, chain) in self.only_in_candidate.iter() {
+            writeln!(f, "entity {entity} chain {chain} only in candidate")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, Clone, Copy)]
+    struct Stand { volume: f64, income: f64 }
+
+    #[test]
+    fn runs_are_compared() {
+        let stand = |volume, income| Stand { volume, income };
+        let baseline = EntityResults::from([
+            (1, ChainResults::from([(0, stand(100.0, 0.0)), (1, stand(80.0, 20.0))])),
+            (2, ChainResults::from([(0, stand(50.0, 0.0))])),
+            (4, ChainResults::from([(0, stand(40.0, 0.0)), (2, stand(30.0, 10.0))]))
+        ]);
+        let candidate = EntityResults::from([
+            (1, ChainResults::from([(0, stand(100.0, 0.0)), (1, stand(70.0, 25.0)), (2, stand(60.0, 30.0))])),
+            (3, ChainResults::from([(0, stand(10.0, 0.0))])),
+            (4, ChainResults::from([(2, stand(30.0, 10.0))]))
+        ]);
+        let variables: [ComparedVariable<Stand>; 2] = [("volume", |stand| stand.volume), ("income", |stand| stand.income)];
+        let comparison = RunComparison::of(&baseline, &candidate, &variables);
+        assert!(!comparison.is_identical());
+        assert_eq!(vec![
+            ChainDifference { entity: 1, chain: 1, variable: "volume", baseline: 80.0, candidate: 70.0 },
+            ChainDifference { entity: 1, chain: 1, variable: "income", baseline: 20.0, candidate: 25.0 }
+        ], comparison.chains);
+        assert_eq!(VariableSummary { compared: 3, changed: 1, baseline_total: 210.0, candidate_total: 200.0, max_abs_difference: 10.0 }, comparison.variables["volume"]);
+        assert!((comparison.variables["volume"].mean_difference() + 10.0 / 3.0).abs() < 1e-12);
+        assert_eq!(vec![(2, 0), (4, 0)], comparison.only_in_baseline);
+        assert_eq!(vec![(1, 2), (3, 0)], comparison.only_in_candidate);
+        assert_eq!("\
+income: 1 of 3 chains changed, total 30 -> 35, mean difference 1.6666666666666667, max abs difference 5
+volume: 1 of 3 chains changed, total 210 -> 200, mean difference -3.3333333333333335, max abs difference 10
+entity 1 chain 1 volume: 80 -> 70 (-10)
+entity 1 chain 1 income: 20 -> 25 (+5)
+entity 2 chain 0 only in baseline
+entity 4 chain 0 only in baseline
+entity 1 chain 2 only in candidate
+entity 3 chain 0 only in candidate
+", comparison.to_string());
+        assert!(RunComparison::of(&baseline, &baseline, &variables).is_identical());
+    }
+}
@@ -0,0 +1,116 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use crate::batch_runner::BatchOutcome;
+use crate::event_graph::BoxedOperation;
+
+/// Accumulated timing of a single named operation.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct OperationTiming {
+    pub calls: u64,
+    pub total: Duration,
+}
+
+pub type OperationTimings = BTreeMap<&'static str, OperationTiming>;
+pub type SharedTimings = Arc<Mutex<OperationTimings>>;
+
+/// Wrap a named operation to accumulate its call count and duration into the shared timings.
+pub fn timed<T: 'static>(timings: SharedTimings) -> impl Fn(&'static str, BoxedOperation<T>) -> BoxedOperation<T> {
+    move |name, operation| {
+        let timings = Arc::clone(&timings);
+        Box::new(move |payload| {
+            let start = Instant::now();
+            let result = operation(payload);
+            let elapsed = start.elapsed();
+            let mut timings = timings.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+            let timing = timings.entry(name).or_default();
+            timing.calls += 1;
+            timing.total += elapsed;
+            result
+        })
+    }
+}
+
+/// Structured summary of a completed batch run.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RunSummary {
+    /// Number of entities evaluated, including failed ones.
+    pub entities: usize,
+    /// Number of event chains evaluated for the successful entities.
+    pub chains: usize,
+    /// Number of entities with a failing operation.
+    pub failures: usize,
+    pub wall_time: Duration,
+    pub operation_timings: OperationTimings,
+    /// Locations of the outputs written for the run.
+    pub outputs: Vec<PathBuf>,
+}
+
+impl RunSummary {
+    /// Summarize the outcome of a batch run.
+    pub fn of<K, T>(outcome: &BatchOutcome<K, T>, wall_time: Duration, operation_timings: OperationTimings) -> RunSummary {
+        RunSummary {
+            entities: outcome.results.len() + outcome.failures.len(),
+            chains: outcome.results.values().map(|results| results.len()).sum(),
+            failures: outcome.failures.len(),
+            wall_time,
+            operation_timings,
+            outputs: Vec::new(),
+        }
+    }
+
+    /// Record the location of an output written for the run.
+    pub fn add_output<P: Into<PathBuf>>(&mut self, output: P) {
+        self.outputs.push(output.into());
+    }
+}
+
+impl fmt::Display for RunSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "entities processed: {}", self.entities)?;
+        writeln!(f, "chains evaluated: {}", self.chains)?;
+        writeln!(f, "failures: {}", self.failures)?;
+        writeln!(f, "wall time: {:.3} s", self.wall_time.as_secs_f64())?;
+        if !self.operation_timings.is_empty() {
+            writeln!(f, "operation timings:")?;
+            for (name, timing) in self.operation_timings.iter() {
+                writeln!(f, "  {name}: {} calls, {:.3} s", timing.calls, timing.total.as_secs_f64())?;
+            }
+        }
+        if !self.outputs.is_empty() {
+            writeln!(f, "outputs:")?;
+            for output in self.outputs.iter() {
+                writeln!(f, "  {}", output.display())?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::batch_runner::EntityResults;
+    use crate::event_graph::ChainResults;
+    use super::*;
+
+    #[test]
+    fn operations_are_timed() {
+        let timings = SharedTimings::default();
+        let wrap = timed::<i32>(Arc::clone(&timings));
+        let operation = wrap("increment", Box::new(|x| x + 1));
+        assert_eq!(3, operation(operation(1)));
+        assert_eq!(2, timings.lock().unwrap()["increment"].calls);
+    }
+
+    #[test]
+    fn summary_is_reported() {
+        let outcome = BatchOutcome { results: EntityResults::from([(1, ChainResults::from([(0, 1), (1, 2)])), (2, ChainResults::from([(0, 3), (1, 4)]))]), failures: Vec::new() };
+        let timings = OperationTimings::from([("grow", OperationTiming { calls: 4, total: Duration::from_millis(1500) })]);
+        let mut summary = RunSummary::of(&outcome, Duration::from_secs(2), timings);
+        summary.add_output("results.csv");
+        assert_eq!((2, 4, 0), (summary.entities, summary.chains, summary.failures));
+        assert_eq!("entities processed: 2\nchains evaluated: 4\nfailures: 0\nwall time: 2.000 s\noperation timings:\n  grow: 4 calls, 1.500 s\noutputs:\n  results.csv\n", summary.to_string());
+    }
+}
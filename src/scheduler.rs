@@ -0,0 +1,254 @@
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+use std::panic::{catch_unwind, resume_unwind, AssertUnwindSafe};
+use crate::collectors::{CollectionPoint, Collector};
+use crate::event_graph::{is_pruned, panic_message, BoxedOperation, EvaluationFailure};
+use crate::time_points::{SimTime, TimePoint};
+
+pub type BoxedProcess<S> = Box<dyn Fn(S, &mut EventQueue<S>) -> S>;
+
+/// Event of a discrete-event simulation. Operations transform the payload as in event graphs,
+/// processes may in addition schedule further events, such as the next arrival of a
+/// disturbance.
+pub enum Event<S> {
+    Operation(BoxedOperation<S>),
+    Process(BoxedProcess<S>),
+}
+
+/// Event waiting in an EventQueue, ordered by time and then by scheduling order.
+struct Queued<S> {
+    time: TimePoint,
+    sequence: usize,
+    event: Event<S>,
+}
+
+impl<S> PartialEq for Queued<S> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl<S> Eq for Queued<S> {}
+
+impl<S> PartialOrd for Queued<S> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<S> Ord for Queued<S> {
+    /// Reversed, for the earliest event to be on top of the max-heap.
+    fn cmp(&self, other: &Self) -> Ordering {
+        (other.time, other.sequence).cmp(&(self.time, self.sequence))
+    }
+}
+
+/// Discrete-event simulation of a single payload, as an alternative to event graphs for
+/// processes not known in advance. Events are queued by simulated time and occur in time order,
+/// events of the same time point in the order they were scheduled. Before each event the payload
+/// is moved to the time of the event. As in event graphs, operations may prune the simulation.
+pub struct EventQueue<S> {
+    queue: BinaryHeap<Queued<S>>,
+    scheduled: usize,
+    processed: usize,
+}
+
+impl<S> Default for EventQueue<S> {
+    fn default() -> Self {
+        EventQueue::new()
+    }
+}
+
+impl<S> EventQueue<S> {
+    pub fn new() -> EventQueue<S> {
+        EventQueue { queue: BinaryHeap::new(), scheduled: 0, processed: 0 }
+    }
+
+    pub fn schedule(&mut self, time: TimePoint, event: Event<S>) {
+        self.queue.push(Queued { time, sequence: self.scheduled, event });
+        self.scheduled += 1;
+    }
+
+    pub fn schedule_operation(&mut self, time: TimePoint, operation: BoxedOperation<S>) {
+        self.schedule(time, Event::Operation(operation));
+    }
+
+    pub fn schedule_process(&mut self, time: TimePoint, process: BoxedProcess<S>) {
+        self.schedule(time, Event::Process(process));
+    }
+
+    /// Time of the earliest queued event.
+    pub fn next_time(&self) -> Option<TimePoint> {
+        self.queue.peek().map(|queued| queued.time)
+    }
+
+    pub fn len(&self) -> usize {
+        self.queue.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.queue.is_empty()
+    }
+
+    /// Number of events occurred so far.
+    pub fn processed(&self) -> usize {
+        self.processed
+    }
+}
+
+impl<S: SimTime> EventQueue<S> {
+    /// Let the queued events up to and including the horizon occur on the payload. Later events
+    /// remain queued, so that the simulation may be continued with a later horizon. Returns None
+    /// if the simulation was pruned.
+    pub fn run(&mut self, payload: S, horizon: TimePoint) -> Option<S> {
+        self.run_collecting(payload, horizon, &mut [])
+    }
+
+    /// Run as EventQueue::run, describing a panicking event as an EvaluationFailure of chain 0
+    /// at the position of the event among the occurred events.
+    pub fn try_run(&mut self, payload: S, horizon: TimePoint) -> Result<Option<S>, EvaluationFailure> {
+        catch_unwind(AssertUnwindSafe(|| self.run(payload, horizon)))
+            .map_err(|payload| EvaluationFailure { chain: 0, operation: self.processed, label: None, error: panic_message(payload) })
+    }
+
+    /// Run as EventQueue::run, invoking the collectors as if the occurred events formed event
+    /// chain 0. Time points end before each event of a later time and at the horizon.
+    pub fn run_collecting(&mut self, mut payload: S, horizon: TimePoint, collectors: &mut [&mut dyn Collector<S>]) -> Option<S> {
+        let notify = |collectors: &mut [&mut dyn Collector<S>], point: CollectionPoint, state: &S| {
+            for collector in collectors.iter_mut().filter(|collector| collector.point() == point) {
+                collector.observe(0, state);
+            }
+        };
+        let mut time_point_started = false;
+        while self.next_time().is_some_and(|time| time <= horizon) {
+            let Queued { time, event, .. } = self.queue.pop().unwrap();
+            if time_point_started && time != payload.current_time() {
+                notify(collectors, CollectionPoint::TimePoint, &payload);
+            }
+            time_point_started = true;
+            let current = payload.with_time(time);
+            payload = match catch_unwind(AssertUnwindSafe(|| match &event {
+                Event::Operation(operation) => operation(current),
+                Event::Process(process) => process(current, self),
+            })) {
+                Ok(payload) => payload,
+                Err(payload) if is_pruned(&*payload) => return None,
+                Err(payload) => resume_unwind(payload),
+            };
+            self.processed += 1;
+            notify(collectors, CollectionPoint::Operation, &payload);
+        }
+        notify(collectors, CollectionPoint::TimePoint, &payload);
+        notify(collectors, CollectionPoint::ChainEnd, &payload);
+        Some(payload)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::collectors::{LastValue, TimeSeries};
+    use crate::event_graph::prune;
+    use crate::rng::{RngStream, Stochastic};
+    use crate::time_points::TimedState;
+    use super::*;
+
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    struct Stand {
+        volume: f64,
+        storms: u32,
+    }
+
+    type Payload = Stochastic<TimedState<Stand>>;
+
+    fn grow(mut payload: Payload) -> Payload {
+        payload.state.state.volume += 10.0;
+        payload
+    }
+
+    fn growth(payload: Payload, queue: &mut EventQueue<Payload>) -> Payload {
+        queue.schedule_process(payload.current_time() + 5, Box::new(growth));
+        grow(payload)
+    }
+
+    /// Storms arrive at exponentially distributed intervals with a mean of 20 years.
+    fn storm(mut payload: Payload, queue: &mut EventQueue<Payload>) -> Payload {
+        let interval = (-payload.rng.next_f64().ln() * 20.0).ceil() as TimePoint;
+        queue.schedule_process(payload.current_time() + interval, Box::new(storm));
+        payload.state.state.volume *= 0.5;
+        payload.state.state.storms += 1;
+        payload
+    }
+
+    fn simulate(seed: u64, horizon: TimePoint) -> Payload {
+        let mut queue = EventQueue::new();
+        queue.schedule_process(5, Box::new(growth));
+        queue.schedule_process(0, Box::new(storm));
+        let payload = Stochastic { rng: RngStream::new(seed), state: TimedState { time: 0, state: Stand { volume: 100.0, storms: 0 } } };
+        queue.run(payload, horizon).unwrap()
+    }
+
+    #[test]
+    fn events_occur_in_time_and_scheduling_order() {
+        let mut queue: EventQueue<TimedState<Vec<TimePoint>>> = EventQueue::new();
+        let record: fn(TimedState<Vec<TimePoint>>) -> TimedState<Vec<TimePoint>> = |mut payload| {
+            payload.state.push(payload.time);
+            payload
+        };
+        queue.schedule_operation(20, Box::new(record));
+        queue.schedule_operation(10, Box::new(|mut payload: TimedState<Vec<TimePoint>>| {
+            payload.state.push(-1);
+            payload
+        }));
+        queue.schedule_operation(10, Box::new(record));
+        queue.schedule_process(5, Box::new(move |payload, queue| {
+            queue.schedule_operation(10, Box::new(record));
+            queue.schedule_operation(15, Box::new(record));
+            record(payload)
+        }));
+        assert_eq!(Some(5), queue.next_time());
+        let payload = queue.run(TimedState { time: 0, state: vec![] }, 15).unwrap();
+        assert_eq!(vec![5, -1, 10, 10, 15], payload.state);
+        assert_eq!(15, payload.time);
+        assert_eq!((1, 5), (queue.len(), queue.processed()));
+
+        let payload = queue.run(payload, 100).unwrap();
+        assert_eq!(vec![5, -1, 10, 10, 15, 20], payload.state);
+        assert!(queue.is_empty());
+    }
+
+    #[test]
+    fn disturbances_arrive_stochastically() {
+        let result = simulate(1, 100);
+        assert_eq!(100, result.current_time());
+        assert!(result.state.state.storms > 1);
+        assert_eq!(result, simulate(1, 100));
+        let storms: Vec<u32> = (0..10).map(|seed| simulate(seed, 100).state.state.storms).collect();
+        assert!(storms.iter().any(|count| *count != storms[0]));
+    }
+
+    #[test]
+    fn pruning_and_failures_end_the_simulation() {
+        let mut queue: EventQueue<TimedState<i32>> = EventQueue::new();
+        queue.schedule_operation(1, Box::new(|payload| payload));
+        queue.schedule_operation(2, Box::new(|_| prune()));
+        queue.schedule_operation(3, Box::new(|_| panic!("late")));
+        assert_eq!(None, queue.run(TimedState { time: 0, state: 0 }, 10));
+        let failure = queue.try_run(TimedState { time: 0, state: 0 }, 10).unwrap_err();
+        assert_eq!(EvaluationFailure { chain: 0, operation: 1, label: None, error: "late".to_string() }, failure);
+    }
+
+    #[test]
+    fn collectors_observe_the_events() {
+        let mut queue: EventQueue<TimedState<i32>> = EventQueue::new();
+        for time in [1, 1, 2, 4] {
+            queue.schedule_operation(time, Box::new(|payload| TimedState { state: payload.state + 1, ..payload }));
+        }
+        let mut operations = TimeSeries::new(CollectionPoint::Operation, |payload: &TimedState<i32>| payload.state);
+        let mut time_points = TimeSeries::new(CollectionPoint::TimePoint, |payload: &TimedState<i32>| (payload.time, payload.state));
+        let mut last = LastValue::new(CollectionPoint::ChainEnd, |payload: &TimedState<i32>| payload.state);
+        queue.run_collecting(TimedState { time: 0, state: 0 }, 3, &mut [&mut operations, &mut time_points, &mut last]);
+        assert_eq!(vec![1, 2, 3], operations.series[&0]);
+        assert_eq!(vec![(1, 2), (2, 3)], time_points.series[&0]);
+        assert_eq!(3, last.values[&0]);
+    }
+}
@@ -0,0 +1,264 @@
+use crate::decisions::{try_evaluate_decided, DecidedResult};
+use crate::event_graph::{EvaluationFailure, EventNode, NodeMode};
+use crate::pareto::Objective;
+use crate::rng::RngStream;
+
+/// Enabled states of the optional events of a graph structure, in the order of the events.
+pub type Candidate = Vec<bool>;
+
+/// Candidate with its score, None if all of its event chains were pruned.
+pub type Scored = (Candidate, Option<f64>);
+
+/// Strategy proposing graph structures to evaluate, given the scores of its earlier proposals.
+pub trait SearchStrategy {
+    /// First candidates to evaluate, given the structure of the graph as declared.
+    fn start(&mut self, initial: &Candidate, stream: &mut RngStream) -> Vec<Candidate>;
+
+    /// Next candidates to evaluate given the scored candidates of the previous proposal, in the
+    /// order proposed. No candidates end the search.
+    fn next(&mut self, scored: &[Scored], stream: &mut RngStream) -> Vec<Candidate>;
+}
+
+/// Best found graph structure with the score and decision path of its best event chain.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StructureOptimum<T> {
+    pub score: f64,
+    pub enabled: Candidate,
+    pub best: DecidedResult<T>,
+    /// Number of candidates evaluated.
+    pub evaluations: usize,
+}
+
+/// Candidate with the state of a random optional event flipped.
+fn flipped(candidate: &Candidate, stream: &mut RngStream) -> Candidate {
+    let mut flipped = candidate.clone();
+    if !flipped.is_empty() {
+        let index = (stream.next_u64() % flipped.len() as u64) as usize;
+        flipped[index] = !flipped[index];
+    }
+    flipped
+}
+
+fn better(score: Option<f64>, than: Option<f64>) -> bool {
+    match (score, than) {
+        (Some(score), Some(than)) => score > than,
+        (Some(_), None) => true,
+        (None, _) => false,
+    }
+}
+
+/// Simulated annealing flipping a single optional event per step, accepting worse candidates
+/// with a probability decreasing with the temperature.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SimulatedAnnealing {
+    pub temperature: f64,
+    /// Factor multiplying the temperature after each step.
+    pub cooling: f64,
+    pub steps: usize,
+    current: Option<Scored>,
+}
+
+impl SimulatedAnnealing {
+    pub fn new(temperature: f64, cooling: f64, steps: usize) -> SimulatedAnnealing {
+        SimulatedAnnealing { temperature, cooling, steps, current: None }
+    }
+}
+
+impl SearchStrategy for SimulatedAnnealing {
+    fn start(&mut self, initial: &Candidate, _stream: &mut RngStream) -> Vec<Candidate> {
+        self.current = None;
+        vec![initial.clone()]
+    }
+
+    fn next(&mut self, scored: &[Scored], stream: &mut RngStream) -> Vec<Candidate> {
+        let Some((candidate, score)) = scored.first() else {
+            return Vec::new();
+        };
+        let accepted = match &self.current {
+            None => true,
+            Some((_, current)) => better(*score, *current) || match (score, current) {
+                (Some(score), Some(current)) => stream.next_f64() < ((score - current) / self.temperature).exp(),
+                _ => false,
+            },
+        };
+        if accepted {
+            self.current = Some((candidate.clone(), *score));
+        }
+        if self.steps == 0 {
+            return Vec::new();
+        }
+        self.steps -= 1;
+        self.temperature *= self.cooling;
+        vec![flipped(&self.current.as_ref().unwrap().0, stream)]
+    }
+}
+
+/// Genetic algorithm over populations of candidates, breeding each generation by tournament
+/// selection, uniform crossover and mutation while keeping the best candidate.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Genetic {
+    pub population: usize,
+    pub generations: usize,
+    /// Probability of flipping the state of each optional event of an offspring.
+    pub mutation_rate: f64,
+}
+
+impl Genetic {
+    /// Pick the better of two random candidates.
+    fn tournament<'a>(scored: &'a [Scored], stream: &mut RngStream) -> &'a Candidate {
+        let mut pick = || &scored[(stream.next_u64() % scored.len() as u64) as usize];
+        let (first, second) = (pick(), pick());
+        if better(second.1, first.1) { &second.0 } else { &first.0 }
+    }
+}
+
+impl SearchStrategy for Genetic {
+    fn start(&mut self, initial: &Candidate, stream: &mut RngStream) -> Vec<Candidate> {
+        let random = (1..self.population.max(1)).map(|_| initial.iter().map(|_| stream.next_f64() < 0.5).collect());
+        std::iter::once(initial.clone()).chain(random).collect()
+    }
+
+    fn next(&mut self, scored: &[Scored], stream: &mut RngStream) -> Vec<Candidate> {
+        if self.generations == 0 || scored.is_empty() {
+            return Vec::new();
+        }
+        self.generations -= 1;
+        let elite = scored.iter().reduce(|best, candidate| if better(candidate.1, best.1) { candidate } else { best }).unwrap();
+        let offspring = (1..self.population.max(1)).map(|_| {
+            let (first, second) = (Genetic::tournament(scored, stream).clone(), Genetic::tournament(scored, stream));
+            first.into_iter().zip(second)
+                .map(|(a, b)| {
+                    let inherited = if stream.next_f64() < 0.5 { a } else { *b };
+                    inherited ^ (stream.next_f64() < self.mutation_rate)
+                })
+                .collect()
+        });
+        std::iter::once(elite.0.clone()).chain(offspring).collect()
+    }
+}
+
+/// Score of the graph structure of the candidate by its best event chain.
+fn evaluate<T: Clone>(root: &EventNode<T>, optional: &[EventNode<T>], candidate: &Candidate, payload: T, objective: Objective<T>) -> Result<Option<(f64, DecidedResult<T>)>, EvaluationFailure> {
+    for (node, enabled) in optional.iter().zip(candidate.iter()) {
+        node.borrow_mut().set_mode(if *enabled { NodeMode::Enabled } else { NodeMode::Skipped });
+    }
+    Ok(try_evaluate_decided(root, payload)?.into_iter()
+        .map(|result| (objective(&result.result), result))
+        .reduce(|chosen, scored| if scored.0 > chosen.0 { scored } else { chosen }))
+}
+
+/// Evaluate the candidates proposed by the strategy until it proposes none or the given number
+/// of candidates is evaluated, returning the best structure and the number of evaluations.
+fn explore<T: Clone, S: SearchStrategy>(
+    root: &EventNode<T>,
+    optional: &[EventNode<T>],
+    payload: T,
+    objective: Objective<T>,
+    strategy: &mut S,
+    max_evaluations: usize,
+    mut stream: RngStream
+) -> Result<Option<StructureOptimum<T>>, EvaluationFailure> {
+    let initial: Candidate = optional.iter().map(|node| node.borrow().mode() == NodeMode::Enabled).collect();
+    let mut best: Option<StructureOptimum<T>> = None;
+    let mut evaluations = 0;
+    let mut candidates = strategy.start(&initial, &mut stream);
+    while !candidates.is_empty() && evaluations < max_evaluations {
+        candidates.truncate(max_evaluations - evaluations);
+        let mut scored = Vec::with_capacity(candidates.len());
+        for candidate in candidates {
+            evaluations += 1;
+            let chosen = evaluate(root, optional, &candidate, payload.clone(), objective)?;
+            let score = chosen.as_ref().map(|(score, _)| *score);
+            if let Some((score, result)) = chosen {
+                if best.as_ref().is_none_or(|best| score > best.score) {
+                    best = Some(StructureOptimum { score, enabled: candidate.clone(), best: result, evaluations: 0 });
+                }
+            }
+            scored.push((candidate, score));
+        }
+        candidates = strategy.next(&scored, &mut stream);
+    }
+    Ok(best.map(|best| StructureOptimum { evaluations, ..best }))
+}
+
+/// Search for the graph structure maximizing the objective by enabling and skipping the given
+/// optional events, such as the events of time points found by tag, as proposed by the strategy.
+/// Each candidate structure is scored by its best event chain. The search ends when the strategy
+/// proposes no candidates or the given number of candidates is evaluated, restoring the modes of
+/// the optional events. Returns None if all chains of all candidates were pruned.
+pub fn search<T: Clone, S: SearchStrategy>(
+    root: &EventNode<T>,
+    optional: &[EventNode<T>],
+    payload: T,
+    objective: Objective<T>,
+    strategy: &mut S,
+    max_evaluations: usize,
+    stream: RngStream
+) -> Result<Option<StructureOptimum<T>>, EvaluationFailure> {
+    let modes: Vec<NodeMode> = optional.iter().map(|node| node.borrow().mode()).collect();
+    let outcome = explore(root, optional, payload, objective, strategy, max_evaluations, stream);
+    for (node, mode) in optional.iter().zip(modes) {
+        node.borrow_mut().set_mode(mode);
+    }
+    outcome
+}
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+    use crate::branching_generators::sequence;
+    use crate::event_graph::{BoxedOperation, EventDAG};
+    use super::*;
+
+    /// Total value and the completed thinnings as bits.
+    type Program = (i32, u32);
+
+    /// Program of six optional thinnings, each adding its value, with the fourth and fifth
+    /// thinning together losing more than they add.
+    fn program() -> (EventNode<Program>, Vec<EventNode<Program>>) {
+        let root = EventDAG::new_node(Box::new(|state: Program| state));
+        let values = [3, -1, 2, 4, 5, 1];
+        let operations: Vec<BoxedOperation<Program>> = values.iter().enumerate()
+            .map(|(index, value)| -> BoxedOperation<Program> {
+                let value = *value;
+                Box::new(move |(total, done): Program| {
+                    let done = done | 1 << index;
+                    let penalty = if index == 4 && done & 1 << 3 != 0 { 12 } else { 0 };
+                    (total + value - penalty, done)
+                })
+            })
+            .collect();
+        sequence(vec![Rc::clone(&root)], operations).unwrap();
+        for node in EventDAG::reachable_nodes(&[Rc::clone(&root)]).iter().skip(1) {
+            node.borrow_mut().add_tag("optional");
+        }
+        let optional = EventDAG::find_all(&root, "optional");
+        (root, optional)
+    }
+
+    fn total(state: &Program) -> f64 { state.0 as f64 }
+
+    const BEST: [bool; 6] = [true, false, true, false, true, true];
+
+    #[test]
+    fn annealing_finds_good_structures() {
+        let (root, optional) = program();
+        let mut strategy = SimulatedAnnealing::new(5.0, 0.99, 300);
+        let optimum = search(&root, &optional, (0, 0), total, &mut strategy, 1000, RngStream::new(1)).unwrap().unwrap();
+        assert_eq!(11.0, optimum.score);
+        assert_eq!(BEST.to_vec(), optimum.enabled);
+        assert_eq!(301, optimum.evaluations);
+        assert!(optional.iter().all(|node| node.borrow().mode() == NodeMode::Enabled));
+        assert_eq!(vec![(2, 63)], EventDAG::evaluate_chains(&root, (0, 0)));
+    }
+
+    #[test]
+    fn genetic_search_finds_good_structures() {
+        let (root, optional) = program();
+        let mut strategy = Genetic { population: 20, generations: 20, mutation_rate: 0.2 };
+        let optimum = search(&root, &optional, (0, 0), total, &mut strategy, 100, RngStream::new(1)).unwrap().unwrap();
+        assert_eq!(100, optimum.evaluations);
+        assert_eq!(11.0, optimum.score);
+        assert_eq!(BEST.to_vec(), optimum.enabled);
+    }
+}
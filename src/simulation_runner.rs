@@ -0,0 +1,1164 @@
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::rc::Rc;
+use std::sync::Arc;
+use std::time::Instant;
+use rayon::ThreadPoolBuildError;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use crate::batch_runner::{run_batch, run_batch_parallel, BatchOutcome, EntityFailure, Threads};
+use crate::branching_generators::{generator_map, GeneratorError, GeneratorFn};
+use crate::checkpoint::{resume_batch_checkpointed, resume_batch_parallel_checkpointed, run_batch_checkpointed, run_batch_parallel_checkpointed, Checkpoint};
+use crate::collectors::{evaluate_collecting, Collector};
+use crate::decision_log::{run_batch_logged, DecisionLog};
+use crate::configuration_utils::{bound_operation, bound_owned_operation, check_parameters, ParameterMap, ParameterSchema, ParameteredOperation};
+use crate::graph_fingerprint::{fingerprint, GraphFingerprint};
+use crate::graph_statistics::GraphStatistics;
+use crate::warm_start::WarmStart;
+use crate::streaming::{stream_batch, stream_batch_parallel, ResultSender, StreamedResult};
+use crate::lint::{lint_graph, lint_references, Lint, CHAIN_COUNT_WARNING};
+use crate::run_summary::{timed, RunSummary, SharedTimings};
+use crate::metrics::{counted, Counter, Metrics};
+use crate::manifest::{config_hash, OperationVersions, RunManifest};
+use crate::provenance::{chain_provenance, effective_parameters, EffectiveParameters, ParameterProvenance};
+use crate::operation_cache::{cached, OperationCache, StateKey};
+use crate::event_graph::{prune, BoxedOperation, ChainResults, EvaluationFailure, EventDAG, EventNode, EventNodes, OperationChain};
+
+pub type OperationRegistry<T> = HashMap<&'static str, ParameteredOperation<'static, T>>;
+pub type GeneratorRegistry<T> = HashMap<&'static str, GeneratorFn<T>>;
+pub type OperationParameters = HashMap<&'static str, ParameterMap<'static>>;
+/// Operation parameters owned by the caller, such as parameters received over the network.
+pub type OwnedParameters = HashMap<String, HashMap<String, String>>;
+
+/// Copy of the operation parameters owned by the caller, for overriding values computed at run
+/// time without interning them.
+pub fn owned_parameters(parameters: &OperationParameters) -> OwnedParameters {
+    parameters.iter()
+        .map(|(operation, map)| (operation.to_string(), map.iter().map(|(name, value)| (name.to_string(), value.to_string())).collect()))
+        .collect()
+}
+pub type GeneratorDeclaration = (&'static str, Vec<&'static str>);
+pub type SimulationDeclaration = Vec<GeneratorDeclaration>;
+/// Named branch combination, given as the operations chosen among the alternatives.
+pub type ScenarioDeclaration = (&'static str, Vec<&'static str>);
+
+/// Behavior of an operation whose precondition does not hold for the state.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PreconditionFailure {
+    /// Skip the event, passing the state through unchanged.
+    Skip,
+    /// Prune the event chain, leaving it without a result.
+    Prune,
+}
+
+/// Condition for the state to hold for applying an operation.
+#[derive(Debug, Clone, Copy)]
+pub struct Precondition<T> {
+    pub check: fn(&T) -> bool,
+    pub on_failure: PreconditionFailure,
+}
+
+pub type Preconditions<T> = HashMap<&'static str, Precondition<T>>;
+/// Declared parameters of the operations, by operation name.
+pub type ParameterSchemas = HashMap<&'static str, ParameterSchema>;
+
+/// Reasons for failing to compile a simulation declaration into an event graph.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RunnerError {
+    UnknownGenerator(&'static str),
+    UnknownOperation(&'static str),
+    Generator(GeneratorError),
+    UnknownScenario(String),
+    /// A scenario chooses an operation which is not an alternative in the declaration.
+    ScenarioChoice { scenario: &'static str, operation: &'static str },
+    /// The compiled event graph has been edited, so it can not be recompiled without losing the
+    /// edits.
+    ModifiedGraph,
+}
+
+impl fmt::Display for RunnerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RunnerError::UnknownGenerator(name) => write!(f, "unknown generator '{name}'"),
+            RunnerError::UnknownOperation(name) => write!(f, "unknown operation '{name}'"),
+            RunnerError::Generator(err) => write!(f, "{err}"),
+            RunnerError::UnknownScenario(name) => write!(f, "unknown scenario '{name}'"),
+            RunnerError::ScenarioChoice { scenario, operation } =>
+                write!(f, "scenario '{scenario}' chooses '{operation}' which is not an alternative operation"),
+            RunnerError::ModifiedGraph => write!(f, "event graph has been edited since it was compiled"),
+        }
+    }
+}
+
+impl Error for RunnerError {}
+
+impl From<GeneratorError> for RunnerError {
+    fn from(err: GeneratorError) -> Self {
+        RunnerError::Generator(err)
+    }
+}
+
+/// Results of evaluating the compiled event graph for a single initial state. Results are in
+/// the order of unique event chains through the graph, pruned chains leaving no results.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RunResult<T> {
+    pub initial_state: T,
+    pub results: Vec<T>,
+}
+
+/// Outcome of compiling and validating a simulation without evaluating it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DryRunReport {
+    pub statistics: GraphStatistics,
+    /// Problems found in the configuration which do not prevent compiling the event graph.
+    pub issues: Vec<String>,
+    /// Suspicious parts of the configuration, such as unreferenced operations.
+    pub warnings: Vec<Lint>,
+}
+
+impl fmt::Display for DryRunReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{}", self.statistics)?;
+        for issue in self.issues.iter() {
+            writeln!(f, "issue: {issue}")?;
+        }
+        for warning in self.warnings.iter() {
+            writeln!(f, "warning: {warning}")?;
+        }
+        Ok(())
+    }
+}
+
+/// SimulationRunner owns the registries and configuration of a simulation along with the event
+/// graph compiled from them.
+pub struct SimulationRunner<T> {
+    operations: OperationRegistry<T>,
+    generators: GeneratorRegistry<T>,
+    parameters: OperationParameters,
+    declaration: SimulationDeclaration,
+    operation_versions: OperationVersions,
+    scenarios: Vec<ScenarioDeclaration>,
+    preconditions: Preconditions<T>,
+    parameter_schemas: ParameterSchemas,
+    metrics: Option<Arc<dyn Metrics>>,
+    operation_cache: Option<Rc<OperationCache<T>>>,
+    root: EventNode<T>,
+    compiled: GraphShape,
+}
+
+impl<T: Clone + 'static> SimulationRunner<T> {
+    /// Construct a SimulationRunner using the built-in generators and compile its event graph.
+    pub fn new(
+        operations: OperationRegistry<T>,
+        parameters: OperationParameters,
+        declaration: SimulationDeclaration
+    ) -> Result<SimulationRunner<T>, RunnerError> {
+        SimulationRunner::with_generators(generator_map(), operations, parameters, declaration)
+    }
+
+    /// Construct a SimulationRunner using the given generators and compile its event graph.
+    pub fn with_generators(
+        generators: GeneratorRegistry<T>,
+        operations: OperationRegistry<T>,
+        parameters: OperationParameters,
+        declaration: SimulationDeclaration
+    ) -> Result<SimulationRunner<T>, RunnerError> {
+        let root = compile(&generators, &operations, &parameters, &declaration, &unwrapped)?;
+        let compiled = graph_shape(&root);
+        Ok(SimulationRunner {
+            operations,
+            generators,
+            parameters,
+            declaration,
+            operation_versions: OperationVersions::new(),
+            scenarios: Vec::new(),
+            preconditions: Preconditions::new(),
+            parameter_schemas: ParameterSchemas::new(),
+            metrics: None,
+            operation_cache: None,
+            root,
+            compiled
+        })
+    }
+
+    pub fn operations(&self) -> &OperationRegistry<T> {
+        &self.operations
+    }
+
+    pub fn generators(&self) -> &GeneratorRegistry<T> {
+        &self.generators
+    }
+
+    pub fn parameters(&self) -> &OperationParameters {
+        &self.parameters
+    }
+
+    pub fn declaration(&self) -> &SimulationDeclaration {
+        &self.declaration
+    }
+
+    pub fn scenarios(&self) -> &[ScenarioDeclaration] {
+        &self.scenarios
+    }
+
+    /// Declare named scenarios of the simulation. Each scenario chooses operations among the
+    /// alternatives of the declaration, an alternatives declaration without a chosen operation
+    /// keeping all of its branches.
+    pub fn set_scenarios(&mut self, scenarios: Vec<ScenarioDeclaration>) -> Result<(), RunnerError> {
+        for (scenario, choices) in scenarios.iter() {
+            let unknown = choices.iter().find(|operation| !self.declaration.iter()
+                .any(|(generator, operations)| *generator == ALTERNATIVES && operations.contains(operation)));
+            if let Some(operation) = unknown {
+                return Err(RunnerError::ScenarioChoice { scenario, operation });
+            }
+        }
+        self.scenarios = scenarios;
+        Ok(())
+    }
+
+    fn find_scenario(&self, name: &str) -> Result<&ScenarioDeclaration, RunnerError> {
+        self.scenarios.iter()
+            .find(|(scenario, _)| *scenario == name)
+            .ok_or_else(|| RunnerError::UnknownScenario(name.to_string()))
+    }
+
+    /// Construct a SimulationRunner evaluating only the branches of the named scenario. The
+    /// scenario is compiled from the configuration, so edited graphs are refused.
+    pub fn scenario(&self, name: &str) -> Result<SimulationRunner<T>, RunnerError> {
+        self.check_unmodified()?;
+        let (_, choices) = self.find_scenario(name)?;
+        Ok(self.restricted(choices))
+    }
+
+    /// Construct SimulationRunners for the named scenarios, in the given order, refusing edited
+    /// graphs as scenario does.
+    pub fn select_scenarios<S: AsRef<str>>(&self, names: &[S]) -> Result<Vec<(&'static str, SimulationRunner<T>)>, RunnerError> {
+        self.check_unmodified()?;
+        names.iter().map(|name| {
+            let (scenario, choices) = self.find_scenario(name.as_ref())?;
+            Ok((*scenario, self.restricted(choices)))
+        }).collect()
+    }
+
+    /// Construct a SimulationRunner with the alternatives of the declaration restricted into the
+    /// chosen operations.
+    fn restricted(&self, choices: &[&'static str]) -> SimulationRunner<T> {
+        let declaration = self.declaration.iter().map(|(generator, operations)| {
+            let chosen: Vec<&'static str> = operations.iter().filter(|operation| choices.contains(operation)).copied().collect();
+            match *generator {
+                ALTERNATIVES if !chosen.is_empty() => (*generator, chosen),
+                _ => (*generator, operations.clone()),
+            }
+        }).collect();
+        self.variant(self.parameters.clone(), declaration)
+    }
+
+    /// Construct a SimulationRunner of the same simulation with the operations bound to the
+    /// given parameters, such as a combination of a parameter sweep. The variant is compiled from
+    /// the configuration, so edited graphs are refused.
+    pub fn with_parameters(&self, parameters: OperationParameters) -> Result<SimulationRunner<T>, RunnerError> {
+        self.check_unmodified()?;
+        Ok(self.variant(parameters, self.declaration.clone()))
+    }
+
+    /// Construct a SimulationRunner with the given parameters and declaration, recompiling its
+    /// event graph.
+    fn variant(&self, parameters: OperationParameters, declaration: SimulationDeclaration) -> SimulationRunner<T> {
+        let mut runner = SimulationRunner {
+            operations: self.operations.clone(),
+            generators: self.generators.clone(),
+            parameters,
+            declaration,
+            operation_versions: self.operation_versions.clone(),
+            scenarios: self.scenarios.clone(),
+            preconditions: self.preconditions.clone(),
+            parameter_schemas: self.parameter_schemas.clone(),
+            metrics: self.metrics.clone(),
+            operation_cache: self.operation_cache.as_ref().map(|cache| Rc::new(OperationCache::new(cache.key()))),
+            root: Rc::clone(&self.root),
+            compiled: GraphShape::new()
+        };
+        runner.recompile();
+        runner
+    }
+
+    /// Compile and validate a simulation using the built-in generators without evaluating it.
+    pub fn dry_run(
+        operations: OperationRegistry<T>,
+        parameters: OperationParameters,
+        declaration: SimulationDeclaration
+    ) -> Result<DryRunReport, RunnerError> {
+        Ok(SimulationRunner::new(operations, parameters, declaration)?.validate())
+    }
+
+    /// Validate the configuration and compute statistics of the compiled event graph without
+    /// evaluating it, linting the configuration with the default chain count warning limit.
+    pub fn validate(&self) -> DryRunReport {
+        let mut unregistered: Vec<&&'static str> = self.parameters.keys()
+            .filter(|name| !self.operations.contains_key(*name))
+            .collect();
+        unregistered.sort();
+        let mut issues: Vec<String> = unregistered.into_iter()
+            .map(|name| format!("parameters given for unknown operation '{name}'"))
+            .collect();
+        let mut declared: Vec<&'static str> = self.declaration.iter().flat_map(|(_, names)| names.iter().copied()).collect();
+        declared.sort();
+        declared.dedup();
+        for name in declared {
+            if let Some(schema) = self.parameter_schemas.get(name) {
+                let parameters = self.parameters.get(name).cloned().unwrap_or_default();
+                issues.extend(check_parameters(schema, &parameters).into_iter().map(|error| format!("operation '{name}': {error}")));
+            }
+        }
+        DryRunReport { statistics: GraphStatistics::of(&self.root), issues, warnings: self.lint(CHAIN_COUNT_WARNING) }
+    }
+
+    /// Warn about operations registered or given parameters but never referenced by the
+    /// declaration, unreachable branches of the event graph and more event chains than the
+    /// given limit.
+    pub fn lint(&self, chain_limit: usize) -> Vec<Lint> {
+        let referenced: Vec<&'static str> = self.declaration.iter().flat_map(|(_, names)| names.iter().copied()).collect();
+        let mut lints = lint_references(self.operations.keys(), self.parameters.keys(), &referenced);
+        lints.extend(lint_graph(&self.root, chain_limit));
+        lints
+    }
+
+    /// Effective parameters of the operation: its given parameters over the defaults of its
+    /// parameter schema, if declared.
+    pub fn effective_parameters(&self, operation: &str) -> EffectiveParameters {
+        let defaults: Vec<(&'static str, &'static str)> = self.parameter_schemas.get(operation).into_iter()
+            .flat_map(|schema| schema.iter().filter_map(|spec| spec.default.map(|default| (spec.name, default))))
+            .collect();
+        effective_parameters(self.parameters.get(operation), &defaults)
+    }
+
+    /// Operations applied along each event chain of the compiled graph with their effective
+    /// parameters, by chain id, for recording with the results.
+    pub fn parameter_provenance(&self) -> ParameterProvenance {
+        chain_provenance(&self.root, |operation| {
+            self.operations.contains_key(operation).then(|| self.effective_parameters(operation))
+        })
+    }
+
+    /// Fingerprint of the compiled event graph with the effective parameters bound to its
+    /// operations, equal for configurations compiling into the same simulation.
+    pub fn fingerprint(&self) -> GraphFingerprint {
+        fingerprint(&self.root, |operation| {
+            self.operations.contains_key(operation).then(|| self.effective_parameters(operation))
+        })
+    }
+
+    /// Evaluate the compiled event graph as WarmStart::evaluate, reusing the states of the prefix
+    /// shared with the graph evaluated previously, such as the graph of an earlier configuration.
+    /// The identity root of an unedited graph is reused although unlabeled.
+    pub fn evaluate_warm(&self, warm_start: &mut WarmStart<T>) -> Result<ChainResults<T>, EvaluationFailure> {
+        warm_start.evaluate_reusing(&self.root, !self.is_modified(), |operation| {
+            self.operations.contains_key(operation).then(|| self.effective_parameters(operation))
+        })
+    }
+
+    pub fn parameter_schemas(&self) -> &ParameterSchemas {
+        &self.parameter_schemas
+    }
+
+    /// Declare the parameters of the registered operations, such as derived with
+    /// #[derive(OperationParams)], for validating the given parameters.
+    pub fn set_parameter_schemas(&mut self, schemas: ParameterSchemas) -> Result<(), RunnerError> {
+        if let Some(name) = schemas.keys().find(|name| !self.operations.contains_key(*name)) {
+            return Err(RunnerError::UnknownOperation(name));
+        }
+        self.parameter_schemas = schemas;
+        Ok(())
+    }
+
+    /// Declare versions of the registered operations for recording in run manifests.
+    pub fn set_operation_versions(&mut self, versions: OperationVersions) {
+        self.operation_versions = versions;
+    }
+
+    pub fn preconditions(&self) -> &Preconditions<T> {
+        &self.preconditions
+    }
+
+    /// Declare preconditions of the registered operations, recompiling the event graph with the
+    /// operations guarded by their preconditions. The preconditions must be declared before
+    /// editing the compiled graph.
+    pub fn set_preconditions(&mut self, preconditions: Preconditions<T>) -> Result<(), RunnerError> {
+        if let Some(name) = preconditions.keys().find(|name| !self.operations.contains_key(*name)) {
+            return Err(RunnerError::UnknownOperation(name));
+        }
+        self.check_unmodified()?;
+        self.preconditions = preconditions;
+        self.recompile();
+        Ok(())
+    }
+
+    /// Report counters of graph construction and evaluation into the given metrics. The event
+    /// graph is recompiled with operations counting their applications, so the metrics must be
+    /// set before editing the compiled graph. Setting the metrics already set leaves the graph
+    /// as it is.
+    pub fn set_metrics(&mut self, metrics: Arc<dyn Metrics>) -> Result<(), RunnerError> {
+        self.check_unmodified()?;
+        if self.metrics.as_ref().is_some_and(|current| Arc::ptr_eq(current, &metrics)) {
+            return Ok(());
+        }
+        self.metrics = Some(metrics);
+        self.recompile();
+        Ok(())
+    }
+
+    /// Memoize the outputs of the operations by the keys of their input states, so that
+    /// deterministic operations applied to equal states on different branches compute once.
+    /// The event graph is recompiled with memoizing operations, so the cache must be set before
+    /// editing the compiled graph. Parallel runs memoize per thread.
+    pub fn set_operation_cache(&mut self, key: StateKey<T>) -> Result<(), RunnerError> {
+        self.check_unmodified()?;
+        self.operation_cache = Some(Rc::new(OperationCache::new(key)));
+        self.recompile();
+        Ok(())
+    }
+
+    /// Cache of the operation outputs of the compiled event graph, if set.
+    pub fn operation_cache(&self) -> Option<&OperationCache<T>> {
+        self.operation_cache.as_deref()
+    }
+
+    /// Key of the operation cache, for compiling event graphs with caches of their own.
+    fn cache_key(&self) -> Option<StateKey<T>> {
+        self.operation_cache.as_ref().map(|cache| cache.key())
+    }
+
+    /// Compile the declaration with operations wrapped by the given wrapper, counting their
+    /// applications when metrics are set and memoizing them when the operation cache is set.
+    fn compile_instrumented(&self, wrapper: &OperationWrapper<'_, T>) -> EventNode<T> {
+        let wrapper = with_cache(self.operation_cache.clone(), wrapper);
+        compile_instrumented(&self.generators, &self.operations, &self.parameters, &self.declaration, &self.preconditions, &self.metrics, &*wrapper)
+    }
+
+    /// Recompile the event graph from the configuration of the runner.
+    fn recompile(&mut self) {
+        self.root = self.compile_instrumented(&unwrapped);
+        self.compiled = graph_shape(&self.root);
+    }
+
+    /// Check whether the compiled event graph has been edited, such as by rewriting it, after
+    /// compiling it from the configuration.
+    pub fn is_modified(&self) -> bool {
+        graph_shape(&self.root) != self.compiled
+    }
+
+    fn check_unmodified(&self) -> Result<(), RunnerError> {
+        match self.is_modified() {
+            true => Err(RunnerError::ModifiedGraph),
+            false => Ok(()),
+        }
+    }
+
+    /// Report failed entities and pruned event chains into the metrics, if set.
+    fn count_outcome<K>(&self, outcome: &BatchOutcome<K, T>) {
+        if let Some(metrics) = &self.metrics {
+            metrics.increment(Counter::Errors, outcome.failures.len() as u64);
+            self.count_pruned(outcome.results.values().map(ChainResults::len));
+        }
+    }
+
+    /// Report event chains missing from the given result counts as pruned, if metrics are set.
+    fn count_pruned<I: IntoIterator<Item = usize>>(&self, result_counts: I) {
+        if let Some(metrics) = &self.metrics {
+            let chains = GraphStatistics::of(&self.root).chains;
+            let pruned: usize = result_counts.into_iter().map(|results| chains.saturating_sub(results)).sum();
+            metrics.increment(Counter::ChainsPruned, pruned as u64);
+        }
+    }
+
+    /// Evaluate the compiled event graph for each of the given independent entities as run_batch
+    /// does, writing the completed entities into the checkpoint after every interval of entities.
+    pub fn run_batch_checkpointed<K, I>(&self, entities: I, checkpoint: &Checkpoint) -> io::Result<BatchOutcome<K, T>>
+    where K: Ord + Clone + Serialize, T: Serialize, I: IntoIterator<Item = (K, T)> {
+        let outcome = run_batch_checkpointed(&self.root, entities, checkpoint)?;
+        self.count_outcome(&outcome);
+        Ok(outcome)
+    }
+
+    /// Evaluate the event graph for each of the given independent entities in parallel as
+    /// run_batch_parallel does, writing the completed entities into the checkpoint after every
+    /// interval of entities.
+    pub fn run_batch_parallel_checkpointed<'p, K>(&self, entities: Vec<(K, T)>, threads: impl Into<Threads<'p>>, checkpoint: &Checkpoint) -> io::Result<BatchOutcome<K, T>>
+    where K: Ord + Clone + Send + Serialize, T: Send + Serialize {
+        self.assert_unmodified();
+        let (factory, parameters) = (self.graph_factory(), &self.parameters);
+        let outcome = run_batch_parallel_checkpointed(|| factory(parameters), entities, threads, checkpoint)?;
+        self.count_outcome(&outcome);
+        Ok(outcome)
+    }
+
+    /// Resume a checkpointed run as run_batch_checkpointed, skipping the entities completed in the
+    /// checkpoint and merging their checkpointed results into the returned outcome.
+    pub fn resume_batch_checkpointed<K, I>(&self, entities: I, checkpoint: &Checkpoint) -> io::Result<BatchOutcome<K, T>>
+    where K: Ord + Clone + Serialize + DeserializeOwned, T: Serialize + DeserializeOwned, I: IntoIterator<Item = (K, T)> {
+        let outcome = resume_batch_checkpointed(&self.root, entities, checkpoint)?;
+        self.count_outcome(&outcome);
+        Ok(outcome)
+    }
+
+    /// Resume a checkpointed run as run_batch_parallel_checkpointed, skipping the entities
+    /// completed in the checkpoint and merging their checkpointed results into the returned outcome.
+    pub fn resume_batch_parallel_checkpointed<'p, K>(&self, entities: Vec<(K, T)>, threads: impl Into<Threads<'p>>, checkpoint: &Checkpoint) -> io::Result<BatchOutcome<K, T>>
+    where K: Ord + Clone + Send + Serialize + DeserializeOwned, T: Send + Serialize + DeserializeOwned {
+        self.assert_unmodified();
+        let (factory, parameters) = (self.graph_factory(), &self.parameters);
+        let outcome = resume_batch_parallel_checkpointed(|| factory(parameters), entities, threads, checkpoint)?;
+        self.count_outcome(&outcome);
+        Ok(outcome)
+    }
+
+    /// Evaluate the event graph for each of the given independent entities as run_batch does,
+    /// timing the operations and summarizing the run. The graph is compiled with timed operations,
+    /// so it panics as run_batch_parallel if the compiled graph has been edited.
+    pub fn run_batch_summarized<K: Ord, I: IntoIterator<Item = (K, T)>>(&self, entities: I) -> (BatchOutcome<K, T>, RunSummary) {
+        self.assert_unmodified();
+        let start = Instant::now();
+        let timings = SharedTimings::default();
+        let root = self.compile_instrumented(&timed(Arc::clone(&timings)));
+        let outcome = run_batch(&root, entities);
+        self.count_outcome(&outcome);
+        let timings = timings.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).clone();
+        let summary = RunSummary::of(&outcome, start.elapsed(), timings);
+        (outcome, summary)
+    }
+
+    /// Evaluate the event graph for each of the given independent entities in parallel as
+    /// run_batch_parallel does, timing the operations and summarizing the run.
+    pub fn run_batch_parallel_summarized<'p, K>(&self, entities: Vec<(K, T)>, threads: impl Into<Threads<'p>>) -> Result<(BatchOutcome<K, T>, RunSummary), ThreadPoolBuildError>
+    where K: Ord + Send, T: Send {
+        self.assert_unmodified();
+        let start = Instant::now();
+        let timings = SharedTimings::default();
+        let (factory, parameters) = (self.wrapped_graph_factory(timed(Arc::clone(&timings))), &self.parameters);
+        let outcome = run_batch_parallel(|| factory(parameters), entities, threads)?;
+        self.count_outcome(&outcome);
+        let timings = timings.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).clone();
+        let summary = RunSummary::of(&outcome, start.elapsed(), timings);
+        Ok((outcome, summary))
+    }
+
+    /// Construct a RunManifest describing a run of this simulation starting now.
+    pub fn manifest(&self) -> RunManifest {
+        let mut operations: Vec<&&'static str> = self.operations.keys().collect();
+        operations.sort();
+        RunManifest::new(config_hash(&self.declaration, &self.parameters), operations, &self.operation_versions, Vec::new())
+    }
+
+    /// Root EventNode of the compiled event graph. The graph may be edited, such as by rewriting
+    /// it, after which only the runs evaluating it in place, such as run and run_batch, are
+    /// available.
+    pub fn root(&self) -> &EventNode<T> {
+        &self.root
+    }
+
+    /// Evaluate the compiled event graph for each of the given initial states.
+    pub fn run<I: IntoIterator<Item = T>>(&self, initial_states: I) -> Vec<RunResult<T>> {
+        let results: Vec<RunResult<T>> = initial_states.into_iter()
+            .map(|initial_state| RunResult {
+                initial_state: initial_state.clone(),
+                results: EventDAG::evaluate_chains(&self.root, initial_state)
+            })
+            .collect();
+        self.count_pruned(results.iter().map(|result| result.results.len()));
+        results
+    }
+
+    /// Evaluate the compiled event graph for the given initial state, invoking the collectors at
+    /// their collection points.
+    pub fn run_collecting(&self, initial_state: T, collectors: &mut [&mut dyn Collector<T>]) -> RunResult<T> {
+        RunResult { initial_state: initial_state.clone(), results: evaluate_collecting(&self.root, initial_state, &[], collectors) }
+    }
+
+    /// Evaluate the compiled event graph for each of the given independent entities, collecting
+    /// the results by entity id and isolating failing entities.
+    pub fn run_batch<K: Ord, I: IntoIterator<Item = (K, T)>>(&self, entities: I) -> BatchOutcome<K, T> {
+        let outcome = run_batch(&self.root, entities);
+        self.count_outcome(&outcome);
+        outcome
+    }
+
+    /// Evaluate the compiled event graph for each of the given independent entities as run_batch,
+    /// recording the branching decisions, prunings and failures of each entity into the log.
+    pub fn run_batch_logged<K, I, W>(&self, entities: I, log: &mut DecisionLog<W>) -> BatchOutcome<K, T>
+    where K: Ord + fmt::Display, I: IntoIterator<Item = (K, T)>, W: io::Write {
+        let outcome = run_batch_logged(&self.root, entities, log);
+        self.count_outcome(&outcome);
+        outcome
+    }
+
+    /// Factory compiling the event graph of the simulation with the given parameters, shareable
+    /// across threads for compiling a graph of their own.
+    pub(crate) fn graph_factory<P: BindParameters<T> + ?Sized>(&self) -> impl Fn(&P) -> EventNode<T> + Sync + '_ {
+        self.wrapped_graph_factory(unwrapped)
+    }
+
+    /// Factory compiling the event graph as graph_factory, with operations wrapped by the wrapper.
+    fn wrapped_graph_factory<'a, P, W>(&'a self, wrapper: W) -> impl Fn(&P) -> EventNode<T> + Sync + 'a
+    where P: BindParameters<T> + ?Sized, W: Fn(&'static str, BoxedOperation<T>) -> BoxedOperation<T> + Sync + 'a {
+        let (generators, operations, declaration, preconditions, metrics, cache_key) =
+            (&self.generators, &self.operations, &self.declaration, &self.preconditions, &self.metrics, self.cache_key());
+        move |parameters: &P| compile_instrumented(generators, operations, parameters, declaration, preconditions, metrics, &*with_cache(thread_cache(cache_key), &wrapper))
+    }
+
+    /// Panic if the compiled event graph has been edited, for runs compiling graphs of their own
+    /// which would not see the edits.
+    fn assert_unmodified(&self) {
+        assert!(!self.is_modified(), "{}", RunnerError::ModifiedGraph);
+    }
+
+    /// Evaluate the event graph for each of the given independent entities in parallel in the
+    /// given thread pool or over the given number of worker threads, collecting the results by
+    /// entity id and isolating failing entities. A thread count of 0 lets rayon choose the number
+    /// of worker threads. The worker threads compile graphs of their own from the configuration,
+    /// so this panics if the compiled graph has been edited.
+    pub fn run_batch_parallel<'p, K>(&self, entities: Vec<(K, T)>, threads: impl Into<Threads<'p>>) -> Result<BatchOutcome<K, T>, ThreadPoolBuildError>
+    where K: Ord + Send, T: Send {
+        self.assert_unmodified();
+        let (factory, parameters) = (self.graph_factory(), &self.parameters);
+        let outcome = run_batch_parallel(|| factory(parameters), entities, threads)?;
+        self.count_outcome(&outcome);
+        Ok(outcome)
+    }
+
+    /// Evaluate the compiled event graph for each of the given independent entities, sending the
+    /// results over the channel as their event chains complete and returning the failures.
+    pub fn stream_batch<K, I, S>(&self, entities: I, sender: &S) -> Vec<EntityFailure<K>>
+    where K: Clone, I: IntoIterator<Item = (K, T)>, S: ResultSender<StreamedResult<K, T>> + ?Sized {
+        stream_batch(&self.root, entities, sender)
+    }
+
+    /// Evaluate the event graph for each of the given independent entities in parallel as
+    /// run_batch_parallel, sending the results over the channel as their event chains complete.
+    pub fn stream_batch_parallel<'p, K, S>(&self, entities: Vec<(K, T)>, threads: impl Into<Threads<'p>>, sender: &S) -> Result<Vec<EntityFailure<K>>, ThreadPoolBuildError>
+    where K: Clone + Send, T: Send, S: ResultSender<StreamedResult<K, T>> + Sync + ?Sized {
+        self.assert_unmodified();
+        let (factory, parameters) = (self.graph_factory(), &self.parameters);
+        stream_batch_parallel(|| factory(parameters), entities, threads, sender)
+    }
+}
+
+/// Name of the generator whose operations scenarios choose among.
+const ALTERNATIVES: &str = "alternatives";
+
+/// Wrapper applied into each bound operation while compiling a declaration, given the name of
+/// the operation. Used for instrumenting operations.
+pub type OperationWrapper<'a, T> = dyn Fn(&'static str, BoxedOperation<T>) -> BoxedOperation<T> + 'a;
+
+/// Parameters bound into the operations of a declaration while compiling it.
+pub(crate) trait BindParameters<T> {
+    /// Bind the parameters of the named operation into it.
+    fn bind(&self, name: &'static str, operation: ParameteredOperation<'static, T>) -> BoxedOperation<T>;
+}
+
+impl<T: 'static> BindParameters<T> for OperationParameters {
+    fn bind(&self, name: &'static str, operation: ParameteredOperation<'static, T>) -> BoxedOperation<T> {
+        bound_operation(operation, self.get(name).cloned().unwrap_or_default())
+    }
+}
+
+impl<T: 'static> BindParameters<T> for OwnedParameters {
+    fn bind(&self, name: &'static str, operation: ParameteredOperation<'static, T>) -> BoxedOperation<T> {
+        bound_owned_operation(operation, self.get(name).cloned().unwrap_or_default())
+    }
+}
+
+/// Bind the named operations of a generator declaration with their configured parameters.
+fn bind_operations<T: Clone + 'static, P: BindParameters<T> + ?Sized>(
+    operations: &OperationRegistry<T>,
+    parameters: &P,
+    names: &[&'static str],
+    wrapper: &OperationWrapper<'_, T>
+) -> Result<OperationChain<T>, RunnerError> {
+    names.iter().map(|name| {
+        let op = *operations.get(name).ok_or(RunnerError::UnknownOperation(name))?;
+        Ok(wrapper(name, parameters.bind(name, op)))
+    }).collect()
+}
+
+/// Extend the graph from the given EventNodes according to a simulation declaration, returning
+/// the new frontier of EventNodes.
+pub(crate) fn extend_graph<T: Clone + 'static, P: BindParameters<T> + ?Sized>(
+    generators: &GeneratorRegistry<T>,
+    operations: &OperationRegistry<T>,
+    parameters: &P,
+    declaration: &SimulationDeclaration,
+    mut nodes: EventNodes<T>,
+    wrapper: &OperationWrapper<'_, T>
+) -> Result<EventNodes<T>, RunnerError> {
+    for (generator_name, operation_names) in declaration {
+        let generator_fn = generators.get(generator_name).ok_or(RunnerError::UnknownGenerator(generator_name))?;
+        let chain = bind_operations(operations, parameters, operation_names, wrapper)?;
+        let previous = nodes.clone();
+        let existing: HashSet<*const ()> = EventDAG::reachable_nodes(&previous).iter().map(|node| node.as_ptr() as *const ()).collect();
+        nodes = generator_fn(nodes, chain)?;
+        label_generated(&previous, &existing, operation_names);
+    }
+    Ok(nodes)
+}
+
+/// Label the EventNodes generated from the previous frontier with their operation names, taking
+/// the generated nodes in breadth-first order as the built-in generators generate them. Nodes of
+/// generators generating another number of nodes than operations are left unlabeled.
+fn label_generated<T: Clone>(previous: &EventNodes<T>, existing: &HashSet<*const ()>, names: &[&'static str]) {
+    let generated: EventNodes<T> = EventDAG::reachable_nodes(previous).into_iter()
+        .filter(|node| !existing.contains(&(node.as_ptr() as *const ())))
+        .collect();
+    if generated.len() == names.len() {
+        for (node, name) in generated.iter().zip(names) {
+            node.borrow_mut().set_label(name);
+        }
+    }
+}
+
+/// Leave the operation as such.
+pub(crate) fn unwrapped<T>(_name: &'static str, operation: BoxedOperation<T>) -> BoxedOperation<T> {
+    operation
+}
+
+/// Compile a simulation declaration into an event graph, returning its root EventNode.
+fn compile<T: Clone + 'static, P: BindParameters<T> + ?Sized>(
+    generators: &GeneratorRegistry<T>,
+    operations: &OperationRegistry<T>,
+    parameters: &P,
+    declaration: &SimulationDeclaration,
+    wrapper: &OperationWrapper<'_, T>
+) -> Result<EventNode<T>, RunnerError> {
+    let root: EventNode<T> = EventDAG::new_node(Box::new(|state| state));
+    extend_graph(generators, operations, parameters, declaration, vec![Rc::clone(&root)], wrapper)?;
+    Ok(root)
+}
+
+/// Compile a declaration and operation parameters owned by the caller, such as received over the
+/// network, into an event graph with the built-in generators. Names resolve to the registered
+/// generators and operations, and the operations own their parameters, so unlike interning them
+/// for a SimulationRunner nothing outlives the returned graph.
+pub fn compile_owned<T: Clone + 'static>(
+    operations: &OperationRegistry<T>,
+    parameters: &OwnedParameters,
+    declaration: &[(String, Vec<String>)]
+) -> Result<EventNode<T>, String> {
+    let generators = generator_map::<T>();
+    let root: EventNode<T> = EventDAG::new_node(Box::new(|state| state));
+    let mut nodes = vec![Rc::clone(&root)];
+    for (generator_name, operation_names) in declaration {
+        let generator_fn = generators.get(generator_name.as_str()).ok_or_else(|| format!("unknown generator '{generator_name}'"))?;
+        let names: Vec<&'static str> = operation_names.iter()
+            .map(|name| operations.get_key_value(name.as_str()).map(|(name, _)| *name).ok_or_else(|| format!("unknown operation '{name}'")))
+            .collect::<Result<_, _>>()?;
+        let chain = names.iter()
+            .map(|name| bound_owned_operation(operations[name], parameters.get(*name).cloned().unwrap_or_default()))
+            .collect();
+        let previous = nodes.clone();
+        let existing: HashSet<*const ()> = EventDAG::reachable_nodes(&previous).iter().map(|node| node.as_ptr() as *const ()).collect();
+        nodes = generator_fn(nodes, chain).map_err(|err| err.to_string())?;
+        label_generated(&previous, &existing, &names);
+    }
+    Ok(root)
+}
+
+/// Identities, revisions, priorities and followers of the nodes of an event graph, changed by any
+/// edit of the operations, modes, evaluation order or structure of the graph.
+type GraphShape = Vec<(*const (), u64, i32, Vec<*const ()>)>;
+
+fn graph_shape<T: Clone>(root: &EventNode<T>) -> GraphShape {
+    EventDAG::reachable_nodes(&[Rc::clone(root)]).iter()
+        .map(|node| {
+            let borrowed = node.borrow();
+            let followers = borrowed.followers().iter().map(|follower| follower.as_ptr() as *const ()).collect();
+            (node.as_ptr() as *const (), borrowed.revision(), borrowed.priority(), followers)
+        })
+        .collect()
+}
+
+/// Guard the operation by its precondition.
+fn guarded<T: Clone + 'static>(precondition: Precondition<T>, operation: BoxedOperation<T>) -> BoxedOperation<T> {
+    Box::new(move |state| match (precondition.check)(&state) {
+        true => operation(state),
+        false => match precondition.on_failure {
+            PreconditionFailure::Skip => state,
+            PreconditionFailure::Prune => prune(),
+        }
+    })
+}
+
+/// Compose the wrapper with memoizing the wrapped operations into the cache, if given.
+fn with_cache<'a, T: Clone + 'static>(cache: Option<Rc<OperationCache<T>>>, wrapper: &'a OperationWrapper<'a, T>) -> Box<OperationWrapper<'a, T>> {
+    match cache {
+        Some(cache) => {
+            let memoized = cached(cache);
+            Box::new(move |name, operation| memoized(name, wrapper(name, operation)))
+        }
+        None => Box::new(wrapper),
+    }
+}
+
+/// Operation cache of an event graph compiled for a thread of a parallel run.
+fn thread_cache<T: Clone>(key: Option<StateKey<T>>) -> Option<Rc<OperationCache<T>>> {
+    key.map(|key| Rc::new(OperationCache::new(key)))
+}
+
+/// Compile an already validated declaration with operations guarded by their preconditions and
+/// wrapped by the given wrapper, counting the created branches and the applications of the
+/// operations inside the wrapper when metrics are given.
+fn compile_instrumented<T: Clone + 'static, P: BindParameters<T> + ?Sized>(
+    generators: &GeneratorRegistry<T>,
+    operations: &OperationRegistry<T>,
+    parameters: &P,
+    declaration: &SimulationDeclaration,
+    preconditions: &Preconditions<T>,
+    metrics: &Option<Arc<dyn Metrics>>,
+    wrapper: &OperationWrapper<'_, T>
+) -> EventNode<T> {
+    let instrumented = |name, operation| {
+        let operation = match metrics {
+            Some(metrics) => wrapper(name, counted(Arc::clone(metrics))(name, operation)),
+            None => wrapper(name, operation),
+        };
+        match preconditions.get(name) {
+            Some(precondition) => guarded(precondition.clone(), operation),
+            None => operation,
+        }
+    };
+    let root = compile(generators, operations, parameters, declaration, &instrumented)
+        .expect("declaration compiled successfully on construction");
+    if let Some(metrics) = metrics {
+        let statistics = GraphStatistics::of(&root);
+        metrics.increment(Counter::BranchesCreated, (statistics.edges - (statistics.nodes - statistics.leaves)) as u64);
+    }
+    root
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::mpsc::channel;
+    use crate::event_graph::EventNodeExt;
+    use crate::external_model::CacheStatistics;
+    use crate::metrics::AtomicMetrics;
+    use crate::configuration_utils::{OperationParams, ParameterSchema};
+    use crate::operation_cache::hash_key;
+    use super::*;
+
+    fn increment(val: i32, params: ParameterMap) -> i32 {
+        val + params.get("increase").map_or(1, |value| value.parse::<i32>().unwrap())
+    }
+
+    fn double(val: i32, _params: ParameterMap) -> i32 {
+        val * 2
+    }
+
+    fn create_operations() -> OperationRegistry<i32> {
+        HashMap::from([
+            ("increment", increment as ParameteredOperation<i32>),
+            ("double", double as ParameteredOperation<i32>)
+        ])
+    }
+
+    #[test]
+    fn runner_evaluates_initial_states() {
+        let parameters = OperationParameters::from([("increment", ParameterMap::from([("increase", "3")]))]);
+        let declaration = vec![
+            ("sequence", vec!["increment"]),
+            ("alternatives", vec!["increment", "double"])
+        ];
+        let runner = SimulationRunner::new(create_operations(), parameters, declaration).unwrap();
+        let results = runner.run([0, 1]);
+        assert_eq!(vec![
+            RunResult { initial_state: 0, results: vec![6, 6] },
+            RunResult { initial_state: 1, results: vec![7, 8] }
+        ], results);
+    }
+
+    #[test]
+    fn nodes_are_labeled_by_operation() {
+        let declaration = vec![
+            ("sequence", vec!["increment", "double"]),
+            ("alternatives", vec!["double", "increment"])
+        ];
+        let runner = SimulationRunner::new(create_operations(), OperationParameters::new(), declaration).unwrap();
+        let labels: Vec<Option<&str>> = EventDAG::reachable_nodes(&[Rc::clone(runner.root())]).iter()
+            .map(|node| node.borrow().label())
+            .collect();
+        assert_eq!(vec![None, Some("increment"), Some("double"), Some("double"), Some("increment")], labels);
+    }
+
+    #[test]
+    fn parameters_are_optional() {
+        let declaration = vec![("sequence", vec!["increment", "increment"])];
+        let runner = SimulationRunner::new(create_operations(), OperationParameters::new(), declaration).unwrap();
+        assert_eq!(vec![2], runner.run([0])[0].results);
+    }
+
+    #[test]
+    fn unknown_names_are_rejected() {
+        let unknown_generator = SimulationRunner::new(create_operations(), OperationParameters::new(), vec![("shuffle", vec!["double"])]);
+        assert_eq!(Some(RunnerError::UnknownGenerator("shuffle")), unknown_generator.err());
+        let unknown_operation = SimulationRunner::new(create_operations(), OperationParameters::new(), vec![("sequence", vec!["halve"])]);
+        assert_eq!(Some(RunnerError::UnknownOperation("halve")), unknown_operation.err());
+    }
+
+    #[test]
+    fn manifest_describes_runner() {
+        let declaration = vec![("sequence", vec!["increment"])];
+        let mut runner = SimulationRunner::new(create_operations(), OperationParameters::new(), declaration.clone()).unwrap();
+        runner.set_operation_versions(OperationVersions::from([("double", "2.0")]));
+        let manifest = runner.manifest();
+        assert_eq!(config_hash(&declaration, &OperationParameters::new()), manifest.config_hash);
+        assert_eq!(vec![("double", "2.0"), ("increment", "unversioned")], manifest.operation_versions.iter()
+            .map(|(name, version)| (name.as_str(), version.as_str())).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn dry_run_validates_without_evaluating() {
+        let parameters = OperationParameters::from([
+            ("increment", ParameterMap::from([("increase", "3")])),
+            ("thin", ParameterMap::from([("ratio", "0.3")]))
+        ]);
+        let declaration = vec![
+            ("alternatives", vec!["increment", "double"]),
+            ("alternatives", vec!["increment", "double", "increment"])
+        ];
+        let report = SimulationRunner::dry_run(create_operations(), parameters, declaration).unwrap();
+        assert_eq!(6, report.statistics.chains);
+        assert_eq!(3, report.statistics.depth);
+        assert_eq!(vec!["parameters given for unknown operation 'thin'".to_string()], report.issues);
+        assert!(report.warnings.is_empty());
+
+        let parameters = OperationParameters::from([("double", ParameterMap::new())]);
+        let runner = SimulationRunner::new(create_operations(), parameters, vec![("sequence", vec!["increment"])]).unwrap();
+        let report = runner.validate();
+        assert_eq!(vec![Lint::UnusedParameters("double"), Lint::UnreferencedOperation("double")], report.warnings);
+        assert!(report.to_string().contains("warning: operation 'double' is registered but never referenced"));
+        assert_eq!(Some(&Lint::LargeChainCount { chains: 1, limit: 0 }), runner.lint(0).last());
+
+        let invalid = SimulationRunner::dry_run(create_operations(), OperationParameters::new(), vec![("sequence", vec!["thin"])]);
+        assert_eq!(Some(RunnerError::UnknownOperation("thin")), invalid.err());
+    }
+
+    #[derive(OperationParams)]
+    #[allow(dead_code)]
+    struct IncrementParams {
+        increase: i32,
+    }
+
+    #[test]
+    fn parameters_are_validated_against_schemas() {
+        let parameters = OperationParameters::from([("double", ParameterMap::from([("factor", "2")]))]);
+        let declaration = vec![("sequence", vec!["increment", "double"])];
+        let mut runner = SimulationRunner::new(create_operations(), parameters, declaration).unwrap();
+        assert!(runner.validate().issues.is_empty());
+        let schemas = ParameterSchemas::from([("increment", IncrementParams::schema()), ("double", ParameterSchema::new())]);
+        runner.set_parameter_schemas(schemas).unwrap();
+        assert_eq!(vec![
+            "operation 'double': unknown parameter 'factor'".to_string(),
+            "operation 'increment': missing parameter 'increase'".to_string(),
+        ], runner.validate().issues);
+        let unknown = runner.set_parameter_schemas(ParameterSchemas::from([("thin", ParameterSchema::new())]));
+        assert_eq!(Err(RunnerError::UnknownOperation("thin")), unknown);
+    }
+
+    #[derive(OperationParams)]
+    #[allow(dead_code)]
+    struct DoubleParams {
+        #[param(default = "2")]
+        factor: i32,
+        #[param(default = "all")]
+        target: String,
+    }
+
+    #[test]
+    fn chains_record_effective_parameters() {
+        let parameters = OperationParameters::from([("double", ParameterMap::from([("target", "stems")]))]);
+        let declaration = vec![("sequence", vec!["increment"]), ("alternatives", vec!["increment", "double"])];
+        let mut runner = SimulationRunner::new(create_operations(), parameters, declaration).unwrap();
+        runner.set_parameter_schemas(ParameterSchemas::from([("double", DoubleParams::schema())])).unwrap();
+        let provenance = runner.parameter_provenance();
+        assert_eq!("increment() > increment()", provenance[&0].to_string());
+        assert_eq!("increment() > double(factor=2,target=stems)", provenance[&1].to_string());
+
+        let variant = runner.with_parameters(OperationParameters::from([("increment", ParameterMap::from([("increase", "3")]))])).unwrap();
+        assert_eq!("increment(increase=3) > double(factor=2,target=all)", variant.parameter_provenance()[&1].to_string());
+    }
+
+    #[test]
+    fn metrics_are_reported() {
+        let declaration = vec![
+            ("sequence", vec!["increment"]),
+            ("alternatives", vec!["increment", "double", "increment"])
+        ];
+        let mut runner = SimulationRunner::new(create_operations(), OperationParameters::new(), declaration).unwrap();
+        let metrics = Arc::new(AtomicMetrics::new());
+        runner.set_metrics(metrics.clone()).unwrap();
+        runner.set_metrics(metrics.clone()).unwrap();
+        assert_eq!(2, metrics.get(Counter::BranchesCreated));
+        runner.with_parameters(OperationParameters::new()).unwrap();
+        assert_eq!(4, metrics.get(Counter::BranchesCreated));
+        runner.run_batch([(1, 1), (2, 2)]);
+        assert_eq!(12, metrics.get(Counter::OperationsApplied));
+        runner.run_batch_parallel(vec![(1, 1)], 2).unwrap();
+        assert_eq!(18, metrics.get(Counter::OperationsApplied));
+        assert_eq!(0, metrics.get(Counter::Errors));
+    }
+
+    #[test]
+    fn edited_graphs_are_not_recompiled() {
+        let mut runner = SimulationRunner::new(create_operations(), OperationParameters::new(), vec![("sequence", vec!["increment"])]).unwrap();
+        assert!(!runner.is_modified());
+        let leaf = runner.root().leaves().next().unwrap();
+        leaf.borrow_mut().set_operation(Box::new(|x| x * 10));
+        assert!(runner.is_modified());
+        assert_eq!(Err(RunnerError::ModifiedGraph), runner.set_metrics(Arc::new(AtomicMetrics::new())));
+        let skipping = Precondition { check: |_: &i32| false, on_failure: PreconditionFailure::Skip };
+        assert_eq!(Err(RunnerError::ModifiedGraph), runner.set_preconditions(Preconditions::from([("increment", skipping)])));
+        assert_eq!(Err(RunnerError::ModifiedGraph), runner.set_operation_cache(hash_key::<i32>));
+        assert!(runner.operation_cache().is_none());
+        assert_eq!(vec![10], runner.run([1])[0].results);
+    }
+
+    #[test]
+    #[should_panic(expected = "event graph has been edited")]
+    fn edited_graphs_are_not_run_in_parallel() {
+        let runner = SimulationRunner::new(create_operations(), OperationParameters::new(), vec![("sequence", vec!["increment"])]).unwrap();
+        runner.root().borrow_mut().set_priority(1);
+        let _ = runner.run_batch_parallel(vec![(1, 1)], 2);
+    }
+
+    #[test]
+    fn operation_outputs_are_cached() {
+        let declaration = vec![
+            ("alternatives", vec!["increment", "double"]),
+            ("sequence", vec!["double", "increment"])
+        ];
+        let mut runner = SimulationRunner::new(create_operations(), OperationParameters::new(), declaration).unwrap();
+        let metrics = Arc::new(AtomicMetrics::new());
+        runner.set_metrics(metrics.clone()).unwrap();
+        runner.set_operation_cache(hash_key::<i32>).unwrap();
+        assert_eq!(vec![5, 5], runner.run([1])[0].results);
+        assert_eq!(4, metrics.get(Counter::OperationsApplied));
+        assert_eq!(CacheStatistics { hits: 2, misses: 4 }, runner.operation_cache().unwrap().statistics());
+        assert_eq!(vec![5, 5], runner.run([1])[0].results);
+        assert_eq!(4, metrics.get(Counter::OperationsApplied));
+        let outcome = runner.run_batch_parallel(vec![(1, 1), (2, 1)], 2).unwrap();
+        assert_eq!(ChainResults::from([(0, 5), (1, 5)]), outcome.results[&2]);
+        assert!(metrics.get(Counter::OperationsApplied) <= 12);
+    }
+
+    #[test]
+    fn edited_graphs_have_no_variants() {
+        let mut runner = SimulationRunner::new(create_operations(), OperationParameters::new(), vec![("alternatives", vec!["increment", "double"])]).unwrap();
+        runner.set_scenarios(vec![("no_double", vec!["increment"])]).unwrap();
+        let leaf = runner.root().leaves().next().unwrap();
+        leaf.borrow_mut().set_operation(Box::new(|x| x * 10));
+        assert!(matches!(runner.scenario("no_double"), Err(RunnerError::ModifiedGraph)));
+        assert!(matches!(runner.select_scenarios(&["no_double"]), Err(RunnerError::ModifiedGraph)));
+        assert!(matches!(runner.with_parameters(OperationParameters::new()), Err(RunnerError::ModifiedGraph)));
+    }
+
+    #[test]
+    fn scenarios_are_selected() {
+        let declaration = vec![
+            ("alternatives", vec!["increment", "double"]),
+            ("sequence", vec!["increment"]),
+            ("alternatives", vec!["increment", "double"])
+        ];
+        let mut runner = SimulationRunner::new(create_operations(), OperationParameters::new(), declaration).unwrap();
+        runner.set_scenarios(vec![("no_double", vec!["increment"]), ("double_first", vec!["double"]), ("open", vec![])]).unwrap();
+        assert_eq!(vec![4, 6, 4, 6], runner.run([1])[0].results);
+        assert_eq!(vec![4], runner.scenario("no_double").unwrap().run([1])[0].results);
+        let selected = runner.select_scenarios(&["open", "double_first"]).unwrap();
+        assert_eq!(vec!["open", "double_first"], selected.iter().map(|(name, _)| *name).collect::<Vec<_>>());
+        assert_eq!(vec![4, 6, 4, 6], selected[0].1.run([1])[0].results);
+        assert_eq!(vec![6], selected[1].1.run([1])[0].results);
+        assert_ne!(runner.manifest().config_hash, selected[1].1.manifest().config_hash);
+
+        assert_eq!(Some(RunnerError::UnknownScenario("thin".to_string())), runner.scenario("thin").err());
+        assert_eq!(
+            Err(RunnerError::ScenarioChoice { scenario: "thin", operation: "thin" }),
+            runner.set_scenarios(vec![("thin", vec!["thin"])])
+        );
+    }
+
+    #[test]
+    fn preconditions_skip_or_prune() {
+        let declaration = vec![
+            ("alternatives", vec!["increment", "double"]),
+            ("sequence", vec!["double", "increment"])
+        ];
+        let mut runner = SimulationRunner::new(create_operations(), OperationParameters::new(), declaration).unwrap();
+        let metrics = Arc::new(AtomicMetrics::new());
+        runner.set_metrics(metrics.clone()).unwrap();
+        assert_eq!(vec![9, 13], runner.run([3])[0].results);
+
+        let below_five = Precondition { check: |state: &i32| *state < 5, on_failure: PreconditionFailure::Skip };
+        runner.set_preconditions(Preconditions::from([("double", below_five)])).unwrap();
+        assert_eq!(vec![9, 7], runner.run([3])[0].results);
+
+        let pruning = Precondition { on_failure: PreconditionFailure::Prune, ..below_five };
+        runner.set_preconditions(Preconditions::from([("double", pruning)])).unwrap();
+        assert_eq!(vec![9], runner.run([3])[0].results);
+        assert_eq!(1, metrics.get(Counter::ChainsPruned));
+        let outcome = runner.run_batch_parallel(vec![(1, 3), (2, 4)], 2).unwrap();
+        assert_eq!(ChainResults::from([(0, 9)]), outcome.results[&1]);
+        assert!(outcome.results[&2].is_empty());
+        assert_eq!(4, metrics.get(Counter::ChainsPruned));
+
+        assert_eq!(
+            Err(RunnerError::UnknownOperation("thin")),
+            runner.set_preconditions(Preconditions::from([("thin", pruning)]))
+        );
+    }
+
+    #[test]
+    fn batches_are_streamed_over_channels() {
+        let declaration = vec![("alternatives", vec!["increment", "double"])];
+        let mut runner = SimulationRunner::new(create_operations(), OperationParameters::new(), declaration).unwrap();
+        let pruning = Precondition { check: |state: &i32| *state < 5, on_failure: PreconditionFailure::Prune };
+        runner.set_preconditions(Preconditions::from([("double", pruning)])).unwrap();
+        let (sender, receiver) = channel();
+        assert!(runner.stream_batch([(1, 3)], &sender).is_empty());
+        assert!(runner.stream_batch_parallel(vec![(2, 7)], 2, &sender).unwrap().is_empty());
+        drop(sender);
+        let received: Vec<(i32, usize, i32)> = receiver.iter().map(|streamed| (streamed.entity, streamed.chain, streamed.result)).collect();
+        assert_eq!(vec![(1, 0, 4), (1, 1, 6), (2, 0, 8)], received);
+    }
+
+    #[test]
+    fn equivalent_configurations_share_fingerprints() {
+        let declaration = vec![("alternatives", vec!["increment", "double"])];
+        let runner = SimulationRunner::new(create_operations(), OperationParameters::new(), declaration.clone()).unwrap();
+        let rebuilt = SimulationRunner::new(create_operations(), OperationParameters::new(), declaration).unwrap();
+        assert_eq!(runner.fingerprint(), rebuilt.fingerprint());
+        let parameters = OperationParameters::from([("double", ParameterMap::from([("factor", "3")]))]);
+        assert_ne!(runner.fingerprint(), runner.with_parameters(parameters).unwrap().fingerprint());
+        let reordered = SimulationRunner::new(create_operations(), OperationParameters::new(), vec![("alternatives", vec!["double", "increment"])]).unwrap();
+        assert_ne!(runner.fingerprint(), reordered.fingerprint());
+    }
+
+    #[test]
+    fn changed_configurations_are_evaluated_warm() {
+        let runner = SimulationRunner::new(create_operations(), OperationParameters::new(), vec![("sequence", vec!["increment", "double"])]).unwrap();
+        let mut warm_start = WarmStart::new(1, 2);
+        assert_eq!(vec![4], runner.evaluate_warm(&mut warm_start).unwrap().into_values().collect::<Vec<_>>());
+        let changed = SimulationRunner::new(create_operations(), OperationParameters::new(), vec![("sequence", vec!["increment", "increment"])]).unwrap();
+        assert_eq!(vec![3], changed.evaluate_warm(&mut warm_start).unwrap().into_values().collect::<Vec<_>>());
+        assert_eq!((Some(1), 1), (warm_start.reused_depth(), warm_start.applications()));
+    }
+
+    #[test]
+    fn batches_are_logged() {
+        let declaration = vec![("alternatives", vec!["increment", "double"])];
+        let mut runner = SimulationRunner::new(create_operations(), OperationParameters::new(), declaration).unwrap();
+        let pruning = Precondition { check: |state: &i32| *state < 5, on_failure: PreconditionFailure::Prune };
+        runner.set_preconditions(Preconditions::from([("double", pruning)])).unwrap();
+        let mut log = DecisionLog::new(Vec::new());
+        assert_eq!(ChainResults::from([(0, 8)]), runner.run_batch_logged([(1, 7)], &mut log).results[&1]);
+        let log = String::from_utf8(log.finish().unwrap()).unwrap();
+        assert!(log.ends_with("{\"entity\":\"1\",\"chain\":1,\"event\":\"pruned\",\"position\":1,\"operation\":\"double\"}\n"));
+    }
+}
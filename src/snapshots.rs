@@ -0,0 +1,109 @@
+use std::collections::BTreeMap;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use crate::event_graph::{is_pruned, panic_message, ChainResults, EvaluationFailure, EventDAG, EventNode};
+
+/// State of an event chain entering a snapshot node.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Snapshot<T> {
+    /// Position of the snapshot node within the event chain, the starting node being 0.
+    pub position: usize,
+    /// Label of the snapshot node.
+    pub label: Option<&'static str>,
+    pub state: T,
+}
+
+/// Results of event chains by chain id, with the snapshots taken along each chain. Pruned chains
+/// have no result but keep their snapshots preceding the pruning.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SnapshotResults<T> {
+    pub results: ChainResults<T>,
+    pub snapshots: BTreeMap<usize, Vec<Snapshot<T>>>,
+}
+
+impl<T> SnapshotResults<T> {
+    /// Snapshots of the given event chain in chain order.
+    pub fn of_chain(&self, chain: usize) -> &[Snapshot<T>] {
+        self.snapshots.get(&chain).map_or(&[], |snapshots| snapshots.as_slice())
+    }
+
+    /// States entering the snapshot nodes with the given label, by chain id. Of several such
+    /// nodes along a chain, the first one is taken.
+    pub fn at(&self, label: &str) -> BTreeMap<usize, &T> {
+        self.snapshots.iter()
+            .filter_map(|(chain, snapshots)| {
+                snapshots.iter().find(|snapshot| snapshot.label == Some(label)).map(|snapshot| (*chain, &snapshot.state))
+            })
+            .collect()
+    }
+}
+
+/// Evaluate the unique event chains as EventDAG::try_evaluate_chains_by_id, storing the states
+/// entering the nodes marked as snapshot points, such as the state preceding a treatment
+/// decision.
+pub fn try_evaluate_snapshots<T: Clone>(root: &EventNode<T>, payload: T) -> Result<SnapshotResults<T>, EvaluationFailure> {
+    let mut results = SnapshotResults { results: ChainResults::new(), snapshots: BTreeMap::new() };
+    'chains: for (chain_index, chain) in EventDAG::node_chains(root).iter().enumerate() {
+        let mut current = payload.clone();
+        for (position, node) in chain.iter().enumerate() {
+            let node = node.borrow();
+            if node.is_snapshot() {
+                let snapshot = Snapshot { position, label: node.label(), state: current.clone() };
+                results.snapshots.entry(chain_index).or_default().push(snapshot);
+            }
+            current = match catch_unwind(AssertUnwindSafe(|| node.apply(current))) {
+                Ok(current) => current,
+                Err(payload) if is_pruned(&*payload) => continue 'chains,
+                Err(payload) => return Err(EvaluationFailure { chain: chain_index, operation: position, label: node.label().map(str::to_string), error: panic_message(payload) }),
+            };
+        }
+        results.results.insert(chain_index, current);
+    }
+    Ok(results)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+    use crate::branching_generators::{alternatives, sequence};
+    use crate::event_graph::{prune, BoxedOperation};
+    use super::*;
+
+    /// Growth, then thinning or a pruned alternative, each followed by growth and a clearcut.
+    fn programs() -> EventNode<i32> {
+        let root = EventDAG::new_node(Box::new(|x: i32| x));
+        let grown = sequence(vec![Rc::clone(&root)], [Box::new(|x| x + 5) as BoxedOperation<i32>]).unwrap();
+        let treatments: Vec<BoxedOperation<i32>> = vec![Box::new(|x| x / 2), Box::new(|x| if x > 4 { prune() } else { x })];
+        let treated = alternatives(grown, treatments).unwrap();
+        let operations: Vec<BoxedOperation<i32>> = vec![Box::new(|x| x + 5), Box::new(|_| 0)];
+        let ends = sequence(treated.clone(), operations).unwrap();
+        for node in treated {
+            node.borrow_mut().set_label("treatment");
+            node.borrow_mut().set_snapshot(true);
+        }
+        let clearcut = EventDAG::reachable_nodes(&ends).into_iter().find(|node| node.borrow().is_leaf()).unwrap();
+        clearcut.borrow_mut().set_label("clearcut");
+        clearcut.borrow_mut().set_snapshot(true);
+        root
+    }
+
+    #[test]
+    fn states_are_stored_at_snapshot_nodes() {
+        let results = try_evaluate_snapshots(&programs(), 1).unwrap();
+        assert_eq!(ChainResults::from([(0, 0)]), results.results);
+        assert_eq!(vec![
+            Snapshot { position: 2, label: Some("treatment"), state: 6 },
+            Snapshot { position: 4, label: Some("clearcut"), state: 8 },
+        ], results.of_chain(0));
+        assert_eq!(BTreeMap::from([(0, &6), (1, &6)]), results.at("treatment"));
+        assert_eq!(BTreeMap::from([(0, &8)]), results.at("clearcut"));
+        assert!(results.of_chain(2).is_empty());
+    }
+
+    #[test]
+    fn failures_are_described() {
+        let root = programs();
+        root.borrow_mut().set_operation(Box::new(|x| if x < 0 { panic!("negative") } else { x }));
+        let failure = try_evaluate_snapshots(&root, -1).unwrap_err();
+        assert_eq!(EvaluationFailure { chain: 0, operation: 0, label: None, error: "negative".to_string() }, failure);
+    }
+}
@@ -0,0 +1,62 @@
+use std::hash::Hasher;
+
+const FNV_OFFSET_BASIS: u64 = 0xcbf29ce484222325;
+const FNV_PRIME: u64 = 0x100000001b3;
+
+/// StableHasher is a 64-bit FNV-1a hasher. Unlike the std DefaultHasher its output does not vary
+/// between program runs or Rust releases, which makes it suitable for hashes persisted into
+/// outputs. Integers hash with their native byte order, so only string and byte input hashes
+/// identically across platforms.
+#[derive(Debug, Clone, Copy)]
+pub struct StableHasher {
+    state: u64,
+}
+
+impl StableHasher {
+    pub fn new() -> StableHasher {
+        StableHasher { state: FNV_OFFSET_BASIS }
+    }
+
+    /// Hex representation of the current hash value.
+    pub fn hex(&self) -> String {
+        format!("{:016x}", self.finish())
+    }
+}
+
+impl Default for StableHasher {
+    fn default() -> Self {
+        StableHasher::new()
+    }
+}
+
+impl Hasher for StableHasher {
+    fn finish(&self) -> u64 {
+        self.state
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.state ^= *byte as u64;
+            self.state = self.state.wrapping_mul(FNV_PRIME);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::hash::Hash;
+    use super::*;
+
+    #[test]
+    fn hashes_are_stable() {
+        let mut hasher = StableHasher::new();
+        hasher.write(b"a");
+        assert_eq!(0xaf63dc4c8601ec8c, hasher.finish());
+        let mut hasher = StableHasher::new();
+        "metsi".hash(&mut hasher);
+        assert_eq!(16, hasher.hex().len());
+        let mut other = StableHasher::new();
+        "metsi".hash(&mut other);
+        assert_eq!(hasher.hex(), other.hex());
+    }
+}
@@ -0,0 +1,126 @@
+use std::collections::BTreeMap;
+use std::fmt::{self, Display};
+use std::hash::{Hash, Hasher};
+use serde::{Deserialize, Serialize};
+use crate::batch_runner::EntityResults;
+use crate::event_graph::ChainResults;
+use crate::stable_hash::StableHasher;
+
+/// Stable hashes of the chain results of each entity by chain id.
+pub type StateHashes<K> = BTreeMap<K, ChainResults<u64>>;
+
+/// Stable hash of a state by its Hash implementation. As integers hash in native byte order,
+/// the hash is stable between runs and builds but not across platforms of different endianness.
+pub fn hash_state<T: Hash>(state: &T) -> u64 {
+    let mut hasher = StableHasher::new();
+    state.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Stable hash of a state by its JSON serialization, for states with floating point fields which
+/// do not implement Hash. The hash is the same on all platforms, floats being serialized in their
+/// shortest exact representation, so any change in a numerical outcome changes the hash.
+pub fn hash_serialized<T: Serialize>(state: &T) -> serde_json::Result<u64> {
+    let mut hasher = StableHasher::new();
+    hasher.write(&serde_json::to_vec(state)?);
+    Ok(hasher.finish())
+}
+
+/// Hash the chain results of each entity with the given state hash.
+pub fn state_hashes<K: Ord + Clone, T, H: Fn(&T) -> u64>(results: &EntityResults<K, T>, hash: H) -> StateHashes<K> {
+    results.iter().map(|(entity, states)| (entity.clone(), states.iter().map(|(chain, state)| (*chain, hash(state))).collect())).collect()
+}
+
+/// Entities and chain ids whose state hashes differ between the runs, including chains present
+/// in only one of the runs, in entity and chain order.
+pub fn differing_states<K: Ord + Clone>(baseline: &StateHashes<K>, candidate: &StateHashes<K>) -> Vec<(K, usize)> {
+    let mut entities: Vec<&K> = baseline.keys().chain(candidate.keys()).collect();
+    entities.sort();
+    entities.dedup();
+    let empty = ChainResults::new();
+    entities.into_iter()
+        .flat_map(|entity| {
+            let (baseline, candidate) = (baseline.get(entity).unwrap_or(&empty), candidate.get(entity).unwrap_or(&empty));
+            let mut chains: Vec<usize> = baseline.keys().chain(candidate.keys()).copied().collect();
+            chains.sort();
+            chains.dedup();
+            chains.into_iter()
+                .filter(|chain| baseline.get(chain) != candidate.get(chain))
+                .map(|chain| (entity.clone(), chain))
+                .collect::<Vec<_>>()
+        })
+        .collect()
+}
+
+/// Digest of the final states of a run, for cheaply verifying that a refactoring or a different
+/// degree of parallelism did not change the outcomes. The digest covers the entity ids, the chain
+/// ids and the state hashes in entity order, so it does not depend on the evaluation order.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RunDigest {
+    pub digest: String,
+    pub entities: usize,
+    pub states: usize,
+}
+
+impl RunDigest {
+    /// Digest of the state hashes of a run.
+    pub fn of<K: Display>(hashes: &StateHashes<K>) -> RunDigest {
+        let mut hasher = StableHasher::new();
+        for (entity, states) in hashes {
+            hasher.write(entity.to_string().as_bytes());
+            hasher.write(&(states.len() as u64).to_le_bytes());
+            for (chain, state) in states {
+                hasher.write(&(*chain as u64).to_le_bytes());
+                hasher.write(&state.to_le_bytes());
+            }
+        }
+        RunDigest { digest: hasher.hex(), entities: hashes.len(), states: hashes.values().map(ChainResults::len).sum() }
+    }
+}
+
+impl fmt::Display for RunDigest {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ({} entities, {} states)", self.digest, self.entities, self.states)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::batch_runner::{run_batch, run_batch_parallel};
+    use crate::branching_generators::alternatives;
+    use crate::event_graph::{BoxedOperation, EventDAG, EventNode};
+    use super::*;
+
+    fn graph() -> EventNode<f64> {
+        let root = EventDAG::new_node(Box::new(|x: f64| x));
+        let operations: Vec<BoxedOperation<f64>> = vec![Box::new(|x| x * 1.1), Box::new(|x| x / 3.0)];
+        alternatives(vec![root.clone()], operations).unwrap();
+        root
+    }
+
+    #[test]
+    fn digests_do_not_depend_on_parallelism() {
+        let entities: Vec<(i32, f64)> = (0..50).map(|entity| (entity, entity as f64)).collect();
+        let sequential = run_batch(&graph(), entities.clone()).results;
+        let parallel = run_batch_parallel(graph, entities, 4).unwrap().results;
+        let hash = |state: &f64| hash_serialized(state).unwrap();
+        let digest = RunDigest::of(&state_hashes(&sequential, hash));
+        assert_eq!(digest, RunDigest::of(&state_hashes(&parallel, hash)));
+        assert_eq!((50, 100, 16), (digest.entities, digest.states, digest.digest.len()));
+    }
+
+    #[test]
+    fn changed_outcomes_are_located() {
+        let baseline = state_hashes(&EntityResults::from([("a", ChainResults::from([(0, 1.0), (1, 2.0)])), ("b", ChainResults::from([(0, 3.0)]))]), |state| hash_serialized(state).unwrap());
+        let candidate = state_hashes(&EntityResults::from([("a", ChainResults::from([(0, 1.0), (1, 2.0000001)])), ("c", ChainResults::from([(0, 3.0)]))]), |state| hash_serialized(state).unwrap());
+        assert_ne!(RunDigest::of(&baseline), RunDigest::of(&candidate));
+        assert_eq!(vec![("a", 1), ("b", 0), ("c", 0)], differing_states(&baseline, &candidate));
+
+        let pruned = state_hashes(&EntityResults::from([("a", ChainResults::from([(1, 2.0)]))]), |state| hash_serialized(state).unwrap());
+        let shifted = state_hashes(&EntityResults::from([("a", ChainResults::from([(0, 2.0)]))]), |state| hash_serialized(state).unwrap());
+        assert_eq!(vec![("a", 0), ("b", 0)], differing_states(&baseline, &pruned));
+        assert_ne!(RunDigest::of(&pruned), RunDigest::of(&shifted));
+        assert_eq!(hash_state(&(1, "a")), hash_state(&(1, "a")));
+        assert_ne!(hash_state(&(1, "a")), hash_state(&(2, "a")));
+    }
+}
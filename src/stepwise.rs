@@ -0,0 +1,283 @@
+use std::collections::{BTreeMap, HashMap};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::rc::Rc;
+use crate::collectors::Projector;
+use crate::event_graph::{chain_count, is_pruned, panic_message, ChainResults, EvaluationFailure, EventNode};
+
+/// State of a live branch after the latest evaluated node of the branch.
+#[derive(Clone)]
+pub struct PartialState<T> {
+    /// Id of the first event chain through the branch.
+    pub chain: usize,
+    /// Position of the node within the event chains through it, the starting node being 0.
+    pub position: usize,
+    pub node: EventNode<T>,
+    pub state: T,
+}
+
+/// Stepwise evaluation of the unique event chains of an event graph, for simulation loops
+/// controlled from outside. Each step advances all live branches by one node, leaving the
+/// frontier of partial states for the caller to inspect, filter, modify or persist before the
+/// next step. Branches reaching a leaf produce their results by chain id, pruned branches
+/// produce no results.
+pub struct StepwiseEvaluation<T> {
+    start: Option<(EventNode<T>, T)>,
+    frontier: Vec<PartialState<T>>,
+    results: ChainResults<T>,
+    chain_counts: HashMap<*const (), usize>,
+}
+
+impl<T: Clone> StepwiseEvaluation<T> {
+    /// Stepwise evaluation starting from the given root, its operation applied by the first step.
+    pub fn new(root: &EventNode<T>, payload: T) -> StepwiseEvaluation<T> {
+        StepwiseEvaluation { start: Some((Rc::clone(root), payload)), frontier: Vec::new(), results: ChainResults::new(), chain_counts: HashMap::new() }
+    }
+
+    /// Stepwise evaluation continuing from the given frontier, such as one saved from an earlier
+    /// stepwise evaluation.
+    pub fn from_frontier(frontier: Vec<PartialState<T>>) -> StepwiseEvaluation<T> {
+        StepwiseEvaluation { start: None, frontier, results: ChainResults::new(), chain_counts: HashMap::new() }
+    }
+
+    /// Partial states of the live branches.
+    pub fn frontier(&self) -> &[PartialState<T>] {
+        &self.frontier
+    }
+
+    /// Partial states of the live branches, for modifying the states or removing branches.
+    pub fn frontier_mut(&mut self) -> &mut Vec<PartialState<T>> {
+        &mut self.frontier
+    }
+
+    /// Results of the branches evaluated to their end so far, by chain id.
+    pub fn results(&self) -> &ChainResults<T> {
+        &self.results
+    }
+
+    pub fn into_results(self) -> ChainResults<T> {
+        self.results
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.start.is_none() && self.frontier.is_empty()
+    }
+
+    /// Advance each live branch by one node, returning the new frontier. A panicking operation
+    /// is described as an EvaluationFailure, leaving the frontier unchanged.
+    pub fn evaluate_step(&mut self) -> Result<&[PartialState<T>], EvaluationFailure> {
+        self.advance(|_| true, |_| {})?;
+        Ok(&self.frontier)
+    }
+
+    /// Advance each live branch to the end of the next time layer of the event graph, that is
+    /// until the node of the branch is followed by nodes of other layers. Nodes outside time
+    /// layers are advanced over one by one.
+    pub fn evaluate_layer(&mut self) -> Result<&[PartialState<T>], EvaluationFailure> {
+        self.advance(|_| true, |_| {})?;
+        while self.frontier.iter().any(within_layer) {
+            self.advance(within_layer, |_| {})?;
+        }
+        Ok(&self.frontier)
+    }
+
+    /// Advance the live branches accepted by the predicate by one node, passing the states of
+    /// the advanced branches to the observer.
+    fn advance<P, O>(&mut self, predicate: P, mut observe: O) -> Result<(), EvaluationFailure>
+    where P: Fn(&PartialState<T>) -> bool, O: FnMut(&[PartialState<T>]) {
+        let mut steps = Vec::new();
+        match &self.start {
+            Some((root, payload)) => steps.push((0, 0, Rc::clone(root), payload.clone())),
+            None => {
+                for partial in self.frontier.iter().filter(|partial| predicate(partial)) {
+                    let mut chain = partial.chain;
+                    for follower in partial.node.borrow().evaluation_order() {
+                        let chains = chain_count(&follower, &mut self.chain_counts);
+                        steps.push((chain, partial.position + 1, follower, partial.state.clone()));
+                        chain += chains;
+                    }
+                }
+            }
+        }
+        let mut level = Vec::new();
+        for (chain, position, node, state) in steps {
+            let state = match catch_unwind(AssertUnwindSafe(|| node.borrow().apply(state))) {
+                Ok(state) => state,
+                Err(payload) if is_pruned(&*payload) => continue,
+                Err(payload) => return Err(EvaluationFailure { chain, operation: position, label: node.borrow().label().map(str::to_string), error: panic_message(payload) }),
+            };
+            level.push(PartialState { chain, position, node, state });
+        }
+        observe(&level);
+        self.start = None;
+        self.frontier.retain(|partial| !predicate(partial));
+        for partial in level {
+            if partial.node.borrow().is_leaf() {
+                self.results.insert(partial.chain, partial.state);
+            }
+            else {
+                self.frontier.push(partial);
+            }
+        }
+        self.frontier.sort_by_key(|partial| partial.chain);
+        Ok(())
+    }
+}
+
+/// Collector observing evaluation level by level, that is the states of all live branches at
+/// each depth of the event graph at once.
+pub trait LevelCollector<T> {
+    /// Observe the states of the branches at the given depth, in chain id order.
+    fn observe_level(&mut self, depth: usize, level: &[PartialState<T>]);
+}
+
+/// Collect the projected values of the states at each depth by the first chain id of the branch.
+pub struct LevelValues<T, V> {
+    projector: Projector<T, V>,
+    pub values: Vec<BTreeMap<usize, V>>,
+}
+
+impl<T, V> LevelValues<T, V> {
+    pub fn new(projector: Projector<T, V>) -> LevelValues<T, V> {
+        LevelValues { projector, values: Vec::new() }
+    }
+}
+
+impl<T, V> LevelCollector<T> for LevelValues<T, V> {
+    fn observe_level(&mut self, _depth: usize, level: &[PartialState<T>]) {
+        self.values.push(level.iter().map(|partial| (partial.chain, (self.projector)(&partial.state))).collect());
+    }
+}
+
+/// Evaluate the unique event chains breadth-first, level by level, holding one state per live
+/// branch at the current depth instead of one state per event chain. Chains sharing a prefix
+/// share its evaluation, which suits shallow and wide graphs. The collectors observe each level
+/// once evaluated. Produces the results by chain id as EventDAG::try_evaluate_chains_by_id.
+pub fn try_evaluate_breadth_first<T: Clone>(
+    root: &EventNode<T>,
+    payload: T,
+    collectors: &mut [&mut dyn LevelCollector<T>]
+) -> Result<ChainResults<T>, EvaluationFailure> {
+    let mut evaluation = StepwiseEvaluation::new(root, payload);
+    let mut depth = 0;
+    while !evaluation.is_finished() {
+        evaluation.advance(|_| true, |level| {
+            for collector in collectors.iter_mut() {
+                collector.observe_level(depth, level);
+            }
+        })?;
+        depth += 1;
+    }
+    Ok(evaluation.into_results())
+}
+
+/// Check whether the node of the branch is followed by nodes of its own time layer.
+fn within_layer<T: Clone>(partial: &PartialState<T>) -> bool {
+    let node = partial.node.borrow();
+    node.layer().is_some() && node.followers().iter().any(|follower| follower.borrow().layer() == node.layer())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::branching_generators::{alternatives, sequence};
+    use crate::event_graph::{prune, BoxedOperation, EventDAG};
+    use crate::time_points::{TimeLayers, TimedState};
+    use super::*;
+
+    fn ops(operations: Vec<fn(i32) -> i32>) -> Vec<BoxedOperation<i32>> {
+        operations.into_iter().map(|operation| -> BoxedOperation<i32> { Box::new(operation) }).collect()
+    }
+
+    /// Three alternatives, the last one pruning, followed by two alternatives of which the first
+    /// ends the event chain.
+    fn graph() -> EventNode<i32> {
+        let root = EventDAG::new_node(Box::new(|x: i32| x));
+        let first = alternatives(vec![Rc::clone(&root)], ops(vec![|x| x + 1, |x| x + 2, |_| prune()])).unwrap();
+        let second = alternatives(first, ops(vec![|x| x * 10, |x| x * 100])).unwrap();
+        sequence(vec![Rc::clone(&second[1])], ops(vec![|x| x + 5])).unwrap();
+        root
+    }
+
+    #[test]
+    fn steps_advance_all_live_branches() {
+        let root = graph();
+        let mut evaluation = StepwiseEvaluation::new(&root, 0);
+        assert_eq!(1, evaluation.evaluate_step().unwrap().len());
+        let states: Vec<(usize, i32)> = evaluation.evaluate_step().unwrap().iter().map(|partial| (partial.chain, partial.state)).collect();
+        assert_eq!(vec![(0, 1), (2, 2)], states);
+        assert_eq!(vec![(1, 100), (3, 200)], evaluation.evaluate_step().unwrap().iter().map(|partial| (partial.chain, partial.state)).collect::<Vec<_>>());
+        assert_eq!(ChainResults::from([(0, 10), (2, 20)]), *evaluation.results());
+        assert!(evaluation.evaluate_step().unwrap().is_empty());
+        assert!(evaluation.is_finished());
+        assert_eq!(EventDAG::evaluate_chains_by_id(&root, 0), evaluation.into_results());
+    }
+
+    #[test]
+    fn levels_are_evaluated_breadth_first() {
+        let root = graph();
+        let mut values = LevelValues::new(|x: &i32| *x);
+        let results = try_evaluate_breadth_first(&root, 1, &mut [&mut values]).unwrap();
+        assert_eq!(EventDAG::evaluate_chains_by_id(&root, 1), results);
+        assert_eq!(vec![
+            BTreeMap::from([(0, 1)]),
+            BTreeMap::from([(0, 2), (2, 3)]),
+            BTreeMap::from([(0, 20), (1, 200), (2, 30), (3, 300)]),
+            BTreeMap::from([(1, 205), (3, 305)]),
+        ], values.values);
+    }
+
+    #[test]
+    fn frontier_is_controlled_between_steps() {
+        let mut evaluation = StepwiseEvaluation::new(&graph(), 0);
+        evaluation.evaluate_step().unwrap();
+        evaluation.evaluate_step().unwrap();
+        evaluation.frontier_mut().retain(|partial| partial.state > 1);
+        evaluation.frontier_mut()[0].state = 3;
+        let saved = evaluation.frontier().to_vec();
+        while !evaluation.is_finished() {
+            evaluation.evaluate_step().unwrap();
+        }
+        assert_eq!(ChainResults::from([(2, 30), (3, 305)]), *evaluation.results());
+
+        let mut resumed = StepwiseEvaluation::from_frontier(saved);
+        resumed.evaluate_step().unwrap();
+        resumed.evaluate_step().unwrap();
+        assert_eq!(ChainResults::from([(2, 30), (3, 305)]), resumed.into_results());
+    }
+
+    #[test]
+    fn failures_leave_the_frontier() {
+        let root = graph();
+        root.borrow().followers()[1].borrow_mut().set_operation(Box::new(|_| panic!("failed")));
+        let mut evaluation = StepwiseEvaluation::new(&root, 0);
+        evaluation.evaluate_step().unwrap();
+        let failure = evaluation.evaluate_step().err();
+        assert_eq!(Some(EvaluationFailure { chain: 2, operation: 1, label: None, error: "failed".to_string() }), failure);
+        assert_eq!(1, evaluation.frontier().len());
+    }
+
+    #[test]
+    fn layers_are_evaluated_in_steps() {
+        let add = |amount: i32| -> BoxedOperation<TimedState<i32>> {
+            Box::new(move |timed: TimedState<i32>| TimedState { state: timed.state + amount, ..timed })
+        };
+        let root = EventDAG::new_node(Box::new(|timed: TimedState<i32>| timed));
+        let mut layers = TimeLayers::new(&root);
+        layers.add_layer(2030, |nodes| alternatives(nodes, [add(1), add(2)])).unwrap();
+        layers.add_layer(2040, |nodes| sequence(nodes, [add(10), add(10), add(10)])).unwrap();
+        layers.add_layer(2050, |nodes| sequence(nodes, [add(100)])).unwrap();
+
+        let mut evaluation = StepwiseEvaluation::new(&root, TimedState { time: 2020, state: 0 });
+        evaluation.evaluate_layer().unwrap();
+        let states = |evaluation: &StepwiseEvaluation<TimedState<i32>>| -> Vec<TimedState<i32>> {
+            evaluation.frontier().iter().map(|partial| partial.state).collect()
+        };
+        assert_eq!(vec![TimedState { time: 2020, state: 0 }], states(&evaluation));
+        evaluation.evaluate_layer().unwrap();
+        assert_eq!(vec![TimedState { time: 2030, state: 1 }, TimedState { time: 2030, state: 2 }], states(&evaluation));
+        evaluation.evaluate_layer().unwrap();
+        assert_eq!(vec![TimedState { time: 2040, state: 31 }, TimedState { time: 2040, state: 32 }], states(&evaluation));
+        evaluation.evaluate_layer().unwrap();
+        assert!(evaluation.is_finished());
+        assert_eq!(ChainResults::from([(0, TimedState { time: 2050, state: 131 }), (1, TimedState { time: 2050, state: 132 })]), evaluation.into_results());
+    }
+}
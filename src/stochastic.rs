@@ -0,0 +1,76 @@
+use crate::event_graph::BoxedOperation;
+use crate::rng::Stochastic;
+
+/// Lift a deterministic operation into an operation on stochastic payloads, leaving the random
+/// number stream untouched.
+pub fn lift<T: Clone + 'static>(operation: BoxedOperation<T>) -> BoxedOperation<Stochastic<T>> {
+    Box::new(move |payload: Stochastic<T>| Stochastic { state: operation(payload.state), ..payload })
+}
+
+/// Create an operation applying the given operation with the given probability, drawing the
+/// occurrence from the random number stream of the payload.
+pub fn bernoulli<T: Clone + 'static>(probability: f64, operation: BoxedOperation<T>) -> BoxedOperation<Stochastic<T>> {
+    Box::new(move |mut payload: Stochastic<T>| {
+        if payload.rng.next_f64() < probability {
+            payload.state = operation(payload.state);
+        }
+        payload
+    })
+}
+
+/// Create an operation adding normally distributed noise with zero mean and the given standard
+/// deviation into a field of the state, accessed with the given getter and setter.
+pub fn normal_noise<T: Clone + 'static>(getter: fn(&T) -> f64, setter: fn(T, f64) -> T, std_dev: f64) -> BoxedOperation<Stochastic<T>> {
+    Box::new(move |mut payload: Stochastic<T>| {
+        let noise = payload.rng.next_normal() * std_dev;
+        let value = getter(&payload.state) + noise;
+        payload.state = setter(payload.state, value);
+        payload
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::rng::RngStream;
+    use super::*;
+
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    struct Stand {
+        age: i32,
+        volume: f64,
+    }
+
+    fn volume(stand: &Stand) -> f64 { stand.volume }
+    fn set_volume(stand: Stand, volume: f64) -> Stand { Stand { volume, ..stand } }
+
+    fn payload(seed: u64) -> Stochastic<Stand> {
+        Stochastic { rng: RngStream::new(seed), state: Stand { age: 10, volume: 100.0 } }
+    }
+
+    #[test]
+    fn deterministic_operations_are_lifted() {
+        let grow = lift(Box::new(|stand: Stand| Stand { age: stand.age + 5, ..stand }));
+        let result = grow(payload(1));
+        assert_eq!(15, result.state.age);
+        assert_eq!(RngStream::new(1), result.rng);
+    }
+
+    #[test]
+    fn occurrence_follows_probability() {
+        let storm = bernoulli(0.25, Box::new(|stand: Stand| Stand { volume: 0.0, ..stand }));
+        let occurrences = (0..10000).filter(|seed| storm(payload(*seed)).state.volume == 0.0).count();
+        assert!((2300..2700).contains(&occurrences));
+        assert_eq!(100.0, bernoulli(0.0, Box::new(|_: Stand| panic!("never occurs")))(payload(1)).state.volume);
+    }
+
+    #[test]
+    fn noise_is_added_to_field() {
+        let noise = normal_noise(volume, set_volume, 10.0);
+        let volumes: Vec<f64> = (0..10000).map(|seed| noise(payload(seed)).state.volume).collect();
+        let mean = volumes.iter().sum::<f64>() / volumes.len() as f64;
+        let std_dev = (volumes.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / volumes.len() as f64).sqrt();
+        assert!((mean - 100.0).abs() < 0.5);
+        assert!((std_dev - 10.0).abs() < 0.5);
+        assert_eq!(noise(payload(3)), noise(payload(3)));
+    }
+}
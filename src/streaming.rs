@@ -0,0 +1,163 @@
+use std::sync::mpsc::{Sender, SyncSender};
+use rayon::prelude::*;
+use rayon::ThreadPoolBuildError;
+use crate::batch_runner::{EntityFailure, Threads};
+use crate::event_graph::{EvaluationFailure, EventDAG, EventNode};
+
+/// Sending end of a channel of evaluation results, implemented for the senders of both
+/// unbounded and bounded std channels. A bounded channel holds evaluation back while the
+/// consumer lags behind.
+pub trait ResultSender<M> {
+    /// Send the message, returning false if the receiver has hung up.
+    fn send_result(&self, message: M) -> bool;
+}
+
+impl<M> ResultSender<M> for Sender<M> {
+    fn send_result(&self, message: M) -> bool {
+        self.send(message).is_ok()
+    }
+}
+
+impl<M> ResultSender<M> for SyncSender<M> {
+    fn send_result(&self, message: M) -> bool {
+        self.send(message).is_ok()
+    }
+}
+
+/// Result of an entity's event chain streamed from a batch evaluation.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StreamedResult<K, T> {
+    pub entity: K,
+    pub chain: usize,
+    pub result: T,
+}
+
+/// Evaluate the unique event chains of the graph one at a time, sending the result of each chain
+/// along with its chain id as soon as the chain completes, so that a consumer thread can process
+/// the results during evaluation. Pruned chains send nothing. Evaluation stops at a failing
+/// operation, the results of the earlier chains having been sent, and when the receiver hangs
+/// up. Returns the number of results sent.
+pub fn stream_chains<T, S>(root: &EventNode<T>, payload: T, sender: &S) -> Result<usize, EvaluationFailure>
+where T: Clone, S: ResultSender<(usize, T)> + ?Sized {
+    let chains = EventDAG::node_chains(root);
+    let mut sent = 0;
+    for chain in 0..chains.len() {
+        let evaluated = EventDAG::try_evaluate_indexed(&chains[chain..=chain], |_| payload.clone())
+            .map_err(|failure| EvaluationFailure { chain, ..failure })?;
+        for (_, result) in evaluated {
+            if !sender.send_result((chain, result)) {
+                return Ok(sent);
+            }
+            sent += 1;
+        }
+    }
+    Ok(sent)
+}
+
+/// Evaluate the event graph for each of the given independent entities as stream_chains,
+/// sending each chain result with its entity. A failing entity does not abort the batch, the
+/// failures being returned in the order the entities were given. Results of the chains of a
+/// failing entity completed before the failure have been sent.
+pub fn stream_batch<K, T, I, S>(root: &EventNode<T>, entities: I, sender: &S) -> Vec<EntityFailure<K>>
+where K: Clone, T: Clone, I: IntoIterator<Item = (K, T)>, S: ResultSender<StreamedResult<K, T>> + ?Sized {
+    let mut failures = Vec::new();
+    for (entity, state) in entities {
+        if let Err(failure) = stream_entity(root, &entity, state, sender) {
+            failures.push(EntityFailure { entity, failure });
+        }
+    }
+    failures
+}
+
+/// Evaluate an event graph for each of the given independent entities in parallel as
+/// stream_batch, each worker sending the results of its entities as their chains complete. As
+/// event graphs are not shareable across threads, each worker builds its own graph with the given
+/// factory. Evaluates in the given thread pool or in a pool of the given number of threads.
+pub fn stream_batch_parallel<'p, K, T, F, S>(graph_factory: F, entities: Vec<(K, T)>, threads: impl Into<Threads<'p>>, sender: &S) -> Result<Vec<EntityFailure<K>>, ThreadPoolBuildError>
+where K: Clone + Send, T: Clone + Send, F: Fn() -> EventNode<T> + Sync, S: ResultSender<StreamedResult<K, T>> + Sync + ?Sized {
+    let failures = threads.into().install(|| {
+        entities.into_par_iter()
+            .map_init(&graph_factory, |root, (entity, state)| {
+                stream_entity(root, &entity, state, sender).err().map(|failure| EntityFailure { entity, failure })
+            })
+            .collect::<Vec<_>>()
+    })?;
+    Ok(failures.into_iter().flatten().collect())
+}
+
+fn stream_entity<K, T, S>(root: &EventNode<T>, entity: &K, state: T, sender: &S) -> Result<usize, EvaluationFailure>
+where K: Clone, T: Clone, S: ResultSender<StreamedResult<K, T>> + ?Sized {
+    let entity_sender = EntitySender { entity, sender };
+    stream_chains(root, state, &entity_sender)
+}
+
+/// Sender attaching the entity to the chain results of the entity.
+struct EntitySender<'a, K, S: ?Sized> {
+    entity: &'a K,
+    sender: &'a S,
+}
+
+impl<K: Clone, T, S> ResultSender<(usize, T)> for EntitySender<'_, K, S>
+where S: ResultSender<StreamedResult<K, T>> + ?Sized {
+    fn send_result(&self, (chain, result): (usize, T)) -> bool {
+        self.sender.send_result(StreamedResult { entity: self.entity.clone(), chain, result })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::mpsc::{channel, sync_channel};
+    use std::thread;
+    use crate::branching_generators::alternatives;
+    use crate::event_graph::{prune, BoxedOperation};
+    use super::*;
+
+    fn graph() -> EventNode<i32> {
+        let root = EventDAG::new_node(Box::new(|x: i32| x));
+        let operations: Vec<BoxedOperation<i32>> = vec![
+            Box::new(|x| x + 1),
+            Box::new(|x| if x > 0 { prune() } else { x }),
+            Box::new(|x| if x < 0 { panic!("negative") } else { x * 2 }),
+        ];
+        alternatives(vec![root.clone()], operations).unwrap();
+        root
+    }
+
+    #[test]
+    fn chain_results_are_received_during_evaluation() {
+        let (sender, receiver) = sync_channel(1);
+        let consumer = thread::spawn(move || receiver.iter().collect::<Vec<(usize, i32)>>());
+        assert_eq!(Ok(2), stream_chains(&graph(), 5, &sender));
+        drop(sender);
+        assert_eq!(vec![(0, 6), (2, 10)], consumer.join().unwrap());
+
+        let (sender, receiver) = channel();
+        drop(receiver);
+        assert_eq!(Ok(0), stream_chains(&graph(), 5, &sender));
+    }
+
+    #[test]
+    fn batches_stream_results_and_collect_failures() {
+        let (sender, receiver) = channel();
+        let failures = stream_batch(&graph(), [("a", 1), ("b", -1)], &sender);
+        assert_eq!(vec!["b"], failures.iter().map(|failure| failure.entity).collect::<Vec<_>>());
+        assert_eq!((2, 1), (failures[0].failure.chain, failures[0].failure.operation));
+        drop(sender);
+        let received: Vec<StreamedResult<&str, i32>> = receiver.iter().collect();
+        assert_eq!(StreamedResult { entity: "a", chain: 2, result: 2 }, received[1]);
+        assert_eq!(vec![("a", 0), ("a", 2), ("b", 0), ("b", 1)], received.iter().map(|streamed| (streamed.entity, streamed.chain)).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn parallel_batches_stream_from_workers() {
+        let (sender, receiver) = channel();
+        let entities: Vec<(i32, i32)> = (0..20).map(|entity| (entity, entity + 1)).collect();
+        let failures = stream_batch_parallel(graph, entities, 4, &sender).unwrap();
+        assert!(failures.is_empty());
+        drop(sender);
+        let mut received: Vec<(i32, usize, i32)> = receiver.iter().map(|streamed| (streamed.entity, streamed.chain, streamed.result)).collect();
+        received.sort();
+        assert_eq!(40, received.len());
+        assert_eq!(vec![(0, 0, 2), (0, 2, 2)], received[..2].to_vec());
+    }
+}
@@ -0,0 +1,319 @@
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt;
+use rayon::prelude::*;
+use rayon::ThreadPoolBuildError;
+use crate::batch_runner::Threads;
+use crate::event_graph::EventDAG;
+use crate::rng::RngStream;
+use crate::simulation_runner::{owned_parameters, OperationParameters, OwnedParameters, RunResult, RunnerError, SimulationRunner};
+
+/// Values of a single operation parameter to sweep over.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SweepAxis {
+    pub operation: &'static str,
+    pub parameter: &'static str,
+    pub values: Vec<&'static str>,
+}
+
+/// Numeric range of an operation parameter for sampled designs.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ParameterRange {
+    pub operation: &'static str,
+    pub parameter: &'static str,
+    pub min: f64,
+    pub max: f64,
+}
+
+impl ParameterRange {
+    /// Value at the given fraction of the range.
+    fn at(&self, fraction: f64) -> f64 {
+        self.min + fraction * (self.max - self.min)
+    }
+}
+
+/// Parameter values of a single combination, in the order of the axes.
+pub type ParameterTuple = Vec<&'static str>;
+
+/// Run results of the initial states by parameter combination.
+pub type SweepResults<T> = BTreeMap<ParameterTuple, Vec<RunResult<T>>>;
+
+/// Sampled parameter values of each combination, in the order of the ranges.
+pub type SampledDesign = Vec<Vec<f64>>;
+
+/// Run results of the initial states for each combination of a sampled design, in design order.
+pub type SampledResults<T> = Vec<Vec<RunResult<T>>>;
+
+/// Reasons for a parameter sweep to fail.
+#[derive(Debug)]
+pub enum SweepError {
+    /// An axis sweeps a parameter of an operation missing from the registry.
+    Runner(RunnerError),
+    /// A combination has another number of values than there are axes.
+    Combination { index: usize, expected: usize, found: usize },
+    ThreadPool(ThreadPoolBuildError),
+}
+
+impl fmt::Display for SweepError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SweepError::Runner(err) => write!(f, "{err}"),
+            SweepError::Combination { index, expected, found } =>
+                write!(f, "combination {index} has {found} values for {expected} axes"),
+            SweepError::ThreadPool(err) => write!(f, "{err}"),
+        }
+    }
+}
+
+impl Error for SweepError {}
+
+impl From<RunnerError> for SweepError {
+    fn from(err: RunnerError) -> Self {
+        SweepError::Runner(err)
+    }
+}
+
+impl From<ThreadPoolBuildError> for SweepError {
+    fn from(err: ThreadPoolBuildError) -> Self {
+        SweepError::ThreadPool(err)
+    }
+}
+
+/// Full grid of the combinations of the values of the axes, varying the last axis fastest.
+pub fn grid(axes: &[SweepAxis]) -> Vec<ParameterTuple> {
+    axes.iter().fold(vec![ParameterTuple::new()], |combinations, axis| {
+        combinations.iter()
+            .flat_map(|combination| axis.values.iter().map(move |value| {
+                let mut extended = combination.clone();
+                extended.push(*value);
+                extended
+            }))
+            .collect()
+    })
+}
+
+/// Design of the given number of combinations drawn independently and uniformly from the ranges.
+/// Combinations are drawn from the given stream, the same stream yielding the same design.
+pub fn uniform_design(ranges: &[ParameterRange], samples: usize, mut stream: RngStream) -> SampledDesign {
+    (0..samples)
+        .map(|_| ranges.iter().map(|range| range.at(stream.next_f64())).collect())
+        .collect()
+}
+
+/// Latin hypercube design of the given number of combinations over the ranges. Each range is
+/// divided into as many equal strata as there are combinations and each stratum is sampled by
+/// exactly one combination, covering large parameter spaces evenly with few combinations.
+/// Combinations are drawn from streams derived from the given stream per range.
+pub fn latin_hypercube(ranges: &[ParameterRange], samples: usize, stream: RngStream) -> SampledDesign {
+    let columns: Vec<Vec<f64>> = ranges.iter().enumerate().map(|(index, range)| {
+        let mut stream = stream.derive(index as u64);
+        let mut strata: Vec<usize> = (0..samples).collect();
+        // Fisher-Yates shuffle for assigning the strata into the combinations.
+        for last in (1..samples).rev() {
+            strata.swap(last, (stream.next_u64() % (last as u64 + 1)) as usize);
+        }
+        strata.into_iter()
+            .map(|stratum| range.at((stratum as f64 + stream.next_f64()) / samples as f64))
+            .collect()
+    }).collect();
+    (0..samples).map(|sample| columns.iter().map(|column| column[sample]).collect()).collect()
+}
+
+/// Parameters with the values of the combination set into the parameters of the axes.
+pub fn combined(parameters: &OperationParameters, axes: &[SweepAxis], combination: &[&'static str]) -> OperationParameters {
+    let mut combined = parameters.clone();
+    for (axis, value) in axes.iter().zip(combination) {
+        combined.entry(axis.operation).or_default().insert(axis.parameter, value);
+    }
+    combined
+}
+
+/// Parameters with the sampled values of the combination set into the parameters of the ranges.
+/// The values are formatted into parameters owned by the combination, so they are freed with the
+/// graph compiled for it.
+pub fn sampled(parameters: &OperationParameters, ranges: &[ParameterRange], combination: &[f64]) -> OwnedParameters {
+    let mut sampled = owned_parameters(parameters);
+    for (range, value) in ranges.iter().zip(combination) {
+        sampled.entry(range.operation.to_string()).or_default().insert(range.parameter.to_string(), value.to_string());
+    }
+    sampled
+}
+
+/// Check that the operations are registered and the combinations have a value per operation.
+fn validate<T: Clone + 'static, V>(runner: &SimulationRunner<T>, operations: &[&'static str], design: &[Vec<V>]) -> Result<(), SweepError> {
+    if let Some(operation) = operations.iter().find(|operation| !runner.operations().contains_key(*operation)) {
+        return Err(RunnerError::UnknownOperation(operation).into());
+    }
+    match design.iter().position(|combination| combination.len() != operations.len()) {
+        Some(index) => Err(SweepError::Combination { index, expected: operations.len(), found: design[index].len() }),
+        None => Ok(()),
+    }
+}
+
+/// Operations of the axes, in axis order.
+fn axis_operations(axes: &[SweepAxis]) -> Vec<&'static str> {
+    axes.iter().map(|axis| axis.operation).collect()
+}
+
+/// Run the simulation of the runner for each of the initial states with each of the parameter
+/// combinations of the design, such as a grid, compiling a graph variant per combination.
+pub fn sweep<T: Clone + 'static>(runner: &SimulationRunner<T>, axes: &[SweepAxis], design: &[ParameterTuple], initial_states: &[T]) -> Result<SweepResults<T>, SweepError> {
+    validate(runner, &axis_operations(axes), design)?;
+    Ok(design.iter()
+        .map(|combination| {
+            let variant = runner.with_parameters(combined(runner.parameters(), axes, combination))?;
+            Ok((combination.clone(), variant.run(initial_states.iter().cloned())))
+        })
+        .collect::<Result<_, RunnerError>>()?)
+}
+
+/// Run the sweep as sweep, the combinations in parallel in the given thread pool or in a pool of
+/// the given number of worker threads.
+pub fn sweep_parallel<'p, T>(runner: &SimulationRunner<T>, axes: &[SweepAxis], design: &[ParameterTuple], initial_states: &[T], threads: impl Into<Threads<'p>>) -> Result<SweepResults<T>, SweepError>
+where T: Clone + Send + Sync + 'static {
+    validate(runner, &axis_operations(axes), design)?;
+    let (factory, parameters) = (runner.graph_factory(), runner.parameters());
+    Ok(threads.into().install(|| {
+        design.par_iter()
+            .map(|combination| {
+                let root = factory(&combined(parameters, axes, combination));
+                let results = initial_states.iter()
+                    .map(|state| RunResult { initial_state: state.clone(), results: EventDAG::evaluate_chains(&root, state.clone()) })
+                    .collect();
+                (combination.clone(), results)
+            })
+            .collect()
+    })?)
+}
+
+/// Run the simulation of the runner for each of the initial states with each combination of the
+/// sampled design, such as a latin hypercube over the ranges, compiling a graph per combination.
+pub fn sweep_sampled<T: Clone + 'static>(runner: &SimulationRunner<T>, ranges: &[ParameterRange], design: &[Vec<f64>], initial_states: &[T]) -> Result<SampledResults<T>, SweepError> {
+    let operations: Vec<&'static str> = ranges.iter().map(|range| range.operation).collect();
+    validate(runner, &operations, design)?;
+    let factory = runner.graph_factory();
+    Ok(design.iter()
+        .map(|combination| {
+            let root = factory(&sampled(runner.parameters(), ranges, combination));
+            initial_states.iter()
+                .map(|state| RunResult { initial_state: state.clone(), results: EventDAG::evaluate_chains(&root, state.clone()) })
+                .collect()
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use crate::configuration_utils::{ParameterMap, ParameteredOperation};
+    use super::*;
+
+    fn increment(value: i32, params: ParameterMap) -> i32 {
+        value + params.get("increase").map_or(1, |increase| increase.parse::<i32>().unwrap())
+    }
+
+    fn multiply(value: i32, params: ParameterMap) -> i32 {
+        value * params.get("factor").map_or(1, |factor| factor.parse::<i32>().unwrap())
+    }
+
+    fn runner() -> SimulationRunner<i32> {
+        let operations = HashMap::from([
+            ("increment", increment as ParameteredOperation<i32>),
+            ("multiply", multiply as ParameteredOperation<i32>),
+        ]);
+        let parameters = HashMap::from([("multiply", ParameterMap::from([("factor", "2")]))]);
+        SimulationRunner::new(operations, parameters, vec![("sequence", vec!["increment", "multiply"])]).unwrap()
+    }
+
+    fn axes() -> Vec<SweepAxis> {
+        vec![
+            SweepAxis { operation: "increment", parameter: "increase", values: vec!["1", "2"] },
+            SweepAxis { operation: "multiply", parameter: "factor", values: vec!["3", "10"] },
+        ]
+    }
+
+    #[test]
+    fn grids_are_full() {
+        assert_eq!(vec![vec!["1", "3"], vec!["1", "10"], vec!["2", "3"], vec!["2", "10"]], grid(&axes()));
+        assert_eq!(vec![ParameterTuple::new()], grid(&[]));
+    }
+
+    #[test]
+    fn combinations_are_run() {
+        let runner = runner();
+        let results = sweep(&runner, &axes(), &grid(&axes()), &[0, 1]).unwrap();
+        let finals: Vec<(ParameterTuple, Vec<i32>)> = results.iter()
+            .map(|(combination, runs)| (combination.clone(), runs.iter().flat_map(|run| run.results.clone()).collect()))
+            .collect();
+        assert_eq!(vec![
+            (vec!["1", "10"], vec![10, 20]),
+            (vec!["1", "3"], vec![3, 6]),
+            (vec!["2", "10"], vec![20, 30]),
+            (vec!["2", "3"], vec![6, 9]),
+        ], finals);
+        assert_eq!(results, sweep_parallel(&runner, &axes(), &grid(&axes()), &[0, 1], 2).unwrap());
+        assert_eq!(vec![2], runner.run([0])[0].results);
+    }
+
+    fn ranges() -> Vec<ParameterRange> {
+        vec![
+            ParameterRange { operation: "increment", parameter: "increase", min: 0.0, max: 10.0 },
+            ParameterRange { operation: "multiply", parameter: "factor", min: 1.0, max: 3.0 },
+        ]
+    }
+
+    fn values(design: &SampledDesign, axis: usize) -> Vec<f64> {
+        design.iter().map(|combination| combination[axis]).collect()
+    }
+
+    #[test]
+    fn uniform_designs_are_seeded() {
+        let design = uniform_design(&ranges(), 20, RngStream::new(1));
+        assert_eq!(20, design.len());
+        assert!(values(&design, 0).iter().all(|value| (0.0..10.0).contains(value)));
+        assert!(values(&design, 1).iter().all(|value| (1.0..3.0).contains(value)));
+        assert_eq!(design, uniform_design(&ranges(), 20, RngStream::new(1)));
+        assert_ne!(design, uniform_design(&ranges(), 20, RngStream::new(2)));
+    }
+
+    #[test]
+    fn latin_hypercubes_cover_all_strata() {
+        let design = latin_hypercube(&ranges(), 10, RngStream::new(1));
+        for (axis, range) in ranges().iter().enumerate() {
+            let mut strata: Vec<usize> = values(&design, axis).iter()
+                .map(|value| ((value - range.min) / (range.max - range.min) * 10.0) as usize)
+                .collect();
+            strata.sort_unstable();
+            assert_eq!((0..10).collect::<Vec<_>>(), strata);
+        }
+        assert_eq!(design, latin_hypercube(&ranges(), 10, RngStream::new(1)));
+    }
+
+    fn truncating(value: i32, params: ParameterMap) -> i32 {
+        value + params["increase"].parse::<f64>().unwrap() as i32
+    }
+
+    #[test]
+    fn sampled_designs_are_swept() {
+        let operations = HashMap::from([("increment", truncating as ParameteredOperation<i32>)]);
+        let runner = SimulationRunner::new(operations, HashMap::new(), vec![("sequence", vec!["increment"])]).unwrap();
+        let ranges = &ranges()[..1];
+        let design = latin_hypercube(ranges, 5, RngStream::new(3));
+        let results = sweep_sampled(&runner, ranges, &design, &[0]).unwrap();
+        assert_eq!(5, results.len());
+        let mut strata: Vec<i32> = results.iter().map(|runs| runs[0].results[0] / 2).collect();
+        strata.sort_unstable();
+        assert_eq!(vec![0, 1, 2, 3, 4], strata);
+    }
+
+    #[test]
+    fn invalid_sweeps_are_refused() {
+        let runner = runner();
+        let mut unknown = axes();
+        unknown[0].operation = "thin";
+        let err = sweep(&runner, &unknown, &grid(&unknown), &[0]).unwrap_err();
+        assert_eq!("unknown operation 'thin'", err.to_string());
+        let err = sweep_parallel(&runner, &axes(), &[vec!["1"]], &[0], 1).unwrap_err();
+        assert_eq!("combination 0 has 1 values for 2 axes", err.to_string());
+    }
+}
@@ -0,0 +1,717 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::panic::{catch_unwind, resume_unwind, AssertUnwindSafe};
+use std::rc::Rc;
+use serde::{Deserialize, Serialize};
+use crate::branching_generators::{generator_map, sequence, GeneratorError, GeneratorResult};
+use crate::collectors::{evaluate_collecting, CollectionPoint, Collector};
+use crate::event_graph::{is_pruned, BoxedOperation, ChainResults, EventDAG, EventNode, EventNodes};
+pub use crate::event_graph::TimePoint;
+use crate::rng::Stochastic;
+use crate::simulation_runner::{
+    extend_graph, owned_parameters, unwrapped, GeneratorDeclaration, GeneratorRegistry, OperationParameters, OperationRegistry, OwnedParameters,
+    RunResult, RunnerError, SimulationDeclaration
+};
+
+/// Simulation payload carrying the current simulated time alongside the simulated state.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct TimedState<T> {
+    pub time: TimePoint,
+    pub state: T,
+}
+
+/// Simulation payload carrying its simulated time, so that operations, constraints and
+/// collectors reason about time uniformly whatever the payload.
+pub trait SimTime {
+    fn current_time(&self) -> TimePoint;
+
+    /// Payload moved to the given time point.
+    fn with_time(self, time_point: TimePoint) -> Self;
+
+    /// Payload moved forward by the given interval.
+    fn advance(self, interval: TimePoint) -> Self where Self: Sized {
+        let time_point = self.current_time() + interval;
+        self.with_time(time_point)
+    }
+}
+
+impl<T> SimTime for TimedState<T> {
+    fn current_time(&self) -> TimePoint {
+        self.time
+    }
+
+    fn with_time(self, time: TimePoint) -> TimedState<T> {
+        TimedState { time, ..self }
+    }
+}
+
+impl<T: SimTime> SimTime for Stochastic<T> {
+    fn current_time(&self) -> TimePoint {
+        self.state.current_time()
+    }
+
+    fn with_time(self, time_point: TimePoint) -> Stochastic<T> {
+        Stochastic { state: self.state.with_time(time_point), ..self }
+    }
+}
+
+/// Generator declarations to apply at each of the listed time points.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScheduledEvents {
+    pub time_points: Vec<TimePoint>,
+    pub declaration: SimulationDeclaration,
+}
+
+pub type EventSchedule = Vec<ScheduledEvents>;
+
+/// Minimum interval in time between the given operations within an event chain, such as at
+/// least 10 years between thinnings.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MinimumInterval {
+    pub operations: Vec<&'static str>,
+    pub interval: TimePoint,
+}
+
+/// Interpolation of a time-scoped parameter between its declared time points.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Interpolation {
+    /// The value of the latest declared time point.
+    #[default]
+    Step,
+    /// Linear interpolation between the surrounding declared time points. Values declared as
+    /// integers interpolate into integers rounded to the nearest, so float parameters should be
+    /// declared with a decimal point, and values not parsing as numbers are stepped instead.
+    Linear,
+}
+
+/// Parameter value declared at time points, such as a timber price path, taking values at the
+/// time points between them by interpolation. Before the first and after the last declared time
+/// point the value is that of the nearest one.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TimedParameter {
+    pub values: BTreeMap<TimePoint, &'static str>,
+    pub interpolation: Interpolation,
+}
+
+/// Time-scoped parameters of the operations, by operation and parameter name.
+pub type TimedParameters = HashMap<&'static str, HashMap<&'static str, TimedParameter>>;
+
+impl TimedParameter {
+    pub fn new(values: BTreeMap<TimePoint, &'static str>, interpolation: Interpolation) -> TimedParameter {
+        TimedParameter { values, interpolation }
+    }
+
+    /// Value of the parameter at the given time point, if any values are declared.
+    pub fn at(&self, time_point: TimePoint) -> Option<String> {
+        let before = self.values.range(..=time_point).next_back();
+        let after = self.values.range(time_point..).next();
+        match (before, after) {
+            (Some((start, low)), Some((end, high))) if self.interpolation == Interpolation::Linear && start != end => {
+                let fraction = (time_point - start) as f64 / (end - start) as f64;
+                match (low.parse::<i64>(), high.parse::<i64>(), low.parse::<f64>(), high.parse::<f64>()) {
+                    (Ok(low), Ok(high), _, _) => Some((low as f64 + (high - low) as f64 * fraction).round().to_string()),
+                    (_, _, Ok(low), Ok(high)) => Some((low + (high - low) * fraction).to_string()),
+                    _ => Some(low.to_string()),
+                }
+            }
+            (Some((_, value)), _) | (None, Some((_, value))) => Some(value.to_string()),
+            (None, None) => None,
+        }
+    }
+}
+
+/// Parameters of the operations at the given time point, the values of the time-scoped
+/// parameters overriding the given parameters. The parameters are owned, so the values computed
+/// for each time point are freed with the operations bound to them.
+pub fn parameters_at(parameters: &OperationParameters, timed: &TimedParameters, time_point: TimePoint) -> OwnedParameters {
+    let mut scoped = owned_parameters(parameters);
+    for (operation, timed_parameters) in timed.iter() {
+        let map = scoped.entry(operation.to_string()).or_default();
+        for (name, parameter) in timed_parameters.iter() {
+            if let Some(value) = parameter.at(time_point) {
+                map.insert(name.to_string(), value);
+            }
+        }
+    }
+    scoped
+}
+
+/// Time points of the latest constrained operations along a branch, by constraint.
+type ConstraintHistory = Vec<Option<TimePoint>>;
+/// Frontier EventNodes of branches sharing a ConstraintHistory.
+type ConstrainedBranch<T> = (ConstraintHistory, EventNodes<TimedState<T>>);
+
+/// TimePointSimulation compiles an EventSchedule into an event graph where the events of each
+/// time point are preceded by a time-advance node setting the simulated time of the payload.
+pub struct TimePointSimulation<T> {
+    schedule: EventSchedule,
+    constraints: Vec<MinimumInterval>,
+    time_points: Vec<TimePoint>,
+    time_advances: EventNodes<TimedState<T>>,
+    root: EventNode<TimedState<T>>,
+}
+
+/// Create an operation moving the simulated time of the payload to the given time point.
+pub fn time_advance<T: Clone + 'static>(time_point: TimePoint) -> BoxedOperation<TimedState<T>> {
+    advance_to(time_point)
+}
+
+/// Create an operation moving the simulated time of any SimTime payload to the given time point.
+pub fn advance_to<S: SimTime + 'static>(time_point: TimePoint) -> BoxedOperation<S> {
+    Box::new(move |payload: S| payload.with_time(time_point))
+}
+
+/// Create an operation moving the simulated time of any SimTime payload forward by the given
+/// interval, such as a growth period.
+pub fn advance_by<S: SimTime + 'static>(interval: TimePoint) -> BoxedOperation<S> {
+    Box::new(move |payload: S| payload.advance(interval))
+}
+
+/// Mark the EventNodes reachable from the given EventNodes, and not yet in a layer, as the
+/// layer of the given time point.
+pub fn mark_layer<T: Clone>(from: &[EventNode<T>], time_point: TimePoint) {
+    for node in EventDAG::reachable_nodes(from) {
+        let mut node = node.borrow_mut();
+        if node.layer().is_none() {
+            node.set_layer(time_point);
+        }
+    }
+}
+
+/// EventNodes of the layer of the given time point reachable from the root.
+pub fn layer_nodes<T: Clone>(root: &EventNode<T>, time_point: TimePoint) -> EventNodes<T> {
+    EventDAG::reachable_nodes(std::slice::from_ref(root)).into_iter()
+        .filter(|node| node.borrow().layer() == Some(time_point))
+        .collect()
+}
+
+/// EventNodes of the layer of the given time point not followed by other EventNodes of the same
+/// layer, that is the frontier of the event graph at the end of the time point.
+pub fn layer_frontier<T: Clone>(root: &EventNode<T>, time_point: TimePoint) -> EventNodes<T> {
+    layer_nodes(root, time_point).into_iter()
+        .filter(|node| node.borrow().followers().iter().all(|follower| follower.borrow().layer() != Some(time_point)))
+        .collect()
+}
+
+/// Event graph constructed in explicit layers per time point. Each layer begins with a time
+/// advance node following the frontier of the previous layer, and its EventNodes are marked with
+/// the time point of the layer.
+pub struct TimeLayers<S> {
+    root: EventNode<S>,
+    frontier: EventNodes<S>,
+    time_points: Vec<TimePoint>,
+}
+
+impl<S: SimTime + Clone + 'static> TimeLayers<S> {
+    pub fn new(root: &EventNode<S>) -> TimeLayers<S> {
+        TimeLayers { root: Rc::clone(root), frontier: vec![Rc::clone(root)], time_points: Vec::new() }
+    }
+
+    /// Append the layer of the given time point, extending the graph from its time advance node
+    /// with the given function, such as a generator. Returns the frontier of the layer.
+    pub fn add_layer<F>(&mut self, time_point: TimePoint, extend: F) -> Result<&[EventNode<S>], GeneratorError>
+    where F: FnOnce(EventNodes<S>) -> GeneratorResult<S> {
+        let advance = sequence(self.frontier.clone(), [advance_to(time_point)])?;
+        advance[0].borrow_mut().set_label("time_advance");
+        let frontier = extend(advance.clone())?;
+        mark_layer(&advance, time_point);
+        self.frontier = frontier;
+        self.time_points.push(time_point);
+        Ok(&self.frontier)
+    }
+
+    pub fn root(&self) -> &EventNode<S> {
+        &self.root
+    }
+
+    /// Time points of the layers in the order they were added.
+    pub fn time_points(&self) -> &[TimePoint] {
+        &self.time_points
+    }
+
+    /// Frontier of the latest layer.
+    pub fn frontier(&self) -> &[EventNode<S>] {
+        &self.frontier
+    }
+
+    pub fn layer_nodes(&self, time_point: TimePoint) -> EventNodes<S> {
+        layer_nodes(&self.root, time_point)
+    }
+
+    pub fn layer_frontier(&self, time_point: TimePoint) -> EventNodes<S> {
+        layer_frontier(&self.root, time_point)
+    }
+}
+
+/// Evaluate unique event chains starting from the given EventNode, reporting the state after each
+/// operation of a layer with the chain index, the time point of the layer and whether the
+/// operation ends the layer within the chain.
+fn evaluate_layers<T: Clone, F: FnMut(usize, TimePoint, bool, &T)>(root: &EventNode<T>, payload: T, mut observe: F) -> Vec<T> {
+    let mut results = Vec::new();
+    'chains: for (chain_index, chain) in EventDAG::node_chains(root).iter().enumerate() {
+        let mut current = payload.clone();
+        for (position, node) in chain.iter().enumerate() {
+            let layer = node.borrow().layer();
+            current = match catch_unwind(AssertUnwindSafe(|| node.borrow().apply(current))) {
+                Ok(current) => current,
+                Err(payload) if is_pruned(&*payload) => continue 'chains,
+                Err(payload) => resume_unwind(payload),
+            };
+            if let Some(time_point) = layer {
+                let ends = chain.get(position + 1).is_none_or(|next| next.borrow().layer() != layer);
+                observe(chain_index, time_point, ends, &current);
+            }
+        }
+        results.push(current);
+    }
+    results
+}
+
+/// Evaluate unique event chains starting from the given EventNode, each collector observing the
+/// layer of its time point: after each operation of the layer at CollectionPoint::Operation,
+/// otherwise at the end of the layer. Pruned chains produce no results as in evaluate_collecting.
+pub fn evaluate_collecting_layers<T: Clone>(root: &EventNode<T>, payload: T, collectors: &mut [(TimePoint, &mut dyn Collector<T>)]) -> Vec<T> {
+    evaluate_layers(root, payload, |chain, time_point, ends, state| {
+        for (_, collector) in collectors.iter_mut().filter(|(layer, _)| *layer == time_point) {
+            if ends || collector.point() == CollectionPoint::Operation {
+                collector.observe(chain, state);
+            }
+        }
+    })
+}
+
+/// States of the event chains at the end of each layer, by the time point of the layer and chain
+/// id, for exporting results aligned by time.
+pub fn layer_states<T: Clone>(root: &EventNode<T>, payload: T) -> BTreeMap<TimePoint, ChainResults<T>> {
+    let mut states: BTreeMap<TimePoint, ChainResults<T>> = BTreeMap::new();
+    evaluate_layers(root, payload, |chain, time_point, ends, state| {
+        if ends {
+            states.entry(time_point).or_default().insert(chain, state.clone());
+        }
+    });
+    states
+}
+
+/// Check whether the operation may occur at the time point after the history of the branch.
+fn allowed(constraints: &[MinimumInterval], history: &ConstraintHistory, operation: &str, time_point: TimePoint) -> bool {
+    constraints.iter().zip(history).all(|(constraint, latest)| {
+        !constraint.operations.contains(&operation) || latest.is_none_or(|latest| time_point - latest >= constraint.interval)
+    })
+}
+
+/// Record the operation occurring at the time point into the history of the branch.
+fn record(constraints: &[MinimumInterval], history: &mut ConstraintHistory, operation: &str, time_point: TimePoint) {
+    for (constraint, latest) in constraints.iter().zip(history.iter_mut()) {
+        if constraint.operations.contains(&operation) {
+            *latest = Some(time_point);
+        }
+    }
+}
+
+/// Extend a branch by a generator declaration, leaving out operations violating the constraints
+/// and skipping the generator if none of its operations remain. Constrained alternatives are
+/// extended as branches of their own for tracking their histories, other generators are
+/// considered to apply all of their operations.
+fn extend_constrained<T: Clone + 'static>(
+    generators: &GeneratorRegistry<TimedState<T>>,
+    operations: &OperationRegistry<TimedState<T>>,
+    parameters: &OwnedParameters,
+    constraints: &[MinimumInterval],
+    (generator, names): &GeneratorDeclaration,
+    time_point: TimePoint,
+    (history, nodes): ConstrainedBranch<T>
+) -> Result<Vec<ConstrainedBranch<T>>, RunnerError> {
+    let constrained = |name: &&'static str| constraints.iter().any(|constraint| constraint.operations.contains(name));
+    let extend = |names: Vec<&'static str>, nodes: EventNodes<TimedState<T>>| {
+        extend_graph(generators, operations, parameters, &vec![(*generator, names)], nodes, &unwrapped)
+    };
+    if *generator != "alternatives" || !names.iter().any(constrained) {
+        let mut history = history;
+        let kept: Vec<&'static str> = names.iter().copied()
+            .filter(|name| {
+                let allowed = allowed(constraints, &history, name, time_point);
+                if allowed {
+                    record(constraints, &mut history, name, time_point);
+                }
+                allowed
+            })
+            .collect();
+        return match kept.is_empty() && !names.is_empty() {
+            true => Ok(vec![(history, nodes)]),
+            false => Ok(vec![(history, extend(kept, nodes)?)]),
+        };
+    }
+    // Consecutive unconstrained alternatives share a branch, keeping the declared order.
+    let mut branches = Vec::new();
+    let mut unconstrained = Vec::new();
+    for name in names.iter() {
+        if !constrained(name) {
+            unconstrained.push(*name);
+            continue;
+        }
+        if !unconstrained.is_empty() {
+            branches.push((history.clone(), extend(std::mem::take(&mut unconstrained), nodes.clone())?));
+        }
+        if allowed(constraints, &history, name, time_point) {
+            let mut history = history.clone();
+            record(constraints, &mut history, name, time_point);
+            branches.push((history, extend(vec![*name], nodes.clone())?));
+        }
+    }
+    if !unconstrained.is_empty() {
+        branches.push((history.clone(), extend(unconstrained, nodes.clone())?));
+    }
+    if branches.is_empty() {
+        branches.push((history, nodes));
+    }
+    Ok(branches)
+}
+
+/// Merge branches with equal histories into a single branch.
+fn merge_branches<T>(branches: Vec<ConstrainedBranch<T>>) -> Vec<ConstrainedBranch<T>> {
+    let mut merged: Vec<ConstrainedBranch<T>> = Vec::new();
+    for (history, nodes) in branches {
+        match merged.iter_mut().find(|(existing, _)| *existing == history) {
+            Some((_, existing)) => existing.extend(nodes),
+            None => merged.push((history, nodes)),
+        }
+    }
+    merged
+}
+
+impl<T: Clone + 'static> TimePointSimulation<T> {
+    /// Construct a TimePointSimulation using the built-in generators and compile its event graph.
+    pub fn new(
+        operations: &OperationRegistry<TimedState<T>>,
+        parameters: &OperationParameters,
+        schedule: EventSchedule
+    ) -> Result<TimePointSimulation<T>, RunnerError> {
+        TimePointSimulation::with_generators(&generator_map(), operations, parameters, schedule)
+    }
+
+    /// Construct a TimePointSimulation using the given generators and compile its event graph.
+    pub fn with_generators(
+        generators: &GeneratorRegistry<TimedState<T>>,
+        operations: &OperationRegistry<TimedState<T>>,
+        parameters: &OperationParameters,
+        schedule: EventSchedule
+    ) -> Result<TimePointSimulation<T>, RunnerError> {
+        TimePointSimulation::with_constraints(generators, operations, parameters, schedule, Vec::new())
+    }
+
+    /// Construct a TimePointSimulation using the given generators and compile its event graph,
+    /// leaving out branches of operations which would violate the minimum interval constraints.
+    pub fn with_constraints(
+        generators: &GeneratorRegistry<TimedState<T>>,
+        operations: &OperationRegistry<TimedState<T>>,
+        parameters: &OperationParameters,
+        schedule: EventSchedule,
+        constraints: Vec<MinimumInterval>
+    ) -> Result<TimePointSimulation<T>, RunnerError> {
+        TimePointSimulation::with_timed_parameters(generators, operations, parameters, &TimedParameters::new(), schedule, constraints)
+    }
+
+    /// Construct a TimePointSimulation as with_constraints, binding the operations of each time
+    /// point with the values of the time-scoped parameters at the time point.
+    pub fn with_timed_parameters(
+        generators: &GeneratorRegistry<TimedState<T>>,
+        operations: &OperationRegistry<TimedState<T>>,
+        parameters: &OperationParameters,
+        timed_parameters: &TimedParameters,
+        schedule: EventSchedule,
+        constraints: Vec<MinimumInterval>
+    ) -> Result<TimePointSimulation<T>, RunnerError> {
+        let constrained = constraints.iter().flat_map(|constraint| constraint.operations.iter());
+        if let Some(name) = constrained.chain(timed_parameters.keys()).find(|name| !operations.contains_key(*name)) {
+            return Err(RunnerError::UnknownOperation(name));
+        }
+        let time_points: Vec<TimePoint> = schedule.iter()
+            .flat_map(|events| events.time_points.iter().copied())
+            .collect::<BTreeSet<TimePoint>>()
+            .into_iter()
+            .collect();
+        let root: EventNode<TimedState<T>> = EventDAG::new_node(Box::new(|timed| timed));
+        let mut branches: Vec<ConstrainedBranch<T>> = vec![(vec![None; constraints.len()], vec![Rc::clone(&root)])];
+        let mut time_advances = EventNodes::new();
+        for time_point in time_points.iter() {
+            let mut extended = Vec::new();
+            let mut layer = EventNodes::new();
+            for (history, nodes) in branches {
+                let nodes = sequence(nodes, [time_advance(*time_point)])?;
+                nodes[0].borrow_mut().set_label("time_advance");
+                layer.extend(nodes.iter().cloned());
+                extended.push((history, nodes));
+            }
+            let parameters = parameters_at(parameters, timed_parameters, *time_point);
+            for events in schedule.iter().filter(|events| events.time_points.contains(time_point)) {
+                for declaration in events.declaration.iter() {
+                    let mut next = Vec::new();
+                    for branch in extended {
+                        next.extend(extend_constrained(generators, operations, &parameters, &constraints, declaration, *time_point, branch)?);
+                    }
+                    extended = next;
+                }
+            }
+            branches = merge_branches(extended);
+            mark_layer(&layer, *time_point);
+            time_advances.extend(layer);
+        }
+        Ok(TimePointSimulation { schedule, constraints, time_points, time_advances, root })
+    }
+
+    pub fn schedule(&self) -> &EventSchedule {
+        &self.schedule
+    }
+
+    pub fn constraints(&self) -> &[MinimumInterval] {
+        &self.constraints
+    }
+
+    /// Declared time points in simulation order.
+    pub fn time_points(&self) -> &[TimePoint] {
+        &self.time_points
+    }
+
+    /// Root EventNode of the compiled event graph.
+    pub fn root(&self) -> &EventNode<TimedState<T>> {
+        &self.root
+    }
+
+    /// EventNodes of the layer of the given time point, from its time advance nodes onwards.
+    pub fn layer_nodes(&self, time_point: TimePoint) -> EventNodes<TimedState<T>> {
+        layer_nodes(&self.root, time_point)
+    }
+
+    /// Last EventNodes of the layer of the given time point, such as for attaching further
+    /// events at the end of the time point.
+    pub fn frontier(&self, time_point: TimePoint) -> EventNodes<TimedState<T>> {
+        layer_frontier(&self.root, time_point)
+    }
+
+    /// Initial payload for the given state, with simulated time at the first declared time point.
+    fn initial_state(&self, state: T) -> TimedState<T> {
+        TimedState { time: self.time_points.first().copied().unwrap_or_default(), state }
+    }
+
+    /// Evaluate the compiled event graph for each of the given initial states, starting the
+    /// simulated time from the first declared time point.
+    pub fn run<I: IntoIterator<Item = T>>(&self, initial_states: I) -> Vec<RunResult<TimedState<T>>> {
+        initial_states.into_iter()
+            .map(|state| {
+                let initial_state = self.initial_state(state);
+                RunResult { initial_state: initial_state.clone(), results: EventDAG::evaluate_chains(&self.root, initial_state) }
+            })
+            .collect()
+    }
+
+    /// Evaluate the compiled event graph for the given initial state, invoking the collectors at
+    /// their collection points. Each declared time point is a collection point for time points.
+    pub fn run_collecting(&self, state: T, collectors: &mut [&mut dyn Collector<TimedState<T>>]) -> RunResult<TimedState<T>> {
+        let initial_state = self.initial_state(state);
+        RunResult { initial_state: initial_state.clone(), results: evaluate_collecting(&self.root, initial_state, &self.time_advances, collectors) }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{BTreeMap, HashMap};
+    use crate::branching_generators::alternatives;
+    use crate::collectors::{evaluate_collecting, CollectionPoint, TimeSeries, TimedValues};
+    use crate::configuration_utils::{ParameterMap, ParameteredOperation};
+    use super::*;
+
+    fn grow(timed: TimedState<i32>, _params: ParameterMap) -> TimedState<i32> {
+        TimedState { state: timed.state + 1, ..timed }
+    }
+
+    fn record_time(timed: TimedState<i32>, _params: ParameterMap) -> TimedState<i32> {
+        TimedState { state: timed.state * 10000 + timed.time, ..timed }
+    }
+
+    fn create_operations() -> OperationRegistry<TimedState<i32>> {
+        HashMap::from([
+            ("grow", grow as ParameteredOperation<TimedState<i32>>),
+            ("record_time", record_time as ParameteredOperation<TimedState<i32>>)
+        ])
+    }
+
+    /// Stand keeping its own calendar year.
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    struct Stand {
+        year: TimePoint,
+        volume: f64,
+    }
+
+    impl SimTime for Stand {
+        fn current_time(&self) -> TimePoint {
+            self.year
+        }
+
+        fn with_time(self, year: TimePoint) -> Stand {
+            Stand { year, ..self }
+        }
+    }
+
+    fn volume(stand: &Stand) -> f64 { stand.volume }
+
+    #[test]
+    fn payloads_advance_their_own_time() {
+        let root = EventDAG::new_node(advance_to(2025));
+        let grow: BoxedOperation<Stand> = Box::new(|stand| Stand { volume: stand.volume * 1.5, ..stand });
+        let leaves = sequence(vec![Rc::clone(&root)], [advance_by(5), grow, advance_by(5)]).unwrap();
+        sequence(leaves, [Box::new(|stand: Stand| Stand { volume: 0.0, ..stand }) as BoxedOperation<Stand>]).unwrap();
+        let mut volumes = TimedValues::new(CollectionPoint::Operation, volume);
+        let results = evaluate_collecting(&root, Stand { year: 0, volume: 100.0 }, &[], &mut [&mut volumes]);
+        assert_eq!(vec![Stand { year: 2035, volume: 0.0 }], results);
+        assert_eq!(BTreeMap::from([(2025, 100.0), (2030, 150.0), (2035, 0.0)]), volumes.values[&0]);
+        assert_eq!(TimedState { time: 7, state: 1 }, TimedState { time: 2, state: 1 }.advance(5));
+    }
+
+    #[test]
+    fn events_are_scheduled_by_time_point() {
+        let schedule = vec![
+            ScheduledEvents { time_points: vec![2030, 2020, 2025], declaration: vec![("sequence", vec!["grow"])] },
+            ScheduledEvents { time_points: vec![2025], declaration: vec![("alternatives", vec!["grow", "record_time"])] },
+        ];
+        let simulation = TimePointSimulation::new(&create_operations(), &OperationParameters::new(), schedule).unwrap();
+        assert_eq!(&[2020, 2025, 2030], simulation.time_points());
+
+        assert_eq!((2, 4, 2), (simulation.layer_nodes(2020).len(), simulation.layer_nodes(2025).len(), simulation.layer_nodes(2030).len()));
+        assert_eq!(2, simulation.frontier(2025).len());
+
+        let results = simulation.run([0]);
+        assert_eq!(TimedState { time: 2020, state: 0 }, results[0].initial_state);
+        assert_eq!(vec![
+            TimedState { time: 2030, state: 4 },
+            TimedState { time: 2030, state: 22_026 }
+        ], results[0].results);
+    }
+
+    fn add(amount: i32) -> BoxedOperation<TimedState<i32>> {
+        Box::new(move |timed: TimedState<i32>| TimedState { state: timed.state + amount, ..timed })
+    }
+
+    #[test]
+    fn graphs_are_constructed_in_time_layers() {
+        let root = EventDAG::new_node(Box::new(|timed: TimedState<i32>| timed));
+        let mut layers = TimeLayers::new(&root);
+        assert_eq!(2, layers.add_layer(2030, |nodes| alternatives(nodes, [add(1), add(2)])).unwrap().len());
+        layers.add_layer(2040, |nodes| sequence(nodes, [add(10), add(10)])).unwrap();
+        assert_eq!(&[2030, 2040], layers.time_points());
+        assert_eq!(None, root.borrow().layer());
+        assert_eq!((3, 3), (layers.layer_nodes(2030).len(), layers.layer_nodes(2040).len()));
+        assert_eq!(2, layers.layer_frontier(2030).len());
+        assert!(Rc::ptr_eq(&layers.frontier()[0], &layers.layer_frontier(2040)[0]));
+
+        let initial = TimedState { time: 2020, state: 0 };
+        assert_eq!(BTreeMap::from([
+            (2030, BTreeMap::from([(0, TimedState { time: 2030, state: 1 }), (1, TimedState { time: 2030, state: 2 })])),
+            (2040, BTreeMap::from([(0, TimedState { time: 2040, state: 21 }), (1, TimedState { time: 2040, state: 22 })])),
+        ]), layer_states(&root, initial));
+
+        let mut operations = TimeSeries::new(CollectionPoint::Operation, |timed: &TimedState<i32>| timed.state);
+        let mut ends = TimeSeries::new(CollectionPoint::ChainEnd, |timed: &TimedState<i32>| timed.state);
+        let results = evaluate_collecting_layers(&root, initial, &mut [(2040, &mut operations), (2030, &mut ends)]);
+        assert_eq!(vec![TimedState { time: 2040, state: 21 }, TimedState { time: 2040, state: 22 }], results);
+        assert_eq!(BTreeMap::from([(0, vec![1, 11, 21]), (1, vec![2, 12, 22])]), operations.series);
+        assert_eq!(BTreeMap::from([(0, vec![1]), (1, vec![2])]), ends.series);
+    }
+
+    #[test]
+    fn unknown_operations_are_rejected() {
+        let schedule = vec![ScheduledEvents { time_points: vec![2020], declaration: vec![("sequence", vec!["thin"])] }];
+        let simulation = TimePointSimulation::new(&create_operations(), &OperationParameters::new(), schedule);
+        assert_eq!(Some(RunnerError::UnknownOperation("thin")), simulation.err());
+    }
+
+    #[test]
+    fn time_points_are_collected() {
+        let schedule = vec![
+            ScheduledEvents { time_points: vec![2020, 2025, 2030], declaration: vec![("sequence", vec!["grow", "grow"])] },
+            ScheduledEvents { time_points: vec![2025], declaration: vec![("alternatives", vec!["grow", "record_time"])] },
+        ];
+        let simulation = TimePointSimulation::new(&create_operations(), &OperationParameters::new(), schedule).unwrap();
+        let mut series = TimeSeries::new(CollectionPoint::TimePoint, |timed: &TimedState<i32>| (timed.time, timed.state));
+        let result = simulation.run_collecting(0, &mut [&mut series]);
+        assert_eq!(vec![TimedState { time: 2030, state: 7 }, TimedState { time: 2030, state: 42027 }], result.results);
+        assert_eq!(vec![(2020, 2), (2025, 5), (2030, 7)], series.series[&0]);
+        assert_eq!(vec![(2020, 2), (2025, 42025), (2030, 42027)], series.series[&1]);
+    }
+
+    fn thin(timed: TimedState<i32>, _params: ParameterMap) -> TimedState<i32> {
+        TimedState { state: timed.state * 100 + timed.time % 100, ..timed }
+    }
+
+    #[test]
+    fn minimum_intervals_are_enforced() {
+        let mut operations = create_operations();
+        operations.insert("thin", thin as ParameteredOperation<TimedState<i32>>);
+        let schedule = vec![
+            ScheduledEvents { time_points: vec![2020, 2025, 2030], declaration: vec![("alternatives", vec!["grow", "thin"])] },
+        ];
+        let unconstrained = TimePointSimulation::new(&operations, &OperationParameters::new(), schedule.clone()).unwrap();
+        assert_eq!(8, unconstrained.run([0])[0].results.len());
+
+        let constraints = vec![MinimumInterval { operations: vec!["thin"], interval: 10 }];
+        let simulation = TimePointSimulation::with_constraints(&generator_map(), &operations, &OperationParameters::new(), schedule, constraints).unwrap();
+        let states: Vec<i32> = simulation.run([0])[0].results.iter().map(|timed| timed.state).collect();
+        // Thinnings in 2020 and 2030 are the only combination of several thinnings.
+        assert_eq!(vec![3, 230, 126, 22, 2130], states);
+
+        let collected = simulation.run_collecting(0, &mut []);
+        assert_eq!(simulation.run([0])[0].results, collected.results);
+    }
+
+    #[test]
+    fn sequences_skip_violating_operations() {
+        let mut operations = create_operations();
+        operations.insert("thin", thin as ParameteredOperation<TimedState<i32>>);
+        let schedule = vec![ScheduledEvents { time_points: vec![2020, 2025], declaration: vec![("sequence", vec!["thin", "grow", "thin"])] }];
+        let constraints = vec![MinimumInterval { operations: vec!["thin"], interval: 5 }];
+        let simulation = TimePointSimulation::with_constraints(&generator_map(), &operations, &OperationParameters::new(), schedule, constraints.clone()).unwrap();
+        assert_eq!(vec![TimedState { time: 2025, state: 12126 }], simulation.run([1])[0].results);
+
+        let unknown = vec![MinimumInterval { operations: vec!["clearcut"], interval: 5 }];
+        let schedule = vec![ScheduledEvents { time_points: vec![2020], declaration: vec![("sequence", vec!["thin"])] }];
+        let simulation = TimePointSimulation::with_constraints(&generator_map(), &operations, &OperationParameters::new(), schedule, unknown);
+        assert_eq!(Some(RunnerError::UnknownOperation("clearcut")), simulation.err());
+    }
+
+    fn timed(values: &[(TimePoint, &'static str)], interpolation: Interpolation) -> TimedParameter {
+        TimedParameter::new(values.iter().copied().collect(), interpolation)
+    }
+
+    #[test]
+    fn timed_parameters_are_interpolated() {
+        let step = timed(&[(2020, "1"), (2040, "3")], Interpolation::Step);
+        assert_eq!(vec![Some("1"), Some("1"), Some("1"), Some("3"), Some("3")],
+            [2010, 2020, 2030, 2040, 2050].map(|tp| step.at(tp)).iter().map(|value| value.as_deref()).collect::<Vec<_>>());
+        let linear = timed(&[(2020, "1.0"), (2040, "3.0")], Interpolation::Linear);
+        assert_eq!(vec![Some("1.0"), Some("1.0"), Some("1.5"), Some("3.0"), Some("3.0")],
+            [2010, 2020, 2025, 2040, 2050].map(|tp| linear.at(tp)).iter().map(|value| value.as_deref()).collect::<Vec<_>>());
+        let species = timed(&[(2020, "pine"), (2040, "spruce")], Interpolation::Linear);
+        assert_eq!(Some("pine".to_string()), species.at(2030));
+        let stems = timed(&[(2020, "10"), (2050, "20")], Interpolation::Linear);
+        assert_eq!(vec![Some("13"), Some("17")], [2030, 2040].map(|tp| stems.at(tp)).iter().map(|value| value.as_deref()).collect::<Vec<_>>());
+        assert_eq!(None, timed(&[], Interpolation::Step).at(2030));
+    }
+
+    fn add_amount(timed: TimedState<i32>, params: ParameterMap) -> TimedState<i32> {
+        TimedState { state: timed.state + params["amount"].parse::<i32>().unwrap(), ..timed }
+    }
+
+    #[test]
+    fn operations_get_parameters_of_their_time_point() {
+        let mut operations = create_operations();
+        operations.insert("add", add_amount as ParameteredOperation<TimedState<i32>>);
+        let parameters = OperationParameters::from([("add", ParameterMap::from([("amount", "1000")]))]);
+        let schedule = vec![ScheduledEvents { time_points: vec![2020, 2025, 2030, 2040], declaration: vec![("sequence", vec!["add"])] }];
+        let timed_parameters = TimedParameters::from([("add", HashMap::from([("amount", timed(&[(2020, "10"), (2030, "20")], Interpolation::Linear))]))]);
+        let simulation = TimePointSimulation::with_timed_parameters(&generator_map(), &operations, &parameters, &timed_parameters, schedule.clone(), Vec::new()).unwrap();
+        assert_eq!(vec![TimedState { time: 2040, state: 65 }], simulation.run([0])[0].results);
+
+        let unknown = TimedParameters::from([("clearcut", HashMap::new())]);
+        let simulation = TimePointSimulation::with_timed_parameters(&generator_map(), &operations, &parameters, &unknown, schedule, Vec::new());
+        assert_eq!(Some(RunnerError::UnknownOperation("clearcut")), simulation.err());
+    }
+}
@@ -0,0 +1,192 @@
+use std::collections::HashMap;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use crate::event_graph::{is_pruned, panic_message, ChainResults, EvaluationFailure, EventNode};
+use crate::graph_fingerprint::{fingerprint_to_depth, GraphFingerprint};
+use crate::graph_statistics::GraphStatistics;
+use crate::provenance::EffectiveParameters;
+
+/// Output states of the nodes by their paths of follower indices, in evaluation order, from the
+/// root. Pruned paths have no state.
+type PathStates<T> = HashMap<Vec<usize>, Option<T>>;
+
+/// Evaluation of successive event graphs sharing a common prefix, such as those compiled from a
+/// configuration under iterative experimentation with its late events. Each evaluation stores the
+/// states after the nodes within the given depth, the root being at depth 0, along with the
+/// fingerprints of the graph up to each depth. Evaluating the next graph reuses the stored states
+/// down to the deepest depth at which the fingerprints of the graphs are equal, so only the
+/// differing suffixes are evaluated. Assumes deterministic operations. Unlabeled operations do
+/// not affect the fingerprints, so the states of unlabeled nodes and their followers are never
+/// reused.
+pub struct WarmStart<T> {
+    initial_state: T,
+    depth: usize,
+    fingerprints: Vec<GraphFingerprint>,
+    states: PathStates<T>,
+    reused_depth: Option<usize>,
+    applications: usize,
+}
+
+impl<T: Clone> WarmStart<T> {
+    /// Warm start of evaluations from the initial state, storing the states within the depth.
+    pub fn new(initial_state: T, depth: usize) -> WarmStart<T> {
+        WarmStart { initial_state, depth, fingerprints: Vec::new(), states: PathStates::new(), reused_depth: None, applications: 0 }
+    }
+
+    /// Depth down to which the latest evaluation reused the stored states, None if it reused none.
+    pub fn reused_depth(&self) -> Option<usize> {
+        self.reused_depth
+    }
+
+    /// Number of operations applied by the latest evaluation.
+    pub fn applications(&self) -> usize {
+        self.applications
+    }
+
+    /// Evaluate the unique event chains of the graph as EventDAG::try_evaluate_chains_by_id,
+    /// reusing the states stored by the previous evaluation for the prefix it shares with the
+    /// graph. The graphs are compared as by graph_fingerprint::fingerprint, identifying
+    /// operations by their labels and looking up the parameters bound to the labels. A failing
+    /// evaluation keeps the stored states.
+    pub fn evaluate<F>(&mut self, root: &EventNode<T>, parameters: F) -> Result<ChainResults<T>, EvaluationFailure>
+    where F: Fn(&'static str) -> Option<EffectiveParameters> {
+        let reusable = root.borrow().label().is_some();
+        self.evaluate_reusing(root, reusable, parameters)
+    }
+
+    /// Evaluate as evaluate, reusing the stored states of the root and its followers only if the
+    /// root is reusable, such as the unlabeled identity root of a compiled graph.
+    pub(crate) fn evaluate_reusing<F>(&mut self, root: &EventNode<T>, reusable: bool, parameters: F) -> Result<ChainResults<T>, EvaluationFailure>
+    where F: Fn(&'static str) -> Option<EffectiveParameters> {
+        let fingerprints: Vec<GraphFingerprint> = (0..=self.depth).map(|depth| fingerprint_to_depth(root, depth, &parameters)).collect();
+        self.reused_depth = (0..fingerprints.len().min(self.fingerprints.len())).rev()
+            .find(|depth| fingerprints[*depth] == self.fingerprints[*depth]);
+        self.applications = 0;
+        let mut evaluation = Evaluation { results: ChainResults::new(), chain: 0, states: PathStates::new() };
+        self.visit(root, &mut Vec::new(), reusable, self.initial_state.clone(), &mut evaluation)?;
+        self.fingerprints = fingerprints;
+        self.states = evaluation.states;
+        Ok(evaluation.results)
+    }
+
+    /// Evaluate the node and its followers, reusing the stored state of a reusable node within the
+    /// reused depth. Followers are reusable if the node is and they are labeled.
+    fn visit(&mut self, node: &EventNode<T>, path: &mut Vec<usize>, reusable: bool, state: T, evaluation: &mut Evaluation<T>) -> Result<(), EvaluationFailure> {
+        let depth = path.len();
+        let borrowed = node.borrow();
+        let reused = self.reused_depth.filter(|reused| reusable && depth <= *reused).and_then(|_| self.states.get(path.as_slice()).cloned());
+        let output = match reused {
+            Some(output) => output,
+            None => {
+                self.applications += 1;
+                match catch_unwind(AssertUnwindSafe(|| borrowed.apply(state))) {
+                    Ok(output) => Some(output),
+                    Err(payload) if is_pruned(&*payload) => None,
+                    Err(payload) => return Err(EvaluationFailure { chain: evaluation.chain, operation: depth, label: borrowed.label().map(str::to_string), error: panic_message(payload) }),
+                }
+            }
+        };
+        if depth <= self.depth {
+            evaluation.states.insert(path.clone(), output.clone());
+        }
+        match output {
+            None => evaluation.chain += GraphStatistics::of(node).chains,
+            Some(output) if borrowed.followers().is_empty() => {
+                evaluation.results.insert(evaluation.chain, output);
+                evaluation.chain += 1;
+            }
+            Some(output) => {
+                for (index, follower) in borrowed.evaluation_order().iter().enumerate() {
+                    path.push(index);
+                    let reusable = reusable && follower.borrow().label().is_some();
+                    self.visit(follower, path, reusable, output.clone(), evaluation)?;
+                    path.pop();
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Progress of a single evaluation.
+struct Evaluation<T> {
+    results: ChainResults<T>,
+    chain: usize,
+    states: PathStates<T>,
+}
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+    use crate::branching_generators::{alternatives, sequence};
+    use crate::event_graph::{prune, BoxedOperation, EventDAG, EventNodeExt};
+    use super::*;
+
+    fn add(amount: i32) -> BoxedOperation<i32> {
+        Box::new(move |x| x + amount)
+    }
+
+    /// Growth, thinning alternatives of which the second prunes large states, and a final event
+    /// labeled by the given label.
+    fn rotation(last: &'static str, amount: i32) -> EventNode<i32> {
+        let root = EventDAG::new_node(add(0));
+        root.borrow_mut().set_label("initial");
+        let grown = sequence(vec![Rc::clone(&root)], [add(10)]).unwrap();
+        grown[0].borrow_mut().set_label("grow");
+        let thinnings: [BoxedOperation<i32>; 2] = [add(-1), Box::new(|x| if x > 5 { prune() } else { x })];
+        let branches = alternatives(grown, thinnings).unwrap();
+        branches[0].borrow_mut().set_label("thin");
+        branches[1].borrow_mut().set_label("thin_pruning");
+        let ends = sequence(branches, [add(amount)]).unwrap();
+        ends[0].borrow_mut().set_label(last);
+        root
+    }
+
+    fn no_parameters(_: &'static str) -> Option<EffectiveParameters> {
+        None
+    }
+
+    #[test]
+    fn shared_prefixes_are_reused() {
+        let mut warm = WarmStart::new(0, 2);
+        let results = warm.evaluate(&rotation("clearcut", 100), no_parameters).unwrap();
+        assert_eq!(vec![(0, 109)], results.into_iter().collect::<Vec<_>>());
+        assert_eq!((None, 5), (warm.reused_depth(), warm.applications()));
+
+        let results = warm.evaluate(&rotation("planting", 1), no_parameters).unwrap();
+        assert_eq!(EventDAG::evaluate_chains_by_id(&rotation("planting", 1), 0), results);
+        assert_eq!((Some(2), 1), (warm.reused_depth(), warm.applications()));
+
+        let thin_ratio = |operation: &'static str| (operation == "thin").then(|| EffectiveParameters::from([("ratio", "0.5")]));
+        warm.evaluate(&rotation("planting", 1), thin_ratio).unwrap();
+        assert_eq!((Some(1), 3), (warm.reused_depth(), warm.applications()));
+    }
+
+    #[test]
+    fn failures_keep_the_stored_states() {
+        let mut warm = WarmStart::new(0, 1);
+        warm.evaluate(&rotation("clearcut", 100), no_parameters).unwrap();
+        let failing = rotation("clearcut", 100);
+        let leaf = failing.leaves().next().unwrap();
+        leaf.borrow_mut().set_operation(Box::new(|_| panic!("failed")));
+        let failure = warm.evaluate(&failing, no_parameters).unwrap_err();
+        assert_eq!((0, 3), (failure.chain, failure.operation));
+        warm.evaluate(&rotation("clearcut", 100), no_parameters).unwrap();
+        assert_eq!((Some(1), 3), (warm.reused_depth(), warm.applications()));
+    }
+
+    #[test]
+    fn unlabeled_operations_are_not_reused() {
+        let unlabeled = |amount: i32| {
+            let root = EventDAG::new_node(add(0));
+            root.borrow_mut().set_label("initial");
+            let planted = sequence(vec![Rc::clone(&root)], [add(amount)]).unwrap();
+            sequence(planted, [add(10)]).unwrap()[0].borrow_mut().set_label("grow");
+            root
+        };
+        let mut warm = WarmStart::new(0, 2);
+        warm.evaluate(&unlabeled(1), no_parameters).unwrap();
+        let results = warm.evaluate(&unlabeled(2), no_parameters).unwrap();
+        assert_eq!(vec![(0, 12)], results.into_iter().collect::<Vec<_>>());
+        assert_eq!((Some(2), 2), (warm.reused_depth(), warm.applications()));
+    }
+}
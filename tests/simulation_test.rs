@@ -2,7 +2,12 @@ use std::collections::HashMap;
 use std::rc::Rc;
 use metsi_rust::configuration_utils::{bound_operation, ParameteredOperation, ParameterMap};
 use metsi_rust::branching_generators::{generator_map, GeneratorFn};
-use metsi_rust::event_graph::{BoxedOperation, EventDAG, EventNode, EventNodes, OperationChain, UnboundOperation};
+use metsi_rust::event_graph::{ChainResults, EventDAG, EventNode, EventNodes, OperationChain};
+use metsi_rust::checkpoint::Checkpoint;
+use metsi_rust::golden::assert_golden;
+use metsi_rust::input::InputReader;
+use metsi_rust::input::csv::CsvReader;
+use metsi_rust::simulation_runner::{OperationParameters, OperationRegistry, RunResult, SimulationRunner};
 
 fn increment(val: i32, params: ParameterMap) -> i32 {
     let addition = params.get("increase").unwrap().parse::<i32>().unwrap();
@@ -56,9 +61,203 @@ fn test_simple_run() {
         (generator_fn, operations)
     }).collect();
     for generable in sim {
-        nodes = generable.0(nodes, generable.1);
+        nodes = generable.0(nodes, generable.1).unwrap();
     }
 
     let result = root.borrow().evaluate_depth(10);
     assert_eq!(vec![20, 17], result);
-}
\ No newline at end of file
+}
+
+#[test]
+fn test_runner_run() {
+    let parameters = OperationParameters::from([
+        ("increment", ParameterMap::from([("increase", "2")])),
+        ("decrement", ParameterMap::from([("decrease", "1")]))
+    ]);
+
+    let operations: OperationRegistry<i32> = HashMap::from([
+        ("increment", increment as ParameteredOperation<i32>),
+        ("decrement", decrement as ParameteredOperation<i32>)
+    ]);
+
+    let declaration = Vec::from([
+        ("sequence", Vec::from(["increment", "increment"])),
+        ("alternatives", Vec::from(["increment", "decrement"])),
+        ("sequence", Vec::from(["increment", "increment"]))
+    ]);
+
+    let runner = SimulationRunner::new(operations, parameters, declaration).unwrap();
+    let results = runner.run([10]);
+    assert_eq!(vec![RunResult { initial_state: 10, results: vec![20, 17] }], results);
+}
+
+
+#[test]
+fn test_runner_batch() {
+    let operations: OperationRegistry<i32> = HashMap::from([
+        ("increment", increment as ParameteredOperation<i32>),
+        ("decrement", decrement as ParameteredOperation<i32>)
+    ]);
+    let parameters = OperationParameters::from([
+        ("increment", ParameterMap::from([("increase", "2")])),
+        ("decrement", ParameterMap::from([("decrease", "1")]))
+    ]);
+    let declaration = Vec::from([("alternatives", Vec::from(["increment", "decrement"]))]);
+
+    let runner = SimulationRunner::new(operations, parameters, declaration).unwrap();
+    let entities = (0..100).map(|id| (id, id * 10));
+    let results = runner.run_batch(entities).results;
+    assert_eq!(100, results.len());
+    assert_eq!(ChainResults::from([(0, 992), (1, 989)]), results[&99]);
+}
+
+
+#[test]
+fn test_runner_batch_parallel() {
+    let operations: OperationRegistry<i32> = HashMap::from([
+        ("increment", increment as ParameteredOperation<i32>),
+        ("decrement", decrement as ParameteredOperation<i32>)
+    ]);
+    let parameters = OperationParameters::from([
+        ("increment", ParameterMap::from([("increase", "2")])),
+        ("decrement", ParameterMap::from([("decrease", "1")]))
+    ]);
+    let declaration = Vec::from([
+        ("sequence", Vec::from(["increment"])),
+        ("alternatives", Vec::from(["increment", "decrement"]))
+    ]);
+
+    let runner = SimulationRunner::new(operations, parameters, declaration).unwrap();
+    let entities: Vec<(i32, i32)> = (0..1000).map(|id| (id, id)).collect();
+    let sequential = runner.run_batch(entities.clone());
+    let parallel = runner.run_batch_parallel(entities, 4).unwrap();
+    assert_eq!(sequential, parallel);
+    assert_eq!(ChainResults::from([(0, 1003), (1, 1000)]), parallel.results[&999]);
+}
+
+
+#[test]
+fn test_runner_summary() {
+    let operations: OperationRegistry<i32> = HashMap::from([
+        ("increment", increment as ParameteredOperation<i32>),
+        ("decrement", decrement as ParameteredOperation<i32>)
+    ]);
+    let parameters = OperationParameters::from([
+        ("increment", ParameterMap::from([("increase", "2")])),
+        ("decrement", ParameterMap::from([("decrease", "1")]))
+    ]);
+    let declaration = Vec::from([
+        ("sequence", Vec::from(["increment"])),
+        ("alternatives", Vec::from(["increment", "decrement"]))
+    ]);
+
+    let runner = SimulationRunner::new(operations, parameters, declaration).unwrap();
+    let entities: Vec<(i32, i32)> = (0..10).map(|id| (id, id)).collect();
+    let (outcome, summary) = runner.run_batch_summarized(entities.clone());
+    assert_eq!(10, outcome.results.len());
+    assert_eq!((10, 20, 0), (summary.entities, summary.chains, summary.failures));
+    assert_eq!(30, summary.operation_timings["increment"].calls);
+    assert_eq!(10, summary.operation_timings["decrement"].calls);
+
+    let (_, parallel_summary) = runner.run_batch_parallel_summarized(entities, 2).unwrap();
+    assert_eq!(summary.operation_timings.keys().collect::<Vec<_>>(), parallel_summary.operation_timings.keys().collect::<Vec<_>>());
+    assert_eq!(30, parallel_summary.operation_timings["increment"].calls);
+}
+
+
+#[test]
+fn test_runner_checkpointed() {
+    let operations: OperationRegistry<i32> = HashMap::from([
+        ("increment", increment as ParameteredOperation<i32>),
+        ("decrement", decrement as ParameteredOperation<i32>)
+    ]);
+    let parameters = OperationParameters::from([
+        ("increment", ParameterMap::from([("increase", "2")])),
+        ("decrement", ParameterMap::from([("decrease", "1")]))
+    ]);
+    let declaration = Vec::from([("alternatives", Vec::from(["increment", "decrement"]))]);
+
+    let runner = SimulationRunner::new(operations, parameters, declaration).unwrap();
+    let directory = tempfile::tempdir().unwrap();
+    let checkpoint = Checkpoint::new(directory.path(), 7);
+    let entities: Vec<(i32, i32)> = (0..50).map(|id| (id, id)).collect();
+    let outcome = runner.run_batch_parallel_checkpointed(entities.clone(), 2, &checkpoint).unwrap();
+    assert_eq!(runner.run_batch(entities.clone()), outcome);
+    assert_eq!(50, std::fs::read_to_string(checkpoint.results_path()).unwrap().lines().count());
+
+    let more: Vec<(i32, i32)> = (0..60).map(|id| (id, id)).collect();
+    let resumed = runner.resume_batch_parallel_checkpointed(more.clone(), 2, &checkpoint).unwrap();
+    assert_eq!(runner.run_batch(more), resumed);
+    assert_eq!(60, std::fs::read_to_string(checkpoint.results_path()).unwrap().lines().count());
+}
+
+#[test]
+fn test_runner_input() {
+    let operations: OperationRegistry<i32> = HashMap::from([
+        ("increment", increment as ParameteredOperation<i32>),
+        ("decrement", decrement as ParameteredOperation<i32>)
+    ]);
+    let parameters = OperationParameters::from([
+        ("increment", ParameterMap::from([("increase", "2")])),
+        ("decrement", ParameterMap::from([("decrease", "1")]))
+    ]);
+    let declaration = Vec::from([("alternatives", Vec::from(["increment", "decrement"]))]);
+
+    let runner = SimulationRunner::new(operations, parameters, declaration).unwrap();
+    let input = "stand,volume\n1,10\n2,20\n";
+    let entities: Vec<(u32, i32)> = CsvReader::new(input.as_bytes(), "stand").read_entities().unwrap();
+    let outcome = runner.run_batch(entities);
+    assert_eq!(ChainResults::from([(0, 12), (1, 9)]), outcome.results[&1]);
+    assert_eq!(ChainResults::from([(0, 22), (1, 19)]), outcome.results[&2]);
+}
+
+#[test]
+fn test_runner_golden() {
+    let operations: OperationRegistry<i32> = HashMap::from([
+        ("increment", increment as ParameteredOperation<i32>),
+        ("decrement", decrement as ParameteredOperation<i32>)
+    ]);
+    let parameters = OperationParameters::from([
+        ("increment", ParameterMap::from([("increase", "2")])),
+        ("decrement", ParameterMap::from([("decrease", "1")]))
+    ]);
+    let declaration = Vec::from([
+        ("alternatives", Vec::from(["increment", "decrement"])),
+        ("sequence", Vec::from(["increment"])),
+        ("alternatives", Vec::from(["increment", "decrement"]))
+    ]);
+
+    let runner = SimulationRunner::new(operations, parameters, declaration).unwrap();
+    let outcome = runner.run_batch((0..3).map(|id| (id, id * 10)));
+    assert_golden("tests/golden/runner_batch.json", &outcome.results, 0.0);
+}
+
+#[cfg(feature = "forestry")]
+#[test]
+fn test_forestry_growth() {
+    use metsi_rust::forestry::{operations, ForestStand, ReferenceTree, Species};
+    let stand = ForestStand {
+        identifier: "1".to_string(),
+        year: 2025,
+        area: 1.0,
+        site_type: 3,
+        degree_days: 1200.0,
+        reference_trees: vec![ReferenceTree {
+            identifier: "1-1".to_string(),
+            species: Species::Pine,
+            breast_height_diameter: 15.0,
+            height: 12.0,
+            stems_per_ha: 800.0,
+            biological_age: 30.0,
+        }],
+        tree_strata: Vec::new(),
+        removals: Vec::new(),
+    };
+    let parameters = OperationParameters::from([("grow", ParameterMap::from([("years", "10")]))]);
+    let declaration = vec![("sequence", vec!["grow", "grow", "grow"])];
+    let runner = SimulationRunner::new(operations(), parameters, declaration).unwrap();
+    let result = &runner.run([stand.clone()])[0].results[0];
+    assert_eq!(2055, result.year);
+    assert_eq!(60.0, result.reference_trees[0].biological_age);
+    assert!(result.volume() > stand.volume());
+}